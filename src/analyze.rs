@@ -0,0 +1,104 @@
+// --trash-analyze: where the trash wastes space, and what to run about it.
+// Three kinds of slack are looked for: items with the same contents as a
+// newer item, cold items trashed long ago, and orphans, which are contents
+// without a .trashinfo record or records whose contents are gone. Nothing is
+// changed; each finding comes with the exact command that reclaims it.
+#![cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Items trashed longer ago than this are cold
+pub const COLD_DAYS: i64 = 90;
+
+/// Entries of one trash directory that don't belong to an item.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Orphans {
+    /// Under files/ with no record in info/
+    pub contents: Vec<PathBuf>,
+    /// In info/ with nothing under files/
+    pub records: Vec<PathBuf>,
+}
+
+/// Orphans in the freedesktop trash directory `trash_dir`.
+pub fn orphans(trash_dir: &Path) -> io::Result<Orphans> {
+    let files = trash_dir.join("files");
+    let info = trash_dir.join("info");
+    let mut orphans = Orphans::default();
+    for entry in sorted_entries(&files)? {
+        let mut record = entry.file_name();
+        record.push(".trashinfo");
+        if fs::symlink_metadata(info.join(record)).is_err() {
+            orphans.contents.push(entry.path());
+        }
+    }
+    for entry in sorted_entries(&info)? {
+        let name = entry.file_name();
+        let Some(stem) = name.to_str().and_then(|n| n.strip_suffix(".trashinfo")) else {
+            continue;
+        };
+        if fs::symlink_metadata(files.join(stem)).is_err() {
+            orphans.records.push(entry.path());
+        }
+    }
+    Ok(orphans)
+}
+
+fn sorted_entries(dir: &Path) -> io::Result<Vec<fs::DirEntry>> {
+    let mut entries: Vec<_> = match fs::read_dir(dir) {
+        Ok(entries) => entries.collect::<io::Result<_>>()?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    entries.sort_by_key(|e| e.file_name());
+    Ok(entries)
+}
+
+/// `arg` quoted for a POSIX shell, only when it needs to be.
+pub fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_orphans() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let trash = tmp.path();
+        fs::create_dir_all(trash.join("files/dir")).unwrap();
+        fs::create_dir_all(trash.join("info")).unwrap();
+        for name in ["kept", "stray"] {
+            fs::write(trash.join("files").join(name), "").unwrap();
+        }
+        for name in ["kept", "dir", "gone"] {
+            fs::write(trash.join("info").join(format!("{name}.trashinfo")), "").unwrap();
+        }
+
+        let found = orphans(trash).unwrap();
+        assert_eq!(found.contents, [trash.join("files/stray")]);
+        assert_eq!(found.records, [trash.join("info/gone.trashinfo")]);
+        assert_eq!(orphans(&trash.join("missing")).unwrap(), Orphans::default());
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("/tmp/a.txt"), "/tmp/a.txt");
+        assert_eq!(
+            shell_quote("deleted:<2024-01-01:*"),
+            "'deleted:<2024-01-01:*'"
+        );
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+}
@@ -0,0 +1,126 @@
+// What counts as "yes" at a prompt: y and yes, the words listed as
+// `yes_answers` in config.toml, and whatever the locale's yesexpr accepts
+// (j for German, o for French, ...). The expression has to match the whole
+// answer, not just its start, so "yep" or "Jawohl" isn't taken for a yes.
+// Anything else is a no.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+static ANSWERS: OnceLock<Answers> = OnceLock::new();
+
+struct Answers {
+    /// Lowercased
+    words: Vec<String>,
+    yesexpr: Option<Regex>,
+}
+
+impl Answers {
+    fn new(words: &[String], yesexpr: Option<&str>) -> Answers {
+        Answers {
+            words: words.iter().map(|w| w.trim().to_lowercase()).collect(),
+            // A locale's expression that Rust can't compile is ignored
+            yesexpr: yesexpr.and_then(|e| Regex::new(&format!("^(?:{e})$")).ok()),
+        }
+    }
+
+    fn is_yes(&self, answer: &str) -> bool {
+        let answer = answer.trim();
+        let lower = answer.to_lowercase();
+        matches!(lower.as_str(), "y" | "yes")
+            || self.words.contains(&lower)
+            || self.yesexpr.as_ref().is_some_and(|re| re.is_match(answer))
+    }
+}
+
+/// Accept `words` as yes too, for the rest of the run.
+pub fn init(words: &[String]) {
+    let _ = ANSWERS.set(Answers::new(words, locale_yesexpr().as_deref()));
+}
+
+pub fn is_yes(answer: &str) -> bool {
+    ANSWERS
+        .get_or_init(|| Answers::new(&[], locale_yesexpr().as_deref()))
+        .is_yes(answer)
+}
+
+/// The yesexpr of the user's LC_MESSAGES locale. The process locale is
+/// put back afterwards, so error messages stay as they were.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "dragonfly"
+))]
+fn locale_yesexpr() -> Option<String> {
+    use std::ffi::{CStr, CString};
+
+    // SAFETY: called from the main thread before any prompt; every string
+    // libc returns is copied before the next call can overwrite it
+    unsafe {
+        let current = libc::setlocale(libc::LC_MESSAGES, std::ptr::null());
+        let saved = (!current.is_null()).then(|| CStr::from_ptr(current).to_owned());
+        if libc::setlocale(libc::LC_MESSAGES, c"".as_ptr()).is_null() {
+            return None;
+        }
+        let expr = libc::nl_langinfo(libc::YESEXPR);
+        let expr = (!expr.is_null()).then(|| CStr::from_ptr(expr).to_string_lossy().into_owned());
+        let restore = saved.unwrap_or_else(|| CString::from(c"C"));
+        libc::setlocale(libc::LC_MESSAGES, restore.as_ptr());
+        expr.filter(|e| !e.is_empty())
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "dragonfly"
+)))]
+fn locale_yesexpr() -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_english_answers() {
+        let answers = Answers::new(&[], None);
+        assert!(answers.is_yes("y\n"));
+        assert!(answers.is_yes(" YES "));
+        assert!(!answers.is_yes("n"));
+        assert!(!answers.is_yes(""));
+        assert!(!answers.is_yes("yep"));
+    }
+
+    #[test]
+    fn test_configured_words() {
+        let answers = Answers::new(&["Ja".into(), "oui".into()], None);
+        assert!(answers.is_yes("ja"));
+        assert!(answers.is_yes("OUI"));
+        assert!(!answers.is_yes("nein"));
+    }
+
+    #[test]
+    fn test_locale_yesexpr() {
+        // de_DE
+        let answers = Answers::new(&[], Some("^[+1jJyY]"));
+        assert!(answers.is_yes("j"));
+        assert!(answers.is_yes(" J\n"));
+        assert!(!answers.is_yes("Jawohl"));
+        assert!(!answers.is_yes("n"));
+        // The C locale's
+        let answers = Answers::new(&[], Some("^[yY]"));
+        assert!(answers.is_yes("Y"));
+        assert!(!answers.is_yes("yep"));
+        // A broken expression leaves the defaults
+        assert!(Answers::new(&[], Some("^[")).is_yes("y"));
+    }
+}
@@ -0,0 +1,301 @@
+// File attributes that should survive a trip through the trash: permissions,
+// ownership, modification time and (on Linux) user extended attributes.
+// A same-volume rename keeps them all, but copies across volumes and some
+// trash implementations don't, so restores check and re-apply them.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Attributes captured from a path before it is moved.
+#[derive(Debug, Clone)]
+pub struct Attrs {
+    is_symlink: bool,
+    permissions: fs::Permissions,
+    modified: Option<SystemTime>,
+    #[cfg(unix)]
+    owner: (u32, u32),
+    #[cfg(target_os = "linux")]
+    xattrs: Vec<(std::ffi::OsString, Vec<u8>)>,
+}
+
+/// An attribute that differed after a move and could not be re-applied.
+#[derive(Debug)]
+pub struct Problem {
+    path: PathBuf,
+    what: &'static str,
+    error: io::Error,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "could not preserve {} of '{}': {}",
+            self.what,
+            self.path.display(),
+            self.error
+        )
+    }
+}
+
+pub fn capture(path: &Path) -> io::Result<Attrs> {
+    let meta = path.symlink_metadata()?;
+    Ok(Attrs {
+        is_symlink: meta.is_symlink(),
+        permissions: meta.permissions(),
+        modified: meta.modified().ok(),
+        #[cfg(unix)]
+        owner: {
+            use std::os::unix::fs::MetadataExt;
+            (meta.uid(), meta.gid())
+        },
+        #[cfg(target_os = "linux")]
+        xattrs: if meta.is_symlink() {
+            Vec::new()
+        } else {
+            xattr::list_user(path)?
+        },
+    })
+}
+
+/// Re-apply whatever in `want` differs from what `path` has now.
+pub fn restore(path: &Path, want: &Attrs) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let current = match capture(path) {
+        Ok(c) => c,
+        Err(error) => {
+            problems.push(Problem {
+                path: path.to_path_buf(),
+                what: "attributes",
+                error,
+            });
+            return problems;
+        }
+    };
+    let mut check = |what, result: io::Result<()>| {
+        if let Err(error) = result {
+            problems.push(Problem {
+                path: path.to_path_buf(),
+                what,
+                error,
+            });
+        }
+    };
+
+    // Ownership first: chown clears setuid/setgid bits
+    #[cfg(unix)]
+    if current.owner != want.owner {
+        let (uid, gid) = want.owner;
+        check(
+            "ownership",
+            std::os::unix::fs::lchown(path, Some(uid), Some(gid)),
+        );
+    }
+    if want.is_symlink || current.is_symlink {
+        return problems;
+    }
+
+    if current.permissions != want.permissions {
+        check(
+            "permissions",
+            fs::set_permissions(path, want.permissions.clone()),
+        );
+    }
+    #[cfg(target_os = "linux")]
+    for (name, value) in &want.xattrs {
+        if !current.xattrs.iter().any(|(n, v)| n == name && v == value) {
+            check("extended attributes", xattr::set(path, name, value));
+        }
+    }
+    // Last, since the other changes may touch it
+    if let Some(modified) = want.modified
+        && current.modified != Some(modified)
+    {
+        check("modification time", set_modified(path, modified));
+    }
+    problems
+}
+
+/// Copy the attributes of `from` onto `to`.
+pub fn copy(from: &Path, to: &Path) -> io::Result<Vec<Problem>> {
+    Ok(restore(to, &capture(from)?))
+}
+
+#[cfg(unix)]
+fn set_modified(path: &Path, modified: SystemTime) -> io::Result<()> {
+    File::open(path)?.set_modified(modified)
+}
+
+#[cfg(windows)]
+fn set_modified(path: &Path, modified: SystemTime) -> io::Result<()> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    // Directories can only be opened with FILE_FLAG_BACKUP_SEMANTICS
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+    fs::OpenOptions::new()
+        .write(true)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(path)?
+        .set_modified(modified)
+}
+
+#[cfg(target_os = "linux")]
+mod xattr {
+    use std::ffi::{CString, OsStr, OsString};
+    use std::io;
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::path::Path;
+
+    fn c_path(path: &Path) -> io::Result<CString> {
+        CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    fn unsupported(err: &io::Error) -> bool {
+        err.raw_os_error() == Some(libc::ENOTSUP)
+    }
+
+    /// The `user.` attributes of `path`, which unprivileged users can set.
+    pub fn list_user(path: &Path) -> io::Result<Vec<(OsString, Vec<u8>)>> {
+        let c = c_path(path)?;
+        // SAFETY: a null buffer of size 0 asks for the required size
+        let size = unsafe { libc::llistxattr(c.as_ptr(), std::ptr::null_mut(), 0) };
+        if size < 0 {
+            let err = io::Error::last_os_error();
+            return if unsupported(&err) {
+                Ok(Vec::new())
+            } else {
+                Err(err)
+            };
+        }
+        let mut names = vec![0u8; size as usize];
+        // SAFETY: the buffer is valid for names.len() bytes
+        let size = unsafe { libc::llistxattr(c.as_ptr(), names.as_mut_ptr().cast(), names.len()) };
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        names.truncate(size as usize);
+
+        let mut attrs = Vec::new();
+        for name in names.split(|&b| b == 0).filter(|n| n.starts_with(b"user.")) {
+            let name = OsStr::from_bytes(name);
+            attrs.push((name.to_os_string(), get(&c, name)?));
+        }
+        Ok(attrs)
+    }
+
+    fn get(path: &CString, name: &OsStr) -> io::Result<Vec<u8>> {
+        let c_name = CString::new(name.as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // SAFETY: a null buffer of size 0 asks for the required size
+        let size =
+            unsafe { libc::lgetxattr(path.as_ptr(), c_name.as_ptr(), std::ptr::null_mut(), 0) };
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut value = vec![0u8; size as usize];
+        // SAFETY: the buffer is valid for value.len() bytes
+        let size = unsafe {
+            libc::lgetxattr(
+                path.as_ptr(),
+                c_name.as_ptr(),
+                value.as_mut_ptr().cast(),
+                value.len(),
+            )
+        };
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        value.truncate(size as usize);
+        Ok(value)
+    }
+
+    pub fn set(path: &Path, name: &OsStr, value: &[u8]) -> io::Result<()> {
+        let c = c_path(path)?;
+        let c_name = OsString::from(name).into_vec();
+        let c_name =
+            CString::new(c_name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // SAFETY: all pointers are valid for the given lengths
+        let rc = unsafe {
+            libc::lsetxattr(
+                c.as_ptr(),
+                c_name.as_ptr(),
+                value.as_ptr().cast(),
+                value.len(),
+                0,
+            )
+        };
+        if rc < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_restore_modified_time() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let f = tmp.path().join("a.txt");
+        fs::write(&f, "a").unwrap();
+        let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        File::open(&f).unwrap().set_modified(old).unwrap();
+        let want = capture(&f).unwrap();
+
+        fs::write(&f, "changed").unwrap();
+        assert!(restore(&f, &want).is_empty());
+        assert_eq!(f.metadata().unwrap().modified().unwrap(), old);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_restore_execute_bit() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let f = tmp.path().join("script.sh");
+        fs::write(&f, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&f, fs::Permissions::from_mode(0o755)).unwrap();
+        let want = capture(&f).unwrap();
+
+        fs::set_permissions(&f, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(restore(&f, &want).is_empty());
+        assert_eq!(f.metadata().unwrap().permissions().mode() & 0o777, 0o755);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_between_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let from = tmp.path().join("from");
+        let to = tmp.path().join("to");
+        fs::write(&from, "a").unwrap();
+        fs::write(&to, "a").unwrap();
+        fs::set_permissions(&from, fs::Permissions::from_mode(0o600)).unwrap();
+
+        assert!(copy(&from, &to).unwrap().is_empty());
+        assert_eq!(to.metadata().unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_problem_display() {
+        let p = Problem {
+            path: PathBuf::from("/tmp/a"),
+            what: "ownership",
+            error: io::Error::from(io::ErrorKind::PermissionDenied),
+        };
+        assert_eq!(
+            p.to_string(),
+            "could not preserve ownership of '/tmp/a': permission denied"
+        );
+    }
+}
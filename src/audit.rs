@@ -0,0 +1,230 @@
+// Optional append-only log of destructive operations.
+// Enabled via [audit] in config.toml; a no-op otherwise.
+
+use std::fmt::Display;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::config::{self, AuditFormat};
+use crate::{batch, hooks, interrupt, labels, notify, status, transcript};
+
+// Only Trash is recorded on platforms without restore/purge support
+#[cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Op {
+    Trash,
+    Restore,
+    Purge,
+    Empty,
+    /// An existing file was removed to make room for a restored item
+    Overwrite,
+    /// A trash item's recorded original path was changed
+    Rename,
+    /// A file was overwritten and deleted with --shred
+    Shred,
+    /// A file was deleted permanently because its volume had no usable trash
+    Delete,
+    /// A file was moved with --mv, or moved back with --trash-undo-op
+    Move,
+}
+
+impl Op {
+    fn as_str(self) -> &'static str {
+        match self {
+            Op::Trash => "trash",
+            Op::Restore => "restore",
+            Op::Purge => "purge",
+            Op::Empty => "empty",
+            Op::Overwrite => "overwrite",
+            Op::Rename => "rename",
+            Op::Shred => "shred",
+            Op::Delete => "delete",
+            Op::Move => "move",
+        }
+    }
+}
+
+struct AuditLog {
+    file: File,
+    format: AuditFormat,
+}
+
+static LOG: Mutex<Option<AuditLog>> = Mutex::new(None);
+
+/// One line of the audit log with format = "json"
+#[derive(Serialize, JsonSchema)]
+struct Entry<'a> {
+    time: String,
+    pid: u32,
+    op: Op,
+    path: &'a str,
+    outcome: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// The run's --label
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+}
+
+/// JSON schema of an audit log line, for --schema.
+pub fn schema() -> schemars::Schema {
+    schemars::schema_for!(Entry<'static>)
+}
+
+/// Default log location: $XDG_STATE_HOME/trache/audit.log (or the platform equivalent).
+pub fn default_path() -> Option<PathBuf> {
+    config::state_dir().map(|d| d.join("audit.log"))
+}
+
+/// Open the audit log if the config enables it.
+pub fn init(cfg: &config::Audit) -> Result<(), String> {
+    if !cfg.enabled {
+        return Ok(());
+    }
+    let path = cfg
+        .path
+        .clone()
+        .or_else(default_path)
+        .ok_or("cannot determine audit log location; set audit.path")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("cannot create '{}': {e}", parent.display()))?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("cannot open audit log '{}': {e}", path.display()))?;
+
+    *LOG.lock().unwrap() = Some(AuditLog {
+        file,
+        format: cfg.format,
+    });
+    Ok(())
+}
+
+/// Append one line describing `op` on `path` and its result.
+pub fn record<T, E: Display>(op: Op, path: &Path, result: &Result<T, E>) {
+    if result.is_ok() {
+        status::note_done();
+        hooks::record(op, path);
+        notify::record(op);
+        interrupt::record(op);
+        batch::record(op, path);
+        labels::record(op, path);
+    }
+    transcript::action(
+        op.as_str(),
+        &std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+        result.as_ref().err().map(|e| e.to_string()),
+    );
+
+    let mut guard = LOG.lock().unwrap();
+    let Some(log) = guard.as_mut() else {
+        return;
+    };
+
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let path = path.to_string_lossy();
+    let entry = Entry {
+        time: chrono::Local::now().to_rfc3339(),
+        pid: std::process::id(),
+        op,
+        path: &path,
+        outcome: if result.is_ok() { "ok" } else { "error" },
+        error: result.as_ref().err().map(|e| e.to_string()),
+        label: labels::current(),
+    };
+
+    let line = match log.format {
+        AuditFormat::Json => serde_json::to_string(&entry).unwrap_or_default(),
+        AuditFormat::Logfmt => format_logfmt(&entry),
+    };
+    if let Err(e) = writeln!(log.file, "{line}") {
+        eprintln!("trache: cannot write audit log: {e}");
+        // Don't repeat the warning for every remaining item
+        *guard = None;
+    }
+}
+
+fn format_logfmt(entry: &Entry) -> String {
+    let mut line = format!(
+        "time={} pid={} op={} path={} outcome={}",
+        logfmt_value(&entry.time),
+        entry.pid,
+        entry.op.as_str(),
+        logfmt_value(entry.path),
+        entry.outcome
+    );
+    if let Some(ref e) = entry.error {
+        line.push_str(&format!(" error={}", logfmt_value(e)));
+    }
+    if let Some(ref label) = entry.label {
+        line.push_str(&format!(" label={}", logfmt_value(label)));
+    }
+    line
+}
+
+fn logfmt_value(value: &str) -> String {
+    let needs_quotes = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || c == '"' || c == '=' || c.is_control());
+    if !needs_quotes {
+        return value.to_string();
+    }
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logfmt_value_plain() {
+        assert_eq!(logfmt_value("/tmp/foo.txt"), "/tmp/foo.txt");
+    }
+
+    #[test]
+    fn test_logfmt_value_quoted() {
+        assert_eq!(logfmt_value("/tmp/my file"), "\"/tmp/my file\"");
+        assert_eq!(logfmt_value("a\"b"), "\"a\\\"b\"");
+        assert_eq!(logfmt_value(""), "\"\"");
+    }
+
+    #[test]
+    fn test_format_logfmt_error() {
+        let entry = Entry {
+            time: "2024-01-15T10:30:00+00:00".into(),
+            pid: 42,
+            op: Op::Trash,
+            path: "/tmp/a b",
+            outcome: "error",
+            error: Some("Permission denied".into()),
+            label: Some("nightly cleanup".into()),
+        };
+        assert_eq!(
+            format_logfmt(&entry),
+            "time=2024-01-15T10:30:00+00:00 pid=42 op=trash path=\"/tmp/a b\" \
+             outcome=error error=\"Permission denied\" label=\"nightly cleanup\""
+        );
+    }
+}
@@ -0,0 +1,476 @@
+// Where trashed items live. Everything that lists, trashes, restores or
+// purges goes through the TrashBackend picked at startup: the system trash
+// through the trash crate, which is the default, or with --trash-dir (or
+// trash_dir in the config) a plain directory laid out like a freedesktop
+// trash, files/ beside info/, wherever it is. The directory backend keeps a
+// run away from the user's real trash entirely, which is what tests and
+// scratch setups want; items on another file system than the directory are
+// copied in and then removed. Tests can use the bookkeeping-only Mock,
+// which TRACHE_BACKEND=mock selects for a whole run.
+#![cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+
+use std::path::Path;
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use trash::{TrashContext, TrashItem};
+
+pub trait TrashBackend: Send + Sync {
+    /// Every item in the trash.
+    fn list(&self) -> Result<Vec<TrashItem>, trash::Error>;
+    /// Move `path` into the trash.
+    fn delete(&self, path: &Path) -> Result<(), trash::Error>;
+    /// Put `items` back at their original paths, in order, stopping at the
+    /// first that can't go back.
+    fn restore(&self, items: Vec<TrashItem>) -> Result<(), trash::Error>;
+    /// Remove `items` from the trash for good.
+    fn purge(&self, items: &[TrashItem]) -> Result<(), trash::Error>;
+    /// The directory items are written to directly, as --trash-import does,
+    /// if it isn't simply the home trash.
+    fn directory(&self) -> Option<&Path> {
+        None
+    }
+}
+
+static BACKEND: OnceLock<Box<dyn TrashBackend>> = OnceLock::new();
+
+/// Use `backend` for the rest of the run. Only the first call counts.
+pub fn set(backend: Box<dyn TrashBackend>) {
+    let _ = BACKEND.set(backend);
+}
+
+/// The backend for this run: the system trash unless [`set`] chose another.
+pub fn get() -> &'static dyn TrashBackend {
+    BACKEND.get_or_init(|| Box::new(System)).as_ref()
+}
+
+/// The trash of the operating system, through the trash crate
+pub struct System;
+
+fn new_trash_ctx() -> TrashContext {
+    #[allow(unused_mut)]
+    let mut ctx = TrashContext::new();
+    #[cfg(target_os = "macos")]
+    {
+        use trash::macos::TrashContextExtMacos;
+        ctx.set_delete_method(trash::macos::DeleteMethod::NsFileManager);
+    }
+    ctx
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+impl TrashBackend for System {
+    fn list(&self) -> Result<Vec<TrashItem>, trash::Error> {
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+        if let Ok(folders) = trash::os_limited::trash_folders() {
+            let folders: Vec<_> = folders.into_iter().collect();
+            return Ok(crate::cache::list(&folders));
+        }
+        trash::os_limited::list()
+    }
+
+    fn delete(&self, path: &Path) -> Result<(), trash::Error> {
+        new_trash_ctx().delete(path)
+    }
+
+    fn restore(&self, items: Vec<TrashItem>) -> Result<(), trash::Error> {
+        trash::os_limited::restore_all(items)
+    }
+
+    fn purge(&self, items: &[TrashItem]) -> Result<(), trash::Error> {
+        trash::os_limited::purge_all(items)
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+impl TrashBackend for System {
+    fn list(&self) -> Result<Vec<TrashItem>, trash::Error> {
+        Err(unsupported())
+    }
+
+    fn delete(&self, path: &Path) -> Result<(), trash::Error> {
+        new_trash_ctx().delete(path)
+    }
+
+    fn restore(&self, _items: Vec<TrashItem>) -> Result<(), trash::Error> {
+        Err(unsupported())
+    }
+
+    fn purge(&self, _items: &[TrashItem]) -> Result<(), trash::Error> {
+        Err(unsupported())
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn unsupported() -> trash::Error {
+    trash::Error::Unknown {
+        description: "managing the system trash is not supported on this platform".into(),
+    }
+}
+
+/// A directory used as the trash: items in `files/`, each with a
+/// `.trashinfo` record of the same name in `info/`
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+pub struct Directory {
+    root: PathBuf,
+}
+
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+impl Directory {
+    /// The trash in `root`, created if need be.
+    pub fn open(root: &Path) -> std::io::Result<Directory> {
+        let root = std::path::absolute(root)?;
+        std::fs::create_dir_all(root.join("files"))?;
+        std::fs::create_dir_all(root.join("info"))?;
+        Ok(Directory { root })
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+fn fs_error(path: &Path, source: std::io::Error) -> trash::Error {
+    trash::Error::FileSystem {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+impl TrashBackend for Directory {
+    fn list(&self) -> Result<Vec<TrashItem>, trash::Error> {
+        let info = self.root.join("info");
+        std::fs::read_dir(&info).map_err(|e| fs_error(&info, e))?;
+        Ok(crate::cache::list(std::slice::from_ref(&self.root)))
+    }
+
+    fn delete(&self, path: &Path) -> Result<(), trash::Error> {
+        let original = std::path::absolute(path).map_err(|e| fs_error(path, e))?;
+        path.symlink_metadata().map_err(|e| fs_error(path, e))?;
+        let now = chrono::Local::now().timestamp();
+        let (dest, info) = claim(&self.root, &original, now).map_err(|e| fs_error(path, e))?;
+        if let Err(e) = move_path(path, &dest) {
+            let _ = std::fs::remove_file(&info);
+            return Err(fs_error(path, e));
+        }
+        Ok(())
+    }
+
+    fn restore(&self, items: Vec<TrashItem>) -> Result<(), trash::Error> {
+        for (i, item) in items.iter().enumerate() {
+            let original = item.original_path();
+            if original.symlink_metadata().is_ok() {
+                return Err(trash::Error::RestoreCollision {
+                    path: original,
+                    remaining_items: items[i..].to_vec(),
+                });
+            }
+            std::fs::create_dir_all(&item.original_parent)
+                .map_err(|e| fs_error(&item.original_parent, e))?;
+            let content = crate::items::item_path(item);
+            move_path(&content, &original).map_err(|e| fs_error(&content, e))?;
+            crate::items::forget(item).map_err(|e| fs_error(Path::new(&item.id), e))?;
+        }
+        Ok(())
+    }
+
+    fn purge(&self, items: &[TrashItem]) -> Result<(), trash::Error> {
+        for item in items {
+            let content = crate::items::item_path(item);
+            let removed = match content.symlink_metadata() {
+                Ok(meta) if meta.is_dir() => std::fs::remove_dir_all(&content),
+                Ok(_) => std::fs::remove_file(&content),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(e),
+            };
+            removed.map_err(|e| fs_error(&content, e))?;
+            crate::items::forget(item).map_err(|e| fs_error(Path::new(&item.id), e))?;
+        }
+        Ok(())
+    }
+
+    fn directory(&self) -> Option<&Path> {
+        Some(&self.root)
+    }
+}
+
+/// Rename `from` to `to`, or across file systems copy it there, flushed to
+/// disk, before removing it. A copy that fails partway is removed again.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+fn move_path(from: &Path, to: &Path) -> std::io::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {}
+        other => return other,
+    }
+    if let Err(e) = copy_synced(from, to) {
+        let _ = match to.symlink_metadata() {
+            Ok(meta) if meta.is_dir() => std::fs::remove_dir_all(to),
+            Ok(_) => std::fs::remove_file(to),
+            Err(_) => Ok(()),
+        };
+        return Err(e);
+    }
+    if from.symlink_metadata()?.is_dir() {
+        std::fs::remove_dir_all(from)
+    } else {
+        std::fs::remove_file(from)
+    }
+}
+
+/// Copy `from` to `to` as it is, symlinks as symlinks, and sync what was
+/// written so the original can safely go.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+fn copy_synced(from: &Path, to: &Path) -> std::io::Result<()> {
+    let meta = from.symlink_metadata()?;
+    if meta.is_symlink() {
+        std::os::unix::fs::symlink(std::fs::read_link(from)?, to)?;
+        return Ok(());
+    }
+    if meta.is_dir() {
+        std::fs::create_dir(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_synced(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        std::fs::File::open(to)?.sync_all()?;
+    } else {
+        let mut copy = std::fs::File::create_new(to)?;
+        std::io::copy(&mut std::fs::File::open(from)?, &mut copy)?;
+        copy.sync_all()?;
+    }
+    // Best effort, as for any copy across volumes; the content is what counts
+    let _ = crate::attrs::copy(from, to);
+    Ok(())
+}
+
+/// Write a .trashinfo record in `trash` for an item trashed from `original`
+/// at `deleted`, under a name no other item there uses, and return where
+/// the item's content goes and the record.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+pub fn claim(trash: &Path, original: &Path, deleted: i64) -> std::io::Result<(PathBuf, PathBuf)> {
+    use std::io::{self, Write};
+
+    let files = trash.join("files");
+    let info_dir = trash.join("info");
+    std::fs::create_dir_all(&files)?;
+    std::fs::create_dir_all(&info_dir)?;
+    let name = original
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no file name"))?;
+    let record = trashinfo(original, deleted)?;
+    for n in 1.. {
+        let mut stem = name.to_os_string();
+        if n > 1 {
+            stem.push(format!(".{n}"));
+        }
+        let dest = files.join(&stem);
+        if dest.symlink_metadata().is_ok() {
+            continue;
+        }
+        let mut info_name = stem;
+        info_name.push(".trashinfo");
+        let info = info_dir.join(info_name);
+        match std::fs::File::create_new(&info) {
+            Ok(mut file) => {
+                file.write_all(record.as_bytes())?;
+                return Ok((dest, info));
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!()
+}
+
+/// The .trashinfo record of an item trashed from `original` at `deleted`.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+pub fn trashinfo(original: &Path, deleted: i64) -> std::io::Result<String> {
+    let when = chrono::DateTime::from_timestamp(deleted, 0)
+        .map(|t| t.with_timezone(&chrono::Local))
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("bad deletion time {deleted}"),
+            )
+        })?;
+    Ok(format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        crate::items::encode_uri_path(original),
+        when.format("%Y-%m-%dT%H:%M:%S")
+    ))
+}
+
+/// A backend that only keeps books, for tests: deleting records an item
+/// and leaves the file alone, and restoring or purging forgets it.
+#[derive(Default)]
+pub struct Mock {
+    items: std::sync::Mutex<Vec<TrashItem>>,
+}
+
+#[cfg(test)]
+impl Mock {
+    /// A mock trash already holding `items`.
+    pub fn holding(items: Vec<TrashItem>) -> Mock {
+        Mock {
+            items: std::sync::Mutex::new(items),
+        }
+    }
+}
+
+impl TrashBackend for Mock {
+    fn list(&self) -> Result<Vec<TrashItem>, trash::Error> {
+        Ok(self.items.lock().unwrap().clone())
+    }
+
+    fn delete(&self, path: &Path) -> Result<(), trash::Error> {
+        let mut items = self.items.lock().unwrap();
+        let n = items.len() + 1;
+        items.push(TrashItem {
+            id: format!("mock-{n}").into(),
+            name: path.file_name().unwrap_or_default().to_os_string(),
+            original_parent: path.parent().unwrap_or(Path::new("/")).to_path_buf(),
+            time_deleted: chrono::Local::now().timestamp(),
+        });
+        Ok(())
+    }
+
+    fn restore(&self, restored: Vec<TrashItem>) -> Result<(), trash::Error> {
+        self.items
+            .lock()
+            .unwrap()
+            .retain(|item| !restored.iter().any(|r| r.id == item.id));
+        Ok(())
+    }
+
+    fn purge(&self, purged: &[TrashItem]) -> Result<(), trash::Error> {
+        self.items
+            .lock()
+            .unwrap()
+            .retain(|item| !purged.iter().any(|p| p.id == item.id));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Trash two paths, restore one and purge the other, checking the books
+    /// after each step.
+    fn exercise(backend: &dyn TrashBackend, a: &Path, b: &Path) {
+        assert!(backend.list().unwrap().is_empty());
+        backend.delete(a).unwrap();
+        backend.delete(b).unwrap();
+        let mut items = backend.list().unwrap();
+        items.sort_by_key(|item| item.original_path());
+        let originals: Vec<_> = items.iter().map(|item| item.original_path()).collect();
+        assert_eq!(originals, [a, b]);
+
+        backend.restore(vec![items[0].clone()]).unwrap();
+        let left = backend.list().unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].original_path(), b);
+
+        backend.purge(&left).unwrap();
+        assert!(backend.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_mock() {
+        exercise(&Mock::default(), Path::new("/m/a"), Path::new("/m/b"));
+    }
+
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    fn test_directory() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let trash = Directory::open(&tmp.path().join("trash")).unwrap();
+        let a = tmp.path().join("a file");
+        let b = tmp.path().join("dir");
+        std::fs::write(&a, "a").unwrap();
+        std::fs::create_dir(&b).unwrap();
+        std::fs::write(b.join("inner"), "b").unwrap();
+        exercise(&trash, &a, &b);
+
+        // Restored in place; purged for good
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "a");
+        assert!(!b.exists());
+        let root = tmp.path().join("trash");
+        assert_eq!(std::fs::read_dir(root.join("files")).unwrap().count(), 0);
+        assert_eq!(std::fs::read_dir(root.join("info")).unwrap().count(), 0);
+    }
+
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    fn test_directory_across_file_systems() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let Ok(shm) = tempfile::TempDir::new_in("/dev/shm") else {
+            eprintln!("skipping test_directory_across_file_systems: no /dev/shm");
+            return;
+        };
+        use std::os::unix::fs::MetadataExt;
+        let dev = |path: &Path| path.metadata().unwrap().dev();
+        if dev(tmp.path()) == dev(shm.path()) {
+            eprintln!(
+                "skipping test_directory_across_file_systems: /dev/shm is not another file system"
+            );
+            return;
+        }
+        let trash = Directory::open(&shm.path().join("trash")).unwrap();
+        let dir = tmp.path().join("dir");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(dir.join("inner"), "b").unwrap();
+        std::os::unix::fs::symlink("inner", dir.join("link")).unwrap();
+
+        trash.delete(&dir).unwrap();
+        assert!(!dir.exists());
+        let files = shm.path().join("trash/files/dir");
+        assert_eq!(std::fs::read_to_string(files.join("inner")).unwrap(), "b");
+        assert_eq!(
+            std::fs::read_link(files.join("link")).unwrap(),
+            Path::new("inner")
+        );
+
+        trash.restore(trash.list().unwrap()).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("inner")).unwrap(), "b");
+        assert!(!files.exists());
+    }
+
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    fn test_directory_errors_read_plainly() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let trash = Directory::open(&tmp.path().join("trash")).unwrap();
+        let missing = tmp.path().join("missing");
+        let message = trash.delete(&missing).unwrap_err().to_string();
+        assert_eq!(
+            message,
+            format!(
+                "'{}': No such file or directory (os error 2)",
+                missing.display()
+            )
+        );
+    }
+
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    fn test_directory_restore_collision() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let trash = Directory::open(&tmp.path().join("trash")).unwrap();
+        let a = tmp.path().join("a");
+        std::fs::write(&a, "old").unwrap();
+        trash.delete(&a).unwrap();
+        std::fs::write(&a, "new").unwrap();
+        trash.delete(&a).unwrap();
+        std::fs::write(&a, "newest").unwrap();
+
+        let items = trash.list().unwrap();
+        assert!(matches!(
+            trash.restore(items),
+            Err(trash::Error::RestoreCollision { remaining_items, .. }) if remaining_items.len() == 2
+        ));
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "newest");
+    }
+}
@@ -0,0 +1,260 @@
+// Journals of batches, so a trash or restore run that dies halfway can be
+// finished or undone with --resume. Before a batch of several items starts,
+// its journal lists them all; each item trashed is then appended as it
+// goes. A batch that ends (even with errors) removes its journal, so any
+// journal left behind belongs to a run that was killed or interrupted.
+// Restores go through the backend in one call, so what a restore batch got
+// done is read off the trash instead. One JSON object per line, in
+// $XDG_STATE_HOME/trache/batches/PID.jsonl (or the platform equivalent).
+#![cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::audit::Op;
+use crate::config;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Trash,
+    Restore,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "line", rename_all = "lowercase")]
+enum Line {
+    Start {
+        kind: Kind,
+        pid: u32,
+        /// Seconds since the epoch
+        time: i64,
+        /// Directories were given with -r
+        #[serde(default)]
+        recursive: bool,
+        /// Absolute paths: to trash, or to restore items to
+        paths: Vec<PathBuf>,
+    },
+    Done {
+        path: PathBuf,
+    },
+}
+
+/// A batch that didn't finish.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Journal {
+    pub file: PathBuf,
+    pub kind: Kind,
+    pub pid: u32,
+    pub time: i64,
+    pub recursive: bool,
+    pub paths: Vec<PathBuf>,
+    /// The paths recorded as done, in order
+    pub done: Vec<PathBuf>,
+}
+
+impl Journal {
+    /// The paths not recorded as done, in order.
+    pub fn remaining(&self) -> Vec<PathBuf> {
+        self.paths
+            .iter()
+            .filter(|p| !self.done.contains(p))
+            .cloned()
+            .collect()
+    }
+}
+
+struct Current {
+    kind: Kind,
+    path: PathBuf,
+    file: File,
+}
+
+static CURRENT: Mutex<Option<Current>> = Mutex::new(None);
+
+fn dir() -> Option<PathBuf> {
+    config::state_dir().map(|d| d.join("batches"))
+}
+
+/// Start journaling a batch of `paths`, unless it is a single item or a
+/// batch is already being journaled (as when resuming one); whether it was
+/// started, and so is the caller's to [`finish`].
+pub fn begin(kind: Kind, paths: &[PathBuf], recursive: bool) -> io::Result<bool> {
+    let mut current = CURRENT.lock().unwrap();
+    if paths.len() < 2 || current.is_some() {
+        return Ok(false);
+    }
+    let Some(dir) = dir() else {
+        return Ok(false);
+    };
+    fs::create_dir_all(&dir)?;
+    let pid = std::process::id();
+    let path = dir.join(format!("{pid}.jsonl"));
+    let start = Line::Start {
+        kind,
+        pid,
+        time: chrono::Local::now().timestamp(),
+        recursive,
+        paths: paths
+            .iter()
+            .map(|p| std::path::absolute(p).unwrap_or_else(|_| p.clone()))
+            .collect(),
+    };
+    let mut file = File::create(&path)?;
+    writeln!(
+        file,
+        "{}",
+        serde_json::to_string(&start).map_err(io::Error::other)?
+    )?;
+    // The list must be on disk before anything is touched
+    file.sync_data()?;
+    *current = Some(Current { kind, path, file });
+    Ok(true)
+}
+
+/// Carry on journaling the unfinished batch in `journal`.
+pub fn adopt(journal: &Journal) -> io::Result<()> {
+    let file = OpenOptions::new().append(true).open(&journal.file)?;
+    *CURRENT.lock().unwrap() = Some(Current {
+        kind: journal.kind,
+        path: journal.file.clone(),
+        file,
+    });
+    Ok(())
+}
+
+/// Note that `op` succeeded on `path`, if that's progress in the batch.
+pub fn record(op: Op, path: &Path) {
+    let mut current = CURRENT.lock().unwrap();
+    let Some(batch) = current.as_mut() else {
+        return;
+    };
+    if batch.kind != Kind::Trash || op != Op::Trash {
+        return;
+    }
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    if let Ok(line) = serde_json::to_string(&Line::Done { path }) {
+        let _ = writeln!(batch.file, "{line}");
+    }
+}
+
+/// Whether a batch is being journaled.
+pub fn active() -> bool {
+    CURRENT.lock().unwrap().is_some()
+}
+
+/// The batch ended: its journal is no longer needed.
+pub fn finish() {
+    if let Some(batch) = CURRENT.lock().unwrap().take() {
+        let _ = fs::remove_file(batch.path);
+    }
+}
+
+/// Drop the journal of a batch that was dealt with some other way.
+pub fn discard(journal: &Journal) -> io::Result<()> {
+    match fs::remove_file(&journal.file) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Journals of batches whose process is gone, oldest first.
+pub fn unfinished() -> io::Result<Vec<Journal>> {
+    let Some(dir) = dir() else {
+        return Ok(Vec::new());
+    };
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut journals = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_none_or(|e| e != "jsonl") {
+            continue;
+        }
+        let text = fs::read_to_string(&path)?;
+        let journal = parse(&path, &text).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {e}", path.display()),
+            )
+        })?;
+        if !running(journal.pid) {
+            journals.push(journal);
+        }
+    }
+    journals.sort_by_key(|j| j.time);
+    Ok(journals)
+}
+
+fn parse(file: &Path, text: &str) -> Result<Journal, String> {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+    let first = lines.next().ok_or("empty journal")?;
+    let Line::Start {
+        kind,
+        pid,
+        time,
+        recursive,
+        paths,
+    } = serde_json::from_str(first).map_err(|e| format!("line 1: {e}"))?
+    else {
+        return Err("line 1: not the start of a batch".into());
+    };
+    let mut done = Vec::new();
+    for line in lines {
+        // The last line may have been cut short by the crash
+        if let Ok(Line::Done { path }) = serde_json::from_str(line) {
+            done.push(path);
+        }
+    }
+    Ok(Journal {
+        file: file.to_path_buf(),
+        kind,
+        pid,
+        time,
+        recursive,
+        paths,
+        done,
+    })
+}
+
+/// Whether the process `pid` is still running (and isn't us).
+#[cfg(unix)]
+fn running(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks that the process exists
+    pid != std::process::id() && unsafe { libc::kill(pid as libc::pid_t, 0) } == 0
+}
+
+#[cfg(not(unix))]
+fn running(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tolerates_a_torn_last_line() {
+        let text = "{\"line\":\"start\",\"kind\":\"trash\",\"pid\":7,\"time\":100,\
+                    \"paths\":[\"/a\",\"/b\",\"/c\"]}\n\
+                    {\"line\":\"done\",\"path\":\"/a\"}\n\
+                    {\"line\":\"done\",\"pa";
+        let journal = parse(Path::new("7.jsonl"), text).unwrap();
+        assert_eq!(journal.kind, Kind::Trash);
+        assert!(!journal.recursive);
+        assert_eq!(journal.done, [PathBuf::from("/a")]);
+        assert_eq!(
+            journal.remaining(),
+            [PathBuf::from("/b"), PathBuf::from("/c")]
+        );
+        assert!(parse(Path::new("x"), "").is_err());
+        assert!(parse(Path::new("x"), "{\"line\":\"done\",\"path\":\"/a\"}").is_err());
+    }
+}
@@ -0,0 +1,369 @@
+// An index of trash items kept between runs, so listing a trash of tens of
+// thousands of items doesn't open and parse every .trashinfo record, and
+// sizes aren't measured again each time. It lives in the state directory as
+// index.json, keyed by record path. A record is only read again when its
+// modification time or length changes, and an item's size is measured again
+// when its contents' do. Records that have gone are dropped the next time
+// their trash directory is listed. Nothing depends on the index being there:
+// a missing or unreadable one is rebuilt from the records.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+use trash::TrashItem;
+
+use crate::{config, sizing};
+
+/// Bumped when the index layout changes; older indexes are rebuilt
+const VERSION: u32 = 1;
+
+/// Enough of a file's metadata to tell that it changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Stamp {
+    secs: i64,
+    nanos: u32,
+    len: u64,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> Option<Stamp> {
+        let meta = path.symlink_metadata().ok()?;
+        let since = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Stamp {
+            secs: since.as_secs() as i64,
+            nanos: since.subsec_nanos(),
+            len: meta.len(),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    /// The record's stamp when it was read
+    stamp: Stamp,
+    path: PathBuf,
+    deleted: i64,
+    /// The contents' stamp and size, once measured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<(Stamp, u64)>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Index {
+    version: u32,
+    /// The size_strategy sizes were measured with
+    strategy: String,
+    entries: HashMap<PathBuf, Entry>,
+}
+
+impl Index {
+    /// Every item in the trash directories `folders`, reading only the
+    /// records that changed since they were indexed. Returns whether the
+    /// index changed.
+    fn list(&mut self, folders: &[PathBuf]) -> (Vec<TrashItem>, bool) {
+        let mut items = Vec::new();
+        let mut changed = false;
+        for folder in folders {
+            // Like the trash crate, skip what can't be read
+            let Ok(entries) = fs::read_dir(folder.join("info")) else {
+                continue;
+            };
+            let mut seen = Vec::new();
+            for entry in entries.flatten() {
+                let record = entry.path();
+                if record.extension().is_none_or(|ext| ext != "trashinfo")
+                    || !entry.file_type().is_ok_and(|t| t.is_file())
+                {
+                    continue;
+                }
+                let Some(stamp) = Stamp::of(&record) else {
+                    continue;
+                };
+                let fresh = self.entries.get(&record).is_some_and(|e| e.stamp == stamp);
+                if !fresh {
+                    let Some((path, deleted)) = fs::read_to_string(&record)
+                        .ok()
+                        .and_then(|text| parse(&text, folder))
+                    else {
+                        continue;
+                    };
+                    let entry = Entry {
+                        stamp,
+                        path,
+                        deleted,
+                        size: None,
+                    };
+                    self.entries.insert(record.clone(), entry);
+                    changed = true;
+                }
+                let entry = &self.entries[&record];
+                items.push(TrashItem {
+                    id: record.clone().into_os_string(),
+                    name: entry.path.file_name().unwrap_or_default().to_os_string(),
+                    original_parent: entry.path.parent().unwrap_or(Path::new("/")).to_path_buf(),
+                    time_deleted: entry.deleted,
+                });
+                seen.push(record);
+            }
+            let info = folder.join("info");
+            let before = self.entries.len();
+            let seen: std::collections::HashSet<_> = seen.into_iter().collect();
+            self.entries
+                .retain(|record, _| record.parent() != Some(&info) || seen.contains(record));
+            changed |= self.entries.len() != before;
+        }
+        (items, changed)
+    }
+}
+
+/// The original path and deletion time in a record from the trash directory
+/// `folder`, which is what relative paths are relative to the top of. As in
+/// the trash crate, a missing deletion time reads as -1 and a bad one makes
+/// the record unusable.
+fn parse(text: &str, folder: &Path) -> Option<(PathBuf, i64)> {
+    use chrono::TimeZone;
+
+    let mut path = None;
+    let mut deleted = -1;
+    for line in text.lines().skip(1) {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match key.trim() {
+            "Path" => {
+                let decoded = crate::items::decode_uri_path(value.trim());
+                path = Some(if decoded.is_relative() {
+                    topdir(folder).join(decoded)
+                } else {
+                    decoded
+                });
+            }
+            "DeletionDate" => {
+                let naive =
+                    chrono::NaiveDateTime::parse_from_str(value.trim(), "%Y-%m-%dT%H:%M:%S")
+                        .ok()?;
+                deleted = chrono::Local
+                    .from_local_datetime(&naive)
+                    .earliest()?
+                    .timestamp();
+            }
+            _ => {}
+        }
+    }
+    path.filter(|p| p.file_name().is_some())
+        .map(|path| (path, deleted))
+}
+
+/// The volume a trash directory belongs to: $topdir for $topdir/.Trash/$uid
+/// and $topdir/.Trash-$uid alike.
+fn topdir(folder: &Path) -> &Path {
+    let parent = folder.parent().unwrap_or(folder);
+    if parent.file_name().is_some_and(|name| name == ".Trash") {
+        parent.parent().unwrap_or(parent)
+    } else {
+        parent
+    }
+}
+
+struct State {
+    index: Index,
+    dirty: bool,
+}
+
+static STATE: Mutex<Option<State>> = Mutex::new(None);
+
+fn index_path() -> Option<PathBuf> {
+    config::state_dir().map(|d| d.join("index.json"))
+}
+
+fn with_state<R>(f: impl FnOnce(&mut State) -> R) -> R {
+    let mut guard = STATE.lock().unwrap();
+    let state = guard.get_or_insert_with(|| {
+        let strategy = format!("{:?}", sizing::kind());
+        let mut index = index_path()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| serde_json::from_slice::<Index>(&bytes).ok())
+            .filter(|index| index.version == VERSION)
+            .unwrap_or_default();
+        if index.strategy != strategy {
+            for entry in index.entries.values_mut() {
+                entry.size = None;
+            }
+        }
+        index.version = VERSION;
+        index.strategy = strategy;
+        State {
+            index,
+            dirty: false,
+        }
+    });
+    f(state)
+}
+
+/// Every item in the trash directories `folders`.
+pub fn list(folders: &[PathBuf]) -> Vec<TrashItem> {
+    with_state(|state| {
+        let (items, changed) = state.index.list(folders);
+        state.dirty |= changed;
+        items
+    })
+}
+
+/// The size of `item`, from the index while its contents are unchanged, or
+/// else `measure`d and remembered.
+pub fn size(item: &TrashItem, measure: impl FnOnce(&Path) -> u64) -> u64 {
+    let record = PathBuf::from(&item.id);
+    let contents = crate::items::item_path(item);
+    let stamp = Stamp::of(&contents);
+    let cached = with_state(|state| {
+        let entry = state.index.entries.get(&record)?;
+        entry
+            .size
+            .filter(|(at, _)| Some(*at) == stamp)
+            .map(|(_, bytes)| bytes)
+    });
+    if let Some(bytes) = cached {
+        return bytes;
+    }
+    // Measure without holding the lock; a big tree can take a while
+    let bytes = measure(&contents);
+    if let Some(stamp) = stamp {
+        with_state(|state| {
+            if let Some(entry) = state.index.entries.get_mut(&record) {
+                entry.size = Some((stamp, bytes));
+                state.dirty = true;
+            }
+        });
+    }
+    bytes
+}
+
+/// Write the index back if this run changed it. Failing to is harmless, so
+/// it's only a warning.
+pub fn flush() {
+    let mut guard = STATE.lock().unwrap();
+    let Some(state) = guard.as_mut().filter(|state| state.dirty) else {
+        return;
+    };
+    let Some(path) = index_path() else {
+        return;
+    };
+    if let Err(e) = write(&path, &state.index) {
+        eprintln!(
+            "{}: warning: cannot save the trash index '{}': {e}",
+            crate::rmcompat::prog(),
+            path.display()
+        );
+    }
+    state.dirty = false;
+}
+
+fn write(path: &Path, index: &Index) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Another run may be saving too; each writes its own sibling and the last
+    // rename wins, which only costs the loser's additions
+    let tmp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+    fs::write(&tmp, serde_json::to_vec(index)?)?;
+    fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trash(dir: &Path, records: &[(&str, &str)]) -> PathBuf {
+        let folder = dir.join("Trash");
+        fs::create_dir_all(folder.join("info")).unwrap();
+        fs::create_dir_all(folder.join("files")).unwrap();
+        for (name, path) in records {
+            fs::write(
+                folder.join(format!("info/{name}.trashinfo")),
+                format!("[Trash Info]\nPath={path}\nDeletionDate=2024-01-15T10:30:00\n"),
+            )
+            .unwrap();
+        }
+        folder
+    }
+
+    fn originals(items: &[TrashItem]) -> Vec<PathBuf> {
+        let mut paths: Vec<_> = items.iter().map(|item| item.original_path()).collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_list_reads_only_changed_records() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let folder = trash(tmp.path(), &[("a", "/x/a"), ("b", "/x/b")]);
+        let mut index = Index::default();
+        let (items, changed) = index.list(std::slice::from_ref(&folder));
+        assert!(changed);
+        assert_eq!(originals(&items), [Path::new("/x/a"), Path::new("/x/b")]);
+
+        // Same length and mtime: the indexed entry is trusted
+        let record = folder.join("info/a.trashinfo");
+        let mtime = fs::metadata(&record).unwrap().modified().unwrap();
+        fs::write(
+            &record,
+            "[Trash Info]\nPath=/y/a\nDeletionDate=2024-01-15T10:30:00\n",
+        )
+        .unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&record)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        let (items, changed) = index.list(std::slice::from_ref(&folder));
+        assert!(!changed);
+        assert_eq!(originals(&items), [Path::new("/x/a"), Path::new("/x/b")]);
+
+        // A changed record is read again, and a removed one dropped
+        fs::write(
+            &record,
+            "[Trash Info]\nPath=/y/aa\nDeletionDate=2024-01-15T10:30:00\n",
+        )
+        .unwrap();
+        fs::remove_file(folder.join("info/b.trashinfo")).unwrap();
+        let (items, changed) = index.list(std::slice::from_ref(&folder));
+        assert!(changed);
+        assert_eq!(originals(&items), [Path::new("/y/aa")]);
+        assert_eq!(index.entries.len(), 1);
+    }
+
+    #[test]
+    fn test_parse() {
+        let folder = Path::new("/media/usb/.Trash-1000");
+        assert_eq!(
+            parse(
+                "[Trash Info]\nPath=dir/a%20b\nDeletionDate=2024-01-15T10:30:00\n",
+                folder
+            )
+            .unwrap()
+            .0,
+            Path::new("/media/usb/dir/a b")
+        );
+        let shared = Path::new("/media/usb/.Trash/1000");
+        assert_eq!(
+            parse("[Trash Info]\nPath=a\n", shared),
+            Some((PathBuf::from("/media/usb/a"), -1))
+        );
+        assert_eq!(
+            parse("[Trash Info]\nPath=/a\nDeletionDate=soon\n", folder),
+            None
+        );
+        assert_eq!(
+            parse("[Trash Info]\nDeletionDate=2024-01-15T10:30:00\n", folder),
+            None
+        );
+    }
+}
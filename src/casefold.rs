@@ -0,0 +1,107 @@
+// Case-insensitive destination handling. On file systems that ignore case
+// (the default on macOS and Windows), restoring `Readme.md` next to an existing
+// `README.md` collides with it even though the names differ.
+
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Whether names in `dir` are looked up case-insensitively, found by checking
+/// whether `dir` can also be reached under a case-swapped name.
+pub fn is_case_insensitive(dir: &Path) -> bool {
+    let Ok(dir) = dir.canonicalize() else {
+        return false;
+    };
+    // Probe with the nearest ancestor whose name has letters to swap
+    for probe in dir.ancestors() {
+        let Some(name) = probe.file_name().and_then(OsStr::to_str) else {
+            continue;
+        };
+        let swapped = swap_case(name);
+        if swapped == name {
+            continue;
+        }
+        return probe.with_file_name(swapped).symlink_metadata().is_ok();
+    }
+    cfg!(any(windows, target_os = "macos"))
+}
+
+fn swap_case(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_lowercase() {
+                c.to_uppercase().next().unwrap_or(c)
+            } else {
+                c.to_lowercase().next().unwrap_or(c)
+            }
+        })
+        .collect()
+}
+
+/// The existing entry that `path` would collide with under a different
+/// spelling, e.g. `README.md` for `Readme.md`, on case-insensitive file systems.
+pub fn existing_variant(path: &Path) -> Option<PathBuf> {
+    let dir = path.parent()?;
+    if !is_case_insensitive(dir) {
+        return None;
+    }
+    let name = find_variant(dir, path.file_name()?)?;
+    Some(dir.join(name))
+}
+
+fn find_variant(dir: &Path, name: &OsStr) -> Option<OsString> {
+    let wanted = fold(name);
+    fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.file_name())
+        .find(|other| other != name && fold(other) == wanted)
+}
+
+fn fold(name: &OsStr) -> String {
+    name.to_string_lossy().to_lowercase()
+}
+
+/// A key under which paths that would collide at their destination are equal.
+pub fn collision_key(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(dir) if is_case_insensitive(dir) => PathBuf::from(fold(path.as_os_str())),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swap_case() {
+        assert_eq!(swap_case("ReadMe.md"), "rEADmE.MD");
+        assert_eq!(swap_case("123"), "123");
+    }
+
+    #[test]
+    fn test_find_variant() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        fs::write(tmp.path().join("README.md"), "").unwrap();
+        assert_eq!(
+            find_variant(tmp.path(), OsStr::new("Readme.md")),
+            Some(OsString::from("README.md"))
+        );
+        assert_eq!(find_variant(tmp.path(), OsStr::new("README.md")), None);
+        assert_eq!(find_variant(tmp.path(), OsStr::new("other.md")), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_linux_tmp_is_case_sensitive() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        assert!(!is_case_insensitive(tmp.path()));
+        fs::write(tmp.path().join("README.md"), "").unwrap();
+        assert_eq!(existing_variant(&tmp.path().join("Readme.md")), None);
+        assert_eq!(
+            collision_key(&tmp.path().join("Readme.md")),
+            tmp.path().join("Readme.md")
+        );
+    }
+}
@@ -0,0 +1,345 @@
+// --trash-check: what in a trash directory doesn't add up to an item. Other
+// tools and crashes leave contents with no .trashinfo record, records whose
+// contents are gone, records with no usable Path or DeletionDate, and entries
+// that can't be read at all. With --repair each is fixed where that's
+// possible: a record is written for stray contents (restoring them puts them
+// in the rescue directory, as their real path is unknown), a record with no
+// contents is removed, a record with a bad date is rewritten, and an item
+// whose original path can't be read is adopted into the rescue directory
+// like stray contents. Nothing is deleted along with its record. Unreadable
+// entries are only reported.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::analyze;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// Under files/ with no record in info/
+    Orphaned(PathBuf),
+    /// In info/ with nothing under files/
+    Dangling(PathBuf),
+    /// A record trash tools can't make sense of; `original` is its Path if
+    /// that much could be read
+    Malformed {
+        record: PathBuf,
+        reason: String,
+        original: Option<PathBuf>,
+    },
+    Unreadable {
+        path: PathBuf,
+        error: String,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Orphaned(path) => {
+                write!(
+                    f,
+                    "'{}': contents with no .trashinfo record",
+                    path.display()
+                )
+            }
+            Problem::Dangling(record) => {
+                write!(f, "'{}': record with no contents", record.display())
+            }
+            Problem::Malformed { record, reason, .. } => {
+                write!(f, "'{}': malformed record: {reason}", record.display())
+            }
+            Problem::Unreadable { path, error } => {
+                write!(f, "'{}': unreadable: {error}", path.display())
+            }
+        }
+    }
+}
+
+/// What --repair does about a problem
+#[derive(Debug, PartialEq, Eq)]
+pub enum Repair {
+    /// Write a record saying the contents were trashed from this path
+    Record(PathBuf),
+    RemoveRecord,
+    /// Rewrite the record with this original path, keeping its deletion
+    /// time if that can be read and using the contents' mtime if not
+    Rewrite(PathBuf),
+}
+
+impl fmt::Display for Repair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Repair::Record(path) => {
+                write!(f, "record it as trashed from '{}'", path.display())
+            }
+            Repair::RemoveRecord => write!(f, "remove the record"),
+            Repair::Rewrite(_) => write!(f, "rewrite the record"),
+        }
+    }
+}
+
+/// Everything wrong in the freedesktop trash directory `trash_dir`.
+pub fn scan(trash_dir: &Path) -> io::Result<Vec<Problem>> {
+    let orphans = analyze::orphans(trash_dir)?;
+    let mut problems: Vec<Problem> = orphans
+        .contents
+        .into_iter()
+        .map(Problem::Orphaned)
+        .chain(orphans.records.iter().cloned().map(Problem::Dangling))
+        .collect();
+
+    let info = trash_dir.join("info");
+    let entries = match fs::read_dir(&info) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(problems),
+        Err(e) => return Err(e),
+    };
+    let mut records: Vec<PathBuf> = entries
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<_>>()?;
+    records.sort();
+    for record in records {
+        if record.extension().is_none_or(|ext| ext != "trashinfo")
+            || orphans.records.contains(&record)
+        {
+            continue;
+        }
+        match fs::read(&record) {
+            Ok(bytes) => {
+                if let Err((reason, original)) = validate(&bytes) {
+                    problems.push(Problem::Malformed {
+                        record,
+                        reason,
+                        original,
+                    });
+                }
+            }
+            Err(e) => problems.push(Problem::Unreadable {
+                path: record,
+                error: e.to_string(),
+            }),
+        }
+    }
+    Ok(problems)
+}
+
+/// Check a record, returning why it's malformed and the original path it
+/// gives, if any.
+fn validate(bytes: &[u8]) -> Result<(), (String, Option<PathBuf>)> {
+    let text = std::str::from_utf8(bytes).map_err(|_| ("not UTF-8".to_string(), None))?;
+    let header = text.lines().next() == Some("[Trash Info]");
+    let mut original = None;
+    let mut date = None;
+    let mut stray = None;
+    for line in text.lines().skip(usize::from(header)) {
+        match line.split_once('=') {
+            Some(("Path", value)) => original = Some(value),
+            Some(("DeletionDate", value)) => date = Some(value),
+            Some(_) => {}
+            None if line.trim().is_empty() => {}
+            None => stray = stray.or(Some(line)),
+        }
+    }
+    let original = original
+        .map(crate::items::decode_uri_path)
+        .filter(|path| path.file_name().is_some());
+    let reason = if original.is_none() {
+        "no usable Path".to_string()
+    } else if !header {
+        "no [Trash Info] header".to_string()
+    } else if let Some(line) = stray {
+        format!("stray line '{line}'")
+    } else {
+        match date {
+            None => "no DeletionDate".to_string(),
+            Some(value) => {
+                if chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").is_ok() {
+                    return Ok(());
+                }
+                format!("bad DeletionDate '{value}'")
+            }
+        }
+    };
+    Err((reason, original))
+}
+
+/// What --repair would do about `problem`; stray contents, and items whose
+/// original path can't be read, are recorded as trashed from `adopt_dir`.
+/// None for what can't be repaired.
+pub fn plan(problem: &Problem, adopt_dir: &Path) -> Option<Repair> {
+    match problem {
+        Problem::Orphaned(path) => Some(Repair::Record(adopt_dir.join(path.file_name()?))),
+        Problem::Dangling(_) => Some(Repair::RemoveRecord),
+        Problem::Malformed {
+            original: Some(original),
+            ..
+        } => Some(Repair::Rewrite(original.clone())),
+        Problem::Malformed {
+            record,
+            original: None,
+            ..
+        } => Some(Repair::Rewrite(adopt_dir.join(record.file_stem()?))),
+        Problem::Unreadable { .. } => None,
+    }
+}
+
+/// Carry out `repair` for `problem` in `trash_dir`.
+pub fn apply(trash_dir: &Path, problem: &Problem, repair: &Repair) -> io::Result<()> {
+    match (problem, repair) {
+        (Problem::Orphaned(contents), Repair::Record(original)) => {
+            let mut name = contents.file_name().unwrap_or_default().to_os_string();
+            name.push(".trashinfo");
+            let record = trash_dir.join("info").join(name);
+            let text = crate::backend::trashinfo(original, mtime(contents))?;
+            let mut file = fs::File::create_new(record)?;
+            io::Write::write_all(&mut file, text.as_bytes())
+        }
+        (Problem::Dangling(record), Repair::RemoveRecord) => fs::remove_file(record),
+        (Problem::Malformed { record, .. }, Repair::Rewrite(original)) => {
+            let deleted = fs::read_to_string(record)
+                .ok()
+                .and_then(|text| recorded_time(&text))
+                .unwrap_or_else(|| mtime(&contents_of(record)));
+            let text = crate::backend::trashinfo(original, deleted)?;
+            let tmp = record.with_extension("trashinfo.tmp");
+            fs::write(&tmp, text)?;
+            fs::rename(&tmp, record)
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cannot {repair} for this problem"),
+        )),
+    }
+}
+
+/// The contents under files/ that `record` in info/ describes.
+pub fn contents_of(record: &Path) -> PathBuf {
+    let stem = record.file_stem().unwrap_or_default();
+    let trash_dir = record.parent().and_then(Path::parent).unwrap_or(record);
+    trash_dir.join("files").join(stem)
+}
+
+/// The DeletionDate of a record, if it parses.
+fn recorded_time(text: &str) -> Option<i64> {
+    use chrono::TimeZone;
+
+    let value = text
+        .lines()
+        .find_map(|line| line.strip_prefix("DeletionDate="))?;
+    let naive = chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").ok()?;
+    Some(
+        chrono::Local
+            .from_local_datetime(&naive)
+            .earliest()?
+            .timestamp(),
+    )
+}
+
+/// When `path` was last modified, standing in for an unknown deletion time.
+fn mtime(path: &Path) -> i64 {
+    path.symlink_metadata()
+        .and_then(|meta| meta.modified())
+        .map(|time| chrono::DateTime::<chrono::Local>::from(time).timestamp())
+        .unwrap_or_else(|_| chrono::Local::now().timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOOD: &str = "[Trash Info]\nPath=/home/u/a.txt\nDeletionDate=2024-01-15T10:30:00\n";
+
+    /// A trash with one healthy item and one of each problem.
+    fn messy(trash: &Path) {
+        fs::create_dir_all(trash.join("files")).unwrap();
+        fs::create_dir_all(trash.join("info")).unwrap();
+        for name in ["good", "stray", "baddate", "nopath"] {
+            fs::write(trash.join("files").join(name), name).unwrap();
+        }
+        let records = [
+            ("good", GOOD),
+            ("gone", GOOD),
+            (
+                "baddate",
+                "[Trash Info]\nPath=/home/u/b\nDeletionDate=yesterday\n",
+            ),
+            ("nopath", "[Trash Info]\nDeletionDate=2024-01-15T10:30:00\n"),
+        ];
+        for (name, text) in records {
+            fs::write(trash.join(format!("info/{name}.trashinfo")), text).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_scan_finds_each_problem() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let trash = tmp.path();
+        messy(trash);
+        let info = trash.join("info");
+        assert_eq!(
+            scan(trash).unwrap(),
+            [
+                Problem::Orphaned(trash.join("files/stray")),
+                Problem::Dangling(info.join("gone.trashinfo")),
+                Problem::Malformed {
+                    record: info.join("baddate.trashinfo"),
+                    reason: "bad DeletionDate 'yesterday'".into(),
+                    original: Some("/home/u/b".into()),
+                },
+                Problem::Malformed {
+                    record: info.join("nopath.trashinfo"),
+                    reason: "no usable Path".into(),
+                    original: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repair_leaves_a_consistent_trash() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let trash = tmp.path();
+        messy(trash);
+        let adopt = Path::new("/rescue");
+        for problem in scan(trash).unwrap() {
+            let repair = plan(&problem, adopt).unwrap();
+            apply(trash, &problem, &repair).unwrap();
+        }
+        assert_eq!(scan(trash).unwrap(), []);
+
+        let stray = fs::read_to_string(trash.join("info/stray.trashinfo")).unwrap();
+        assert!(stray.contains("Path=/rescue/stray\n"));
+        assert!(!trash.join("info/gone.trashinfo").exists());
+        // Kept, and restorable to the rescue directory
+        let nopath = fs::read_to_string(trash.join("info/nopath.trashinfo")).unwrap();
+        assert!(nopath.contains("Path=/rescue/nopath\n"));
+        assert!(nopath.contains("DeletionDate=2024-01-15T10:30:00\n"));
+        assert_eq!(
+            fs::read_to_string(trash.join("files/nopath")).unwrap(),
+            "nopath"
+        );
+        assert_eq!(
+            fs::read_to_string(trash.join("files/good")).unwrap(),
+            "good"
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(validate(GOOD.as_bytes()), Ok(()));
+        let reason = |text: &str| validate(text.as_bytes()).unwrap_err().0;
+        assert_eq!(
+            reason("Path=/a\nDeletionDate=2024-01-15T10:30:00\n"),
+            "no [Trash Info] header"
+        );
+        assert_eq!(
+            reason("[Trash Info]\nPath=/a\ngarbage\nDeletionDate=2024-01-15T10:30:00\n"),
+            "stray line 'garbage'"
+        );
+        assert_eq!(reason("[Trash Info]\nPath=/a\n"), "no DeletionDate");
+        assert_eq!(reason("[Trash Info]\nPath=/\n"), "no usable Path");
+    }
+}
@@ -0,0 +1,343 @@
+// Content hashes of trashed items, so --verify can tell whether a trashed
+// copy was corrupted or modified while it sat in the trash. With --checksum
+// (or checksums = true) each file or directory is hashed just before it's
+// trashed and the SHA-256 kept in the state directory's checksums.jsonl,
+// keyed by the ID of the item it became. The backends don't say which item
+// that is, so hashes wait until the batch is over and are matched to items
+// with one listing of the trash. Records go when their item is
+// restored or purged, since a later item may be given the same ID.
+#![cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use trash::TrashItem;
+
+use crate::config;
+
+#[derive(Serialize, Deserialize)]
+struct Record {
+    id: String,
+    /// The item's original path, for whoever reads the file
+    path: PathBuf,
+    sha256: String,
+}
+
+/// A hash taken just before its path was trashed, not yet matched to the
+/// item the path became
+struct Pending {
+    path: PathBuf,
+    sha256: String,
+    /// No later than the item's deletion time
+    since: i64,
+}
+
+static PENDING: Mutex<Vec<Pending>> = Mutex::new(Vec::new());
+
+/// What --verify found for one item
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Intact,
+    /// The trashed copy no longer hashes to what was recorded
+    Changed,
+    /// Trashed without --checksum
+    Unrecorded,
+}
+
+fn store_path() -> Option<PathBuf> {
+    config::state_dir().map(|d| d.join("checksums.jsonl"))
+}
+
+/// The SHA-256 of `path`, in hex. A directory's covers the names, kinds and
+/// contents of everything inside it, and a symbolic link's its target.
+pub fn hash(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    feed(&mut hasher, path, Path::new(""))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn feed(hasher: &mut Sha256, root: &Path, rel: &Path) -> io::Result<()> {
+    // Joining an empty path would add a trailing slash, which a file refuses
+    let path = if rel.as_os_str().is_empty() {
+        root.to_path_buf()
+    } else {
+        root.join(rel)
+    };
+    let meta = path.symlink_metadata()?;
+    hasher.update(rel.as_os_str().as_encoded_bytes());
+    hasher.update([0]);
+    if meta.is_symlink() {
+        hasher.update(b"l");
+        hasher.update(fs::read_link(&path)?.as_os_str().as_encoded_bytes());
+    } else if meta.is_dir() {
+        hasher.update(b"d");
+        let mut names: Vec<_> = fs::read_dir(&path)?
+            .map(|entry| entry.map(|e| e.file_name()))
+            .collect::<io::Result<_>>()?;
+        names.sort();
+        for name in names {
+            feed(hasher, root, &rel.join(name))?;
+        }
+    } else {
+        hasher.update(b"f");
+        hasher.update(meta.len().to_le_bytes());
+        io::copy(&mut fs::File::open(&path)?, hasher)?;
+    }
+    hasher.update([0]);
+    Ok(())
+}
+
+/// Note that `path`, trashed at or after `since`, hashed to `sha256` just
+/// before it went. [`flush`] records it against the item it became.
+pub fn note(path: &Path, sha256: String, since: i64) {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    PENDING.lock().unwrap().push(Pending {
+        path,
+        sha256,
+        since,
+    });
+}
+
+/// Record every noted hash against the item its path became, listing the
+/// trash once for all of them. Called at the end of each batch and by
+/// status::finish.
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+pub fn flush() {
+    let pending = std::mem::take(&mut *PENDING.lock().unwrap());
+    if pending.is_empty() {
+        return;
+    }
+    let prog = crate::rmcompat::prog();
+    let items = match crate::items::list() {
+        Ok(items) => items,
+        Err(e) => {
+            eprintln!("{prog}: warning: cannot record checksums: {e}");
+            return;
+        }
+    };
+    let (records, lost) = matched(pending, &items);
+    for path in lost {
+        eprintln!(
+            "{prog}: warning: cannot record checksum of '{}': it can't be found in the trash",
+            path.display()
+        );
+    }
+    if let Err(e) = append(&records) {
+        eprintln!("{prog}: warning: cannot record checksums: {e}");
+    }
+}
+
+/// The records for `pending` among `items`: each hash goes with the newest
+/// item trashed from its path since it was taken. Also returns the paths
+/// with no such item.
+fn matched(pending: Vec<Pending>, items: &[TrashItem]) -> (Vec<Record>, Vec<PathBuf>) {
+    let mut by_path: HashMap<PathBuf, Vec<&TrashItem>> = HashMap::new();
+    for item in items {
+        by_path.entry(item.original_path()).or_default().push(item);
+    }
+    let mut records = Vec::new();
+    let mut lost = Vec::new();
+    for Pending {
+        path,
+        sha256,
+        since,
+    } in pending
+    {
+        let item = by_path.get(&path).and_then(|items| {
+            items
+                .iter()
+                .filter(|item| item.time_deleted >= since)
+                .max_by_key(|item| item.time_deleted)
+        });
+        match item {
+            Some(item) => records.push(Record {
+                id: item.id.to_string_lossy().into_owned(),
+                path,
+                sha256,
+            }),
+            None => lost.push(path),
+        }
+    }
+    (records, lost)
+}
+
+fn append(records: &[Record]) -> io::Result<()> {
+    let Some(store) = store_path() else {
+        return Ok(());
+    };
+    if records.is_empty() {
+        return Ok(());
+    }
+    if let Some(parent) = store.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut text = String::new();
+    for record in records {
+        text.push_str(&serde_json::to_string(record)?);
+        text.push('\n');
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(store)?;
+    file.write_all(text.as_bytes())
+}
+
+/// Every recorded hash, by item ID.
+pub fn recorded() -> io::Result<HashMap<String, String>> {
+    let Some(store) = store_path() else {
+        return Ok(HashMap::new());
+    };
+    match fs::File::open(store) {
+        Ok(file) => Ok(parse(BufReader::new(file))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(e) => Err(e),
+    }
+}
+
+fn parse(reader: impl BufRead) -> HashMap<String, String> {
+    reader
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str::<Record>(&line).ok())
+        .map(|r| (r.id, r.sha256))
+        .collect()
+}
+
+/// Hash the trashed copy of `item` and compare it with its record in
+/// `recorded`.
+pub fn verify(item: &TrashItem, recorded: &HashMap<String, String>) -> io::Result<Verdict> {
+    let Some(sha256) = recorded.get(item.id.to_string_lossy().as_ref()) else {
+        return Ok(Verdict::Unrecorded);
+    };
+    Ok(if hash(&crate::items::item_path(item))? == *sha256 {
+        Verdict::Intact
+    } else {
+        Verdict::Changed
+    })
+}
+
+/// Drop the records of `items`, which have left the trash; a later item
+/// may be given the same ID.
+pub fn forget(items: &[TrashItem]) -> io::Result<()> {
+    let Some(store) = store_path() else {
+        return Ok(());
+    };
+    let text = match fs::read_to_string(&store) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let gone: HashSet<String> = items
+        .iter()
+        .map(|item| item.id.to_string_lossy().into_owned())
+        .collect();
+    let kept = without(&text, &gone);
+    if kept.len() == text.len() {
+        return Ok(());
+    }
+    // Write a sibling and rename it over, so a crash can't lose every record
+    let tmp = store.with_extension("jsonl.tmp");
+    fs::write(&tmp, kept)?;
+    fs::rename(&tmp, &store)
+}
+
+/// The lines of `text` except the records of the items in `gone`.
+fn without(text: &str, gone: &HashSet<String>) -> String {
+    text.lines()
+        .filter(|line| serde_json::from_str::<Record>(line).map_or(true, |r| !gone.contains(&r.id)))
+        .flat_map(|line| [line, "\n"])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_file() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let a = tmp.path().join("a");
+        let b = tmp.path().join("b");
+        fs::write(&a, "same").unwrap();
+        fs::write(&b, "same").unwrap();
+        assert_eq!(hash(&a).unwrap(), hash(&b).unwrap());
+        assert_eq!(hash(&a).unwrap().len(), 64);
+        fs::write(&b, "other").unwrap();
+        assert_ne!(hash(&a).unwrap(), hash(&b).unwrap());
+    }
+
+    #[test]
+    fn test_hash_dir_sees_names_and_contents() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path().join("dir");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/x"), "1").unwrap();
+        let before = hash(&dir).unwrap();
+
+        // The same tree elsewhere hashes the same
+        let copy = tmp.path().join("copy");
+        fs::create_dir_all(copy.join("sub")).unwrap();
+        fs::write(copy.join("sub/x"), "1").unwrap();
+        assert_eq!(hash(&copy).unwrap(), before);
+
+        fs::rename(dir.join("sub/x"), dir.join("sub/y")).unwrap();
+        assert_ne!(hash(&dir).unwrap(), before);
+        fs::rename(dir.join("sub/y"), dir.join("sub/x")).unwrap();
+        fs::write(dir.join("sub/x"), "2").unwrap();
+        assert_ne!(hash(&dir).unwrap(), before);
+    }
+
+    #[test]
+    fn test_hashes_go_with_the_newest_item_since() {
+        let item = |id: &str, parent: &str, time| TrashItem {
+            id: id.into(),
+            name: "a".into(),
+            original_parent: parent.into(),
+            time_deleted: time,
+        };
+        let items = [
+            item("old", "/x", 90),
+            item("new", "/x", 110),
+            item("newer", "/x", 120),
+            item("other", "/y", 120),
+        ];
+        let pending = |parent: &str| Pending {
+            path: Path::new(parent).join("a"),
+            sha256: format!("{parent} hash"),
+            since: 100,
+        };
+        let (records, lost) = matched(vec![pending("/x"), pending("/z")], &items);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].id, "newer");
+        assert_eq!(records[0].sha256, "/x hash");
+        assert_eq!(lost, [PathBuf::from("/z/a")]);
+    }
+
+    #[test]
+    fn test_records_by_id() {
+        let lines = [
+            r#"{"id":"/t/info/a.trashinfo","path":"/a","sha256":"old"}"#,
+            r#"{"id":"/t/info/b.trashinfo","path":"/b","sha256":"b"}"#,
+            r#"{"id":"/t/info/a.trashinfo","path":"/a","sha256":"new"}"#,
+            r#"{"path":"/c","time":200,"sha256":"c"}"#,
+        ]
+        .join("\n");
+        let recorded = parse(lines.as_bytes());
+        assert_eq!(recorded.len(), 2);
+        assert_eq!(recorded["/t/info/a.trashinfo"], "new");
+
+        let gone = HashSet::from(["/t/info/a.trashinfo".to_string()]);
+        let kept = without(&lines, &gone);
+        assert_eq!(kept.lines().count(), 2);
+        assert_eq!(
+            parse(kept.as_bytes()).keys().collect::<Vec<_>>(),
+            ["/t/info/b.trashinfo"]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+// Colored output for listings and -v messages, using the same LS_COLORS
+// codes as `ls` so directories, links and executables look familiar. Prompts
+// go to stderr, so whether they're colored is decided separately.
+
+use std::borrow::Cow;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorMode {
+    /// Color when stdout is a terminal and NO_COLOR isn't set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    File,
+    Dir,
+    Symlink,
+    Exec,
+}
+
+/// SGR codes per kind of entry, as LS_COLORS spells them.
+#[derive(Debug)]
+struct Palette {
+    dir: String,
+    link: String,
+    exec: String,
+    file: Option<String>,
+    /// `*.ext=code` entries, as (suffix, code)
+    suffixes: Vec<(String, String)>,
+}
+
+impl Palette {
+    /// ls's own defaults, used when LS_COLORS is unset.
+    fn default_codes() -> Self {
+        Palette {
+            dir: "01;34".into(),
+            link: "01;36".into(),
+            exec: "01;32".into(),
+            file: None,
+            suffixes: Vec::new(),
+        }
+    }
+
+    fn parse(ls_colors: &str) -> Self {
+        let mut palette = Self::default_codes();
+        for entry in ls_colors.split(':') {
+            let Some((key, code)) = entry.split_once('=') else {
+                continue;
+            };
+            let code = code.to_string();
+            match key {
+                "di" => palette.dir = code,
+                "ln" => palette.link = code,
+                "ex" => palette.exec = code,
+                "fi" | "no" => palette.file = Some(code),
+                _ => {
+                    if let Some(suffix) = key.strip_prefix('*') {
+                        palette.suffixes.push((suffix.to_string(), code));
+                    }
+                }
+            }
+        }
+        palette
+    }
+
+    fn code(&self, kind: Kind, name: &str) -> Option<&str> {
+        match kind {
+            Kind::Dir => Some(self.dir.as_str()),
+            Kind::Symlink => Some(self.link.as_str()),
+            Kind::File | Kind::Exec => {
+                // Later LS_COLORS entries win, as in ls
+                let by_suffix = self
+                    .suffixes
+                    .iter()
+                    .rev()
+                    .find(|(suffix, _)| name.ends_with(suffix.as_str()))
+                    .map(|(_, code)| code.as_str());
+                by_suffix
+                    .or((kind == Kind::Exec).then_some(self.exec.as_str()))
+                    .or(self.file.as_deref())
+            }
+        }
+        .filter(|code| !code.is_empty() && *code != "0" && *code != "00")
+    }
+}
+
+static PALETTE: OnceLock<Palette> = OnceLock::new();
+static STDERR: OnceLock<bool> = OnceLock::new();
+
+/// Turn color on or off for the rest of the process.
+pub fn init(mode: ColorMode) {
+    let enabled = |terminal: bool| match mode {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto => {
+            terminal
+                && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                && std::env::var("TERM").map_or(true, |t| t != "dumb")
+        }
+    };
+    let _ = STDERR.set(enabled(std::io::stderr().is_terminal()));
+    if enabled(std::io::stdout().is_terminal()) {
+        let palette = match std::env::var("LS_COLORS") {
+            Ok(spec) if !spec.is_empty() => Palette::parse(&spec),
+            _ => Palette::default_codes(),
+        };
+        let _ = PALETTE.set(palette);
+    }
+}
+
+/// What kind of entry `path` is, or None when color is off (so callers can
+/// ask before the entry goes away without paying for a stat otherwise).
+pub fn kind(path: &Path) -> Option<Kind> {
+    PALETTE.get()?;
+    let meta = path.symlink_metadata().ok()?;
+    Some(if meta.is_symlink() {
+        Kind::Symlink
+    } else if meta.is_dir() {
+        Kind::Dir
+    } else if is_executable(&meta) {
+        Kind::Exec
+    } else {
+        Kind::File
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(meta: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_meta: &std::fs::Metadata) -> bool {
+    false
+}
+
+/// `text` wrapped in the color for `kind`, judging suffixes by `name`.
+pub fn paint<'a>(text: &'a str, kind: Option<Kind>, name: &str) -> Cow<'a, str> {
+    match (PALETTE.get(), kind) {
+        (Some(palette), Some(kind)) => match palette.code(kind, name) {
+            Some(code) => Cow::Owned(format!("\x1b[{code}m{text}\x1b[0m")),
+            None => Cow::Borrowed(text),
+        },
+        _ => Cow::Borrowed(text),
+    }
+}
+
+/// `text` wrapped in the SGR `code`, if stderr gets color.
+pub fn paint_stderr<'a>(text: &'a str, code: &str) -> Cow<'a, str> {
+    if STDERR.get().copied().unwrap_or(false) {
+        Cow::Owned(format!("\x1b[{code}m{text}\x1b[0m"))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ls_colors() {
+        let palette = Palette::parse("rs=0:di=01;35:ln=36:*.tar=01;31:*.TXT=33");
+        assert_eq!(palette.code(Kind::Dir, "x"), Some("01;35"));
+        assert_eq!(palette.code(Kind::Symlink, "x"), Some("36"));
+        assert_eq!(palette.code(Kind::File, "a.tar"), Some("01;31"));
+        assert_eq!(palette.code(Kind::Exec, "a.tar"), Some("01;31"));
+        assert_eq!(palette.code(Kind::Exec, "run"), Some("01;32"));
+        assert_eq!(palette.code(Kind::File, "notes.txt"), None);
+    }
+
+    #[test]
+    fn test_later_suffix_wins_and_empty_codes_ignored() {
+        let palette = Palette::parse("*.gz=31:*.tar.gz=32:di=00");
+        assert_eq!(palette.code(Kind::File, "a.tar.gz"), Some("32"));
+        assert_eq!(palette.code(Kind::Dir, "d"), None);
+    }
+}
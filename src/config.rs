@@ -0,0 +1,438 @@
+// User configuration, read from config.toml.
+// Every field is optional; a missing file is the same as an empty one.
+// Parts are only used by trash management, which macOS doesn't support yet.
+#![cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// How long `--trash-undo --to-temp` sandboxes are kept (e.g. "7d")
+    pub to_temp_ttl: Option<String>,
+    /// Volumes (glob patterns) whose trash trache leaves alone
+    pub ignore_mounts: Vec<String>,
+    /// Default for --rm-fallback
+    pub rm_fallback: Option<RmFallback>,
+    /// Refuse every operation that changes files or the trash
+    pub readonly: bool,
+    /// Where --rescue puts items that can't go back to their original volume
+    pub rescue_dir: Option<PathBuf>,
+    /// How directory sizes are measured for stats, reports and size limits
+    pub size_strategy: SizeStrategy,
+    /// Default for --max-delete
+    pub max_delete: Option<u64>,
+    /// Default for --max-size-no-prompt (e.g. "100G")
+    pub max_size_no_prompt: Option<String>,
+    /// What to do before trashing files other processes have open (Linux)
+    pub open_files: OpenFiles,
+    /// Default for --time-style
+    pub time_style: Option<String>,
+    /// Default for --hidden / --no-hidden
+    pub hidden: Option<bool>,
+    /// -I asks its one question for more arguments than this (default 3)
+    pub bulk_prompt_count: Option<usize>,
+    /// ... or when the arguments add up to more than this (e.g. "1G")
+    pub bulk_prompt_size: Option<String>,
+    /// Answers taken as yes at prompts, besides y, yes and the locale's own
+    pub yes_answers: Vec<String>,
+    /// Record a content hash of everything trashed, as --checksum does
+    pub checksums: bool,
+    /// Directory used as the trash instead of the system trash, as --trash-dir
+    pub trash_dir: Option<PathBuf>,
+    pub retention: Retention,
+    pub audit: Audit,
+    pub report: Report,
+    pub exit_codes: ExitCodes,
+    pub hooks: Hooks,
+    pub notify: Notify,
+}
+
+/// Rules applied by --trash-clean
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Retention {
+    /// Purge items deleted longer ago than this (e.g. "30d")
+    pub max_age: Option<String>,
+    /// Purge the oldest items until the trash is at most this big (e.g. "10G")
+    pub max_total_size: Option<String>,
+    /// Keep only this many of the newest items trashed from each path
+    pub keep_per_path: Option<usize>,
+    /// Patterns (as for --trash-purge) of items that are never cleaned
+    pub protect: Vec<String>,
+    /// Overrides for the trash on particular volumes, as [[retention.mount]]
+    #[serde(rename = "mount")]
+    pub mounts: Vec<MountRetention>,
+    /// Overrides for items matching a pattern, as [[retention.rule]]
+    #[serde(rename = "rule")]
+    pub rules: Vec<PatternRetention>,
+}
+
+/// Retention overrides for one volume; unset fields fall back to [retention]
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MountRetention {
+    pub path: PathBuf,
+    pub max_age: Option<String>,
+    pub max_total_size: Option<String>,
+    pub keep_per_path: Option<usize>,
+}
+
+/// Retention overrides for the items matching a pattern (as for
+/// --trash-purge); the first matching rule applies, and its unset fields
+/// fall back to the rules for the item's volume
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PatternRetention {
+    pub pattern: String,
+    pub max_age: Option<String>,
+    pub keep_per_path: Option<usize>,
+}
+
+/// Append-only log of destructive operations
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Audit {
+    pub enabled: bool,
+    /// Log file (default: <state dir>/trache/audit.log)
+    pub path: Option<PathBuf>,
+    pub format: AuditFormat,
+}
+
+/// External command told about every trashed or deleted item
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Report {
+    /// Program and arguments; receives one JSON object per line on stdin
+    pub command: Vec<String>,
+    /// Items per invocation (default: 100)
+    pub batch_size: Option<usize>,
+}
+
+/// Commands run before and after operations; see hooks.rs. Each is a
+/// program and its arguments.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hooks {
+    pub pre_trash: Vec<String>,
+    pub post_trash: Vec<String>,
+    pub pre_restore: Vec<String>,
+    pub post_restore: Vec<String>,
+    pub pre_purge: Vec<String>,
+    pub post_purge: Vec<String>,
+}
+
+/// When --notify shows a notification: after this many items or seconds
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Notify {
+    /// Items trashed, restored or purged (default: 10)
+    pub min_items: Option<usize>,
+    /// Seconds the run took (default: 5)
+    pub min_seconds: Option<u64>,
+}
+
+/// Exit codes to use instead of the defaults; see status.rs
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExitCodes {
+    pub success: Option<i64>,
+    pub partial: Option<i64>,
+    pub failure: Option<i64>,
+    pub declined: Option<i64>,
+    pub cancelled: Option<i64>,
+    pub unsupported: Option<i64>,
+    pub not_found: Option<i64>,
+    pub is_directory: Option<i64>,
+    pub cross_device: Option<i64>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditFormat {
+    #[default]
+    Json,
+    Logfmt,
+}
+
+/// What to do when a file's volume has no usable trash
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RmFallback {
+    /// Report an error (the default)
+    #[default]
+    Never,
+    /// Ask before deleting permanently
+    Prompt,
+    /// Delete permanently without asking
+    Always,
+}
+
+/// What to do about files other processes have open; see inuse.rs
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OpenFiles {
+    /// Don't look (the default)
+    #[default]
+    Ignore,
+    /// Name the processes holding them, then trash them
+    Warn,
+    /// Name the processes and ask before trashing
+    Prompt,
+}
+
+/// How to measure directories; see sizing.rs
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeStrategy {
+    /// Add up every file (exact, slow on huge trees)
+    #[default]
+    Walk,
+    /// Measure a sample of files and extrapolate from entry counts
+    Estimate,
+    /// Ask btrfs quota groups about subvolumes, walking everything else
+    Btrfs,
+}
+
+/// Location of the config file: $TRACHE_CONFIG, else the platform config dir.
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("TRACHE_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+
+    #[cfg(windows)]
+    {
+        env::var_os("APPDATA").map(|d| PathBuf::from(d).join("trache").join("config.toml"))
+    }
+
+    #[cfg(not(windows))]
+    {
+        let base = match env::var_os("XDG_CONFIG_HOME") {
+            Some(d) if !d.is_empty() => PathBuf::from(d),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("trache").join("config.toml"))
+    }
+}
+
+/// Directory for trache's own state: $XDG_STATE_HOME/trache or the platform equivalent.
+pub fn state_dir() -> Option<PathBuf> {
+    #[cfg(windows)]
+    {
+        env::var_os("LOCALAPPDATA").map(|d| PathBuf::from(d).join("trache"))
+    }
+
+    #[cfg(not(windows))]
+    {
+        let base = match env::var_os("XDG_STATE_HOME") {
+            Some(d) if !d.is_empty() => PathBuf::from(d),
+            _ => PathBuf::from(env::var_os("HOME")?)
+                .join(".local")
+                .join("state"),
+        };
+        Some(base.join("trache"))
+    }
+}
+
+/// Default for `rescue_dir`: ~/trache-rescue (%USERPROFILE%\trache-rescue on Windows).
+pub fn default_rescue_dir() -> Option<PathBuf> {
+    Some(home_dir()?.join("trache-rescue"))
+}
+
+/// The user's home directory, from $HOME (%USERPROFILE% on Windows).
+pub fn home_dir() -> Option<PathBuf> {
+    #[cfg(windows)]
+    let home = env::var_os("USERPROFILE")?;
+    #[cfg(not(windows))]
+    let home = env::var_os("HOME")?;
+    Some(PathBuf::from(home))
+}
+
+/// Whether the environment variable `var` is set to a true value
+/// ("1", "true", "yes", "on"; case-insensitive).
+pub fn env_flag(var: &str) -> bool {
+    env::var(var).is_ok_and(|v| is_truthy(&v))
+}
+
+fn is_truthy(value: &str) -> bool {
+    matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "1" | "true" | "yes" | "on"
+    )
+}
+
+pub fn load() -> Result<Config, String> {
+    let Some(path) = config_path() else {
+        return Ok(Config::default());
+    };
+
+    let text = match fs::read_to_string(&path) {
+        Ok(t) => t,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(format!("cannot read '{}': {e}", path.display())),
+    };
+
+    parse(&text).map_err(|e| format!("invalid config '{}': {e}", path.display()))
+}
+
+fn parse(text: &str) -> Result<Config, String> {
+    toml::from_str(text).map_err(|e| e.message().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_empty() {
+        let cfg = parse("").unwrap();
+        assert!(cfg.to_temp_ttl.is_none());
+    }
+
+    #[test]
+    fn test_parse_to_temp_ttl() {
+        let cfg = parse("to_temp_ttl = \"3d\"\n").unwrap();
+        assert_eq!(cfg.to_temp_ttl.as_deref(), Some("3d"));
+    }
+
+    #[test]
+    fn test_parse_retention() {
+        let cfg = parse(
+            "[retention]\n\
+             max_age = \"30d\"\n\
+             max_total_size = \"10G\"\n\
+             protect = [\"*.keep\", \"path:/home/u/important\"]\n",
+        )
+        .unwrap();
+        assert_eq!(cfg.retention.max_age.as_deref(), Some("30d"));
+        assert_eq!(cfg.retention.max_total_size.as_deref(), Some("10G"));
+        assert_eq!(cfg.retention.protect.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_retention_mounts() {
+        let cfg = parse(
+            "[retention]\n\
+             max_age = \"30d\"\n\
+             [[retention.mount]]\n\
+             path = \"/mnt/ssd\"\n\
+             max_age = \"3d\"\n\
+             [[retention.mount]]\n\
+             path = \"/mnt/hdd\"\n\
+             max_total_size = \"500G\"\n",
+        )
+        .unwrap();
+        let mounts = &cfg.retention.mounts;
+        assert_eq!(mounts.len(), 2);
+        assert_eq!(mounts[0].path, PathBuf::from("/mnt/ssd"));
+        assert_eq!(mounts[0].max_age.as_deref(), Some("3d"));
+        assert!(mounts[1].max_age.is_none());
+        assert_eq!(mounts[1].max_total_size.as_deref(), Some("500G"));
+    }
+
+    #[test]
+    fn test_parse_retention_rules() {
+        let cfg = parse(
+            "[retention]\n\
+             keep_per_path = 3\n\
+             [[retention.rule]]\n\
+             pattern = \"*.log\"\n\
+             max_age = \"2d\"\n\
+             [[retention.rule]]\n\
+             pattern = \"*.bak\"\n\
+             keep_per_path = 1\n",
+        )
+        .unwrap();
+        assert_eq!(cfg.retention.keep_per_path, Some(3));
+        let rules = &cfg.retention.rules;
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].pattern, "*.log");
+        assert_eq!(rules[0].max_age.as_deref(), Some("2d"));
+        assert_eq!(rules[1].keep_per_path, Some(1));
+        assert!(parse("[[retention.rule]]\npattern = \"x\"\nmax_total_size = \"1G\"\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_audit() {
+        let cfg = parse("[audit]\nenabled = true\nformat = \"logfmt\"\n").unwrap();
+        assert!(cfg.audit.enabled);
+        assert_eq!(cfg.audit.format, AuditFormat::Logfmt);
+        assert!(cfg.audit.path.is_none());
+    }
+
+    #[test]
+    fn test_parse_report() {
+        let cfg = parse("[report]\ncommand = [\"logger\", \"-t\", \"trache\"]\nbatch_size = 10\n")
+            .unwrap();
+        assert_eq!(cfg.report.command, vec!["logger", "-t", "trache"]);
+        assert_eq!(cfg.report.batch_size, Some(10));
+    }
+
+    #[test]
+    fn test_parse_ignore_mounts() {
+        let cfg = parse("ignore_mounts = [\"/mnt/backup\", \"/media/*\"]\n").unwrap();
+        assert_eq!(cfg.ignore_mounts, vec!["/mnt/backup", "/media/*"]);
+    }
+
+    #[test]
+    fn test_parse_rm_fallback() {
+        let cfg = parse("rm_fallback = \"prompt\"\n").unwrap();
+        assert_eq!(cfg.rm_fallback, Some(RmFallback::Prompt));
+        assert!(parse("rm_fallback = \"sometimes\"\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_readonly() {
+        assert!(parse("readonly = true\n").unwrap().readonly);
+        assert!(!parse("").unwrap().readonly);
+        assert!(parse("checksums = true\n").unwrap().checksums);
+    }
+
+    #[test]
+    fn test_parse_rescue_dir() {
+        let cfg = parse("rescue_dir = \"/srv/rescue\"\n").unwrap();
+        assert_eq!(cfg.rescue_dir, Some(PathBuf::from("/srv/rescue")));
+    }
+
+    #[test]
+    fn test_parse_open_files() {
+        assert_eq!(parse("").unwrap().open_files, OpenFiles::Ignore);
+        let cfg = parse("open_files = \"prompt\"\n").unwrap();
+        assert_eq!(cfg.open_files, OpenFiles::Prompt);
+        assert!(parse("open_files = \"kill\"\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_size_strategy() {
+        assert_eq!(parse("").unwrap().size_strategy, SizeStrategy::Walk);
+        let cfg = parse("size_strategy = \"estimate\"\n").unwrap();
+        assert_eq!(cfg.size_strategy, SizeStrategy::Estimate);
+        assert!(parse("size_strategy = \"guess\"\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_max_delete() {
+        assert_eq!(parse("max_delete = 500\n").unwrap().max_delete, Some(500));
+        assert!(parse("max_delete = -1\n").is_err());
+    }
+
+    #[test]
+    fn test_is_truthy() {
+        assert!(is_truthy("1"));
+        assert!(is_truthy("Yes"));
+        assert!(is_truthy(" on "));
+        assert!(!is_truthy("0"));
+        assert!(!is_truthy("false"));
+        assert!(!is_truthy(""));
+    }
+
+    #[test]
+    fn test_parse_unknown_key() {
+        assert!(parse("no_such_option = 1\n").is_err());
+    }
+}
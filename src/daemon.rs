@@ -0,0 +1,58 @@
+// --daemon: trache as a D-Bus service on the session bus, so file managers,
+// editors and scripts can use it as the trash broker. The service owns NAME
+// and serves the Trache1 interface at PATH, a thin layer over the operations
+// in the service module. Calls are handled one at a time, in the order they
+// arrive.
+
+use std::collections::HashMap;
+
+use crate::service::{Failures, Service};
+
+/// The well-known bus name the service owns
+pub const NAME: &str = "io.github.mevanlc.Trache";
+
+/// The object serving the interface
+pub const PATH: &str = "/io/github/mevanlc/Trache";
+
+struct Bus(Service);
+
+#[zbus::interface(name = "io.github.mevanlc.Trache1")]
+impl Bus {
+    fn trash(&self, paths: Vec<String>) -> Failures {
+        self.0.trash(paths)
+    }
+
+    /// Each item's short ID, original path, and when it was trashed
+    fn list(&self) -> zbus::fdo::Result<Vec<(String, String, i64)>> {
+        let items = self.0.list().map_err(zbus::fdo::Error::Failed)?;
+        Ok(items
+            .into_iter()
+            .map(|item| (item.id, item.path, item.deleted))
+            .collect())
+    }
+
+    fn restore(&self, ids: Vec<String>) -> zbus::fdo::Result<(HashMap<String, String>, Failures)> {
+        self.0.restore(ids).map_err(zbus::fdo::Error::Failed)
+    }
+
+    fn purge(&self, ids: Vec<String>) -> zbus::fdo::Result<Failures> {
+        self.0.purge(ids).map_err(zbus::fdo::Error::Failed)
+    }
+
+    fn stats(&self) -> zbus::fdo::Result<(u64, u64)> {
+        self.0.stats().map_err(zbus::fdo::Error::Failed)
+    }
+}
+
+/// Own NAME on the session bus and serve calls with `service` until the
+/// process is stopped. Only returns if the service can't be started.
+pub fn serve(service: Service) -> zbus::Result<()> {
+    let _connection = zbus::blocking::connection::Builder::session()?
+        .name(NAME)?
+        .serve_at(PATH, Bus(service))?
+        .build()?;
+    eprintln!("trache: serving {NAME} on the session bus");
+    loop {
+        std::thread::park();
+    }
+}
@@ -0,0 +1,326 @@
+// --trash-diff: a unified diff, as `diff -u` prints it, between a trashed
+// file and whatever now sits at its original path. Lines are compared as
+// bytes, so files that aren't UTF-8 still diff; files with a NUL byte near
+// the start are treated as binary and only reported as differing.
+#![cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+
+use std::ops::Range;
+
+/// Lines of unchanged context around each change
+const CONTEXT: usize = 3;
+
+/// How far into a file to look for a NUL byte, as diff does
+const BINARY_PROBE: usize = 8000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    /// Line `.0` of the old file is line `.1` of the new one
+    Same(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+pub fn is_binary(data: &[u8]) -> bool {
+    data[..data.len().min(BINARY_PROBE)].contains(&0)
+}
+
+/// The diff from `old` to `new` under the given `---` and `+++` labels;
+/// empty when they're the same.
+pub fn unified(old: &[u8], new: &[u8], old_label: &str, new_label: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+    if old == new {
+        return out;
+    }
+    if is_binary(old) || is_binary(new) {
+        out.extend_from_slice(
+            format!("Binary files {old_label} and {new_label} differ\n").as_bytes(),
+        );
+        return out;
+    }
+    let a: Vec<&[u8]> = old.split_inclusive(|&b| b == b'\n').collect();
+    let b: Vec<&[u8]> = new.split_inclusive(|&b| b == b'\n').collect();
+    let edits = edits(&a, &b);
+
+    out.extend_from_slice(format!("--- {old_label}\n+++ {new_label}\n").as_bytes());
+    for (start, end) in hunks(&edits) {
+        let hunk = &edits[start..end];
+        // Where the hunk starts in each file, counted in lines before it
+        let (a_before, b_before) = edits[..start]
+            .iter()
+            .fold((0, 0), |(x, y), edit| match edit {
+                Edit::Same(..) => (x + 1, y + 1),
+                Edit::Removed(_) => (x + 1, y),
+                Edit::Added(_) => (x, y + 1),
+            });
+        let a_len = hunk.iter().filter(|e| !matches!(e, Edit::Added(_))).count();
+        let b_len = hunk
+            .iter()
+            .filter(|e| !matches!(e, Edit::Removed(_)))
+            .count();
+        out.extend_from_slice(
+            format!(
+                "@@ -{} +{} @@\n",
+                range(a_before, a_len),
+                range(b_before, b_len)
+            )
+            .as_bytes(),
+        );
+        for edit in hunk {
+            let (mark, line) = match *edit {
+                Edit::Same(i, _) => (b' ', a[i]),
+                Edit::Removed(i) => (b'-', a[i]),
+                Edit::Added(j) => (b'+', b[j]),
+            };
+            out.push(mark);
+            out.extend_from_slice(line);
+            if !line.ends_with(b"\n") {
+                out.extend_from_slice(b"\n\\ No newline at end of file\n");
+            }
+        }
+    }
+    out
+}
+
+/// A hunk header's range: the first line and the line count, with the
+/// count left out when it's 1 and the line before the hunk given when it's 0.
+fn range(before: usize, len: usize) -> String {
+    match len {
+        0 => format!("{before},0"),
+        1 => format!("{}", before + 1),
+        _ => format!("{},{len}", before + 1),
+    }
+}
+
+/// The spans of `edits` to print as hunks: each change with CONTEXT lines
+/// around it, merging changes whose context would touch.
+fn hunks(edits: &[Edit]) -> Vec<(usize, usize)> {
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    for (i, edit) in edits.iter().enumerate() {
+        if matches!(edit, Edit::Same(..)) {
+            continue;
+        }
+        let start = i.saturating_sub(CONTEXT);
+        let end = (i + 1 + CONTEXT).min(edits.len());
+        match spans.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => spans.push((start, end)),
+        }
+    }
+    spans
+}
+
+/// The shortest edit script turning `a` into `b`, by Myers' algorithm in
+/// its linear-space form: the middle of the script is found with the
+/// furthest-reaching paths alone, and each half worked out the same way,
+/// so memory stays proportional to the files however much they differ.
+fn edits(a: &[&[u8]], b: &[&[u8]]) -> Vec<Edit> {
+    let size = 2 * (a.len() + b.len()) + 3;
+    let (mut forward, mut backward) = (vec![0; size], vec![0; size]);
+    let mut script = Vec::with_capacity(a.len().max(b.len()));
+    conquer(
+        a,
+        0..a.len(),
+        b,
+        0..b.len(),
+        &mut forward,
+        &mut backward,
+        &mut script,
+    );
+    // Within each change, removed lines first, as diff prints them
+    for change in
+        script.chunk_by_mut(|x, y| !matches!(x, Edit::Same(..)) && !matches!(y, Edit::Same(..)))
+    {
+        change.sort_by_key(|edit| matches!(edit, Edit::Added(_)));
+    }
+    script
+}
+
+/// Add the edits turning `a[xs]` into `b[ys]` to `script`.
+fn conquer(
+    a: &[&[u8]],
+    mut xs: Range<usize>,
+    b: &[&[u8]],
+    mut ys: Range<usize>,
+    forward: &mut [usize],
+    backward: &mut [usize],
+    script: &mut Vec<Edit>,
+) {
+    while !xs.is_empty() && !ys.is_empty() && a[xs.start] == b[ys.start] {
+        script.push(Edit::Same(xs.start, ys.start));
+        xs.start += 1;
+        ys.start += 1;
+    }
+    let mut suffix = 0;
+    while !xs.is_empty() && !ys.is_empty() && a[xs.end - 1] == b[ys.end - 1] {
+        xs.end -= 1;
+        ys.end -= 1;
+        suffix += 1;
+    }
+
+    if xs.is_empty() {
+        script.extend(ys.clone().map(Edit::Added));
+    } else if ys.is_empty() {
+        script.extend(xs.clone().map(Edit::Removed));
+    } else {
+        let (x, y) = middle(a, xs.clone(), b, ys.clone(), forward, backward);
+        conquer(a, xs.start..x, b, ys.start..y, forward, backward, script);
+        conquer(a, x..xs.end, b, y..ys.end, forward, backward, script);
+    }
+    script.extend((0..suffix).map(|i| Edit::Same(xs.end + i, ys.end + i)));
+}
+
+/// A point on a shortest path from the start of `a[xs]` and `b[ys]` to
+/// their end about halfway along it, found by searching from both ends at
+/// once until the paths meet. `forward` and `backward` hold the furthest
+/// x reached on each diagonal, counted from the start and from the end.
+fn middle(
+    a: &[&[u8]],
+    xs: Range<usize>,
+    b: &[&[u8]],
+    ys: Range<usize>,
+    forward: &mut [usize],
+    backward: &mut [usize],
+) -> (usize, usize) {
+    let (n, m) = (xs.len() as isize, ys.len() as isize);
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    let offset = n + m + 1;
+    let at = |k: isize| (k + offset) as usize;
+    forward[at(1)] = 0;
+    backward[at(1)] = 0;
+    for d in 0..=(n + m + 1) / 2 {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            } as isize;
+            let start = (x, x - k);
+            while x < n && x - k < m && a[xs.start + x as usize] == b[ys.start + (x - k) as usize] {
+                x += 1;
+            }
+            forward[at(k)] = x as usize;
+            if odd && (k - delta).abs() < d && x + backward[at(delta - k)] as isize >= n {
+                return (xs.start + start.0 as usize, ys.start + start.1 as usize);
+            }
+        }
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)]
+            } else {
+                backward[at(k - 1)] + 1
+            } as isize;
+            while x < n
+                && x - k < m
+                && a[xs.end - 1 - x as usize] == b[ys.end - 1 - (x - k) as usize]
+            {
+                x += 1;
+            }
+            backward[at(k)] = x as usize;
+            if !odd && (k - delta).abs() <= d && x + forward[at(delta - k)] as isize >= n {
+                return (xs.end - x as usize, ys.end - (x - k) as usize);
+            }
+        }
+    }
+    unreachable!("the searches from both ends always meet")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(old: &str, new: &str) -> String {
+        String::from_utf8(unified(old.as_bytes(), new.as_bytes(), "a", "b")).unwrap()
+    }
+
+    #[test]
+    fn test_same_is_empty() {
+        assert_eq!(diff("x\ny\n", "x\ny\n"), "");
+        assert_eq!(diff("", ""), "");
+    }
+
+    #[test]
+    fn test_one_hunk() {
+        assert_eq!(
+            diff("1\n2\n3\n4\n5\n6\n7\n8\n", "1\n2\n3\n4\nfive\n6\n7\n8\n"),
+            "--- a\n+++ b\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n"
+        );
+    }
+
+    #[test]
+    fn test_distant_changes_get_their_own_hunks() {
+        let old: String = (1..=20).map(|i| format!("{i}\n")).collect();
+        let new = old.replace("\n2\n", "\ntwo\n").replace("\n19\n", "\n");
+        assert_eq!(
+            diff(&old, &new),
+            "--- a\n+++ b\n\
+             @@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n\
+             @@ -16,5 +16,4 @@\n 16\n 17\n 18\n-19\n 20\n"
+        );
+    }
+
+    #[test]
+    fn test_empty_side_and_missing_newline() {
+        assert_eq!(diff("", "new\n"), "--- a\n+++ b\n@@ -0,0 +1 @@\n+new\n");
+        assert_eq!(
+            diff("x\n", "x"),
+            "--- a\n+++ b\n@@ -1 +1 @@\n-x\n+x\n\\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    fn test_edits_are_shortest() {
+        // Pseudo-random line sequences over a small alphabet, checked against
+        // the longest common subsequence worked out the slow way
+        let mut seed = 7u32;
+        let mut next = |limit: u32| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 16) % limit
+        };
+        let lines: [&[u8]; 4] = [b"a\n", b"b\n", b"c\n", b"d\n"];
+        for _ in 0..300 {
+            let a: Vec<&[u8]> = (0..next(12)).map(|_| lines[next(4) as usize]).collect();
+            let b: Vec<&[u8]> = (0..next(12)).map(|_| lines[next(4) as usize]).collect();
+            let script = edits(&a, &b);
+
+            let (mut old, mut new) = (Vec::new(), Vec::new());
+            for edit in &script {
+                match *edit {
+                    Edit::Same(i, j) => {
+                        assert_eq!(a[i], b[j]);
+                        old.push(i);
+                        new.push(j);
+                    }
+                    Edit::Removed(i) => old.push(i),
+                    Edit::Added(j) => new.push(j),
+                }
+            }
+            assert_eq!(old, (0..a.len()).collect::<Vec<_>>());
+            assert_eq!(new, (0..b.len()).collect::<Vec<_>>());
+
+            let mut lcs = vec![vec![0; b.len() + 1]; a.len() + 1];
+            for i in (0..a.len()).rev() {
+                for j in (0..b.len()).rev() {
+                    lcs[i][j] = if a[i] == b[j] {
+                        lcs[i + 1][j + 1] + 1
+                    } else {
+                        lcs[i + 1][j].max(lcs[i][j + 1])
+                    };
+                }
+            }
+            let same = script
+                .iter()
+                .filter(|e| matches!(e, Edit::Same(..)))
+                .count();
+            assert_eq!(same, lcs[0][0], "{a:?} -> {b:?}");
+        }
+    }
+
+    #[test]
+    fn test_binary() {
+        assert_eq!(
+            String::from_utf8(unified(b"\0a", b"\0b", "a", "b")).unwrap(),
+            "Binary files a and b differ\n"
+        );
+    }
+}
@@ -0,0 +1,83 @@
+// The plan printed by --trash-dry-run --output json: one step per item, as
+// one JSON object per line, described by --schema. Tools can show it for
+// approval and then run trache again without --trash-dry-run.
+#![cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+
+use std::path::Path;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Trash,
+    Shred,
+    Restore,
+    Purge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Conflict {
+    /// Something already exists at the destination
+    Collision,
+    /// Other items in the plan would be restored to the same destination
+    Twin,
+}
+
+/// One item of the plan.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Step {
+    pub action: Action,
+    /// Where the item is now: on disk, or inside the trash
+    pub source: String,
+    /// Where it would go; absent when it goes into the trash or is destroyed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+    /// What would stand in the way, for a restore
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Step {
+    pub fn new(action: Action, source: &Path) -> Step {
+        Step {
+            action,
+            source: std::path::absolute(source)
+                .unwrap_or_else(|_| source.to_path_buf())
+                .to_string_lossy()
+                .into_owned(),
+            destination: None,
+            conflicts: Vec::new(),
+        }
+    }
+
+    pub fn to(mut self, destination: &Path) -> Step {
+        self.destination = Some(destination.to_string_lossy().into_owned());
+        self
+    }
+
+    pub fn print(&self) {
+        println!("{}", serde_json::to_string(self).unwrap_or_default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_json() {
+        let mut step =
+            Step::new(Action::Restore, Path::new("/t/files/a.txt")).to(Path::new("/home/me/a.txt"));
+        step.conflicts = vec![Conflict::Collision, Conflict::Twin];
+        assert_eq!(
+            serde_json::to_string(&step).unwrap(),
+            r#"{"action":"restore","source":"/t/files/a.txt","destination":"/home/me/a.txt","conflicts":["collision","twin"]}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Step::new(Action::Trash, Path::new("/a"))).unwrap(),
+            r#"{"action":"trash","source":"/a","conflicts":[]}"#
+        );
+    }
+}
@@ -0,0 +1,200 @@
+// Finding trashed items with identical contents, for --duplicates.
+// Items are grouped by size, then by a content hash, and finally compared
+// byte for byte so a hash collision can never get something purged.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+use trash::TrashItem;
+
+use crate::{items, units};
+
+const BUF_SIZE: usize = 64 * 1024;
+
+/// Groups of indices into `list` whose items have identical contents, each
+/// sorted newest first. Items that can't be read are left out.
+pub fn groups(list: &[TrashItem]) -> Vec<Vec<usize>> {
+    let mut by_size: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (i, item) in list.iter().enumerate() {
+        by_size
+            .entry(units::disk_usage(&items::item_path(item)))
+            .or_default()
+            .push(i);
+    }
+
+    let mut groups = Vec::new();
+    for candidates in by_size.into_values().filter(|c| c.len() > 1) {
+        let mut by_hash: HashMap<u64, Vec<usize>> = HashMap::new();
+        for i in candidates {
+            if let Ok(hash) = fingerprint(&items::item_path(&list[i])) {
+                by_hash.entry(hash).or_default().push(i);
+            }
+        }
+        for same_hash in by_hash.into_values().filter(|c| c.len() > 1) {
+            groups.extend(split_identical(list, same_hash));
+        }
+    }
+
+    for group in &mut groups {
+        group.sort_by_key(|&i| std::cmp::Reverse(list[i].time_deleted));
+    }
+    groups.sort_by_key(|group| std::cmp::Reverse(list[group[0]].time_deleted));
+    groups
+}
+
+/// Partition items with equal hashes into groups that really are identical.
+fn split_identical(list: &[TrashItem], mut rest: Vec<usize>) -> Vec<Vec<usize>> {
+    let mut groups = Vec::new();
+    while let Some(first) = rest.first().copied() {
+        let first_path = items::item_path(&list[first]);
+        let (same, different): (Vec<usize>, Vec<usize>) = rest.into_iter().partition(|&i| {
+            i == first || same_content(&first_path, &items::item_path(&list[i])).unwrap_or(false)
+        });
+        if same.len() > 1 {
+            groups.push(same);
+        }
+        rest = different;
+    }
+    groups
+}
+
+/// Hash of a file's contents, or of a directory tree's names and contents.
+pub fn fingerprint(path: &Path) -> io::Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    hash_tree(path, &mut hasher)?;
+    Ok(hasher.finish())
+}
+
+fn hash_tree(path: &Path, hasher: &mut DefaultHasher) -> io::Result<()> {
+    let meta = path.symlink_metadata()?;
+    if meta.is_symlink() {
+        b'l'.hash(hasher);
+        fs::read_link(path)?.hash(hasher);
+    } else if meta.is_dir() {
+        b'd'.hash(hasher);
+        for entry in sorted_entries(path)? {
+            entry.file_name().hash(hasher);
+            hash_tree(&entry.path(), hasher)?;
+        }
+    } else {
+        b'f'.hash(hasher);
+        meta.len().hash(hasher);
+        let mut reader = BufReader::with_capacity(BUF_SIZE, File::open(path)?);
+        let mut buf = vec![0u8; BUF_SIZE];
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.write(&buf[..n]);
+        }
+    }
+    Ok(())
+}
+
+fn sorted_entries(dir: &Path) -> io::Result<Vec<fs::DirEntry>> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    Ok(entries)
+}
+
+/// Byte-for-byte comparison of two files or directory trees.
+pub fn same_content(a: &Path, b: &Path) -> io::Result<bool> {
+    let (ma, mb) = (a.symlink_metadata()?, b.symlink_metadata()?);
+    if ma.is_symlink() || mb.is_symlink() {
+        return Ok(ma.is_symlink() && mb.is_symlink() && fs::read_link(a)? == fs::read_link(b)?);
+    }
+    if ma.is_dir() || mb.is_dir() {
+        if !(ma.is_dir() && mb.is_dir()) {
+            return Ok(false);
+        }
+        let (ea, eb) = (sorted_entries(a)?, sorted_entries(b)?);
+        if ea.len() != eb.len() {
+            return Ok(false);
+        }
+        for (x, y) in ea.iter().zip(&eb) {
+            if x.file_name() != y.file_name() || !same_content(&x.path(), &y.path())? {
+                return Ok(false);
+            }
+        }
+        return Ok(true);
+    }
+    if ma.len() != mb.len() {
+        return Ok(false);
+    }
+
+    let mut ra = BufReader::with_capacity(BUF_SIZE, File::open(a)?);
+    let mut rb = BufReader::with_capacity(BUF_SIZE, File::open(b)?);
+    let (mut ba, mut bb) = (vec![0u8; BUF_SIZE], vec![0u8; BUF_SIZE]);
+    loop {
+        let n = ra.read(&mut ba)?;
+        if n == 0 {
+            return Ok(true);
+        }
+        rb.read_exact(&mut bb[..n])?;
+        if ba[..n] != bb[..n] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Bytes that purging all but one copy in each group would free.
+pub fn reclaimable(list: &[TrashItem], groups: &[Vec<usize>]) -> u64 {
+    groups
+        .iter()
+        .map(|g| units::disk_usage(&items::item_path(&list[g[0]])) * (g.len() as u64 - 1))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_content_files() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let (a, b, c) = (
+            tmp.path().join("a"),
+            tmp.path().join("b"),
+            tmp.path().join("c"),
+        );
+        fs::write(&a, "same").unwrap();
+        fs::write(&b, "same").unwrap();
+        fs::write(&c, "diff").unwrap();
+        assert!(same_content(&a, &b).unwrap());
+        assert!(!same_content(&a, &c).unwrap());
+        assert_eq!(fingerprint(&a).unwrap(), fingerprint(&b).unwrap());
+        assert_ne!(fingerprint(&a).unwrap(), fingerprint(&c).unwrap());
+    }
+
+    #[test]
+    fn test_same_content_trees() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        for name in ["x", "y"] {
+            let d = tmp.path().join(name);
+            fs::create_dir_all(d.join("sub")).unwrap();
+            fs::write(d.join("sub").join("f"), "data").unwrap();
+        }
+        let (x, y) = (tmp.path().join("x"), tmp.path().join("y"));
+        assert!(same_content(&x, &y).unwrap());
+        assert_eq!(fingerprint(&x).unwrap(), fingerprint(&y).unwrap());
+
+        // Same contents under a different name is a different tree
+        fs::rename(y.join("sub").join("f"), y.join("sub").join("g")).unwrap();
+        assert!(!same_content(&x, &y).unwrap());
+        assert_ne!(fingerprint(&x).unwrap(), fingerprint(&y).unwrap());
+    }
+
+    #[test]
+    fn test_file_and_dir_differ() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let f = tmp.path().join("f");
+        let d = tmp.path().join("d");
+        fs::write(&f, "").unwrap();
+        fs::create_dir(&d).unwrap();
+        assert!(!same_content(&f, &d).unwrap());
+    }
+}
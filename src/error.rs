@@ -0,0 +1,158 @@
+// The error every trache operation returns. I/O errors are sorted by cause
+// as they're converted, so callers and the exit status can tell a missing
+// file from a directory or a move across file systems without looking at
+// the message. Errors trache raises itself get the variant for why they
+// happened. Each variant shows just what it wraps, so messages read the same
+// as before there were variants.
+
+use std::io;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TracheError {
+    #[error(transparent)]
+    NotFound(io::Error),
+    #[error(transparent)]
+    IsADirectory(io::Error),
+    /// A rename that would have to cross file systems
+    #[error(transparent)]
+    CrossDevice(io::Error),
+    #[error(transparent)]
+    Io(io::Error),
+    /// The trash itself failed, as the trash crate or a --trash-dir reports
+    #[error(transparent)]
+    BackendFailure(trash::Error),
+    /// Quit at a prompt, leaving the rest undone
+    #[error("quit at a prompt")]
+    PromptAborted,
+    /// Cut short by Ctrl-C or a signal; says what was left undone
+    #[error("{0}")]
+    Interrupted(String),
+    /// The operation isn't available on this platform
+    #[cfg_attr(
+        all(unix, not(target_os = "macos"), not(target_os = "ios")),
+        allow(dead_code)
+    )]
+    #[error("{0}")]
+    Unsupported(String),
+    /// No item in the trash matched the pattern
+    #[error("no items matching '{0}' found in trash")]
+    NoMatch(String),
+    /// Arguments, patterns or settings that can't be used as given
+    #[error("{0}")]
+    Invalid(String),
+    /// Stopped before anything was touched: a pre hook objected, a limit or
+    /// rm's rules refused, or the path is outside the sandbox
+    #[error("{0}")]
+    Refused(String),
+    /// Some of the work failed, as already reported item by item; says how
+    /// much
+    #[error("{0}")]
+    Incomplete(String),
+    /// Some of the files given couldn't be trashed or removed, each already
+    /// reported; the cause they all had, if they had one
+    #[error("some files could not be removed")]
+    NotRemoved(Option<io::ErrorKind>),
+    /// Where the home trash or trache's own state lives couldn't be worked
+    /// out
+    #[error("{0}")]
+    NoLocation(&'static str),
+    /// Anything else, as a message
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<io::Error> for TracheError {
+    fn from(e: io::Error) -> TracheError {
+        match e.kind() {
+            io::ErrorKind::NotFound => TracheError::NotFound(e),
+            io::ErrorKind::IsADirectory => TracheError::IsADirectory(e),
+            io::ErrorKind::CrossesDevices => TracheError::CrossDevice(e),
+            _ => TracheError::Io(e),
+        }
+    }
+}
+
+impl TracheError {
+    /// `e` with `context` in front of its message, sorted by its cause.
+    pub fn io(context: impl std::fmt::Display, e: io::Error) -> TracheError {
+        io::Error::new(e.kind(), format!("{context}: {e}")).into()
+    }
+
+    /// An error of `kind` with `message`, sorted as the I/O error would be.
+    pub fn io_kind(kind: io::ErrorKind, message: String) -> TracheError {
+        io::Error::new(kind, message).into()
+    }
+
+    /// The kind of I/O error this was sorted by, if it was one; a pattern
+    /// nothing matched counts as not found, and files not removed for one
+    /// cause count as that cause.
+    pub fn cause(&self) -> Option<io::ErrorKind> {
+        match self {
+            TracheError::NotFound(e)
+            | TracheError::IsADirectory(e)
+            | TracheError::CrossDevice(e)
+            | TracheError::Io(e) => Some(e.kind()),
+            TracheError::NoMatch(_) => Some(io::ErrorKind::NotFound),
+            TracheError::NotRemoved(cause) => *cause,
+            _ => None,
+        }
+    }
+}
+
+impl From<trash::Error> for TracheError {
+    fn from(e: trash::Error) -> TracheError {
+        TracheError::BackendFailure(e)
+    }
+}
+
+impl From<serde_json::Error> for TracheError {
+    fn from(e: serde_json::Error) -> TracheError {
+        match e.io_error_kind() {
+            Some(_) => io::Error::from(e).into(),
+            None => TracheError::Other(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_errors_are_sorted_by_cause() {
+        let err = |kind: io::ErrorKind| TracheError::from(io::Error::from(kind));
+        assert!(matches!(
+            err(io::ErrorKind::NotFound),
+            TracheError::NotFound(_)
+        ));
+        assert!(matches!(
+            err(io::ErrorKind::IsADirectory),
+            TracheError::IsADirectory(_)
+        ));
+        assert!(matches!(
+            err(io::ErrorKind::CrossesDevices),
+            TracheError::CrossDevice(_)
+        ));
+        assert!(matches!(
+            err(io::ErrorKind::PermissionDenied),
+            TracheError::Io(_)
+        ));
+
+        let e = io::Error::new(io::ErrorKind::NotFound, "gone");
+        assert_eq!(TracheError::from(e).to_string(), "gone");
+    }
+
+    #[test]
+    fn test_messages_are_kept() {
+        assert_eq!(
+            TracheError::NoMatch("*.log".into()).to_string(),
+            "no items matching '*.log' found in trash"
+        );
+        let gone = io::Error::from(io::ErrorKind::NotFound);
+        let e = TracheError::io("cannot read '/x'", gone);
+        assert!(matches!(e, TracheError::NotFound(_)));
+        assert!(e.to_string().starts_with("cannot read '/x': "));
+        let e = TracheError::io_kind(io::ErrorKind::AlreadyExists, "'/x' already exists".into());
+        assert_eq!(e.to_string(), "'/x' already exists");
+    }
+}
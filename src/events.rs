@@ -0,0 +1,64 @@
+// Machine-readable per-file events for --log-format json.
+
+use std::path::Path;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// The path was moved to the trash (or would be, under --trash-dry-run)
+    Trashed { path: &'a Path, dry_run: bool },
+    /// The path was overwritten and deleted by --shred
+    Shredded { path: &'a Path, dry_run: bool },
+    /// The path was deleted permanently by --rm-fallback
+    Deleted { path: &'a Path },
+    /// The path was left alone without being an error
+    Skipped { path: &'a Path, reason: &'a str },
+    /// The user was asked for confirmation; `path` is absent for -I bulk prompts
+    Prompted {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        path: Option<&'a Path>,
+        prompt: &'a str,
+        answer: bool,
+    },
+    /// The path could not be trashed
+    Errored { path: &'a Path, error: String },
+}
+
+impl Event<'_> {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trashed_json() {
+        let e = Event::Trashed {
+            path: Path::new("/tmp/a.txt"),
+            dry_run: false,
+        };
+        assert_eq!(
+            e.to_json(),
+            r#"{"event":"trashed","path":"/tmp/a.txt","dry_run":false}"#
+        );
+    }
+
+    #[test]
+    fn test_bulk_prompt_json_omits_path() {
+        let e = Event::Prompted {
+            path: None,
+            prompt: "remove 5 argument(s)?",
+            answer: true,
+        };
+        assert_eq!(
+            e.to_json(),
+            r#"{"event":"prompted","prompt":"remove 5 argument(s)?","answer":true}"#
+        );
+    }
+}
@@ -0,0 +1,170 @@
+// --trash-export: trashed items packed into a tarball, without restoring
+// them, to hand them to someone else or move them to another machine. The
+// archive starts with a manifest, trache-export.json, giving each item's
+// original path and deletion time; the items follow under items/<n>/<name>,
+// with directories whole and symbolic links kept as links. Names ending in
+// .gz or .tgz are gzip-compressed. --trash-import reads them back.
+#![cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+
+/// Name of the manifest inside the archive
+pub const MANIFEST: &str = "trache-export.json";
+
+/// Bumped when the archive layout changes incompatibly
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    /// When the archive was made, in seconds since the epoch
+    pub exported: i64,
+    pub items: Vec<Entry>,
+}
+
+/// One exported item
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Where the item is inside the archive
+    pub entry: String,
+    pub original_path: PathBuf,
+    /// When it was trashed, in seconds since the epoch
+    pub deleted: i64,
+}
+
+impl Entry {
+    /// The entry for the `n`th item, trashed from `original_path`.
+    pub fn new(n: usize, original_path: PathBuf, deleted: i64) -> Entry {
+        let name = original_path
+            .file_name()
+            .map_or_else(|| "item".into(), |name| name.to_string_lossy());
+        Entry {
+            entry: format!("items/{n}/{name}"),
+            original_path,
+            deleted,
+        }
+    }
+}
+
+/// Whether `archive` should be gzip-compressed, going by its name.
+fn compressed(archive: &Path) -> bool {
+    let name = archive.to_string_lossy();
+    name.ends_with(".gz") || name.ends_with(".tgz")
+}
+
+/// Write `items`, each a path in the trash and its entry, to `archive`.
+/// The archive is assembled beside it and renamed into place, so a failure
+/// never leaves half an archive behind.
+pub fn write(archive: &Path, items: &[(PathBuf, Entry)]) -> io::Result<()> {
+    let tmp = partial_path(archive);
+    let written = write_to(&tmp, archive, items).and_then(|()| fs::rename(&tmp, archive));
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    written
+}
+
+fn partial_path(archive: &Path) -> PathBuf {
+    let mut name = archive.as_os_str().to_os_string();
+    name.push(".partial");
+    PathBuf::from(name)
+}
+
+fn write_to(tmp: &Path, archive: &Path, items: &[(PathBuf, Entry)]) -> io::Result<()> {
+    let file = File::create(tmp)?;
+    if compressed(archive) {
+        let encoder = pack(GzEncoder::new(file, Compression::default()), items)?;
+        encoder.finish()?.sync_all()
+    } else {
+        pack(file, items)?.sync_all()
+    }
+}
+
+fn pack<W: Write>(out: W, items: &[(PathBuf, Entry)]) -> io::Result<W> {
+    let mut builder = tar::Builder::new(out);
+    builder.follow_symlinks(false);
+
+    let manifest = serde_json::to_vec_pretty(&Manifest {
+        version: VERSION,
+        exported: chrono::Local::now().timestamp(),
+        items: items.iter().map(|(_, entry)| entry.clone()).collect(),
+    })?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(chrono::Local::now().timestamp().max(0) as u64);
+    builder.append_data(&mut header, MANIFEST, manifest.as_slice())?;
+
+    for (path, entry) in items {
+        let is_dir = path.symlink_metadata()?.is_dir();
+        if is_dir {
+            builder.append_dir_all(&entry.entry, path)?;
+        } else {
+            builder.append_path_with_name(path, &entry.entry)?;
+        }
+    }
+    builder.into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    /// The names and contents of the entries in `archive`.
+    fn contents(archive: &Path) -> Vec<(String, String)> {
+        let file = File::open(archive).unwrap();
+        let reader: Box<dyn Read> = if compressed(archive) {
+            Box::new(flate2::read::GzDecoder::new(file))
+        } else {
+            Box::new(file)
+        };
+        let mut tar = tar::Archive::new(reader);
+        tar.entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let name = entry.path().unwrap().to_string_lossy().into_owned();
+                let mut text = String::new();
+                entry.read_to_string(&mut text).unwrap();
+                (name, text)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_write_packs_manifest_and_items() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let file = tmp.path().join("notes.txt");
+        fs::write(&file, "hello").unwrap();
+        let dir = tmp.path().join("dir");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("inner"), "inside").unwrap();
+        let items = [
+            (file, Entry::new(1, "/home/me/notes.txt".into(), 100)),
+            (dir, Entry::new(2, "/home/me/dir".into(), 200)),
+        ];
+
+        for name in ["out.tar.gz", "out.tar"] {
+            let archive = tmp.path().join(name);
+            write(&archive, &items).unwrap();
+            assert!(!partial_path(&archive).exists());
+            let contents = contents(&archive);
+            assert_eq!(contents[0].0, MANIFEST);
+            let manifest: serde_json::Value = serde_json::from_str(&contents[0].1).unwrap();
+            assert_eq!(manifest["version"], 1);
+            assert_eq!(manifest["items"][0]["entry"], "items/1/notes.txt");
+            assert_eq!(manifest["items"][1]["original_path"], "/home/me/dir");
+            assert_eq!(manifest["items"][1]["deleted"], 200);
+            assert!(contents.contains(&("items/1/notes.txt".into(), "hello".into())));
+            assert!(contents.contains(&("items/2/dir/inner".into(), "inside".into())));
+        }
+    }
+}
@@ -0,0 +1,496 @@
+// --filter EXPR: a small expression language over trash items, for what
+// stacked pattern prefixes can't say, such as "old logs or anything big":
+//
+//   (name ~ "*.log" && age > 7d) || size >= 1G
+//
+// A comparison is FIELD OP VALUE. The text fields name, path, dir and id take
+// ~ and !~ (glob match against the whole field) and == and !=; size, age and
+// deleted take == != < <= > >=, with sizes as "4k" or "1G", ages as "30m"
+// or "2w", and deletion times as for --trash-undo-since; type is == or !=
+// file, dir or symlink. Comparisons combine with &&, || and !, and group with
+// parentheses; && binds tighter than ||. Values with spaces or operator
+// characters in them are written in double quotes, with \" and \\ inside.
+// Only used by trash management, which macOS doesn't support yet.
+#![cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+
+use std::fmt;
+
+use globset::{Glob, GlobMatcher};
+
+/// The facts about an item an expression can look at. Sizes are asked for
+/// only when an expression gets to a size comparison.
+pub trait Facts {
+    fn name(&self) -> String;
+    fn path(&self) -> String;
+    fn dir(&self) -> String;
+    fn id(&self) -> String;
+    fn size(&self) -> u64;
+    /// Seconds since the epoch
+    fn deleted(&self) -> i64;
+    /// "file", "dir" or "symlink", or None if the item can't be examined
+    fn kind(&self) -> Option<&'static str>;
+}
+
+#[derive(Debug)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Text(TextField, TextTest),
+    Number(NumberField, Cmp, i64),
+    Kind(bool, &'static str),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum TextField {
+    Name,
+    Path,
+    Dir,
+    Id,
+}
+
+#[derive(Debug)]
+pub enum TextTest {
+    Glob(GlobMatcher, bool),
+    Equal(String, bool),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum NumberField {
+    Size,
+    /// Seconds since deletion
+    Age,
+    Deleted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmp {
+    Equal,
+    NotEqual,
+    Less,
+    AtMost,
+    Greater,
+    AtLeast,
+}
+
+impl Expr {
+    /// Parse EXPR; `now` is what ages and relative times count back from.
+    pub fn parse(input: &str, now: chrono::DateTime<chrono::Local>) -> Result<Expr, String> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            now,
+        };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(format!("unexpected {token}")),
+        }
+    }
+
+    /// Whether the item described by `facts` satisfies the expression, at
+    /// `now` in seconds since the epoch.
+    pub fn accepts(&self, facts: &dyn Facts, now: i64) -> bool {
+        match self {
+            Expr::And(a, b) => a.accepts(facts, now) && b.accepts(facts, now),
+            Expr::Or(a, b) => a.accepts(facts, now) || b.accepts(facts, now),
+            Expr::Not(inner) => !inner.accepts(facts, now),
+            Expr::Text(field, test) => {
+                let value = match field {
+                    TextField::Name => facts.name(),
+                    TextField::Path => facts.path(),
+                    TextField::Dir => facts.dir(),
+                    TextField::Id => facts.id(),
+                };
+                match test {
+                    TextTest::Glob(glob, want) => glob.is_match(&value) == *want,
+                    TextTest::Equal(s, want) => (value == *s) == *want,
+                }
+            }
+            Expr::Number(field, ord, target) => {
+                let value = match field {
+                    NumberField::Size => i64::try_from(facts.size()).unwrap_or(i64::MAX),
+                    NumberField::Age => now.saturating_sub(facts.deleted()),
+                    NumberField::Deleted => facts.deleted(),
+                };
+                match ord {
+                    Cmp::Equal => value == *target,
+                    Cmp::NotEqual => value != *target,
+                    Cmp::Less => value < *target,
+                    Cmp::AtMost => value <= *target,
+                    Cmp::Greater => value > *target,
+                    Cmp::AtLeast => value >= *target,
+                }
+            }
+            Expr::Kind(want, kind) => (facts.kind() == Some(*kind)) == *want,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    /// A comparison operator, as written
+    Op(&'static str),
+    Word(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+            Token::And => write!(f, "'&&'"),
+            Token::Or => write!(f, "'||'"),
+            Token::Not => write!(f, "'!'"),
+            Token::Op(op) => write!(f, "'{op}'"),
+            Token::Word(word) => write!(f, "'{word}'"),
+        }
+    }
+}
+
+/// Characters that end a bare word
+const SPECIAL: &str = "()&|!=<>~\"";
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = input;
+    loop {
+        rest = rest.trim_start();
+        let Some(c) = rest.chars().next() else {
+            return Ok(tokens);
+        };
+        // Longest operators first
+        let symbols: [(&str, Token); 13] = [
+            ("&&", Token::And),
+            ("||", Token::Or),
+            ("==", Token::Op("==")),
+            ("!=", Token::Op("!=")),
+            ("!~", Token::Op("!~")),
+            ("<=", Token::Op("<=")),
+            (">=", Token::Op(">=")),
+            ("(", Token::Open),
+            (")", Token::Close),
+            ("!", Token::Not),
+            ("<", Token::Op("<")),
+            (">", Token::Op(">")),
+            ("~", Token::Op("~")),
+        ];
+        if let Some((symbol, token)) = symbols.into_iter().find(|(s, _)| rest.starts_with(s)) {
+            tokens.push(token);
+            rest = &rest[symbol.len()..];
+        } else if c == '=' {
+            tokens.push(Token::Op("=="));
+            rest = &rest[1..];
+        } else if c == '"' {
+            let mut word = String::new();
+            let mut chars = rest[1..].char_indices();
+            loop {
+                match chars.next() {
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, c)) => word.push(c),
+                        None => return Err("unterminated string".to_string()),
+                    },
+                    Some((i, '"')) => {
+                        rest = &rest[i + 2..];
+                        break;
+                    }
+                    Some((_, c)) => word.push(c),
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+            tokens.push(Token::Word(word));
+        } else if c == '&' || c == '|' {
+            return Err(format!("'{c}' on its own; did you mean '{c}{c}'?"));
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || SPECIAL.contains(c))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Word(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    now: chrono::DateTime<chrono::Local>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    Some(token) => Err(format!("expected ')', found {token}")),
+                    None => Err("missing ')'".to_string()),
+                }
+            }
+            Some(Token::Word(field)) => {
+                let op = match self.next() {
+                    Some(Token::Op(op)) => op,
+                    Some(token) => {
+                        return Err(format!(
+                            "expected an operator after '{field}', found {token}"
+                        ));
+                    }
+                    None => return Err(format!("expected an operator after '{field}'")),
+                };
+                let value = match self.next() {
+                    Some(Token::Word(value)) => value,
+                    Some(token) => {
+                        return Err(format!("expected a value after '{op}', found {token}"));
+                    }
+                    None => return Err(format!("expected a value after '{op}'")),
+                };
+                self.comparison(&field, op, &value)
+            }
+            Some(token) => Err(format!("expected a comparison, found {token}")),
+            None => Err("expected a comparison".to_string()),
+        }
+    }
+
+    fn comparison(&self, field: &str, op: &str, value: &str) -> Result<Expr, String> {
+        let text = match field {
+            "name" => Some(TextField::Name),
+            "path" => Some(TextField::Path),
+            "dir" => Some(TextField::Dir),
+            "id" => Some(TextField::Id),
+            _ => None,
+        };
+        if let Some(text) = text {
+            let test = match op {
+                "~" | "!~" => {
+                    let glob =
+                        Glob::new(value).map_err(|e| format!("{field} {op} {value}: {e}"))?;
+                    TextTest::Glob(glob.compile_matcher(), op == "~")
+                }
+                "==" | "!=" => TextTest::Equal(value.to_string(), op == "=="),
+                _ => return Err(format!("{field} takes ~, !~, == or !=, not '{op}'")),
+            };
+            return Ok(Expr::Text(text, test));
+        }
+
+        if field == "type" {
+            let kind = match value {
+                "file" | "f" => "file",
+                "dir" | "d" => "dir",
+                "symlink" | "l" => "symlink",
+                _ => return Err(format!("type {op} {value}: expected file, dir or symlink")),
+            };
+            return match op {
+                "==" | "!=" => Ok(Expr::Kind(op == "==", kind)),
+                _ => Err(format!("type takes == or !=, not '{op}'")),
+            };
+        }
+
+        let (number, target) = match field {
+            "size" => (
+                NumberField::Size,
+                i64::try_from(crate::units::parse_size(value)?).unwrap_or(i64::MAX),
+            ),
+            "age" => (
+                NumberField::Age,
+                i64::try_from(crate::units::parse_duration(value)?.as_secs()).unwrap_or(i64::MAX),
+            ),
+            "deleted" => (
+                NumberField::Deleted,
+                crate::units::parse_since(value, self.now)?,
+            ),
+            _ => {
+                return Err(format!(
+                    "unknown field '{field}' (try name, path, dir, id, size, age, deleted or type)"
+                ));
+            }
+        };
+        let ord = match op {
+            "==" => Cmp::Equal,
+            "!=" => Cmp::NotEqual,
+            "<" => Cmp::Less,
+            "<=" => Cmp::AtMost,
+            ">" => Cmp::Greater,
+            ">=" => Cmp::AtLeast,
+            _ => {
+                return Err(format!(
+                    "{field} takes a comparison such as > or <=, not '{op}'"
+                ));
+            }
+        };
+        Ok(Expr::Number(number, ord, target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    const NOW: i64 = 1_700_000_000;
+    const DAY: i64 = 24 * 60 * 60;
+
+    struct Item {
+        name: &'static str,
+        dir: &'static str,
+        size: u64,
+        deleted: i64,
+        kind: &'static str,
+        sized: Cell<bool>,
+    }
+
+    fn item(name: &'static str, size: u64, days_ago: i64) -> Item {
+        Item {
+            name,
+            dir: "/home/u",
+            size,
+            deleted: NOW - days_ago * DAY,
+            kind: "file",
+            sized: Cell::new(false),
+        }
+    }
+
+    impl Facts for Item {
+        fn name(&self) -> String {
+            self.name.to_string()
+        }
+        fn path(&self) -> String {
+            format!("{}/{}", self.dir, self.name)
+        }
+        fn dir(&self) -> String {
+            self.dir.to_string()
+        }
+        fn id(&self) -> String {
+            "abcd1234".to_string()
+        }
+        fn size(&self) -> u64 {
+            self.sized.set(true);
+            self.size
+        }
+        fn deleted(&self) -> i64 {
+            self.deleted
+        }
+        fn kind(&self) -> Option<&'static str> {
+            Some(self.kind)
+        }
+    }
+
+    fn accepts(expr: &str, item: &Item) -> bool {
+        Expr::parse(expr, chrono::Local::now())
+            .unwrap()
+            .accepts(item, NOW)
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let log = item("app.log", 2048, 10);
+        assert!(accepts(r#"name ~ "*.log""#, &log));
+        assert!(accepts("name ~ *.log", &log));
+        assert!(!accepts("name !~ *.log", &log));
+        assert!(accepts("name == app.log && name != x", &log));
+        assert!(accepts("path ~ /home/**", &log));
+        assert!(accepts("dir = /home/u", &log));
+        assert!(accepts("id == abcd1234", &log));
+        assert!(accepts("size > 1k && size <= 2k && size == 2048", &log));
+        assert!(accepts("age > 7d && age < 2w", &log));
+        assert!(accepts("type == file && type != dir", &log));
+        assert!(accepts(
+            "deleted < 2020-01-01 || deleted > 2000-01-01",
+            &log
+        ));
+    }
+
+    #[test]
+    fn test_precedence_and_grouping() {
+        let big = item("big.iso", 1 << 31, 1);
+        let old = item("old.log", 10, 30);
+        let expr = r#"name ~ "*.log" && age > 7d || size >= 1G"#;
+        assert!(accepts(expr, &big));
+        assert!(accepts(expr, &old));
+        assert!(!accepts(expr, &item("new.log", 10, 1)));
+        assert!(!accepts(
+            r#"name ~ "*.log" && (age > 7d || size >= 1G)"#,
+            &big
+        ));
+        assert!(accepts("!(name ~ *.log)", &big));
+        assert!(accepts("! ! name ~ *.iso", &big));
+    }
+
+    #[test]
+    fn test_size_is_only_measured_when_needed() {
+        let log = item("app.log", 1, 1);
+        assert!(accepts("name ~ *.log || size > 1G", &log));
+        assert!(!log.sized.get());
+        assert!(!accepts("name ~ *.iso && size > 1G", &log));
+        assert!(!log.sized.get());
+    }
+
+    #[test]
+    fn test_quoted_values() {
+        let spaced = item("my \"notes\".txt", 1, 1);
+        assert!(accepts(r#"name == "my \"notes\".txt""#, &spaced));
+        assert!(accepts(r#"name ~ "my *""#, &spaced));
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = |expr: &str| Expr::parse(expr, chrono::Local::now()).unwrap_err();
+        assert_eq!(
+            err("colour == red"),
+            "unknown field 'colour' (try name, path, dir, id, size, age, deleted or type)"
+        );
+        assert_eq!(
+            err("size ~ 1M"),
+            "size takes a comparison such as > or <=, not '~'"
+        );
+        assert_eq!(err("name > a"), "name takes ~, !~, == or !=, not '>'");
+        assert_eq!(
+            err("type == pipe"),
+            "type == pipe: expected file, dir or symlink"
+        );
+        assert_eq!(err("(name ~ a"), "missing ')'");
+        assert_eq!(err("name ~ a b"), "unexpected 'b'");
+        assert_eq!(
+            err("name ~ a & size > 1"),
+            "'&' on its own; did you mean '&&'?"
+        );
+        assert_eq!(err("name ~ \"a"), "unterminated string");
+        assert_eq!(err("name"), "expected an operator after 'name'");
+        assert_eq!(err("age > soon"), "invalid duration: 'soon'");
+    }
+}
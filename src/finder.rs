@@ -0,0 +1,348 @@
+// --find: walk directories like find(1) and pick the entries matching every
+// given predicate (--name, --mtime, --size, --empty), so they can be trashed
+// with the usual prompts and dry run. Directories are descended into rather
+// than taken whole, so only an empty one can match itself; the starting
+// directories are never taken. Entries that can't be read are reported and
+// passed over, as find does.
+// --prune-empty-dirs walks the same way for directories holding nothing but
+// other such directories.
+
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use globset::{Glob, GlobMatcher};
+
+use crate::units;
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// find's +N / -N / N: more than, less than, or exactly N
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Cmp {
+    More(u64),
+    Less(u64),
+    Exactly(u64),
+}
+
+impl Cmp {
+    fn parse(input: &str, number: impl Fn(&str) -> Result<u64, String>) -> Result<Cmp, String> {
+        if let Some(n) = input.strip_prefix('+') {
+            Ok(Cmp::More(number(n)?))
+        } else if let Some(n) = input.strip_prefix('-') {
+            Ok(Cmp::Less(number(n)?))
+        } else {
+            Ok(Cmp::Exactly(number(input)?))
+        }
+    }
+
+    fn accepts(self, value: u64) -> bool {
+        match self {
+            Cmp::More(n) => value > n,
+            Cmp::Less(n) => value < n,
+            Cmp::Exactly(n) => value == n,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Predicates {
+    name: Option<GlobMatcher>,
+    /// Whole days since the last modification
+    mtime: Option<Cmp>,
+    /// Bytes, of files only
+    size: Option<Cmp>,
+    empty: bool,
+}
+
+impl Predicates {
+    /// Parse the predicate flags; None when none was given.
+    pub fn new(
+        name: Option<&str>,
+        mtime: Option<&str>,
+        size: Option<&str>,
+        empty: bool,
+    ) -> Result<Option<Predicates>, String> {
+        if name.is_none() && mtime.is_none() && size.is_none() && !empty {
+            return Ok(None);
+        }
+        let name = name
+            .map(|glob| {
+                Glob::new(glob)
+                    .map(|g| g.compile_matcher())
+                    .map_err(|e| format!("--name: {e}"))
+            })
+            .transpose()?;
+        let mtime = mtime
+            .map(|m| {
+                Cmp::parse(m, |n| {
+                    n.parse()
+                        .map_err(|_| format!("--mtime {m}: expected a number of days, e.g. +30"))
+                })
+            })
+            .transpose()?;
+        let size = size
+            .map(|s| {
+                Cmp::parse(s, |n| {
+                    units::parse_size(n).map_err(|e| format!("--size {s}: {e}"))
+                })
+            })
+            .transpose()?;
+        Ok(Some(Predicates {
+            name,
+            mtime,
+            size,
+            empty,
+        }))
+    }
+
+    fn matches(&self, path: &Path, meta: &Metadata, now: SystemTime) -> io::Result<bool> {
+        if let Some(name) = &self.name
+            && !path.file_name().is_some_and(|n| name.is_match(n))
+        {
+            return Ok(false);
+        }
+        if let Some(mtime) = self.mtime {
+            // Like find, a part of a day doesn't count
+            let age = now
+                .duration_since(meta.modified()?)
+                .map_or(0, |age| age.as_secs());
+            if !mtime.accepts(age / DAY) {
+                return Ok(false);
+            }
+        }
+        if let Some(size) = self.size
+            && (meta.is_dir() || !size.accepts(meta.len()))
+        {
+            return Ok(false);
+        }
+        if self.empty {
+            let empty = if meta.is_dir() {
+                fs::read_dir(path)?.next().is_none()
+            } else {
+                meta.is_file() && meta.len() == 0
+            };
+            if !empty {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Every entry under the directories `roots` that matches, in walk
+    /// order, and why each entry that couldn't be read was passed over.
+    /// Symlinks are matched themselves and never followed.
+    pub fn find(&self, roots: &[PathBuf]) -> Result<Found, String> {
+        let now = SystemTime::now();
+        let mut found = Found::default();
+        for root in roots {
+            if !root.is_dir() {
+                return Err(format!("'{}' is not a directory", root.display()));
+            }
+            self.walk(root, now, &mut found);
+        }
+        Ok(found)
+    }
+
+    fn walk(&self, dir: &Path, now: SystemTime, found: &mut Found) {
+        let entries = fs::read_dir(dir).and_then(|entries| entries.collect::<io::Result<Vec<_>>>());
+        let mut entries = match entries {
+            Ok(entries) => entries,
+            Err(e) => return found.unreadable(dir, e),
+        };
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries {
+            let path = entry.path();
+            let meta = match fs::symlink_metadata(&path) {
+                Ok(meta) => meta,
+                Err(e) => {
+                    found.unreadable(&path, e);
+                    continue;
+                }
+            };
+            if meta.is_dir() {
+                match fs::read_dir(&path).map(|mut d| d.next().is_none()) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        self.walk(&path, now, found);
+                        continue;
+                    }
+                    Err(e) => {
+                        found.unreadable(&path, e);
+                        continue;
+                    }
+                }
+            }
+            match self.matches(&path, &meta, now) {
+                Ok(true) => found.paths.push(path),
+                Ok(false) => {}
+                Err(e) => found.unreadable(&path, e),
+            }
+        }
+    }
+}
+
+/// What --find turned up
+#[derive(Debug, Default)]
+pub struct Found {
+    pub paths: Vec<PathBuf>,
+    /// An error for each entry passed over, naming it
+    pub errors: Vec<String>,
+}
+
+impl Found {
+    fn unreadable(&mut self, path: &Path, e: io::Error) {
+        self.errors
+            .push(format!("cannot read '{}': {e}", path.display()));
+    }
+}
+
+/// Every directory under the directories `roots` that is empty, or would be
+/// once the ones before it are gone: deepest first, so each can be removed
+/// on its own.
+pub fn empty_dirs(roots: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    let mut found = Vec::new();
+    for root in roots {
+        if !root.symlink_metadata().is_ok_and(|m| m.is_dir()) {
+            return Err(format!("'{}' is not a directory", root.display()));
+        }
+        collect_empty(root, &mut found)
+            .map_err(|e| format!("cannot read '{}': {e}", root.display()))?;
+    }
+    Ok(found)
+}
+
+/// Add the empty directories under `dir` to `found`; whether `dir` will be
+/// empty once they are gone.
+fn collect_empty(dir: &Path, found: &mut Vec<PathBuf>) -> io::Result<bool> {
+    let mut entries: Vec<_> = fs::read_dir(dir)?.collect::<io::Result<_>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    let mut empty = true;
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() && collect_empty(&path, found)? {
+            found.push(path);
+        } else {
+            empty = false;
+        }
+    }
+    Ok(empty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::sized_tree as tree;
+
+    fn find(tmp: &tempfile::TempDir, preds: &Predicates) -> Vec<String> {
+        preds
+            .find(&[tmp.path().to_path_buf()])
+            .unwrap()
+            .paths
+            .iter()
+            .map(|p| p.strip_prefix(tmp.path()).unwrap().display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_name_and_size() {
+        let tmp = tree(&[
+            ("a.log", 10),
+            ("b.txt", 10),
+            ("logs/old.log", 5000),
+            ("logs/new.txt", 1),
+        ]);
+        let preds = Predicates::new(Some("*.log"), None, None, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(find(&tmp, &preds), ["a.log", "logs/old.log"]);
+
+        let preds = Predicates::new(None, None, Some("+1k"), false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(find(&tmp, &preds), ["logs/old.log"]);
+        let preds = Predicates::new(Some("*.txt"), None, Some("-5"), false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(find(&tmp, &preds), ["logs/new.txt"]);
+    }
+
+    #[test]
+    fn test_directories_are_descended_into() {
+        let tmp = tree(&[("build/out.o", 1), ("build/logs/a.log", 1), ("hollow/", 0)]);
+        let preds = Predicates::new(None, Some("0"), None, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            find(&tmp, &preds),
+            ["build/logs/a.log", "build/out.o", "hollow"]
+        );
+    }
+
+    #[test]
+    fn test_unreadable_entries_are_passed_over() {
+        let tmp = tree(&[("a.txt", 1)]);
+        let preds = Predicates::new(Some("*.txt"), None, None, false)
+            .unwrap()
+            .unwrap();
+        let mut found = Found::default();
+        let now = SystemTime::now();
+        preds.walk(&tmp.path().join("gone"), now, &mut found);
+        preds.walk(tmp.path(), now, &mut found);
+        assert_eq!(found.paths, [tmp.path().join("a.txt")]);
+        assert_eq!(found.errors.len(), 1);
+        assert!(found.errors[0].starts_with("cannot read '"));
+    }
+
+    #[test]
+    fn test_empty_takes_directories_whole() {
+        let tmp = tree(&[
+            ("full/a", 1),
+            ("hollow/", 0),
+            ("zero", 0),
+            ("outer/inner/", 0),
+        ]);
+        let preds = Predicates::new(None, None, None, true).unwrap().unwrap();
+        assert_eq!(find(&tmp, &preds), ["hollow", "outer/inner", "zero"]);
+    }
+
+    #[test]
+    fn test_mtime() {
+        let tmp = tree(&[("fresh", 1)]);
+        let in_days = |preds: &str| {
+            let preds = Predicates::new(None, Some(preds), None, false)
+                .unwrap()
+                .unwrap();
+            find(&tmp, &preds)
+        };
+        assert_eq!(in_days("0"), ["fresh"]);
+        assert_eq!(in_days("-1"), ["fresh"]);
+        assert!(in_days("+30").is_empty());
+    }
+
+    #[test]
+    fn test_empty_dirs_deepest_first() {
+        let tmp = tree(&[
+            ("a/b/c/", 0),
+            ("a/d/", 0),
+            ("keep/x", 1),
+            ("keep/e/", 0),
+            ("f", 0),
+        ]);
+        let found: Vec<String> = empty_dirs(&[tmp.path().to_path_buf()])
+            .unwrap()
+            .iter()
+            .map(|p| p.strip_prefix(tmp.path()).unwrap().display().to_string())
+            .collect();
+        assert_eq!(found, ["a/b/c", "a/b", "a/d", "a", "keep/e"]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Predicates::new(None, None, None, false).unwrap().is_none());
+        assert!(Predicates::new(None, Some("+x"), None, false).is_err());
+        assert!(Predicates::new(None, None, Some("+1Q"), false).is_err());
+        assert!(Predicates::new(Some("[a"), None, None, false).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+// Commands run around trashing, restoring and purging, from [hooks] in
+// config.toml. A pre hook gets the paths about to be affected and can stop
+// the operation by exiting non-zero; a post hook gets the paths that were
+// affected once the run is over, or under --daemon and --serve-stdio once
+// each call is. Paths go to the hook's stdin, one absolute
+// path per line, and $TRACHE_HOOK names the hook (e.g. "pre_trash").
+// --trash-empty and --trash-clean run the purge hooks, and --shred runs
+// pre_trash, since it removes files at least as surely.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+use crate::audit::Op;
+use crate::config;
+use crate::error::TracheError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Trash,
+    Restore,
+    Purge,
+}
+
+impl Kind {
+    const ALL: [Kind; 3] = [Kind::Trash, Kind::Restore, Kind::Purge];
+
+    fn of(op: Op) -> Option<Kind> {
+        match op {
+            Op::Trash => Some(Kind::Trash),
+            Op::Restore => Some(Kind::Restore),
+            Op::Purge | Op::Empty => Some(Kind::Purge),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Trash => "trash",
+            Kind::Restore => "restore",
+            Kind::Purge => "purge",
+        }
+    }
+
+    /// What the operation did, for messages
+    fn done(self) -> &'static str {
+        match self {
+            Kind::Trash => "trashed",
+            Kind::Restore => "restored",
+            Kind::Purge => "purged",
+        }
+    }
+}
+
+struct Hooks {
+    cfg: config::Hooks,
+    /// Paths affected so far, for the post hooks
+    affected: Vec<(Kind, PathBuf)>,
+}
+
+impl Hooks {
+    fn command(&self, kind: Kind, pre: bool) -> &[String] {
+        let cfg = &self.cfg;
+        match (kind, pre) {
+            (Kind::Trash, true) => &cfg.pre_trash,
+            (Kind::Trash, false) => &cfg.post_trash,
+            (Kind::Restore, true) => &cfg.pre_restore,
+            (Kind::Restore, false) => &cfg.post_restore,
+            (Kind::Purge, true) => &cfg.pre_purge,
+            (Kind::Purge, false) => &cfg.post_purge,
+        }
+    }
+}
+
+static HOOKS: Mutex<Option<Hooks>> = Mutex::new(None);
+
+/// Set up the hooks the config names.
+pub fn init(cfg: &config::Hooks) {
+    *HOOKS.lock().unwrap() = Some(Hooks {
+        cfg: cfg.clone(),
+        affected: Vec::new(),
+    });
+}
+
+/// Run the pre hook for `kind` on `paths`; an error means the operation
+/// must not go ahead.
+pub fn pre(kind: Kind, paths: &[PathBuf]) -> Result<(), TracheError> {
+    let guard = HOOKS.lock().unwrap();
+    let Some(hooks) = guard.as_ref() else {
+        return Ok(());
+    };
+    let command = hooks.command(kind, true);
+    if command.is_empty() || paths.is_empty() {
+        return Ok(());
+    }
+    let name = format!("pre_{}", kind.name());
+    run(command, &name, paths)
+        .map_err(|e| TracheError::Refused(format!("{name} hook: {e}; nothing was {}", kind.done())))
+}
+
+/// Note that `op` succeeded on `path`, for the post hooks.
+pub fn record(op: Op, path: &Path) {
+    let Some(kind) = Kind::of(op) else {
+        return;
+    };
+    let mut guard = HOOKS.lock().unwrap();
+    let Some(hooks) = guard.as_mut() else {
+        return;
+    };
+    if !hooks.command(kind, false).is_empty() {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        hooks.affected.push((kind, path));
+    }
+}
+
+/// Run the post hooks on everything affected so far, and start over with
+/// nothing affected. The service calls this after each operation.
+pub fn flush() {
+    let mut guard = HOOKS.lock().unwrap();
+    let Some(hooks) = guard.as_mut() else {
+        return;
+    };
+    let affected = std::mem::take(&mut hooks.affected);
+    post(hooks, &affected);
+}
+
+/// Run the post hooks on everything affected; only the first call does
+/// anything. Called by status::finish.
+pub fn finish() {
+    let Some(hooks) = HOOKS.lock().unwrap().take() else {
+        return;
+    };
+    post(&hooks, &hooks.affected);
+}
+
+/// For tests: a post_trash hook that adds a line to `<path>.hooked` for each
+/// path it is given. Set up once and shared, since the hooks are global.
+#[cfg(all(test, unix))]
+pub fn mark_trashed() {
+    static ONCE: std::sync::Once = std::sync::Once::new();
+    ONCE.call_once(|| {
+        let script = r#"while read -r path; do echo >> "$path.hooked"; done"#;
+        init(&config::Hooks {
+            post_trash: vec!["sh".into(), "-c".into(), script.into()],
+            ..Default::default()
+        })
+    });
+}
+
+fn post(hooks: &Hooks, affected: &[(Kind, PathBuf)]) {
+    for kind in Kind::ALL {
+        let paths: Vec<PathBuf> = affected
+            .iter()
+            .filter(|(k, _)| *k == kind)
+            .map(|(_, path)| path.clone())
+            .collect();
+        let command = hooks.command(kind, false);
+        if paths.is_empty() || command.is_empty() {
+            continue;
+        }
+        let name = format!("post_{}", kind.name());
+        if let Err(e) = run(command, &name, &paths) {
+            eprintln!("trache: warning: {name} hook: {e}");
+        }
+    }
+}
+
+fn run(command: &[String], name: &str, paths: &[PathBuf]) -> Result<(), String> {
+    let mut child = Command::new(&command[0])
+        .args(&command[1..])
+        .env("TRACHE_HOOK", name)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("{}: {e}", command[0]))?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let mut text = String::new();
+    for path in paths {
+        text.push_str(&path.to_string_lossy());
+        text.push('\n');
+    }
+    // A hook that doesn't read its input may close it early, which must not
+    // kill us with SIGPIPE
+    #[cfg(unix)]
+    // SAFETY: only swaps the disposition of SIGPIPE, restored below
+    let previous = unsafe { libc::signal(libc::SIGPIPE, libc::SIG_IGN) };
+    let _ = stdin.write_all(text.as_bytes());
+    drop(stdin);
+    #[cfg(unix)]
+    // SAFETY: as above
+    unsafe {
+        libc::signal(libc::SIGPIPE, previous);
+    }
+
+    let status = child.wait().map_err(|e| e.to_string())?;
+    if !status.success() {
+        return Err(format!("{} exited with {status}", command[0]));
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_passes_paths_and_name() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let out = tmp.path().join("out");
+        let script = format!(
+            "cat > '{}'; echo \"$TRACHE_HOOK\" >> '{}'",
+            out.display(),
+            out.display()
+        );
+        let command = ["sh".to_string(), "-c".to_string(), script];
+        let paths = [PathBuf::from("/a b"), PathBuf::from("/c")];
+        run(&command, "pre_trash", &paths).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "/a b\n/c\npre_trash\n"
+        );
+    }
+
+    #[test]
+    fn test_failing_hook() {
+        let command = ["sh".to_string(), "-c".to_string(), "exit 3".to_string()];
+        let err = run(&command, "pre_purge", &[PathBuf::from("/a")]).unwrap_err();
+        assert_eq!(err, "sh exited with exit status: 3");
+    }
+}
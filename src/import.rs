@@ -0,0 +1,423 @@
+// --trash-import: the items of an archive made by --trash-export, put back
+// into the local trash with the original paths and deletion times they were
+// exported with (Linux/BSD), or with --in-place restored straight to those
+// paths. An item is skipped when its destination is taken, and one that
+// fails partway is cleared away again, so nothing half-imported is left.
+#![cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+
+use crate::export::{self, Entry, Manifest};
+
+/// Where imported items go
+pub enum Target {
+    /// Into this trash directory, as trashed items
+    Trash(PathBuf),
+    /// Back to their original paths
+    InPlace,
+}
+
+/// What happened to one item
+pub struct Outcome {
+    pub entry: Entry,
+    /// Where it went, or why it didn't
+    pub result: Result<PathBuf, String>,
+}
+
+/// An item being unpacked: where its content goes, and for the trash the
+/// .trashinfo record claimed for it.
+struct Slot {
+    dest: PathBuf,
+    info: Option<PathBuf>,
+    /// Directories made to hold it, outermost first
+    created: Vec<PathBuf>,
+    unpacked: bool,
+}
+
+fn open(archive: &Path) -> io::Result<tar::Archive<Box<dyn Read>>> {
+    let mut reader = BufReader::new(File::open(archive)?);
+    // Go by the gzip magic rather than the name, which may have been changed
+    let gzipped = reader.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+    let reader: Box<dyn Read> = if gzipped {
+        Box::new(flate2::read::GzDecoder::new(reader))
+    } else {
+        Box::new(reader)
+    };
+    Ok(tar::Archive::new(reader))
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_manifest(entry: &mut tar::Entry<impl Read>) -> io::Result<Manifest> {
+    if entry.path()? != Path::new(export::MANIFEST) {
+        return Err(invalid(format!(
+            "not an archive made by --trash-export (no {} at the start)",
+            export::MANIFEST
+        )));
+    }
+    let manifest: Manifest = serde_json::from_reader(entry)
+        .map_err(|e| invalid(format!("{}: {e}", export::MANIFEST)))?;
+    if manifest.version > export::VERSION {
+        return Err(invalid(format!(
+            "archive format {} is newer than this trache understands ({})",
+            manifest.version,
+            export::VERSION
+        )));
+    }
+    Ok(manifest)
+}
+
+/// The manifest of `archive`, without unpacking anything.
+pub fn manifest(archive: &Path) -> io::Result<Manifest> {
+    let mut tar = open(archive)?;
+    let mut entries = tar.entries()?;
+    let mut first = entries
+        .next()
+        .ok_or_else(|| invalid("the archive is empty".into()))??;
+    read_manifest(&mut first)
+}
+
+/// Unpack every item of `archive` to `target`.
+pub fn import(archive: &Path, target: &Target) -> io::Result<Vec<Outcome>> {
+    let mut tar = open(archive)?;
+    let mut entries = tar.entries()?;
+    let mut first = entries
+        .next()
+        .ok_or_else(|| invalid("the archive is empty".into()))??;
+    let manifest = read_manifest(&mut first)?;
+    drop(first);
+
+    let index: HashMap<&str, usize> = manifest
+        .items
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| (entry.entry.as_str(), i))
+        .collect();
+    let mut slots: Vec<Result<Slot, String>> = manifest
+        .items
+        .iter()
+        .map(|entry| claim(entry, target).map_err(|e| e.to_string()))
+        .collect();
+
+    for tar_entry in entries {
+        let mut tar_entry = tar_entry?;
+        let path = tar_entry.path()?.into_owned();
+        let Some((i, rest)) = locate(&path, &index) else {
+            continue;
+        };
+        let Ok(slot) = &mut slots[i] else {
+            continue;
+        };
+        let dest = if rest.as_os_str().is_empty() {
+            slot.dest.clone()
+        } else {
+            slot.dest.join(&rest)
+        };
+        // A hard link can point anywhere, and so can a symlink unpacked
+        // earlier that a later entry would be written through
+        let unpacked = if tar_entry.header().entry_type().is_hard_link() {
+            Err(invalid(format!("'{}' is a hard link", path.display())))
+        } else if !inside(&slot.dest, &rest) {
+            Err(invalid(format!(
+                "'{}' goes through a symbolic link",
+                path.display()
+            )))
+        } else {
+            tar_entry.unpack(&dest).map(|_| ())
+        };
+        match unpacked {
+            Ok(_) => slot.unpacked = true,
+            Err(e) => {
+                let slot = std::mem::replace(&mut slots[i], Err(e.to_string()));
+                if let Ok(slot) = slot {
+                    release(&slot);
+                }
+            }
+        }
+    }
+
+    Ok(manifest
+        .items
+        .into_iter()
+        .zip(slots)
+        .map(|(entry, slot)| {
+            let result = slot.and_then(|slot| {
+                if slot.unpacked {
+                    Ok(slot.dest)
+                } else {
+                    release(&slot);
+                    Err("missing from the archive".into())
+                }
+            });
+            Outcome { entry, result }
+        })
+        .collect())
+}
+
+/// The item a path in the archive belongs to, and the path inside it.
+fn locate(path: &Path, index: &HashMap<&str, usize>) -> Option<(usize, PathBuf)> {
+    let mut parts = path.components();
+    let head: Vec<_> = parts.by_ref().take(3).collect();
+    let key = head
+        .iter()
+        .map(|c| c.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?
+        .join("/");
+    let i = *index.get(key.as_str())?;
+    // Only plain names below the item, so nothing lands outside it
+    let rest = parts.as_path();
+    if !rest.components().all(|c| matches!(c, Component::Normal(_))) {
+        return None;
+    }
+    Some((i, rest.to_path_buf()))
+}
+
+/// Whether `rest` can be unpacked below `dest` without passing through a
+/// symlink: `dest` itself and every directory on the way must be real.
+fn inside(dest: &Path, rest: &Path) -> bool {
+    let Some(parent) = rest.parent() else {
+        return true;
+    };
+    let mut at = dest.to_path_buf();
+    let is_link = |path: &Path| path.symlink_metadata().is_ok_and(|m| m.is_symlink());
+    if is_link(&at) {
+        return false;
+    }
+    for part in parent.components() {
+        at.push(part);
+        if is_link(&at) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Reserve the place `entry` is unpacked to.
+fn claim(entry: &Entry, target: &Target) -> io::Result<Slot> {
+    match target {
+        Target::InPlace => {
+            let dest = entry.original_path.clone();
+            if dest.symlink_metadata().is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    "already exists",
+                ));
+            }
+            let mut created = Vec::new();
+            let mut missing = dest.parent();
+            while let Some(dir) = missing.filter(|dir| dir.symlink_metadata().is_err()) {
+                created.insert(0, dir.to_path_buf());
+                missing = dir.parent();
+            }
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            Ok(Slot {
+                dest,
+                info: None,
+                created,
+                unpacked: false,
+            })
+        }
+        Target::Trash(trash) => claim_in_trash(trash, entry),
+    }
+}
+
+/// Write the .trashinfo record for `entry` under a name no other item in
+/// `trash` uses, and return where its content goes.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+fn claim_in_trash(trash: &Path, entry: &Entry) -> io::Result<Slot> {
+    let (dest, info) = crate::backend::claim(trash, &entry.original_path, entry.deleted)?;
+    Ok(Slot {
+        dest,
+        info: Some(info),
+        created: Vec::new(),
+        unpacked: false,
+    })
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"), not(target_os = "ios"))))]
+fn claim_in_trash(_trash: &Path, _entry: &Entry) -> io::Result<Slot> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "importing into the trash is only supported on Linux/BSD; use --in-place",
+    ))
+}
+
+/// Undo whatever was unpacked for `slot`, and its claim.
+fn release(slot: &Slot) {
+    match slot.dest.symlink_metadata() {
+        Ok(meta) if meta.is_dir() => {
+            let _ = fs::remove_dir_all(&slot.dest);
+        }
+        Ok(_) => {
+            let _ = fs::remove_file(&slot.dest);
+        }
+        Err(_) => {}
+    }
+    if let Some(ref info) = slot.info {
+        let _ = fs::remove_file(info);
+    }
+    // Only while empty, in case something else has been put there since
+    for dir in slot.created.iter().rev() {
+        let _ = fs::remove_dir(dir);
+    }
+}
+
+/// The home trash, where imported items go: $XDG_DATA_HOME/Trash, or
+/// ~/.local/share/Trash.
+pub fn home_trash() -> Option<PathBuf> {
+    if let Some(data_home) = std::env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(data_home).join("Trash"));
+    }
+    let home = std::env::var_os("HOME").filter(|h| !h.is_empty())?;
+    Some(PathBuf::from(home).join(".local/share/Trash"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(dir: &Path) -> PathBuf {
+        let src = dir.join("src");
+        fs::create_dir_all(src.join("tree/sub")).unwrap();
+        fs::write(src.join("a.txt"), "hello").unwrap();
+        fs::write(src.join("tree/sub/x"), "inside").unwrap();
+        let archive = dir.join("out.tar.gz");
+        export::write(
+            &archive,
+            &[
+                (
+                    src.join("a.txt"),
+                    Entry::new(1, dir.join("home/a.txt"), 1_700_000_000),
+                ),
+                (
+                    src.join("tree"),
+                    Entry::new(2, dir.join("home/tree"), 1_700_000_100),
+                ),
+            ],
+        )
+        .unwrap();
+        archive
+    }
+
+    #[test]
+    fn test_import_in_place() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let archive = archive(tmp.path());
+        assert_eq!(manifest(&archive).unwrap().items.len(), 2);
+
+        let outcomes = import(&archive, &Target::InPlace).unwrap();
+        assert!(outcomes.iter().all(|o| o.result.is_ok()));
+        let home = tmp.path().join("home");
+        assert_eq!(fs::read_to_string(home.join("a.txt")).unwrap(), "hello");
+        assert_eq!(
+            fs::read_to_string(home.join("tree/sub/x")).unwrap(),
+            "inside"
+        );
+
+        // Everything is taken now
+        let outcomes = import(&archive, &Target::InPlace).unwrap();
+        assert!(
+            outcomes
+                .iter()
+                .all(|o| o.result.as_ref().is_err_and(|e| e == "already exists"))
+        );
+    }
+
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    fn test_import_into_trash() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let archive = archive(tmp.path());
+        let trash = tmp.path().join("Trash");
+        fs::create_dir_all(trash.join("files/a.txt")).unwrap();
+
+        let outcomes = import(&archive, &Target::Trash(trash.clone())).unwrap();
+        let dests: Vec<_> = outcomes.into_iter().map(|o| o.result.unwrap()).collect();
+        assert_eq!(
+            dests,
+            [trash.join("files/a.txt.2"), trash.join("files/tree")]
+        );
+        assert_eq!(fs::read_to_string(&dests[0]).unwrap(), "hello");
+        assert!(dests[1].join("sub/x").is_file());
+        let info = fs::read_to_string(trash.join("info/a.txt.2.trashinfo")).unwrap();
+        assert!(info.contains(&format!("Path={}/home/a.txt\n", tmp.path().display())));
+        assert!(info.contains("DeletionDate=20"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_import_refuses_to_write_through_symlinks() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let outside = tmp.path().join("outside");
+        fs::create_dir(&outside).unwrap();
+        let archive = tmp.path().join("evil.tar");
+        let original = tmp.path().join("home/sub/d");
+        let mut builder = tar::Builder::new(File::create(&archive).unwrap());
+        let manifest = serde_json::to_vec(&Manifest {
+            version: export::VERSION,
+            exported: 0,
+            items: vec![Entry::new(1, original.clone(), 0)],
+        })
+        .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, export::MANIFEST, manifest.as_slice())
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_mode(0o755);
+        header.set_size(0);
+        builder
+            .append_data(&mut header, "items/1/d", io::empty())
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, "items/1/d/l", &outside)
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(3);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "items/1/d/l/pwn", &b"pwn"[..])
+            .unwrap();
+        builder.into_inner().unwrap();
+
+        let outcomes = import(&archive, &Target::InPlace).unwrap();
+        assert!(
+            outcomes[0]
+                .result
+                .as_ref()
+                .unwrap_err()
+                .contains("symbolic link")
+        );
+        assert!(!outside.join("pwn").exists());
+        // Nothing is left behind, not even the directories made for it
+        assert!(!tmp.path().join("home").exists());
+    }
+
+    #[test]
+    fn test_locate_stays_inside_items() {
+        let index = HashMap::from([("items/1/a", 0)]);
+        assert_eq!(
+            locate(Path::new("items/1/a/b/c"), &index),
+            Some((0, PathBuf::from("b/c")))
+        );
+        assert_eq!(
+            locate(Path::new("items/1/a"), &index),
+            Some((0, PathBuf::new()))
+        );
+        assert_eq!(locate(Path::new("items/1/a/../../x"), &index), None);
+        assert_eq!(locate(Path::new("items/2/a"), &index), None);
+    }
+}
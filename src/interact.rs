@@ -5,6 +5,8 @@
 use std::io::{self, BufRead, Write};
 use std::path::{Path, PathBuf};
 
+use crate::{answers, status, transcript};
+
 // --- Types ---
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -29,19 +31,242 @@ pub struct TwinInfo {
     pub timestamp: String,
 }
 
+// --- Prompt help ---
+//
+// Every answer a prompt takes, with what it leads to. Menus and the help
+// shown when `?` is typed are both built from these tables, so the two
+// can't drift apart.
+
+struct Choice {
+    key: char,
+    /// Menu text; `{}` stands for a detail, such as the name an item would get
+    label: &'static str,
+    /// The consequences, shown by `?`
+    effect: &'static str,
+}
+
+const YES_NO: &[Choice] = &[
+    Choice {
+        key: 'y',
+        label: "Yes: go ahead (also \"yes\", the locale's yes, or a word from yes_answers)",
+        effect: "Does what the question describes. Trashed files can be brought back \
+                 with --trash-undo; with --shred or --rm-fallback, or when purging, \
+                 they are gone for good.",
+    },
+    Choice {
+        key: 'n',
+        label: "No: leave it alone (also anything else, or just Enter)",
+        effect: "Skips this and carries on with the rest.",
+    },
+];
+
+const COLLISION: &[Choice] = &[
+    Choice {
+        key: 'o',
+        label: "Overwrite: replace existing file",
+        effect: "Deletes what is now at the original path permanently (it is not \
+                 trashed) and restores the item in its place.",
+    },
+    Choice {
+        key: 'k',
+        label: "Keep both: restore as {}",
+        effect: "Leaves the existing file alone and restores the item next to it \
+                 under a numbered -untrash_N name.",
+    },
+    Choice {
+        key: 'n',
+        label: "None: skip this file",
+        effect: "Leaves both as they are; the item stays in the trash.",
+    },
+    Choice {
+        key: 'q',
+        label: "Quit",
+        effect: "Stops restoring. Items restored so far stay restored.",
+    },
+];
+
+const CONFLICT_POLICY: &[Choice] = &[
+    Choice {
+        key: 'i',
+        label: "Individually: ask for each one",
+        effect: "Asks about each conflict as it comes up, offering overwrite, keep \
+                 both or skip.",
+    },
+    Choice {
+        key: 'o',
+        label: "Overwrite all",
+        effect: "For every conflict, deletes what is at the original path \
+                 permanently (it is not trashed) and restores the item there.",
+    },
+    Choice {
+        key: 'k',
+        label: "Keep both for all: restore as <name>-untrash_N.<ext>",
+        effect: "Leaves every existing file alone and restores the conflicting \
+                 items next to them under numbered names.",
+    },
+    Choice {
+        key: 's',
+        label: "Skip all",
+        effect: "Restores only the items without a conflict; the rest stay in the \
+                 trash.",
+    },
+    Choice {
+        key: 'q',
+        label: "Quit",
+        effect: "Stops before restoring anything.",
+    },
+];
+
+const TWINS: &[Choice] = &[
+    Choice {
+        key: 'a',
+        label: "All: restore as {}",
+        effect: "Restores every version, each under a numbered -untrash_N name \
+                 beside the original path.",
+    },
+    Choice {
+        key: 's',
+        label: "Some: select versions to restore",
+        effect: "Lists the versions by number and restores only the ones picked.",
+    },
+    Choice {
+        key: 'l',
+        label: "List: show details",
+        effect: "Shows each version with its deletion time, then asks again.",
+    },
+    Choice {
+        key: 'n',
+        label: "None: skip",
+        effect: "Leaves every version in the trash.",
+    },
+    Choice {
+        key: 'q',
+        label: "Quit",
+        effect: "Stops restoring. Items restored so far stay restored.",
+    },
+];
+
+const SELECTION_HELP: &str = "\n\
+    Enter numbers from the list above, separated by commas, with ranges as\n\
+    3-5 (for example 1,3-5). Only the items picked are acted on. End the\n\
+    input (Ctrl-D) to pick nothing.\n";
+
+/// The menu lines for `choices`, with `{}` in labels replaced by `detail`.
+fn menu(choices: &[Choice], detail: &str) -> String {
+    let mut text: String = choices
+        .iter()
+        .map(|c| format!("({}) {}\n", c.key, c.label.replace("{}", detail)))
+        .collect();
+    text.push_str("(?) Help: explain these choices\n");
+    text
+}
+
+/// What `?` shows for a prompt taking `choices`.
+fn help(choices: &[Choice], detail: &str) -> String {
+    let mut text = String::from("\n");
+    for c in choices {
+        text.push_str(&format!(
+            "({}) {}\n    {}\n",
+            c.key,
+            c.label.replace("{}", detail),
+            c.effect
+        ));
+    }
+    text
+}
+
+/// Every answer the restore prompts take, with what it leads to, for the
+/// end of --trash-undo's --help.
+pub fn restore_help() -> String {
+    let prompts = [
+        (
+            "Collision (original path already exists):",
+            COLLISION,
+            "<name>-untrash_N.<ext>",
+        ),
+        (
+            "Several collisions (asked first, when two or more are predicted):",
+            CONFLICT_POLICY,
+            "",
+        ),
+        (
+            "Twins (multiple trashed copies with the same original path):",
+            TWINS,
+            "<name>-untrash_{N..M}.<ext>",
+        ),
+    ];
+    let mut text = String::new();
+    for (title, choices, detail) in prompts {
+        text.push_str(&format!("\n{title}\n"));
+        for c in choices {
+            text.push_str(&format!(
+                "  ({}) {}\n",
+                c.key,
+                c.label.replace("{}", detail)
+            ));
+            let mut line = String::new();
+            for word in c.effect.split_whitespace() {
+                if !line.is_empty() && line.len() + word.len() >= 64 {
+                    text.push_str(&format!("      {line}\n"));
+                    line.clear();
+                }
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line.push_str(word);
+            }
+            text.push_str(&format!("      {line}\n"));
+        }
+    }
+    text
+}
+
+/// Whether `line` asks for help. rm's prompts take '?' as a no, like
+/// anything else that isn't yes.
+fn wants_help(line: &str) -> bool {
+    line.trim() == "?" && !crate::rmcompat::enabled()
+}
+
 // --- Semantic prompt functions ---
 
-pub fn prompt_yes(input: &mut dyn BufRead, prompt: &str) -> bool {
+/// Show `prompt` and read one line of answer, recording both in the
+/// transcript. None at end of input or on a read error.
+fn read_answer(input: &mut dyn BufRead, prompt: &str) -> Option<String> {
     eprint!("{}", prompt);
     io::stderr().flush().ok();
 
     let mut line = String::new();
-    if input.read_line(&mut line).is_err() {
-        return false;
-    }
+    let answer = match input.read_line(&mut line) {
+        Ok(n) if n > 0 => Some(line),
+        _ => None,
+    };
+    transcript::asked(prompt, answer.as_deref());
+    answer
+}
+
+/// Print a menu (or other text leading up to a question) to stderr.
+fn show(text: &str) {
+    eprint!("{text}");
+    transcript::shown(text);
+}
+
+pub fn prompt_yes(input: &mut dyn BufRead, prompt: &str) -> bool {
+    loop {
+        let Some(line) = read_answer(input, prompt) else {
+            status::note_declined();
+            return false;
+        };
+        if wants_help(&line) {
+            show(&help(YES_NO, ""));
+            continue;
+        }
 
-    let response = line.trim().to_lowercase();
-    matches!(response.as_str(), "y" | "yes")
+        let yes = answers::is_yes(&line);
+        if !yes {
+            status::note_declined();
+        }
+        return yes;
+    }
 }
 
 pub fn prompt_collision(
@@ -50,22 +275,24 @@ pub fn prompt_collision(
     keep_name: &Path,
     once: bool,
 ) -> CollisionChoice {
-    eprintln!("\n{} already exists.", path.display());
-    eprintln!("(o) Overwrite: replace existing file");
-    eprintln!("(k) Keep both: restore as {}", keep_name.display());
-    eprintln!("(n) None: skip this file");
-    eprintln!("(q) Quit");
+    let keep_name = keep_name.display().to_string();
+    let mut text = format!(
+        "\n{} already exists.\n{}",
+        path.display(),
+        menu(COLLISION, &keep_name)
+    );
     if once {
-        eprintln!("(this choice will apply to all future 'path already exists' conflicts)");
+        text.push_str("(this choice will apply to all future 'path already exists' conflicts)\n");
     }
+    show(&text);
 
     loop {
-        eprint!("Choice: ");
-        io::stderr().flush().ok();
-
-        let mut line = String::new();
-        if input.read_line(&mut line).unwrap_or(0) == 0 {
+        let Some(line) = read_answer(input, "Choice: ") else {
             return CollisionChoice::Quit; // EOF
+        };
+        if wants_help(&line) {
+            show(&help(COLLISION, &keep_name));
+            continue;
         }
 
         match line.trim().to_lowercase().chars().next() {
@@ -78,6 +305,34 @@ pub fn prompt_collision(
     }
 }
 
+/// Ask once how to handle `count` predicted collisions.
+/// Returns None to resolve them one by one, or the choice to apply to all.
+pub fn prompt_conflict_policy(input: &mut dyn BufRead, count: usize) -> Option<CollisionChoice> {
+    show(&format!(
+        "\n{count} conflicts detected (original paths already exist).\n{}",
+        menu(CONFLICT_POLICY, "")
+    ));
+
+    loop {
+        let Some(line) = read_answer(input, "Choice: ") else {
+            return Some(CollisionChoice::Quit); // EOF
+        };
+        if wants_help(&line) {
+            show(&help(CONFLICT_POLICY, ""));
+            continue;
+        }
+
+        match line.trim().to_lowercase().chars().next() {
+            Some('i') => return None,
+            Some('o') => return Some(CollisionChoice::Overwrite),
+            Some('k') => return Some(CollisionChoice::KeepBoth),
+            Some('s') => return Some(CollisionChoice::None),
+            Some('q') => return Some(CollisionChoice::Quit),
+            _ => eprintln!("Invalid choice."),
+        }
+    }
+}
+
 pub fn prompt_twins(
     input: &mut dyn BufRead,
     path: &Path,
@@ -88,29 +343,33 @@ pub fn prompt_twins(
     let count = twins.len();
 
     loop {
-        eprintln!("\nThe following path was trashed {count} times:");
-        eprintln!("  {}", path.display());
-        eprintln!("(a) All: restore as {range_desc}");
-        eprintln!("(s) Some: select versions to restore");
-        eprintln!("(l) List: show details");
-        eprintln!("(n) None: skip");
-        eprintln!("(q) Quit");
+        let mut text = format!(
+            "\nThe following path was trashed {count} times:\n  {}\n{}",
+            path.display(),
+            menu(TWINS, range_desc)
+        );
         if once {
-            eprintln!("(this choice will apply to all future twin conflicts)");
+            text.push_str("(this choice will apply to all future twin conflicts)\n");
         }
+        show(&text);
 
-        eprint!("Choice: ");
-        io::stderr().flush().ok();
-
-        let mut line = String::new();
-        if input.read_line(&mut line).unwrap_or(0) == 0 {
+        let Some(line) = read_answer(input, "Choice: ") else {
             return TwinChoice::Quit; // EOF
+        };
+        if wants_help(&line) {
+            show(&help(TWINS, range_desc));
+            continue;
         }
 
         match line.trim().to_lowercase().chars().next() {
             Some('l') => {
                 for (i, twin) in twins.iter().enumerate() {
-                    eprintln!("  {}: {} ({})", i + 1, twin.name, twin.timestamp);
+                    show(&format!(
+                        "  {}: {} ({})\n",
+                        i + 1,
+                        twin.name,
+                        twin.timestamp
+                    ));
                 }
                 continue;
             }
@@ -120,7 +379,12 @@ pub fn prompt_twins(
             Some('s') => {
                 // Show numbered list for selection
                 for (i, twin) in twins.iter().enumerate() {
-                    eprintln!("  {}: {} ({})", i + 1, twin.name, twin.timestamp);
+                    show(&format!(
+                        "  {}: {} ({})\n",
+                        i + 1,
+                        twin.name,
+                        twin.timestamp
+                    ));
                 }
                 match prompt_selection(input, count) {
                     Some(sel) => return TwinChoice::Some(sel),
@@ -134,18 +398,18 @@ pub fn prompt_twins(
 
 pub fn prompt_selection(input: &mut dyn BufRead, count: usize) -> Option<Vec<usize>> {
     loop {
-        eprint!("Select items (e.g. 1,3-5): ");
-        io::stderr().flush().ok();
-
-        let mut line = String::new();
-        if input.read_line(&mut line).unwrap_or(0) == 0 {
+        let Some(line) = read_answer(input, "Select items (e.g. 1,3-5): ") else {
             return None; // EOF
-        }
+        };
 
         let trimmed = line.trim();
         if trimmed.is_empty() {
             continue;
         }
+        if wants_help(trimmed) {
+            show(SELECTION_HELP);
+            continue;
+        }
 
         match parse_selection(trimmed, count) {
             Ok(sel) if !sel.is_empty() => return Some(sel),
@@ -372,6 +636,35 @@ mod tests {
         assert!(prompt_yes(&mut input, "proceed? "));
     }
 
+    #[test]
+    fn test_prompt_yes_help_asks_again() {
+        let mut input = Cursor::new(b"?\ny\n");
+        assert!(prompt_yes(&mut input, "proceed? "));
+    }
+
+    #[test]
+    fn test_menu_and_help_share_labels() {
+        assert_eq!(
+            menu(COLLISION, "a-untrash_1"),
+            "(o) Overwrite: replace existing file\n\
+             (k) Keep both: restore as a-untrash_1\n\
+             (n) None: skip this file\n\
+             (q) Quit\n\
+             (?) Help: explain these choices\n"
+        );
+        let help = help(COLLISION, "a-untrash_1");
+        assert!(help.contains("(k) Keep both: restore as a-untrash_1\n    Leaves"));
+        let restore = restore_help();
+        assert!(
+            restore.contains("\n  (k) Keep both: restore as <name>-untrash_N.<ext>\n      Leaves")
+        );
+        assert!(restore.contains("\n  (s) Skip all\n"));
+        assert!(restore.lines().all(|line| line.len() <= 70));
+        for choices in [YES_NO, COLLISION, CONFLICT_POLICY, TWINS] {
+            assert!(choices.iter().all(|c| !c.effect.is_empty()));
+        }
+    }
+
     #[test]
     fn test_prompt_yes_no() {
         let mut input = Cursor::new(b"n\n");
@@ -386,6 +679,27 @@ mod tests {
 
     // --- prompt_collision tests ---
 
+    #[test]
+    fn test_prompt_conflict_policy() {
+        let mut input = Cursor::new(b"i\n");
+        assert_eq!(prompt_conflict_policy(&mut input, 5), None);
+        let mut input = Cursor::new(b"x\nk\n");
+        assert_eq!(
+            prompt_conflict_policy(&mut input, 5),
+            Some(CollisionChoice::KeepBoth)
+        );
+        let mut input = Cursor::new(b"s\n");
+        assert_eq!(
+            prompt_conflict_policy(&mut input, 5),
+            Some(CollisionChoice::None)
+        );
+        let mut input = Cursor::new(b"");
+        assert_eq!(
+            prompt_conflict_policy(&mut input, 5),
+            Some(CollisionChoice::Quit)
+        );
+    }
+
     #[test]
     fn test_prompt_collision_overwrite() {
         let mut input = Cursor::new(b"o\n");
@@ -430,6 +744,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_prompt_collision_help_then_choice() {
+        let mut input = Cursor::new(b"?\nk\n");
+        let path = Path::new("/home/user/foo.txt");
+        let keep = Path::new("/home/user/foo-untrash_1.txt");
+        assert_eq!(
+            prompt_collision(&mut input, path, keep, false),
+            CollisionChoice::KeepBoth
+        );
+    }
+
     #[test]
     fn test_prompt_collision_invalid_then_valid() {
         let mut input = Cursor::new(b"x\no\n");
@@ -0,0 +1,121 @@
+// Ctrl-C while trashing a batch: the first one lets the current item finish
+// and stops before the next, so nothing is left half-moved, and the run ends
+// with what was trashed and how to bring it back. A second Ctrl-C quits at
+// once, e.g. at a prompt. Running as rm, Ctrl-C kills the process as usual.
+
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use chrono::{DateTime, Local};
+
+use crate::audit::Op;
+use crate::{batch, labels};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// Items trashed since [`install`]
+static TRASHED: AtomicUsize = AtomicUsize::new(0);
+static START: OnceLock<DateTime<Local>> = OnceLock::new();
+
+const NOTICE: &str =
+    "\ntrache: interrupted; stopping after the current item (Ctrl-C again to quit now)\n";
+
+/// Catch Ctrl-C from now on.
+pub fn install() {
+    let _ = START.set(Local::now());
+    catch();
+}
+
+/// Whether Ctrl-C was pressed.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Count an item `op` succeeded on.
+pub fn record(op: Op) {
+    if op == Op::Trash {
+        TRASHED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// What was done before the interruption, and how to undo it.
+pub fn summary() -> String {
+    let trashed = TRASHED.load(Ordering::Relaxed);
+    if batch::active() {
+        return format!(
+            "interrupted after trashing {trashed} item(s); trache --resume trashes \
+             the rest, trache --resume=rollback restores what was trashed"
+        );
+    }
+    match START.get() {
+        _ if trashed > 0
+            && let Some(label) = labels::current() =>
+        {
+            format!(
+                "interrupted after trashing {trashed} item(s); to restore them: \
+                 trache --trash-undo-label '{label}'"
+            )
+        }
+        Some(start) if trashed > 0 => format!(
+            "interrupted after trashing {trashed} item(s); to restore them (and anything \
+             else trashed since): trache --trash-undo-since '{}'",
+            start.format("%Y-%m-%d %H:%M:%S")
+        ),
+        _ => "interrupted before anything was trashed".to_string(),
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn on_signal(_: libc::c_int) {
+    // Only async-signal-safe calls in here
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        // SAFETY: _exit is async-signal-safe
+        unsafe { libc::_exit(130) };
+    }
+    // SAFETY: write is async-signal-safe; the buffer is static
+    unsafe {
+        libc::write(2, NOTICE.as_ptr().cast(), NOTICE.len());
+    }
+}
+
+#[cfg(unix)]
+fn catch() {
+    // SAFETY: on_signal only touches an atomic and calls write and _exit
+    unsafe {
+        libc::signal(libc::SIGINT, on_signal as *const () as libc::sighandler_t);
+    }
+}
+
+#[cfg(windows)]
+unsafe extern "system" {
+    fn SetConsoleCtrlHandler(
+        handler: Option<unsafe extern "system" fn(u32) -> i32>,
+        add: i32,
+    ) -> i32;
+}
+
+/// Runs on a thread of its own, so it may print.
+#[cfg(windows)]
+unsafe extern "system" fn on_ctrl(kind: u32) -> i32 {
+    const CTRL_C_EVENT: u32 = 0;
+    const CTRL_BREAK_EVENT: u32 = 1;
+    if kind != CTRL_C_EVENT && kind != CTRL_BREAK_EVENT {
+        return 0;
+    }
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        // Not handled: the default handler ends the process
+        return 0;
+    }
+    eprint!("{NOTICE}");
+    1
+}
+
+#[cfg(windows)]
+fn catch() {
+    // SAFETY: registers a handler that lives as long as the process
+    unsafe {
+        SetConsoleCtrlHandler(Some(on_ctrl), 1);
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn catch() {}
@@ -0,0 +1,181 @@
+// Files held open by other processes. On Windows an open file can't be moved
+// to the Recycle Bin (a sharing violation), and on Unix a busy mount point or
+// running executable can't be moved either; such failures are told apart so
+// --retry can wait them out and the error can say what happened. Linux lets
+// open files be trashed, so there the holders are looked up in /proc when
+// `open_files` in config.toml asks for a warning or a prompt first. /proc is
+// read once per run, the first time it's needed, rather than for each file.
+#![cfg_attr(not(target_os = "linux"), allow(dead_code))]
+
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+#[cfg(target_os = "linux")]
+use std::sync::OnceLock;
+
+/// Whether trashing failed because something else is using the file.
+pub fn is_busy(e: &trash::Error) -> bool {
+    match e {
+        #[cfg(windows)]
+        trash::Error::Os { code, .. } => {
+            // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION, bare or as
+            // the HRESULTs the shell reports
+            const SHARING: i32 = 32;
+            const LOCK: i32 = 33;
+            let code = if *code as u32 & 0xFFFF_0000 == 0x8007_0000 {
+                *code & 0xFFFF
+            } else {
+                *code
+            };
+            code == SHARING || code == LOCK
+        }
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+        trash::Error::FileSystem { source, .. } => {
+            matches!(source.raw_os_error(), Some(libc::EBUSY | libc::ETXTBSY))
+        }
+        _ => false,
+    }
+}
+
+/// A process with a file open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holder {
+    pub pid: u32,
+    pub name: String,
+}
+
+/// The other processes with `path`, or anything under it, open, as they
+/// were when first asked about. Processes whose file descriptors we may not
+/// read are missed.
+#[cfg(target_os = "linux")]
+pub fn holders(path: &Path) -> Vec<Holder> {
+    static OPEN: OnceLock<Vec<(Holder, Vec<PathBuf>)>> = OnceLock::new();
+    held_by(OPEN.get_or_init(open_files), path)
+}
+
+/// Those of `open` holding `path` or anything under it.
+#[cfg(target_os = "linux")]
+fn held_by(open: &[(Holder, Vec<PathBuf>)], path: &Path) -> Vec<Holder> {
+    let Ok(target) = path.canonicalize() else {
+        return Vec::new();
+    };
+    open.iter()
+        .filter(|(_, files)| files.iter().any(|open| open.starts_with(&target)))
+        .map(|(holder, _)| holder.clone())
+        .collect()
+}
+
+/// Every other process with what it has open, by pid.
+#[cfg(target_os = "linux")]
+fn open_files() -> Vec<(Holder, Vec<PathBuf>)> {
+    use std::fs;
+
+    let Ok(procs) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let own = std::process::id();
+    let mut found = Vec::new();
+    for entry in procs.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|n| n.parse().ok()) else {
+            continue;
+        };
+        if pid == own {
+            continue;
+        }
+        let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        let files: Vec<PathBuf> = fds
+            .flatten()
+            .filter_map(|fd| fs::read_link(fd.path()).ok())
+            .collect();
+        if !files.is_empty() {
+            let name = fs::read_to_string(entry.path().join("comm")).unwrap_or_default();
+            let holder = Holder {
+                pid,
+                name: name.trim_end().to_string(),
+            };
+            found.push((holder, files));
+        }
+    }
+    found.sort_by_key(|(h, _)| h.pid);
+    found
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn holders(_path: &Path) -> Vec<Holder> {
+    Vec::new()
+}
+
+/// "firefox (pid 42), vim (pid 7)"
+pub fn describe(holders: &[Holder]) -> String {
+    holders
+        .iter()
+        .map(|h| format!("{} (pid {})", h.name, h.pid))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Why trashing `path` failed when it was busy, after `retries` retries.
+pub fn busy_error(path: &Path, retries: u32) -> String {
+    let holders = holders(path);
+    let mut e = if holders.is_empty() {
+        "in use by another process".to_string()
+    } else {
+        format!("in use by {}", describe(&holders))
+    };
+    if retries > 0 {
+        e.push_str(&format!(" (still after {retries} retries)"));
+    }
+    e
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        let holders = [
+            Holder {
+                pid: 42,
+                name: "firefox".into(),
+            },
+            Holder {
+                pid: 7,
+                name: "vim".into(),
+            },
+        ];
+        assert_eq!(describe(&holders), "firefox (pid 42), vim (pid 7)");
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_holders_finds_an_open_file() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let file = tmp.path().join("held");
+        std::fs::write(&file, "x").unwrap();
+        let mut child = std::process::Command::new("sh")
+            .arg("-c")
+            .arg("exec 3<\"$0\"; read line")
+            .arg(&file)
+            .stdin(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+        let mut open = Vec::new();
+        let mut found = Vec::new();
+        for _ in 0..100 {
+            open = open_files();
+            found = held_by(&open, &file);
+            if !found.is_empty() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        drop(child.stdin.take());
+        child.wait().unwrap();
+        assert_eq!(found.iter().map(|h| h.pid).collect::<Vec<_>>(), [pid]);
+        assert!(held_by(&open, &tmp.path().join("missing")).is_empty());
+    }
+}
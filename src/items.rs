@@ -0,0 +1,434 @@
+// Helpers for looking at trash items where they live inside the trash.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+use std::{fs, io};
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use trash::TrashItem;
+
+use crate::sizing;
+
+static IGNORED_MOUNTS: OnceLock<GlobSet> = OnceLock::new();
+static ONLY_TRASH_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Exclude trash on volumes matching these glob patterns from [`list`].
+pub fn set_ignored_mounts(patterns: &[String]) -> Result<(), String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let trimmed = pattern.trim_end_matches(['/', '\\']);
+        let glob = GlobBuilder::new(if trimmed.is_empty() { pattern } else { trimmed })
+            .literal_separator(true)
+            .build()
+            .map_err(|e| format!("ignore_mounts: invalid pattern '{pattern}': {e}"))?;
+        builder.add(glob);
+    }
+    let set = builder.build().map_err(|e| format!("ignore_mounts: {e}"))?;
+    let _ = IGNORED_MOUNTS.set(set);
+    Ok(())
+}
+
+/// Limit [`list`] to items in one trash directory (used by --sandbox).
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+pub fn restrict_to_trash_dir(dir: PathBuf) {
+    let _ = ONLY_TRASH_DIR.set(dir);
+}
+
+/// All trash items, minus those on volumes listed in `ignore_mounts`.
+///
+/// Every backend reads the whole trash before handing any of it back, so
+/// this holds every item at once however it's used afterwards: memory grows
+/// with the trash, not with what matches.
+pub fn list() -> Result<Vec<TrashItem>, trash::Error> {
+    let mut items = crate::backend::get().list()?;
+    if let Some(ignored) = IGNORED_MOUNTS.get().filter(|set| !set.is_empty()) {
+        items.retain(|item| !on_ignored_mount(ignored, &item_path(item)));
+    }
+    if let Some(only) = ONLY_TRASH_DIR.get() {
+        items.retain(|item| trash_dir(item) == *only);
+    }
+    Ok(items)
+}
+
+/// `list()` in the order `--trash-list` numbers it: oldest deletion first,
+/// ties broken by original path, so numbers survive new items being trashed.
+pub fn list_numbered() -> Result<Vec<TrashItem>, trash::Error> {
+    let mut items = list()?;
+    items.sort_by(|a, b| {
+        (a.time_deleted, a.original_path()).cmp(&(b.time_deleted, b.original_path()))
+    });
+    Ok(items)
+}
+
+/// The paths scoping --trash-purge or --trash-empty, in the form original
+/// paths are recorded: absolute, and resolved while they still exist.
+pub fn scope_roots(paths: &[PathBuf]) -> std::io::Result<Vec<PathBuf>> {
+    paths
+        .iter()
+        .map(|p| p.canonicalize().or_else(|_| std::path::absolute(p)))
+        .collect()
+}
+
+/// Whether an item trashed from `original` is one of `roots` or was inside
+/// one. With no roots, everything is in scope.
+pub fn in_scope(original: &Path, roots: &[PathBuf]) -> bool {
+    roots.is_empty() || roots.iter().any(|root| original.starts_with(root))
+}
+
+/// Whether one of `items` was trashed from inside another, so restoring
+/// them has to bring back the outer one first.
+pub fn any_nested(items: &[TrashItem]) -> bool {
+    let mut paths: Vec<PathBuf> = items.iter().map(TrashItem::original_path).collect();
+    paths.sort();
+    // Sorted, whatever lies inside a path comes right after it (or its twins)
+    paths
+        .windows(2)
+        .any(|pair| pair[1] != pair[0] && pair[1].starts_with(&pair[0]))
+}
+
+fn on_ignored_mount(ignored: &GlobSet, path: &Path) -> bool {
+    path.ancestors().any(|dir| ignored.is_match(dir))
+}
+
+/// Location of the item's content inside the trash.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+pub fn item_path(item: &TrashItem) -> PathBuf {
+    // The id is the .trashinfo file; the content sits in ../files/<stem>
+    let info_file = Path::new(&item.id);
+    let trash_folder = info_file
+        .parent()
+        .and_then(Path::parent)
+        .unwrap_or(Path::new(""));
+    let name_in_trash = info_file.file_stem().unwrap_or_default();
+    trash_folder.join("files").join(name_in_trash)
+}
+
+/// Location of the item's content inside the trash.
+#[cfg(target_os = "windows")]
+pub fn item_path(item: &TrashItem) -> PathBuf {
+    // The id is the parsing name of the $R file inside $Recycle.Bin
+    PathBuf::from(&item.id)
+}
+
+/// The trash directory holding the item, which identifies its volume.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+pub fn trash_dir(item: &TrashItem) -> PathBuf {
+    let info_file = Path::new(&item.id);
+    info_file
+        .parent()
+        .and_then(Path::parent)
+        .unwrap_or(Path::new(""))
+        .to_path_buf()
+}
+
+/// Every trash directory, whether it holds any items or not: those of
+/// `items`, and the --trash-dir directory or else the home trash and the
+/// trash of each volume. Those [`list`] leaves out are left out here too.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+pub fn trash_dirs(items: &[TrashItem]) -> std::collections::BTreeSet<PathBuf> {
+    let mut dirs: std::collections::BTreeSet<PathBuf> = items.iter().map(trash_dir).collect();
+    match crate::backend::get().directory() {
+        Some(dir) => {
+            dirs.insert(dir.to_path_buf());
+        }
+        None => {
+            dirs.extend(crate::import::home_trash());
+            dirs.extend(trash::os_limited::trash_folders().into_iter().flatten());
+        }
+    }
+    if let Some(ignored) = IGNORED_MOUNTS.get().filter(|set| !set.is_empty()) {
+        dirs.retain(|dir| !on_ignored_mount(ignored, dir));
+    }
+    if let Some(only) = ONLY_TRASH_DIR.get() {
+        dirs.retain(|dir| dir == only);
+    }
+    dirs
+}
+
+/// The trash directory holding the item, which identifies its volume.
+#[cfg(target_os = "windows")]
+pub fn trash_dir(item: &TrashItem) -> PathBuf {
+    item_path(item)
+        .parent()
+        .unwrap_or(Path::new(""))
+        .to_path_buf()
+}
+
+/// Whether the item is still in the trash, going by its record.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+pub fn in_trash(item: &TrashItem) -> bool {
+    Path::new(&item.id).symlink_metadata().is_ok()
+}
+
+/// Whether the item is still in the trash, going by its content.
+#[cfg(target_os = "windows")]
+pub fn in_trash(item: &TrashItem) -> bool {
+    item_path(item).symlink_metadata().is_ok()
+}
+
+/// Drop the trash's record of an item whose content has already been moved
+/// out of the trash by hand.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+pub fn forget(item: &TrashItem) -> io::Result<()> {
+    fs::remove_file(&item.id)
+}
+
+/// Drop the trash's record of an item whose content has already been moved
+/// out of the trash by hand.
+#[cfg(target_os = "windows")]
+pub fn forget(item: &TrashItem) -> std::io::Result<()> {
+    // Each $R<suffix> content file has its metadata in $I<suffix>
+    let content = item_path(item);
+    let name = content.file_name().unwrap_or_default().to_string_lossy();
+    let info = content.with_file_name(name.replacen("$R", "$I", 1));
+    std::fs::remove_file(info)
+}
+
+/// Point the item's recorded original location at `new_path` by rewriting
+/// the `Path=` entry of its .trashinfo file.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+pub fn set_original_path(item: &TrashItem, new_path: &Path) -> io::Result<()> {
+    let info_file = Path::new(&item.id);
+    let text = fs::read_to_string(info_file)?;
+
+    let mut replaced = false;
+    let mut rewritten = String::with_capacity(text.len());
+    for line in text.lines() {
+        if !replaced && line.starts_with("Path=") {
+            rewritten.push_str("Path=");
+            rewritten.push_str(&encode_uri_path(new_path));
+            replaced = true;
+        } else {
+            rewritten.push_str(line);
+        }
+        rewritten.push('\n');
+    }
+    if !replaced {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("no Path entry in '{}'", info_file.display()),
+        ));
+    }
+
+    // Write beside the original and rename over it so a crash can't leave a
+    // truncated .trashinfo behind
+    let mut tmp_name = info_file.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(format!(".tmp-{}", std::process::id()));
+    let tmp = info_file.with_file_name(tmp_name);
+    fs::write(&tmp, rewritten)?;
+    fs::rename(&tmp, info_file)
+}
+
+/// Percent-encode each component of an absolute path, as .trashinfo expects.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+pub fn encode_uri_path(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut out = String::new();
+    for component in path.components() {
+        if let std::path::Component::Normal(part) = component {
+            if !out.ends_with('/') {
+                out.push('/');
+            }
+            for &b in part.as_bytes() {
+                if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~') {
+                    out.push(b as char);
+                } else {
+                    out.push_str(&format!("%{b:02X}"));
+                }
+            }
+        } else if out.is_empty() {
+            out.push('/');
+        }
+    }
+    out
+}
+
+/// The path a .trashinfo `Path=` value percent-encodes.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+pub fn decode_uri_path(value: &str) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    PathBuf::from(std::ffi::OsString::from_vec(out))
+}
+
+/// Total size in bytes of the item, including directory contents, as
+/// measured by the configured `size_strategy`.
+pub fn item_size(item: &TrashItem) -> u64 {
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    return crate::cache::size(item, sizing::size);
+    #[cfg(not(all(unix, not(target_os = "macos"), not(target_os = "ios"))))]
+    sizing::size(&item_path(item))
+}
+
+/// Short ID for the item, printed by --trash-list and matched by `id:`.
+/// Derived from the backend's identifier (the .trashinfo path or the $R
+/// file), so it stays the same for as long as the item is in the trash.
+pub fn short_id(item: &TrashItem) -> String {
+    short_id_of(&item.id.to_string_lossy())
+}
+
+fn short_id_of(backend_id: &str) -> String {
+    // FNV-1a: unlike std's hashers, guaranteed to give the same answer in
+    // every build
+    let hash = backend_id.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| {
+        (h ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:08x}", (hash >> 32) ^ (hash & 0xffff_ffff))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_id_is_stable() {
+        let id = "/home/u/.local/share/Trash/info/a.txt.trashinfo";
+        assert_eq!(short_id_of(id), short_id_of(id));
+        assert_eq!(short_id_of(id).len(), 8);
+        assert_eq!(short_id_of(""), "4fd0bfc1");
+        assert_ne!(
+            short_id_of(id),
+            short_id_of("/home/u/.local/share/Trash/info/a.txt.2.trashinfo")
+        );
+    }
+
+    #[test]
+    fn test_in_scope_compares_whole_components() {
+        let roots = [PathBuf::from("/home/u/projects/foo")];
+        assert!(in_scope(Path::new("/home/u/projects/foo"), &roots));
+        assert!(in_scope(Path::new("/home/u/projects/foo/src/a.rs"), &roots));
+        assert!(!in_scope(Path::new("/home/u/projects/foobar/a.rs"), &roots));
+        assert!(!in_scope(Path::new("/home/u/projects"), &roots));
+        assert!(in_scope(Path::new("/anything"), &[]));
+    }
+
+    #[test]
+    fn test_any_nested() {
+        let item = |parent: &str, name: &str| TrashItem {
+            id: format!("{parent}/{name}").into(),
+            name: name.into(),
+            original_parent: parent.into(),
+            time_deleted: 0,
+        };
+        let twins = [item("/p", "a"), item("/p", "a"), item("/p", "ab")];
+        assert!(!any_nested(&twins));
+        let nested = [item("/p/a/b", "c"), item("/p", "x"), item("/p", "a")];
+        assert!(any_nested(&nested));
+        assert!(!any_nested(&[]));
+    }
+
+    fn glob_set(patterns: &[&str]) -> GlobSet {
+        let mut builder = GlobSetBuilder::new();
+        for p in patterns {
+            builder.add(GlobBuilder::new(p).literal_separator(true).build().unwrap());
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_on_ignored_mount_exact() {
+        let set = glob_set(&["/mnt/backup"]);
+        assert!(on_ignored_mount(
+            &set,
+            Path::new("/mnt/backup/.Trash-1000/files/a.txt")
+        ));
+        assert!(!on_ignored_mount(
+            &set,
+            Path::new("/mnt/backups/.Trash-1000/files/a.txt")
+        ));
+        assert!(!on_ignored_mount(
+            &set,
+            Path::new("/home/u/.local/share/Trash/files/a.txt")
+        ));
+    }
+
+    #[test]
+    fn test_on_ignored_mount_glob() {
+        let set = glob_set(&["/media/*"]);
+        assert!(on_ignored_mount(
+            &set,
+            Path::new("/media/usb/.Trash-1000/files/a.txt")
+        ));
+        assert!(!on_ignored_mount(&set, Path::new("/mnt/usb/.Trash-1000")));
+    }
+
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    #[test]
+    fn test_encode_uri_path() {
+        assert_eq!(
+            encode_uri_path(Path::new("/home/u/my file%.txt")),
+            "/home/u/my%20file%25.txt"
+        );
+        assert_eq!(encode_uri_path(Path::new("/")), "/");
+    }
+
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    #[test]
+    fn test_decode_uri_path() {
+        let path = Path::new("/home/u/my file%.txt");
+        assert_eq!(decode_uri_path(&encode_uri_path(path)), path);
+        assert_eq!(decode_uri_path("/a/100%"), Path::new("/a/100%"));
+    }
+
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    #[test]
+    fn test_set_original_path_rewrites_trashinfo() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let info = tmp.path().join("foo.txt.trashinfo");
+        fs::write(
+            &info,
+            "[Trash Info]\nPath=/old/foo.txt\nDeletionDate=2024-01-15T10:30:00\n",
+        )
+        .unwrap();
+        let item = TrashItem {
+            id: info.clone().into(),
+            name: "foo.txt".into(),
+            original_parent: "/old".into(),
+            time_deleted: 0,
+        };
+
+        set_original_path(&item, Path::new("/new dir/foo.txt")).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&info).unwrap(),
+            "[Trash Info]\nPath=/new%20dir/foo.txt\nDeletionDate=2024-01-15T10:30:00\n"
+        );
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    #[test]
+    fn test_item_path_freedesktop() {
+        let item = TrashItem {
+            id: "/home/u/.local/share/Trash/info/foo.txt.trashinfo".into(),
+            name: "foo.txt".into(),
+            original_parent: "/home/u".into(),
+            time_deleted: 0,
+        };
+        assert_eq!(
+            item_path(&item),
+            PathBuf::from("/home/u/.local/share/Trash/files/foo.txt")
+        );
+    }
+}
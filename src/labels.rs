@@ -0,0 +1,217 @@
+// Labels given with --label, so everything a scripted cleanup trashed can be
+// brought back together with --trash-undo-label. Each item trashed under a
+// label adds a line to the state directory's labels.jsonl with its original
+// path and when the run started; the item is the first one trashed from that
+// path since then. Lines whose item has left the trash are dropped when the
+// label is undone.
+#![cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::audit::Op;
+use crate::config;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub label: String,
+    /// When the run started, in seconds since the epoch
+    pub time: i64,
+    pub path: PathBuf,
+}
+
+impl Entry {
+    /// Whether `item` is the kind of item this entry recorded: trashed from
+    /// its path no earlier than its run.
+    fn fits(&self, item: &trash::TrashItem) -> bool {
+        item.time_deleted >= self.time && item.original_path() == self.path
+    }
+}
+
+struct Current {
+    label: String,
+    time: i64,
+    file: File,
+}
+
+static CURRENT: Mutex<Option<Current>> = Mutex::new(None);
+
+/// Label file location: $XDG_STATE_HOME/trache/labels.jsonl (or the platform equivalent).
+pub fn file_path() -> Option<PathBuf> {
+    config::state_dir().map(|d| d.join("labels.jsonl"))
+}
+
+/// Record what this run trashes under `label`.
+pub fn init(label: &str) -> Result<(), String> {
+    let path = file_path().ok_or("cannot determine the state directory for --label")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("cannot create '{}': {e}", dir.display()))?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("cannot open '{}': {e}", path.display()))?;
+    *CURRENT.lock().unwrap() = Some(Current {
+        label: label.to_string(),
+        time: chrono::Local::now().timestamp(),
+        file,
+    });
+    Ok(())
+}
+
+/// The label of this run, if any.
+pub fn current() -> Option<String> {
+    CURRENT.lock().unwrap().as_ref().map(|c| c.label.clone())
+}
+
+/// Note that `op` succeeded on `path`, if that puts an item under the label.
+pub fn record(op: Op, path: &Path) {
+    if op != Op::Trash {
+        return;
+    }
+    let mut guard = CURRENT.lock().unwrap();
+    let Some(current) = guard.as_mut() else {
+        return;
+    };
+    let entry = Entry {
+        label: current.label.clone(),
+        time: current.time,
+        path: std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+    };
+    let written = serde_json::to_string(&entry)
+        .map_err(io::Error::other)
+        .and_then(|line| writeln!(current.file, "{line}"));
+    if let Err(e) = written {
+        eprintln!("trache: cannot record label: {e}");
+        // Don't repeat the warning for every remaining item
+        *guard = None;
+    }
+}
+
+/// Every entry. A missing file has none.
+pub fn load(file: &Path) -> io::Result<Vec<Entry>> {
+    let text = match fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn parse(text: &str) -> Result<Vec<Entry>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| serde_json::from_str(line).map_err(|e| format!("line {}: {e}", i + 1)))
+        .collect()
+}
+
+/// Replace the label file's contents with `entries`.
+fn save(file: &Path, entries: &[Entry]) -> io::Result<()> {
+    let mut text = String::new();
+    for entry in entries {
+        text.push_str(&serde_json::to_string(entry).map_err(io::Error::other)?);
+        text.push('\n');
+    }
+    // Write a sibling and rename it over, so a crash can't lose every label
+    let tmp = file.with_extension("jsonl.tmp");
+    fs::write(&tmp, text)?;
+    fs::rename(&tmp, file)
+}
+
+/// The items in `items` trashed under `label`: for each entry, the first
+/// item trashed from its path since its run started.
+pub fn select(
+    entries: &[Entry],
+    label: &str,
+    mut items: Vec<trash::TrashItem>,
+) -> Vec<trash::TrashItem> {
+    items.sort_by_key(|item| item.time_deleted);
+    let mut taken = vec![false; items.len()];
+    for entry in entries.iter().filter(|e| e.label == label) {
+        if let Some(i) = (0..items.len()).find(|&i| !taken[i] && entry.fits(&items[i])) {
+            taken[i] = true;
+        }
+    }
+    items
+        .into_iter()
+        .zip(taken)
+        .filter_map(|(item, taken)| taken.then_some(item))
+        .collect()
+}
+
+/// Drop the entries of `label` with no item left in `items`.
+pub fn prune(file: &Path, label: &str, items: &[trash::TrashItem]) -> io::Result<()> {
+    let entries = load(file)?;
+    let kept: Vec<Entry> = entries
+        .iter()
+        .filter(|e| e.label != label || items.iter().any(|item| e.fits(item)))
+        .cloned()
+        .collect();
+    if kept.len() == entries.len() {
+        return Ok(());
+    }
+    save(file, &kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, time_deleted: i64) -> trash::TrashItem {
+        trash::TrashItem {
+            id: format!("{name}-{time_deleted}").into(),
+            name: name.into(),
+            original_parent: "/home/me".into(),
+            time_deleted,
+        }
+    }
+
+    fn entry(label: &str, time: i64, name: &str) -> Entry {
+        Entry {
+            label: label.into(),
+            time,
+            path: Path::new("/home/me").join(name),
+        }
+    }
+
+    #[test]
+    fn test_select_takes_the_first_item_since_the_run() {
+        let entries = [
+            entry("cleanup", 100, "a"),
+            entry("cleanup", 100, "b"),
+            entry("other", 100, "c"),
+        ];
+        let items = vec![
+            item("a", 50),
+            item("a", 300),
+            item("a", 120),
+            item("b", 130),
+            item("c", 140),
+        ];
+        let ids: Vec<_> = select(&entries, "cleanup", items)
+            .into_iter()
+            .map(|item| item.id.into_string().unwrap())
+            .collect();
+        assert_eq!(ids, ["a-120", "b-130"]);
+    }
+
+    #[test]
+    fn test_prune_drops_restored_entries() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let file = tmp.path().join("labels.jsonl");
+        let entries = [
+            entry("cleanup", 100, "a"),
+            entry("cleanup", 100, "b"),
+            entry("other", 100, "c"),
+        ];
+        save(&file, &entries).unwrap();
+        prune(&file, "cleanup", &[item("b", 110)]).unwrap();
+        assert_eq!(load(&file).unwrap(), entries[1..]);
+    }
+}
@@ -0,0 +1,207 @@
+// Keeping symbolic links symbolic across a trip through the trash.
+// Link targets are recorded when trashing, so if a backend ever hands back a
+// dereferenced copy on restore, the link can be recreated from the record.
+// Records are dropped once their item is restored or purged.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use trash::TrashItem;
+
+use crate::config;
+
+/// Seconds between the recorded trash time and the backend's deletion time
+/// for a record to count as the same item.
+const TIME_SLACK: i64 = 5;
+
+#[derive(Serialize, Deserialize)]
+struct Record {
+    path: PathBuf,
+    target: PathBuf,
+    time: i64,
+}
+
+fn store_path() -> Option<PathBuf> {
+    config::state_dir().map(|d| d.join("symlinks.jsonl"))
+}
+
+/// Remember that `path`, about to be trashed, is a link to `target`.
+pub fn record(path: &Path, target: &Path) -> io::Result<()> {
+    let Some(store) = store_path() else {
+        return Ok(());
+    };
+    if let Some(parent) = store.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let record = Record {
+        path: std::path::absolute(path)?,
+        target: target.to_path_buf(),
+        time: chrono::Local::now().timestamp(),
+    };
+    let mut file = OpenOptions::new().create(true).append(true).open(store)?;
+    writeln!(file, "{}", serde_json::to_string(&record)?)
+}
+
+/// The recorded link target for an item trashed from `path` at `time_deleted`.
+pub fn lookup(path: &Path, time_deleted: i64) -> Option<PathBuf> {
+    let file = fs::File::open(store_path()?).ok()?;
+    find(BufReader::new(file), path, time_deleted)
+}
+
+fn find(reader: impl BufRead, path: &Path, time_deleted: i64) -> Option<PathBuf> {
+    reader
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str::<Record>(&line).ok())
+        .filter(|r| r.path == path && (r.time - time_deleted).abs() <= TIME_SLACK)
+        .last()
+        .map(|r| r.target)
+}
+
+/// Drop the records of `items`, which have left the trash.
+pub fn forget(items: &[TrashItem]) -> io::Result<()> {
+    let Some(store) = store_path() else {
+        return Ok(());
+    };
+    let text = match fs::read_to_string(&store) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let gone: Vec<(PathBuf, i64)> = items
+        .iter()
+        .map(|item| (item.original_path(), item.time_deleted))
+        .collect();
+    let kept = without(&text, &gone);
+    if kept.len() == text.len() {
+        return Ok(());
+    }
+    // Write a sibling and rename it over, so a crash can't lose every record
+    let tmp = store.with_extension("jsonl.tmp");
+    fs::write(&tmp, kept)?;
+    fs::rename(&tmp, &store)
+}
+
+/// The lines of `text` except the records of the items trashed from the
+/// paths at the times in `gone`.
+fn without(text: &str, gone: &[(PathBuf, i64)]) -> String {
+    let is_gone = |r: &Record| {
+        gone.iter()
+            .any(|(path, time)| r.path == *path && (r.time - time).abs() <= TIME_SLACK)
+    };
+    text.lines()
+        .filter(|line| serde_json::from_str::<Record>(line).map_or(true, |r| !is_gone(&r)))
+        .flat_map(|line| [line, "\n"])
+        .collect()
+}
+
+/// The link target an item should come back with: read from the trash if the
+/// backend kept the link, else from the record made when it was trashed.
+pub fn expected_target(in_trash: &Path, original: &Path, time_deleted: i64) -> Option<PathBuf> {
+    match in_trash.symlink_metadata() {
+        Ok(meta) if meta.is_symlink() => fs::read_link(in_trash).ok(),
+        _ => lookup(original, time_deleted),
+    }
+}
+
+/// Make sure `path` is a symbolic link to `target`, replacing a dereferenced
+/// copy if that's what was restored. Returns whether anything was fixed.
+pub fn ensure(path: &Path, target: &Path) -> io::Result<bool> {
+    let meta = path.symlink_metadata()?;
+    if meta.is_symlink() && fs::read_link(path)? == target {
+        return Ok(false);
+    }
+    if meta.is_dir() {
+        // A copied directory could be large or modified; leave it for the user
+        return Err(io::Error::other(format!(
+            "restored as a directory instead of a link to '{}'",
+            target.display()
+        )));
+    }
+    fs::remove_file(path)?;
+    symlink(target, path)?;
+    Ok(true)
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, path: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, path: &Path) -> io::Result<()> {
+    let resolved = path.parent().unwrap_or(Path::new("")).join(target);
+    if resolved.is_dir() {
+        std::os::windows::fs::symlink_dir(target, path)
+    } else {
+        std::os::windows::fs::symlink_file(target, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_matches_path_and_time() {
+        let store = concat!(
+            r#"{"path":"/home/u/link","target":"old","time":100}"#,
+            "\n",
+            r#"{"path":"/home/u/other","target":"x","time":1000}"#,
+            "\n",
+            r#"{"path":"/home/u/link","target":"new","time":1000}"#,
+            "\n",
+            "not json\n",
+        );
+        let find_at = |time| find(store.as_bytes(), Path::new("/home/u/link"), time);
+        assert_eq!(find_at(1002), Some(PathBuf::from("new")));
+        assert_eq!(find_at(99), Some(PathBuf::from("old")));
+        assert_eq!(find_at(500), None);
+    }
+
+    #[test]
+    fn test_without_drops_only_gone_items() {
+        let store = concat!(
+            r#"{"path":"/home/u/link","target":"old","time":100}"#,
+            "\n",
+            r#"{"path":"/home/u/other","target":"x","time":1000}"#,
+            "\n",
+            r#"{"path":"/home/u/link","target":"new","time":1000}"#,
+            "\n",
+        );
+        let kept = without(store, &[(PathBuf::from("/home/u/link"), 1003)]);
+        assert_eq!(
+            kept,
+            concat!(
+                r#"{"path":"/home/u/link","target":"old","time":100}"#,
+                "\n",
+                r#"{"path":"/home/u/other","target":"x","time":1000}"#,
+                "\n",
+            )
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_ensure_replaces_copy_with_link() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let link = tmp.path().join("link");
+        fs::write(&link, "dereferenced contents").unwrap();
+
+        assert!(ensure(&link, Path::new("target.txt")).unwrap());
+        assert_eq!(fs::read_link(&link).unwrap(), PathBuf::from("target.txt"));
+        assert!(!ensure(&link, Path::new("target.txt")).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_ensure_leaves_directories() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path().join("dir");
+        fs::create_dir(&dir).unwrap();
+        assert!(ensure(&dir, Path::new("target")).is_err());
+        assert!(dir.is_dir());
+    }
+}
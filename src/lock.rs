@@ -0,0 +1,81 @@
+// Advisory locks between trache processes running at the same time, such as
+// parallel `make clean` targets. Each covers a read-decide-write stretch that
+// two processes could otherwise interleave: picking a free -untrash_N name
+// and restoring into it, or reading the move journal and writing it back. A
+// lock is an exclusive lock on a file (flock, LockFileEx on Windows), held
+// until the Lock is dropped; other programs aren't bound by it. Locks aren't
+// reentrant, so code holding one must not take the same one again.
+#![cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+
+/// Held until dropped
+pub struct Lock {
+    _file: Option<File>,
+}
+
+/// Locks on picking free names, for as long as it takes to pick one and
+/// restore into it
+#[derive(Clone, Copy)]
+pub enum Names {
+    /// `-untrash_N` names beside the original path
+    Untrash,
+    /// Names in the rescue directory, taken while restoring with --rescue,
+    /// which may happen while an Untrash lock is held
+    Rescue,
+}
+
+/// Wait for, then hold, the lock on `names`. This is best effort: without
+/// a state directory, or when locking fails, it goes ahead unlocked as
+/// trache always used to.
+pub fn names(names: Names) -> Lock {
+    let name = match names {
+        Names::Untrash => "untrash.lock",
+        Names::Rescue => "rescue.lock",
+    };
+    config::state_dir()
+        .and_then(|dir| acquire(&dir.join(name)).ok())
+        .unwrap_or(Lock { _file: None })
+}
+
+/// Wait for, then hold, the lock guarding `file`, kept beside it as
+/// `<file>.lock`.
+pub fn file(file: &Path) -> io::Result<Lock> {
+    let mut name = file.as_os_str().to_os_string();
+    name.push(".lock");
+    acquire(&PathBuf::from(name))
+}
+
+fn acquire(path: &Path) -> io::Result<Lock> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?;
+    file.lock()?;
+    Ok(Lock { _file: Some(file) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_lock_excludes_others_until_dropped() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let journal = tmp.path().join("state/moves.jsonl");
+        let held = file(&journal).unwrap();
+
+        let other = File::open(tmp.path().join("state/moves.jsonl.lock")).unwrap();
+        assert!(other.try_lock().is_err());
+        drop(held);
+        assert!(other.try_lock().is_ok());
+    }
+}
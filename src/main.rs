@@ -1,10 +1,115 @@
+mod analyze;
+mod answers;
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+mod attrs;
+mod audit;
+mod backend;
+mod batch;
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+mod cache;
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+mod casefold;
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+mod check;
+mod checksum;
+mod color;
+mod config;
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+mod daemon;
+mod diff;
+mod dryrun;
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+mod dupes;
+mod error;
+mod events;
+mod export;
+mod filter;
+mod finder;
+mod hooks;
+mod import;
 mod interact;
+mod interrupt;
+mod inuse;
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+mod items;
+mod labels;
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+mod links;
+mod lock;
+mod manifest;
+mod moves;
+mod notify;
+mod onefs;
+mod pins;
+mod planner;
+mod readonly;
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+mod report;
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+mod rescue;
+mod retention;
+mod risk;
+mod rmcompat;
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+mod rpc;
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+mod sandbox;
+mod schema;
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+mod service;
+mod shred;
+mod sizing;
+mod status;
+mod table;
+mod tally;
+mod template;
+#[cfg(test)]
+mod testutil;
+mod timestyle;
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+mod topdir;
+mod transcript;
+mod tree;
+mod units;
+mod vcs;
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+mod volumes;
+mod winnames;
 
+use std::borrow::Cow;
+use std::ffi::OsString;
 use std::fs;
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, IsTerminal};
 use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
 
-use clap::{ArgGroup, Parser, ValueEnum};
+use clap::{ArgGroup, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use clap_complete::{ArgValueCompleter, CompleteEnv, CompletionCandidate};
+use error::TracheError;
 use interact::prompt_yes;
 #[cfg(any(
     target_os = "windows",
@@ -12,21 +117,24 @@ use interact::prompt_yes;
 ))]
 use interact::{
     CollisionChoice, TwinChoice, TwinInfo, collision_choice_name, find_untrash_range,
-    format_untrash_range, prompt_collision, prompt_selection, prompt_twins, untrash_name,
+    format_untrash_range, prompt_collision, prompt_conflict_policy, prompt_selection, prompt_twins,
+    untrash_name,
 };
-use trash::TrashContext;
-#[cfg(target_os = "macos")]
-use trash::macos::TrashContextExtMacos;
+use status::Status;
 
-#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
 enum InteractiveMode {
     /// Never prompt
     #[default]
+    #[value(alias = "no", alias = "none")]
     Never,
     /// Prompt once before removing more than three files, or when removing recursively
     Once,
     /// Prompt before every removal
+    #[value(alias = "yes")]
     Always,
+    /// Prompt only before risky removals (see --help)
+    Smart,
 }
 
 #[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
@@ -40,22 +148,108 @@ enum PreserveRoot {
     All,
 }
 
+/// Whose behavior --compat reproduces
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Compat {
+    /// GNU rm's messages, prompts and exit codes
+    Gnu,
+}
+
+/// What --resume does with an unfinished batch
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum ResumeAction {
+    /// Finish it: trash or restore the items it didn't get to
+    #[default]
+    Continue,
+    /// Undo it: restore what it trashed, or trash again what it restored
+    Rollback,
+}
+
+/// What --group-by clusters the --trash-list output by
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum GroupBy {
+    /// The directory each item was trashed from
+    Dir,
+}
+
+/// How --trash-list lays out the default listing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Layout {
+    /// --group-by
+    Grouped(GroupBy),
+    /// --tree
+    Tree,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum LogFormat {
+    /// Human-readable messages
+    #[default]
+    Text,
+    /// One JSON object per line on stdout for each file processed
+    Json,
+}
+
+/// What a type: prefix restricts matches to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ItemKind {
+    File,
+    Dir,
+    Symlink,
+}
+
+impl ItemKind {
+    fn parse(input: &str) -> Result<ItemKind, String> {
+        match input {
+            "file" | "f" => Ok(ItemKind::File),
+            "dir" | "d" => Ok(ItemKind::Dir),
+            "symlink" | "l" => Ok(ItemKind::Symlink),
+            _ => Err(format!("type:{input}: expected file, dir or symlink")),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ItemKind::File => "file",
+            ItemKind::Dir => "dir",
+            ItemKind::Symlink => "symlink",
+        }
+    }
+
+    /// The kind of whatever `meta` (from symlink_metadata) describes. Other
+    /// file types (sockets, fifos, devices) count as files.
+    fn of(meta: &fs::Metadata) -> ItemKind {
+        let ft = meta.file_type();
+        if ft.is_symlink() {
+            ItemKind::Symlink
+        } else if ft.is_dir() {
+            ItemKind::Dir
+        } else {
+            ItemKind::File
+        }
+    }
+}
+
 #[derive(Clone, Copy, Default)]
 enum PatternTarget {
     #[default]
     Name,
     Path,
+    Dir,
+    Id,
 }
 
+/// What a pattern's name, path, directory or ID is matched against
 #[allow(dead_code)]
-enum CompiledMatcher {
+enum TextMatcher {
     Glob(globset::GlobMatcher),
     Regex(regex::Regex, bool),
-    String(String, bool),
+    /// The pattern is lowercased up front when matching ignores case
+    String(String, bool, bool),
 }
 
 #[allow(dead_code)]
-impl CompiledMatcher {
+impl TextMatcher {
     fn is_match(&self, haystack: &str) -> bool {
         match self {
             Self::Glob(g) => g.is_match(haystack),
@@ -68,9 +262,14 @@ impl CompiledMatcher {
                     r.is_match(haystack)
                 }
             }
-            Self::String(s, full) => {
+            Self::String(s, full, icase) => {
+                let haystack = if *icase {
+                    Cow::Owned(haystack.to_lowercase())
+                } else {
+                    Cow::Borrowed(haystack)
+                };
                 if *full {
-                    haystack == s.as_str()
+                    *haystack == *s
                 } else {
                     haystack.contains(s.as_str())
                 }
@@ -79,16 +278,62 @@ impl CompiledMatcher {
     }
 }
 
+/// A compiled pattern, matched against trash items with item_matches
+#[allow(dead_code)]
+enum CompiledMatcher {
+    Text(TextMatcher),
+    /// not: matches whatever the inner matcher doesn't
+    Not(Box<CompiledMatcher>),
+    /// --no-hidden: never matches a dotfile, whatever the inner matcher says,
+    /// so it sits outside any not:
+    Visible(Box<CompiledMatcher>),
+    /// size: also requires the item's size to pass, only measured once the
+    /// inner matcher has matched
+    Sized(Box<CompiledMatcher>, units::SizeFilter),
+    /// type: also requires the trashed item to be of this kind, once the
+    /// inner matcher has matched
+    Typed(Box<CompiledMatcher>, ItemKind),
+    /// deleted: also requires the deletion time to fall in a window
+    Dated(Box<CompiledMatcher>, units::DateFilter),
+    /// --patterns-from: any of several patterns, each with its own target
+    Any(Vec<(CompiledMatcher, PatternTarget)>),
+    /// --filter: also requires the item to satisfy an expression, evaluated
+    /// as of a time in seconds since the epoch
+    Filtered(Box<CompiledMatcher>, filter::Expr, i64),
+}
+
+/// size:, type: and deleted:, in the order they're checked: the deletion
+/// time first since it is already known, then the kind, and the size last
+/// since it can be costly to measure
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Narrowing<'a> {
+    Deleted(&'a str),
+    Type(&'a str),
+    Size(&'a str),
+}
+
+/// A prefix that applies to whatever follows it in the pattern
+#[derive(Clone, Copy)]
+enum Layer<'a> {
+    Not,
+    Narrow(Narrowing<'a>),
+}
+
 struct ParsedPattern<'a> {
     pattern: &'a str,
     match_type: &'a str,
     full: bool,
+    icase: bool,
+    /// not:, size:, type: and deleted: as given, outermost first
+    layers: Vec<Layer<'a>>,
     target: PatternTarget,
 }
 
 fn parse_pattern(raw: &str) -> ParsedPattern<'_> {
     let mut match_type = "glob";
     let mut full = false;
+    let mut icase = false;
+    let mut layers = Vec::new();
     let mut target = PatternTarget::Name;
     let mut rest = raw;
 
@@ -108,12 +353,40 @@ fn parse_pattern(raw: &str) -> ParsedPattern<'_> {
         } else if let Some(after) = rest.strip_prefix("partial:") {
             full = false;
             rest = after;
+        } else if let Some(after) = rest.strip_prefix("icase:") {
+            icase = true;
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("case:") {
+            icase = false;
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("size:") {
+            // The comparison runs to the next ':', if any
+            let (term, after) = after.split_once(':').unwrap_or((after, ""));
+            layers.push(Layer::Narrow(Narrowing::Size(term)));
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("type:") {
+            let (term, after) = after.split_once(':').unwrap_or((after, ""));
+            layers.push(Layer::Narrow(Narrowing::Type(term)));
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("deleted:") {
+            let (term, after) = after.split_once(':').unwrap_or((after, ""));
+            layers.push(Layer::Narrow(Narrowing::Deleted(term)));
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("not:") {
+            layers.push(Layer::Not);
+            rest = after;
         } else if let Some(after) = rest.strip_prefix("name:") {
             target = PatternTarget::Name;
             rest = after;
         } else if let Some(after) = rest.strip_prefix("path:") {
             target = PatternTarget::Path;
             rest = after;
+        } else if let Some(after) = rest.strip_prefix("dir:") {
+            target = PatternTarget::Dir;
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("id:") {
+            target = PatternTarget::Id;
+            rest = after;
         } else {
             break;
         }
@@ -123,11 +396,34 @@ fn parse_pattern(raw: &str) -> ParsedPattern<'_> {
         pattern: rest,
         match_type,
         full,
+        icase,
+        layers,
         target,
     }
 }
 
-fn compile_matcher(pattern: &str, kind: &str, full: bool) -> Result<CompiledMatcher, String> {
+/// Whether any component of `haystack` is a dotfile name.
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn has_hidden_component(haystack: &str) -> bool {
+    haystack
+        .split(['/', '\\'])
+        .any(|c| c.starts_with('.') && c != "." && c != "..")
+}
+
+/// `hidden` lets glob wildcards match a leading dot; without it, only
+/// patterns that spell out a leading dot match dotfiles.
+fn compile_matcher(parsed: &ParsedPattern, hidden: bool) -> Result<CompiledMatcher, String> {
+    let ParsedPattern {
+        pattern,
+        match_type: kind,
+        full,
+        icase,
+        ref layers,
+        ..
+    } = *parsed;
     let matcher = match kind {
         "glob" => {
             let (glob_pattern, literal_sep) = if full {
@@ -137,22 +433,141 @@ fn compile_matcher(pattern: &str, kind: &str, full: bool) -> Result<CompiledMatc
             };
             let glob = globset::GlobBuilder::new(&glob_pattern)
                 .literal_separator(literal_sep)
+                .case_insensitive(icase)
                 .build()
                 .map_err(|e| format!("invalid glob pattern: {e}"))?
                 .compile_matcher();
-            CompiledMatcher::Glob(glob)
+            TextMatcher::Glob(glob)
         }
         "regex" => {
-            let re = regex::Regex::new(pattern).map_err(|e| format!("invalid regex: {e}"))?;
-            CompiledMatcher::Regex(re, full)
+            let re = regex::RegexBuilder::new(pattern)
+                .case_insensitive(icase)
+                .build()
+                .map_err(|e| format!("invalid regex: {e}"))?;
+            TextMatcher::Regex(re, full)
         }
-        "string" => CompiledMatcher::String(pattern.to_string(), full),
+        "string" if icase => TextMatcher::String(pattern.to_lowercase(), full, true),
+        "string" => TextMatcher::String(pattern.to_string(), full, false),
         _ => return Err(format!("unknown match type: '{kind}'")),
     };
 
+    // Each not: negates only what follows it, so the layers are wrapped
+    // around the text matcher from the innermost out
+    let mut matcher = CompiledMatcher::Text(matcher);
+    let mut run = Vec::new();
+    for layer in layers.iter().rev() {
+        match *layer {
+            Layer::Narrow(narrowing) => run.push(narrowing),
+            Layer::Not => {
+                matcher = CompiledMatcher::Not(Box::new(narrowed(matcher, &mut run)?));
+            }
+        }
+    }
+    let matcher = narrowed(matcher, &mut run)?;
+    let hidden = hidden || pattern.starts_with('.') || pattern.contains("/.");
+    Ok(if kind == "glob" && !hidden {
+        CompiledMatcher::Visible(Box::new(matcher))
+    } else {
+        matcher
+    })
+}
+
+/// `matcher` narrowed by the size:, type: and deleted: prefixes in `run`,
+/// which is left empty. The cheapest is checked first, whatever order they
+/// were given in.
+fn narrowed(matcher: CompiledMatcher, run: &mut Vec<Narrowing>) -> Result<CompiledMatcher, String> {
+    run.sort();
+    let mut matcher = matcher;
+    for narrowing in run.drain(..) {
+        let inner = Box::new(matcher);
+        matcher = match narrowing {
+            Narrowing::Deleted(term) => {
+                CompiledMatcher::Dated(inner, units::DateFilter::parse(term)?)
+            }
+            Narrowing::Type(term) => CompiledMatcher::Typed(inner, ItemKind::parse(term)?),
+            Narrowing::Size(term) => CompiledMatcher::Sized(inner, units::SizeFilter::parse(term)?),
+        };
+    }
     Ok(matcher)
 }
 
+/// The matcher for `mode` (--trash-undo or --trash-purge): its PATTERN, or
+/// with --patterns-from, PATTERN if given and every pattern in FILE. With
+/// --filter, matching items must satisfy EXPR too, and the pattern may be left
+/// out. Returns the matcher, its target and how to refer to it in messages.
+fn pattern_matcher(
+    mode: &str,
+    raw: Option<&str>,
+    patterns_from: Option<&Path>,
+    filter: Option<&str>,
+    hidden: bool,
+) -> Result<(CompiledMatcher, PatternTarget, String), String> {
+    if let Some(expr) = filter {
+        let (matcher, target, pattern) = if raw.is_none() && patterns_from.is_none() {
+            let everything =
+                compile_matcher(&parse_pattern("full:*"), true).expect("'*' is a valid glob");
+            (everything, PatternTarget::Name, expr.to_string())
+        } else {
+            pattern_matcher(mode, raw, patterns_from, None, hidden)?
+        };
+        return Ok((filtered(matcher, expr)?, target, pattern));
+    }
+    let Some(file) = patterns_from else {
+        let raw = raw.ok_or_else(|| {
+            format!("{mode} needs a PATTERN (or --patterns-from FILE or --filter EXPR)")
+        })?;
+        let parsed = parse_pattern(raw);
+        return Ok((
+            compile_matcher(&parsed, hidden)?,
+            parsed.target,
+            parsed.pattern.to_string(),
+        ));
+    };
+
+    let text = fs::read_to_string(file)
+        .map_err(|e| format!("--patterns-from: cannot read '{}': {e}", file.display()))?;
+    let mut matchers = Vec::new();
+    if let Some(raw) = raw {
+        let parsed = parse_pattern(raw);
+        matchers.push((compile_matcher(&parsed, hidden)?, parsed.target));
+    }
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        // A pattern starting with '#' can be written as glob:#...
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parsed = parse_pattern(line);
+        let matcher = compile_matcher(&parsed, hidden)
+            .map_err(|e| format!("{}:{}: {e}", file.display(), n + 1))?;
+        matchers.push((matcher, parsed.target));
+    }
+    if matchers.is_empty() {
+        return Err(format!(
+            "--patterns-from: no patterns in '{}'",
+            file.display()
+        ));
+    }
+    let described = format!("{} pattern(s) from {}", matchers.len(), file.display());
+    Ok((
+        CompiledMatcher::Any(matchers),
+        PatternTarget::Name,
+        described,
+    ))
+}
+
+/// `matcher`, narrowed down to the items satisfying the --filter expression
+/// `expr`.
+fn filtered(matcher: CompiledMatcher, expr: &str) -> Result<CompiledMatcher, String> {
+    let now = chrono::Local::now();
+    let expr = filter::Expr::parse(expr, now).map_err(|e| format!("--filter: {e}"))?;
+    Ok(CompiledMatcher::Filtered(
+        Box::new(matcher),
+        expr,
+        now.timestamp(),
+    ))
+}
+
 /// Options for trash operations
 struct TrashOptions {
     dir: bool,
@@ -163,1100 +578,6702 @@ struct TrashOptions {
     dry_run: bool,
     preserve_root: PreserveRoot,
     one_file_system: bool,
+    log_format: LogFormat,
+    /// Overwrite passes for --shred; None to use the trash
+    shred: Option<u32>,
+    rm_fallback: config::RmFallback,
+    /// Refuse to touch more entries than this (counted recursively)
+    max_delete: Option<u64>,
+    /// Ask before removing any one argument bigger than this many bytes
+    max_size_no_prompt: Option<u64>,
+    /// Ask before removing write-protected files, as rm does
+    prompt_protected: bool,
+    /// The files are --prune-empty-dirs' directories, deepest first
+    prune: bool,
+    /// When -I asks before anything is removed
+    bulk: planner::Bulk,
+    /// Retries for files in use by another process, and the wait before each
+    retry: u32,
+    retry_delay: Duration,
+    open_files: config::OpenFiles,
+    /// With dry_run: print the --output json plan instead of messages
+    plan: bool,
+    /// Record a hash of each item for --verify
+    checksum: bool,
+}
+
+impl TrashOptions {
+    fn flags(&self) -> planner::Flags {
+        planner::Flags {
+            interactive: self.interactive,
+            force: self.force,
+            recursive: self.recursive,
+            dir: self.dir,
+            dry_run: self.dry_run,
+            shred: self.shred.is_some(),
+            gnu: rmcompat::enabled(),
+            prompt_protected: self.prompt_protected,
+            prune: self.prune,
+        }
+    }
+
+    /// The verb used in prompts
+    fn verb(&self) -> &'static str {
+        self.flags().verb()
+    }
+
+    fn emit(&self, event: events::Event) {
+        tally::record(&event);
+        if self.log_format == LogFormat::Json {
+            println!("{}", event.to_json());
+        }
+    }
+}
+
+/// Options for restore operations
+struct RestoreOptions {
+    dry_run: bool,
+    interactive: InteractiveMode,
+    to_temp: bool,
+    to_temp_ttl: Duration,
+    restore_as: Option<OsString>,
+    inner: Option<String>,
+    /// Only items trashed at or after this time (seconds since the epoch)
+    since: Option<i64>,
+    /// Only items trashed under this --label
+    label: Option<String>,
+    dated_dir: bool,
+    /// With dry_run: print the --output json plan instead of messages
+    plan: bool,
+    /// Check the items against their recorded hashes first
+    verify: bool,
+}
+
+/// Options for --trash-purge
+#[derive(Clone, Copy)]
+struct PurgeOptions<'a> {
+    dry_run: bool,
+    /// Pick which matches to purge from a numbered list
+    interactive: InteractiveMode,
+    keep_newest_duplicates: bool,
+    /// Keep this many of the newest items trashed from each path
+    keep: Option<usize>,
+    /// Only items trashed from these paths or from inside them
+    scope: &'a [PathBuf],
+    /// With dry_run: print the --output json plan instead of messages
+    plan: bool,
 }
 
+/// How long --to-temp sandboxes are kept when the config doesn't say
+const DEFAULT_TO_TEMP_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
 #[cfg(any(
     target_os = "windows",
     all(unix, not(target_os = "macos"), not(target_os = "ios"))
 ))]
 use chrono::{DateTime, Local};
+
 #[cfg(any(
     target_os = "windows",
     all(unix, not(target_os = "macos"), not(target_os = "ios"))
 ))]
-use trash::os_limited::{list, purge_all, restore_all};
+/// Restore `items`, recording each item in the audit log and checking that
+/// symbolic links and attributes came back intact. With --rescue, items are
+/// restored one at a time so any that can't go back in place are rescued.
+///
+/// Items from several volumes are restored in one batch per volume, side by
+/// side, so a slow external disk doesn't hold up the rest. Each batch runs in
+/// original-path order (parents before their contents) and is reported on
+/// its own, how many of its items came back included, followed by a total;
+/// the first failed batch, in volume order, is the error returned. Nothing
+/// is restored if the pre_restore hook objects.
+fn restore_audited(items: Vec<trash::TrashItem>) -> Result<(), TracheError> {
+    let paths: Vec<PathBuf> = items.iter().map(|item| item.original_path()).collect();
+    hooks::pre(hooks::Kind::Restore, &paths)?;
 
-#[derive(Parser)]
-#[command(name = "trache")]
-#[command(version)]
-#[command(about = "Move files to trash. Manage trashed items.", long_about = None)]
-#[command(group(
-    ArgGroup::new("mode")
-        .args(["list", "empty", "undo", "purge"])
+    let journaled = batch::begin(batch::Kind::Restore, &paths, false)?;
+    let result = restore_in_batches(items);
+    if journaled {
+        batch::finish();
+    }
+    result
+}
+
+/// The body of [`restore_audited`], once the hook has agreed.
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
 ))]
-struct Cli {
-    /// List items in trash
-    #[arg(long = "trash-list")]
-    list: bool,
+fn restore_in_batches(mut items: Vec<trash::TrashItem>) -> Result<(), TracheError> {
+    // Directories go back before what was trashed from inside them, which
+    // then lands in the restored directory
+    items.sort_by_key(|item| item.original_path());
+    if rescue::dir().is_some() {
+        for item in items {
+            restore_or_rescue(item)?;
+        }
+        return Ok(());
+    }
+    if items::any_nested(&items) {
+        // Trash directories can't be restored from side by side then: take
+        // them in turns, in path order
+        for run in items.chunk_by(|a, b| items::trash_dir(a) == items::trash_dir(b)) {
+            restore_batch(run.to_vec()).1?;
+        }
+        return Ok(());
+    }
 
-    /// Empty the entire trash
-    #[arg(long = "trash-empty")]
-    empty: bool,
+    let mut batches = volumes::group(items);
+    if batches.len() <= 1 {
+        return match batches.pop() {
+            Some(batch) => Ok(restore_batch(batch).1?),
+            None => Ok(()),
+        };
+    }
 
-    /// Restore items matching pattern from trash (see --help)
-    #[arg(
-        long = "trash-undo",
-        value_name = "PATTERN",
-        long_help = "Restore items matching PATTERN from trash.\n\n\
-            PATTERN may include optional prefixes to control matching:\n\
-            \n\
-            \x20 [glob:|regex:|string:|full:|partial:|name:|path:]*PATTERN\n\
-            \n\
-            Match type (default: glob):\n\
-            \x20 glob:     glob pattern (see https://docs.rs/globset)\n\
-            \x20 regex:    regular expression\n\
-            \x20 string:   literal string\n\
-            \n\
-            Match extent (default: partial):\n\
-            \x20 partial:  pattern matches a substring of the name/path\n\
-            \x20 full:     pattern must match the entire name/path\n\
-            \n\
-            Match target (default: name):\n\
-            \x20 name:  match against file basename\n\
-            \x20 path:  match against original full path\n\
-            \n\
-            Prefixes can be stacked; rightmost wins per group.\n\
-            \n\
-            Examples:\n\
-            \x20 --trash-undo foo            names containing \"foo\"\n\
-            \x20 --trash-undo 'full:*.txt'   names matching *.txt exactly\n\
-            \x20 --trash-undo 'regex:^foo'   names with regex match\n\
-            \x20 --trash-undo 'string:a.txt' names containing \"a.txt\" literally\n\
-            \x20 --trash-undo 'path:/tmp'    paths containing \"/tmp\"\n\
-            \n\
-            Interactive mode (-i, -I, --interactive):\n\
-            \n\
-            Without interactive flags, matching items restore silently. Items whose\n\
-            original path already exists are skipped.\n\
-            \n\
-            With -i (or --interactive=always), you are prompted for each conflict.\n\
-            With -I (or --interactive=once), the first choice of each type is\n\
-            remembered and applied to all subsequent conflicts of that type.\n\
-            \n\
-            Collision (original path already exists):\n\
-            \x20 (o) Overwrite   replace the existing file\n\
-            \x20 (k) Keep both   restore as <name>-untrash_N.<ext>\n\
-            \x20 (n) None        skip this item\n\
-            \x20 (q) Quit\n\
-            \n\
-            Twins (multiple trashed copies with the same original path):\n\
-            \x20 (a) All    restore all as <name>-untrash_{N..M}.<ext>\n\
-            \x20 (s) Some   select which versions to restore\n\
-            \x20 (l) List   show version details and timestamps\n\
-            \x20 (n) None   skip\n\
-            \x20 (q) Quit"
-    )]
-    undo: Option<String>,
+    let outcomes = volumes::restore_side_by_side(batches, restore_batch);
+    let total = volumes::summary(&outcomes);
+    let mut first_error = None;
+    for outcome in outcomes {
+        let dirs: Vec<_> = outcome
+            .dirs
+            .iter()
+            .map(|d| d.display().to_string())
+            .collect();
+        let dirs = dirs.join(", ");
+        match outcome.result {
+            Ok(()) => println!("Restored {} item(s) from {dirs}", outcome.count),
+            Err(e) => {
+                let done = format!(
+                    "restored {} of {} item(s) from {dirs}",
+                    outcome.restored, outcome.count
+                );
+                // The first error is returned, so printed once that's done
+                if first_error.is_none() {
+                    eprintln!("trache: {done}");
+                    first_error = Some(e);
+                } else {
+                    eprintln!("trache: {done}: {e}");
+                }
+            }
+        }
+    }
+    println!("{total}");
+    Ok(first_error.map_or(Ok(()), Err)?)
+}
 
-    /// Permanently delete items matching pattern from trash (see --help)
-    #[arg(
-        long = "trash-purge",
-        value_name = "PATTERN",
-        long_help = "Permanently delete items matching PATTERN from trash.\n\n\
-            PATTERN may include optional prefixes to control matching:\n\
-            \n\
-            \x20 [glob:|regex:|string:|full:|partial:|name:|path:]*PATTERN\n\
-            \n\
-            Match type (default: glob):\n\
-            \x20 glob:     glob pattern (see https://docs.rs/globset)\n\
-            \x20 regex:    regular expression\n\
-            \x20 string:   literal string\n\
-            \n\
-            Match extent (default: partial):\n\
-            \x20 partial:  pattern matches a substring of the name/path\n\
-            \x20 full:     pattern must match the entire name/path\n\
-            \n\
-            Match target (default: name):\n\
-            \x20 name:  match against file basename\n\
-            \x20 path:  match against original full path\n\
-            \n\
-            Prefixes can be stacked; rightmost wins per group.\n\
-            \n\
-            Examples:\n\
-            \x20 --trash-purge foo            names containing \"foo\"\n\
-            \x20 --trash-purge 'full:*.txt'   names matching *.txt exactly\n\
-            \x20 --trash-purge 'regex:^foo'   names with regex match\n\
-            \x20 --trash-purge 'string:a.txt' names containing \"a.txt\" literally\n\
-            \x20 --trash-purge 'path:/tmp'    paths containing \"/tmp\""
-    )]
-    purge: Option<String>,
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+/// One restore call for items from the same volume. Returns how many of
+/// them left the trash, which a failed call may still have got done for
+/// some, along with how the call went.
+fn restore_batch(mut items: Vec<trash::TrashItem>) -> (usize, Result<(), trash::Error>) {
+    items.sort_by(|a, b| {
+        (a.original_path(), a.time_deleted).cmp(&(b.original_path(), b.time_deleted))
+    });
+    let paths: Vec<PathBuf> = items.iter().map(|item| item.original_path()).collect();
+    let checks: Vec<_> = items.iter().map(restore_checks).collect();
+    let batch = items.clone();
+    let result = backend::get().restore(items);
+    let left: Vec<_> = batch
+        .into_iter()
+        .filter(|item| result.is_ok() || !items::in_trash(item))
+        .collect();
+    forget_records(&left);
+    let restored = left.len();
+    if let Err(ref e) = result
+        && rescue::rescuable_cause(e).is_some()
+        && let Some(dir) = config::default_rescue_dir()
+    {
+        eprintln!(
+            "trache: hint: --rescue restores items that can't go back in place under {}",
+            dir.display()
+        );
+    }
+    for (path, checks) in paths.iter().zip(checks) {
+        audit::record(audit::Op::Restore, path, &result);
+        if result.is_ok() {
+            verify_restored(path, checks);
+        }
+    }
+    (restored, result)
+}
 
-    /// Show what would be done without doing it
-    #[arg(long = "trash-dry-run")]
-    dry_run: bool,
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+/// What `verify_restored` needs to know about an item before it leaves the trash.
+type RestoreChecks = (Option<PathBuf>, Option<attrs::Attrs>);
 
-    // --- rm-compatible flags ---
-    /// Remove empty directories
-    #[arg(short = 'd', long = "dir", overrides_with = "dir")]
-    dir: bool,
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn restore_checks(item: &trash::TrashItem) -> RestoreChecks {
+    let in_trash = items::item_path(item);
+    let link = links::expected_target(&in_trash, &item.original_path(), item.time_deleted);
+    (link, attrs::capture(&in_trash).ok())
+}
 
-    /// Remove directories and their contents recursively
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+/// Check that a restored symbolic link is still a link and put back the
+/// attributes a move may have dropped.
+fn verify_restored(path: &Path, (link, attrs): RestoreChecks) {
+    if let Some(target) = link {
+        match links::ensure(path, &target) {
+            Ok(true) => eprintln!(
+                "trache: warning: '{}' was restored as a copy; recreated it as a link to '{}'",
+                path.display(),
+                target.display()
+            ),
+            Ok(false) => {}
+            Err(e) => eprintln!("trache: warning: '{}': {e}", path.display()),
+        }
+    }
+    if let Some(attrs) = attrs {
+        warn_attr_problems(attrs::restore(path, &attrs));
+    }
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+/// Restore one item in place, or under the rescue directory if its original
+/// volume is full or no longer the one it came from.
+fn restore_or_rescue(item: trash::TrashItem) -> Result<(), trash::Error> {
+    let original = item.original_path();
+    let checks = restore_checks(&item);
+    let result = backend::get().restore(vec![item.clone()]);
+    let cause = match result {
+        Ok(()) => {
+            audit::record(audit::Op::Restore, &original, &Ok::<(), String>(()));
+            verify_restored(&original, checks);
+            forget_records(std::slice::from_ref(&item));
+            return Ok(());
+        }
+        Err(ref e) => match rescue::rescuable_cause(e) {
+            Some(cause) => cause.to_string(),
+            None => {
+                audit::record(audit::Op::Restore, &original, &result);
+                return result;
+            }
+        },
+    };
+
+    let dir = rescue::dir().unwrap_or(Path::new(""));
+    let _names = lock::names(lock::Names::Rescue);
+    let mut dest = rescue::destination(dir, &original);
+    if dest.symlink_metadata().is_ok() {
+        dest = untrash_name(&dest, find_untrash_range(&dest, 1));
+    }
+    let result = dest
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| move_path(&items::item_path(&item), &dest))
+        .and_then(|()| items::forget(&item));
+    audit::record(audit::Op::Restore, &dest, &result);
+    match result {
+        Ok(()) => {
+            eprintln!(
+                "trache: cannot restore '{}' in place ({cause}); rescued it to '{}'",
+                original.display(),
+                dest.display()
+            );
+            verify_restored(&dest, checks);
+            forget_records(std::slice::from_ref(&item));
+            Ok(())
+        }
+        Err(rescue_err) => Err(trash::Error::FileSystem {
+            path: dest,
+            source: rescue_err,
+        }),
+    }
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn warn_attr_problems(problems: Vec<attrs::Problem>) {
+    for problem in problems {
+        eprintln!("trache: warning: {problem}");
+    }
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+/// Purge `items`, recording each item in the audit log and report. Nothing is
+/// purged if the pre_purge hook objects.
+///
+/// Items go to the backend PURGE_CHUNK at a time, so a huge purge doesn't
+/// copy or measure everything at once. When a chunk fails, what's left of it
+/// is purged one item at a time, so a bad entry only costs itself: each
+/// failure is reported and the purge carries on, ending in an error that
+/// counts them. Purges of more than one chunk show progress on a terminal.
+fn purge_audited<I>(items: I, op: audit::Op) -> Result<(), TracheError>
+where
+    I: IntoIterator,
+    I::Item: std::borrow::Borrow<trash::TrashItem>,
+{
+    use std::borrow::Borrow;
+
+    let items: Vec<I::Item> = items.into_iter().collect();
+    let paths: Vec<PathBuf> = items
+        .iter()
+        .map(|item| item.borrow().original_path())
+        .collect();
+    hooks::pre(hooks::Kind::Purge, &paths)?;
+    let total = items.len();
+    let progress = total > PURGE_CHUNK && io::stderr().is_terminal();
+    let (mut done, mut failed) = (0, 0);
+    for (chunk, paths) in items.chunks(PURGE_CHUNK).zip(paths.chunks(PURGE_CHUNK)) {
+        let sizes: Vec<u64> = if report::enabled() {
+            chunk
+                .iter()
+                .map(|item| items::item_size(item.borrow()))
+                .collect()
+        } else {
+            vec![0; chunk.len()]
+        };
+        let owned: Vec<trash::TrashItem> = chunk.iter().map(|item| item.borrow().clone()).collect();
+        let results: Vec<Result<(), trash::Error>> = match backend::get().purge(&owned) {
+            Ok(()) => owned.iter().map(|_| Ok(())).collect(),
+            // Items purged before the failure are no longer in the trash
+            Err(_) => owned
+                .iter()
+                .map(|item| {
+                    if items::in_trash(item) {
+                        backend::get().purge(std::slice::from_ref(item))
+                    } else {
+                        Ok(())
+                    }
+                })
+                .collect(),
+        };
+        let purged: Vec<_> = owned
+            .iter()
+            .zip(&results)
+            .filter(|(_, result)| result.is_ok())
+            .map(|(item, _)| item.clone())
+            .collect();
+        forget_records(&purged);
+        for ((path, size), result) in paths.iter().zip(sizes).zip(results) {
+            audit::record(op, path, &result);
+            match result {
+                Ok(()) => {
+                    report::record(op, path, size);
+                    done += 1;
+                }
+                Err(e) => {
+                    if progress {
+                        eprintln!();
+                    }
+                    eprintln!("trache: cannot purge '{}': {e}", path.display());
+                    failed += 1;
+                }
+            }
+        }
+        if progress {
+            eprint!("\rtrache: purged {done} of {total} item(s)");
+        }
+    }
+    if progress {
+        eprintln!();
+    }
+    if failed > 0 {
+        return Err(TracheError::Incomplete(format!(
+            "{failed} of {total} item(s) could not be purged; {done} were"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+/// Drop what's kept beside the trash about `items`, which have left it.
+fn forget_records(items: &[trash::TrashItem]) {
+    if items.is_empty() {
+        return;
+    }
+    if let Err(e) = checksum::forget(items) {
+        eprintln!("trache: warning: cannot update the recorded checksums: {e}");
+    }
+    if let Err(e) = links::forget(items) {
+        eprintln!("trache: warning: cannot update the recorded link targets: {e}");
+    }
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+/// Items handed to the backend per purge call
+const PURGE_CHUNK: usize = 500;
+
+#[derive(Parser)]
+#[command(name = "trache")]
+#[command(version)]
+#[command(about = "Move files to trash. Manage trashed items.", long_about = None)]
+#[command(
+    subcommand_negates_reqs = true,
+    disable_help_subcommand = true,
+    override_usage = "trache [OPTIONS] <FILES>...\n       trache [OPTIONS] <COMMAND>"
+)]
+#[command(group(
+    ArgGroup::new("restore").args(["undo", "undo_since", "undo_label"])
+))]
+#[command(group(
+    ArgGroup::new("mode")
+        .args(["list", "dirs", "stats", "cat", "diff", "info", "export", "import", "empty", "undo", "undo_since", "undo_label", "purge", "purge_n", "purge_dir", "clean", "analyze", "check", "resume", "daemon", "serve_stdio", "batch", "rename", "mv", "undo_op", "pin", "unpin", "tutorial", "schema"])
+))]
+struct Cli {
+    /// List items in trash with numbers for --trash-purge-n and IDs for id: patterns
+    #[arg(
+        long = "trash-list",
+        value_name = "PATTERN",
+        add = ArgValueCompleter::new(complete_items),
+        num_args = 0..=1,
+        require_equals = true,
+        long_help = "List items in trash with numbers for --trash-purge-n and IDs for\n\
+            id: patterns. With =PATTERN (as for --trash-purge), list only the\n\
+            matching items; their numbers stay the same as in the full listing.\n\
+            \n\
+            Example: trache --trash-list='not:*.log'"
+    )]
+    list: Option<Option<String>>,
+
+    /// With --trash-list: show only items whose contents are identical to another item
+    #[arg(long, requires = "list")]
+    duplicates: bool,
+
+    /// With --trash-list: print each item as TEMPLATE, e.g. '{name}\t{size}' (see --help)
     #[arg(
-        short = 'r',
-        visible_short_alias = 'R',
         long,
-        overrides_with = "recursive"
+        value_name = "TEMPLATE",
+        requires = "list",
+        conflicts_with = "duplicates",
+        long_help = "With --trash-list: print one line per item from TEMPLATE instead of\n\
+            the default columns. Placeholders:\n\
+            \n\
+            \x20 {n}             number, as used by --trash-purge-n\n\
+            \x20 {id}            item ID, as used by id: patterns\n\
+            \x20 {name}          file name\n\
+            \x20 {path}          original path\n\
+            \x20 {dir}           original parent directory\n\
+            \x20 {trash_path}    location inside the trash\n\
+            \x20 {size}          size for humans, e.g. 1.5 MiB\n\
+            \x20 {size_bytes}    size in bytes\n\
+            \x20 {deleted}       deletion time, e.g. 2024-05-01 14:30\n\
+            \x20 {deleted_iso}   deletion time in RFC 3339\n\
+            \x20 {deleted_unix}  deletion time in seconds since the epoch\n\
+            \n\
+            Write {{ and }} for literal braces, and \\t, \\n and \\\\ for a tab, a\n\
+            newline and a backslash.\n\
+            \n\
+            Example:\n\
+            \x20 trache --trash-list --format '{deleted_iso}\\t{size_bytes}\\t{path}'"
     )]
-    recursive: bool,
+    format: Option<String>,
 
-    /// Prompt before every removal; also prompts during --trash-undo
-    #[arg(short = 'i', overrides_with_all = ["force", "prompt_once", "interactive", "prompt_always"])]
-    prompt_always: bool,
+    /// Print --trash-list items as CSV, TSV or JSON lines, or the --trash-dry-run plan as JSON
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        conflicts_with_all = ["duplicates", "format"],
+        long_help = "With --trash-list: print the items as CSV, TSV or JSON lines.\n\
+            \n\
+            With --trash-dry-run, --output json prints the plan instead of the\n\
+            'would ...' messages when trashing, shredding, restoring or purging:\n\
+            one object per item with its action, source, destination and the\n\
+            conflicts a restore would meet: 'collision' when something exists at\n\
+            the destination, 'twin' when other items would go to the same place.\n\
+            \n\
+            \x20 {\"action\":\"restore\",\"source\":\"...\",\"destination\":\"...\",\"conflicts\":[\"twin\"]}\n\
+            \n\
+            See --schema for both formats."
+    )]
+    output: Option<table::OutputFormat>,
 
-    /// Prompt once before removing >3 files or recursively; remember first choice during --trash-undo
-    #[arg(short = 'I', overrides_with_all = ["force", "prompt_always", "interactive", "prompt_once"])]
-    prompt_once: bool,
+    /// With --trash-list: cluster items by original directory, with counts and sizes
+    #[arg(
+        long = "group-by",
+        value_enum,
+        value_name = "KEY",
+        requires = "list",
+        conflicts_with_all = ["duplicates", "format", "output"],
+        long_help = "With --trash-list: print the items in groups, one per directory they\n\
+            were trashed from, each headed by the directory with its item count\n\
+            and total size. Groups are sorted by directory; items keep their\n\
+            numbers from the full listing.\n\
+            \n\
+            Example: trache --trash-list --group-by dir"
+    )]
+    group_by: Option<GroupBy>,
 
-    /// Prompt according to WHEN: never, once, or always; also affects --trash-undo (see --help)
+    /// With --trash-list: draw items as a tree of their original paths (see --help)
     #[arg(
-        long = "interactive",
-        value_name = "WHEN",
-        default_missing_value = "always",
-        num_args = 0..=1,
-        overrides_with_all = ["force", "prompt_always", "prompt_once", "interactive"],
-        long_help = "Prompt according to WHEN: never (default), once, or always.\n\n\
-            When trashing files:\n\
-            \x20 always (-i)  prompt before each file\n\
-            \x20 once (-I)    prompt once before >3 files or recursive removal\n\
-            \x20 never        no prompts (default)\n\
+        long,
+        requires = "list",
+        conflicts_with_all = ["duplicates", "format", "output", "group_by"],
+        long_help = "With --trash-list: draw the items as a tree of the directories they\n\
+            were trashed from, rooted at the deepest directory holding them all, so\n\
+            it's plain which trashed files and subtrees belong together. Each item\n\
+            shows its number, ID and deletion time; directories that only lead to\n\
+            items end in '/', and a chain of them is drawn on one line."
+    )]
+    tree: bool,
+
+    /// With --trash-purge: purge duplicate copies among matching items, keeping the newest
+    #[arg(
+        long,
+        requires = "purge",
+        conflicts_with_all = ["list", "empty", "undo"],
+        long_help = "With --trash-purge: among the items matching PATTERN, find those\n\
+            whose contents are byte-for-byte identical and purge all but the most\n\
+            recently deleted copy of each. Directories are compared as whole trees.\n\
             \n\
-            When restoring (--trash-undo):\n\
-            \x20 always (-i)  prompt at every conflict (collision or twin group)\n\
-            \x20 once (-I)    prompt on first conflict of each type, remember for the rest\n\
-            \x20 never        restore without prompting; skip items whose path already exists\n\
+            Example: trache --trash-purge 'full:app.bin' --duplicates-keep-newest"
+    )]
+    duplicates_keep_newest: bool,
+
+    /// With --trash-purge: keep the N newest items trashed from each path, purge the rest
+    #[arg(
+        long,
+        value_name = "N",
+        requires = "purge",
+        conflicts_with = "duplicates_keep_newest",
+        long_help = "With --trash-purge: among the items matching PATTERN, group those\n\
+            trashed from the same original path and purge all but the N most\n\
+            recently deleted of each group. Paths trashed N times or fewer are\n\
+            left alone. Pinned items count towards the N kept.\n\
             \n\
-            -f / --force overrides all interactive flags."
+            Example: trache --trash-purge 'full:notes.txt.bak' --keep 3"
     )]
-    interactive: Option<InteractiveMode>,
+    keep: Option<usize>,
 
-    /// Ignore nonexistent files, never prompt
-    #[arg(short = 'f', long, overrides_with_all = ["prompt_always", "prompt_once", "interactive", "force"])]
-    force: bool,
+    /// With --trash-undo or --trash-purge: also match the patterns in FILE, one per line (see --help)
+    #[arg(
+        long = "patterns-from",
+        value_name = "FILE",
+        global = true,
+        long_help = "With --trash-undo or --trash-purge: act on items matching any of\n\
+            the patterns in FILE, one per line, written as for --trash-purge.\n\
+            Blank lines and lines starting with '#' are ignored; write a pattern\n\
+            that starts with '#' as glob:#... A PATTERN given to the mode as\n\
+            well is one more pattern to match; it may be left out.\n\
+            \n\
+            Example: trache --trash-undo --patterns-from cleaned-up.txt"
+    )]
+    patterns_from: Option<PathBuf>,
 
-    /// Explain what is being done
-    #[arg(short = 'v', long, overrides_with = "verbose")]
-    verbose: bool,
+    /// With --trash-list, --trash-undo or --trash-purge: only items satisfying EXPR (see --help)
+    #[arg(
+        long,
+        value_name = "EXPR",
+        global = true,
+        long_help = "With --trash-list, --trash-undo or --trash-purge: act only on items\n\
+            satisfying EXPR, on top of any PATTERN, which may then be left out.\n\
+            EXPR compares fields of each item with FIELD OP VALUE and combines\n\
+            comparisons with &&, ||, ! and parentheses:\n\
+            \n\
+            \x20 name, path, dir, id   ~ and !~ (glob), == and !=\n\
+            \x20 size                  == != < <= > >=, as in 10k or 1G\n\
+            \x20 age                   the same, as in 30m or 2w\n\
+            \x20 deleted               the same, a time as for --trash-undo-since\n\
+            \x20 type                  == or !=, file, dir or symlink\n\
+            \n\
+            && binds tighter than ||. Quote values with spaces or operators in\n\
+            them in double quotes.\n\
+            \n\
+            Example: trache --trash-purge --filter 'name ~ \"*.log\" && age > 7d && size > 1M'"
+    )]
+    filter: Option<String>,
 
-    /// Do not remove '/'; 'all' also rejects arguments on separate devices
-    #[arg(long = "preserve-root", value_name = "MODE", default_missing_value = "yes", num_args = 0..=1, overrides_with_all = ["no_preserve_root", "preserve_root"])]
-    preserve_root: Option<PreserveRoot>,
+    /// Let glob wildcards match names starting with '.' (default; see --help)
+    #[arg(
+        long,
+        global = true,
+        overrides_with_all = ["hidden", "no_hidden"],
+        long_help = "Let *, ? and [...] in glob patterns match names starting with '.',\n\
+            so --trash-undo '*' really restores everything. This is the default;\n\
+            `hidden = false` in config.toml changes it."
+    )]
+    hidden: bool,
 
-    /// Do not treat '/' specially
-    #[arg(long = "no-preserve-root", overrides_with_all = ["preserve_root", "no_preserve_root"])]
-    no_preserve_root: bool,
+    /// Glob wildcards don't match a leading '.', as in the shell (see --help)
+    #[arg(
+        long = "no-hidden",
+        global = true,
+        overrides_with_all = ["hidden", "no_hidden"],
+        long_help = "Treat dotfiles the way the shell does: a glob pattern matches a name\n\
+            starting with '.' (anywhere in the path, for path: and dir:) only if\n\
+            the pattern spells out the dot itself, as in '.env' or 'path:*/.config/*'.\n\
+            regex: and string: patterns are unaffected."
+    )]
+    no_hidden: bool,
 
-    /// Skip directories on different file systems
+    /// List the original directories of trashed items, with item counts (see --help)
     #[arg(
-        short = 'x',
-        long = "one-file-system",
-        overrides_with = "one_file_system"
+        long = "trash-dirs",
+        long_help = "List the distinct directories trashed items came from, one per line\n\
+            as <count><TAB><directory>, sorted by directory.\n\
+            \n\
+            Use it to pick a directory, then narrow undo or purge to it:\n\
+            \n\
+            \x20 trache --trash-dirs | cut -f2\n\
+            \x20 trache --trash-undo 'dir:full:/home/me/project'"
     )]
-    one_file_system: bool,
+    dirs: bool,
 
-    /// This flag has no effect.  It is kept only for backwards compatibility with BSD.
-    #[arg(short = 'P', hide = true, overrides_with = "_compat_p")]
-    _compat_p: bool,
+    /// Show item counts, sizes and retention policy per trash volume
+    #[arg(long = "trash-stats")]
+    stats: bool,
 
-    /// Unsupported (use --trash-undo instead)
-    #[arg(short = 'W', hide = true, overrides_with = "compat_w")]
-    compat_w: bool,
+    /// Find wasted space in the trash and print the commands that reclaim it (see --help)
+    #[arg(
+        long = "trash-analyze",
+        long_help = "Find wasted space in the trash without changing anything, and print\n\
+            the command that reclaims each kind:\n\
+            \n\
+            \x20 duplicates  items with the same contents as a newer item\n\
+            \x20 cold items  items trashed more than 90 days ago\n\
+            \x20 orphans     contents with no .trashinfo record, and records whose\n\
+            \x20             contents are gone; --trash-check --repair makes items\n\
+            \x20             of the contents, to purge, and drops the records\n\
+            \n\
+            Every trash directory is looked at, holding items or not. Pinned\n\
+            items are left out."
+    )]
+    analyze: bool,
 
-    /// Files to trash
-    #[arg(required_unless_present = "mode")]
-    files: Vec<PathBuf>,
-}
+    /// Look for broken entries in the trash; --repair fixes them (see --help)
+    #[arg(
+        long = "trash-check",
+        long_help = "Scan the trash for entries that don't add up to an item, which other\n\
+            tools and crashes leave behind:\n\
+            \n\
+            \x20 orphaned    contents with no .trashinfo record\n\
+            \x20 dangling    a record whose contents are gone\n\
+            \x20 malformed   a record with no usable Path or DeletionDate\n\
+            \x20 unreadable  an entry that can't be read at all\n\
+            \n\
+            With --repair, orphaned contents get a record that restores them\n\
+            under the rescue directory (their real path is unknown), dangling\n\
+            records are removed and malformed records are rewritten; one whose\n\
+            original path can't be read is recorded under the rescue directory\n\
+            like orphaned contents. Unreadable entries are only reported. Exits\n\
+            nonzero while problems remain.\n\
+            Only supported on Linux/BSD."
+    )]
+    check: bool,
 
-fn main() {
-    // Reset SIGPIPE to default behavior (terminate silently) so piping to
-    // tools like `head` or `grep` doesn't cause a panic
-    #[cfg(unix)]
-    unsafe {
-        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
-    }
+    /// Fix what --trash-check finds
+    #[arg(long, requires = "check")]
+    repair: bool,
 
-    let cli = Cli::parse();
+    /// Serve trash operations over D-Bus for other programs (see --help)
+    #[arg(
+        long,
+        long_help = "Run as a D-Bus service on the session bus, owning the name\n\
+            io.github.mevanlc.Trache, so file managers, editors and scripts can\n\
+            use trache as their trash. The object /io/github/mevanlc/Trache\n\
+            serves the io.github.mevanlc.Trache1 interface:\n\
+            \n\
+            \x20 Trash(as paths) -> a{ss}    trash absolute paths; returns failures\n\
+            \x20 List() -> a(ssx)            short ID, original path, time trashed\n\
+            \x20 Restore(as ids) -> a{ss}a{ss}  where each went; failures\n\
+            \x20 Purge(as ids) -> a{ss}      purge items; returns failures\n\
+            \x20 Stats() -> (tt)             item count and total size in bytes\n\
+            \n\
+            Paths are trashed as with -rf, and an item whose original path is taken\n\
+            is restored beside it as NAME-untrash_N. Runs until stopped. Only\n\
+            supported on Linux/BSD."
+    )]
+    daemon: bool,
 
-    if cli.compat_w {
-        eprintln!("trache: -W is not supported; use --trash-undo <pattern> to restore from trash");
-        std::process::exit(1);
-    }
+    /// Answer JSON-RPC requests on stdin, for front-ends (see --help)
+    #[arg(
+        long,
+        long_help = "Serve the operations --daemon offers as JSON-RPC 2.0: one request, or\n\
+            an array of them, per line on stdin, each answered on one line of\n\
+            stdout. Parameters are given by name:\n\
+            \n\
+            \x20 trash    {\"paths\": [...]}  -> {\"failed\": {path: why}}\n\
+            \x20 list                       -> {\"items\": [{\"id\", \"path\", \"deleted\"}]}\n\
+            \x20 restore  {\"ids\": [...]}    -> {\"restored\": {id: path}, \"failed\": {...}}\n\
+            \x20 purge    {\"ids\": [...]}    -> {\"failed\": {id: why}}\n\
+            \x20 stats                      -> {\"count\", \"size\"}\n\
+            \n\
+            Requests without an id are carried out without a response. Anything\n\
+            else trache would print goes to stderr. Stops at the end of input.\n\
+            Only supported on Linux/BSD."
+    )]
+    serve_stdio: bool,
 
-    let dry_run = cli.dry_run;
+    /// Run trash, undo and purge commands read from stdin (see --help)
+    #[arg(
+        long,
+        long_help = "Read commands from stdin, one per line, and run them all in this one\n\
+            process:\n\
+            \n\
+            \x20 trash PATH       trash PATH, as `trache PATH`\n\
+            \x20 undo PATTERN     restore, as --trash-undo PATTERN\n\
+            \x20 purge PATTERN    purge, as --trash-purge PATTERN\n\
+            \n\
+            Everything after the command word is its argument, spaces and all.\n\
+            Blank lines and lines starting with # are skipped. Flags given with\n\
+            --batch (-i, -f, -r, --trash-dry-run, ...) apply to every command, and\n\
+            prompts are answered on the terminal. A failed command doesn't stop\n\
+            the rest; the run ends by counting the failures."
+    )]
+    batch: bool,
 
-    let interactive = if cli.force {
-        InteractiveMode::Never
-    } else if cli.prompt_always {
-        InteractiveMode::Always
-    } else if cli.prompt_once {
-        InteractiveMode::Once
-    } else if let Some(mode) = cli.interactive {
-        mode
-    } else {
-        InteractiveMode::Never
-    };
+    /// Finish or roll back batches cut short by a crash or Ctrl-C (see --help)
+    #[arg(
+        long,
+        value_name = "ACTION",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "continue",
+        long_help = "Before trashing or restoring several items, trache writes down\n\
+            the whole batch, and ticks items off as it goes. When a run is killed\n\
+            or interrupted with Ctrl-C, --resume deals with what it left behind:\n\
+            \n\
+            \x20 continue  trash or restore the items the batch didn't get to\n\
+            \x20           (--resume alone; give -r again only if you like)\n\
+            \x20 rollback  restore the items the batch trashed, or trash again\n\
+            \x20           the items it restored\n\
+            \n\
+            Batches are taken oldest first. --trash-dry-run shows what would be\n\
+            done and keeps the batches for later."
+    )]
+    resume: Option<ResumeAction>,
+
+    /// Print the contents of a trashed file matching PATTERN without restoring it
+    #[arg(
+        long = "trash-cat",
+        value_name = "PATTERN",
+        add = ArgValueCompleter::new(complete_items),
+        long_help = "Write the contents of the trashed file matching PATTERN to stdout,\n\
+            leaving it in the trash. PATTERN works as for --trash-undo. If several\n\
+            items match, the most recently deleted one is shown.\n\
+            \n\
+            Use --trash-head N to show only the first N lines."
+    )]
+    cat: Option<String>,
+
+    /// Show how a trashed file matching PATTERN differs from the file now at its path
+    #[arg(
+        long = "trash-diff",
+        value_name = "PATTERN",
+        add = ArgValueCompleter::new(complete_items),
+        long_help = "Print a unified diff from the trashed file matching PATTERN to\n\
+            whatever now exists at its original path, to see whether the trashed\n\
+            version is actually different before choosing to overwrite or keep\n\
+            both. PATTERN works as for --trash-undo. If several items match, the\n\
+            most recently deleted one is compared.\n\
+            \n\
+            Binary files are only reported as differing."
+    )]
+    diff: Option<String>,
+
+    /// Pack trashed items matching PATTERN into an archive, leaving them in the trash
+    #[arg(
+        long = "trash-export",
+        value_name = "PATTERN",
+        add = ArgValueCompleter::new(complete_items),
+        requires = "to",
+        long_help = "Pack the trashed items matching PATTERN into the tar archive given\n\
+            with --to, without restoring them, to hand recovered files to someone\n\
+            else or move them to another machine. PATTERN works as for\n\
+            --trash-undo. The archive holds a manifest (trache-export.json) with\n\
+            each item's original path and deletion time, then the items under\n\
+            items/<n>/<name>. It is gzip-compressed when its name ends in .gz or\n\
+            .tgz. An existing archive is only replaced with -f.\n\
+            \n\
+            Example: trache --trash-export 'report*' --to reports.tar.gz"
+    )]
+    export: Option<String>,
+
+    /// With --trash-export: the archive to write
+    #[arg(long, value_name = "ARCHIVE", requires = "export")]
+    to: Option<PathBuf>,
+
+    /// Put the items of an archive from --trash-export back into the trash (see --help)
+    #[arg(
+        long = "trash-import",
+        value_name = "ARCHIVE",
+        long_help = "Put the items of an archive made by --trash-export into the local\n\
+            trash, keeping the original paths and deletion times they were\n\
+            exported with, so they can be listed, restored or purged as if they\n\
+            had been trashed here (Linux/BSD). With --in-place they are restored\n\
+            straight to those original paths instead, creating missing parent\n\
+            directories. Items whose destination is already taken are skipped.\n\
+            \n\
+            Example: trache --trash-import reports.tar.gz --in-place"
+    )]
+    import: Option<PathBuf>,
+
+    /// With --trash-import: restore the items to their original paths instead
+    #[arg(long = "in-place", requires = "import")]
+    in_place: bool,
+
+    /// Show everything known about trashed items matching PATTERN (see --help)
+    #[arg(
+        long = "trash-info",
+        value_name = "PATTERN",
+        add = ArgValueCompleter::new(complete_items),
+        long_help = "Print the full record of every trashed item matching PATTERN: ID,\n\
+            original path, location inside the trash, deletion time, size, type\n\
+            and permissions, plus the raw .trashinfo file on Linux/BSD. Useful\n\
+            before deciding whether to purge or restore.\n\
+            \n\
+            PATTERN uses the same syntax as --trash-undo."
+    )]
+    info: Option<String>,
+
+    /// With --trash-cat: print only the first N lines
+    #[arg(long = "trash-head", value_name = "N", requires = "cat")]
+    head: Option<usize>,
+
+    /// Empty the entire trash, or only what was trashed from under the given paths
+    #[arg(long = "trash-empty")]
+    empty: bool,
+
+    /// Restore items matching pattern from trash (see --help)
+    #[arg(
+        long = "trash-undo",
+        value_name = "PATTERN",
+        add = ArgValueCompleter::new(complete_items),
+        num_args = 0..=1,
+        long_help = "Restore items matching PATTERN from trash.\n\n\
+            PATTERN may include optional prefixes to control matching:\n\
+            \n\
+            \x20 [glob:|regex:|string:|full:|partial:|icase:|case:|not:|size:SIZE:|type:KIND:|deleted:DATE:|name:|path:|dir:|id:]*PATTERN\n\
+            \n\
+            Match type (default: glob):\n\
+            \x20 glob:     glob pattern (see https://docs.rs/globset)\n\
+            \x20 regex:    regular expression\n\
+            \x20 string:   literal string\n\
+            \n\
+            Match extent (default: partial):\n\
+            \x20 partial:  pattern matches a substring of the name/path\n\
+            \x20 full:     pattern must match the entire name/path\n\
+            \n\
+            Case (default: case:):\n\
+            \x20 case:     letters must match in case\n\
+            \x20 icase:    ignore case (any match type)\n\
+            \n\
+            Size (measured only for items that otherwise match):\n\
+            \x20 size:>100M   size:<4k   size:>=1G:*.iso   (<, <=, =, >=, >)\n\
+            \n\
+            Kind:\n\
+            \x20 type:dir:  type:file:  type:symlink:   (or d, f, l)\n\
+            \n\
+            Deletion date (local time; YYYY, YYYY-MM or YYYY-MM-DD):\n\
+            \x20 deleted:2024-05   deleted:>=2024-01-01   deleted:2024-01..2024-03\n\
+            \n\
+            Negation:\n\
+            \x20 not:      match what the rest doesn't (not:not: cancels out)\n\
+            \n\
+            Match target (default: name):\n\
+            \x20 name:  match against file basename\n\
+            \x20 path:  match against original full path\n\
+            \x20 dir:   match against original parent directory (see --trash-dirs)\n\
+            \x20 id:    match against the item ID shown by --trash-list\n\
+            \n\
+            Prefixes can be stacked; rightmost wins per group.\n\
+            \n\
+            Examples:\n\
+            \x20 --trash-undo foo            names containing \"foo\"\n\
+            \x20 --trash-undo 'full:*.txt'   names matching *.txt exactly\n\
+            \x20 --trash-undo 'regex:^foo'   names with regex match\n\
+            \x20 --trash-undo 'string:a.txt' names containing \"a.txt\" literally\n\
+            \x20 --trash-undo 'path:/tmp'    paths containing \"/tmp\"\n\
+            \x20 --trash-undo 'icase:report.pdf'  Report.PDF, report.pdf, ...\n\
+            \n\
+            Interactive mode (-i, -I, --interactive):\n\
+            \n\
+            Without interactive flags, matching items restore silently. Items whose\n\
+            original path already exists are skipped.\n\
+            \n\
+            With -i (or --interactive=always), you are prompted for each conflict.\n\
+            With -I (or --interactive=once), the first choice of each type is\n\
+            remembered and applied to all subsequent conflicts of that type.\n\
+            When two or more collisions are predicted, either mode first asks\n\
+            whether to resolve them individually or overwrite/keep both/skip all.\n\
+            The choices each prompt offers, the same ones '?' explains there, are\n\
+            listed below."
+    )]
+    undo: Option<Option<String>>,
+
+    /// Restore everything trashed since WHEN, e.g. "10 minutes ago" (see --help)
+    #[arg(
+        long = "trash-undo-since",
+        value_name = "WHEN",
+        long_help = "Restore every item trashed since WHEN, whatever its name: the\n\
+            quickest way back from a bad scripted cleanup.\n\
+            \n\
+            WHEN is a duration back from now (\"10m\", \"2h\", \"10 minutes ago\")\n\
+            or a local date and time (\"2024-05-01 14:30\", \"2024-05-01\", or\n\
+            RFC 3339). Collisions and -i/-I/--interactive work as for --trash-undo;\n\
+            try --trash-dry-run first to see what would come back."
+    )]
+    undo_since: Option<String>,
+
+    /// Restore every item trashed with --label NAME
+    #[arg(
+        long = "trash-undo-label",
+        value_name = "NAME",
+        long_help = "Restore every item trashed with --label NAME that is still in the\n\
+            trash, so a labelled cleanup can be undone as a unit. Collisions and\n\
+            -i/-I/--interactive work as for --trash-undo; try --trash-dry-run\n\
+            first to see what would come back."
+    )]
+    undo_label: Option<String>,
+
+    /// Permanently delete items matching pattern from trash (see --help)
+    #[arg(
+        long = "trash-purge",
+        value_name = "PATTERN",
+        add = ArgValueCompleter::new(complete_items),
+        num_args = 0..=1,
+        long_help = "Permanently delete items matching PATTERN from trash.\n\n\
+            PATTERN may include optional prefixes to control matching:\n\
+            \n\
+            \x20 [glob:|regex:|string:|full:|partial:|icase:|case:|not:|size:SIZE:|type:KIND:|deleted:DATE:|name:|path:|dir:|id:]*PATTERN\n\
+            \n\
+            Match type (default: glob):\n\
+            \x20 glob:     glob pattern (see https://docs.rs/globset)\n\
+            \x20 regex:    regular expression\n\
+            \x20 string:   literal string\n\
+            \n\
+            Match extent (default: partial):\n\
+            \x20 partial:  pattern matches a substring of the name/path\n\
+            \x20 full:     pattern must match the entire name/path\n\
+            \n\
+            Case (default: case:):\n\
+            \x20 case:     letters must match in case\n\
+            \x20 icase:    ignore case (any match type)\n\
+            \n\
+            Size (measured only for items that otherwise match):\n\
+            \x20 size:>100M   size:<4k   size:>=1G:*.iso   (<, <=, =, >=, >)\n\
+            \n\
+            Kind:\n\
+            \x20 type:dir:  type:file:  type:symlink:   (or d, f, l)\n\
+            \n\
+            Deletion date (local time; YYYY, YYYY-MM or YYYY-MM-DD):\n\
+            \x20 deleted:2024-05   deleted:>=2024-01-01   deleted:2024-01..2024-03\n\
+            \n\
+            Negation:\n\
+            \x20 not:      match what the rest doesn't (not:not: cancels out)\n\
+            \n\
+            Match target (default: name):\n\
+            \x20 name:  match against file basename\n\
+            \x20 path:  match against original full path\n\
+            \x20 dir:   match against original parent directory (see --trash-dirs)\n\
+            \x20 id:    match against the item ID shown by --trash-list\n\
+            \n\
+            Prefixes can be stacked; rightmost wins per group.\n\
+            \n\
+            Examples:\n\
+            \x20 --trash-purge foo            names containing \"foo\"\n\
+            \x20 --trash-purge 'full:*.txt'   names matching *.txt exactly\n\
+            \x20 --trash-purge 'regex:^foo'   names with regex match\n\
+            \x20 --trash-purge 'string:a.txt' names containing \"a.txt\" literally\n\
+            \x20 --trash-purge 'path:/tmp'    paths containing \"/tmp\"\n\
+            \x20 --trash-purge 'not:*.keep'  everything except names containing \".keep\"\n\
+            \n\
+            Paths after PATTERN narrow it to items trashed from those paths or\n\
+            from inside them, whether or not they still exist:\n\
+            \n\
+            \x20 --trash-purge '*' ~/projects/foo   everything trashed from foo"
+    )]
+    purge: Option<Option<String>>,
+
+    /// Permanently delete items by their --trash-list numbers, e.g. 4-9,12
+    #[arg(
+        long = "trash-purge-n",
+        value_name = "INDICES",
+        long_help = "Permanently delete items by the numbers --trash-list shows next to\n\
+            them. INDICES is a comma-separated list of numbers and ranges,\n\
+            e.g. 4-9,12.\n\
+            \n\
+            Numbers follow deletion time, so they stay put as long as nothing is\n\
+            restored or purged in between; items trashed since the listing get\n\
+            new numbers at the end. The purged items are printed, and\n\
+            --trash-dry-run shows them without purging."
+    )]
+    purge_n: Option<String>,
+
+    /// Permanently delete everything trashed from DIR or from inside it (see --help)
+    #[arg(
+        long = "trash-purge-dir",
+        value_name = "DIR",
+        long_help = "Permanently delete every item trashed from DIR or from anywhere\n\
+            inside it, whether or not DIR still exists: the way to clear out what\n\
+            a project that is gone for good left in the trash.\n\
+            \n\
+            The items are listed and you are asked once before anything is\n\
+            deleted; -f skips the question. Pinned items are left alone, and\n\
+            --trash-dry-run only lists what would go.\n\
+            \n\
+            Example: trache --trash-purge-dir ~/projects/old-prototype"
+    )]
+    purge_dir: Option<PathBuf>,
+
+    /// Change the recorded original path of trashed items from FROM to TO (see --help)
+    #[arg(
+        long = "trash-rename",
+        num_args = 2,
+        value_names = ["FROM", "TO"],
+        long_help = "Change the recorded original path of trashed items, so a later\n\
+            --trash-undo restores them somewhere else.\n\
+            \n\
+            Every item whose original path is FROM, or lies under the directory\n\
+            FROM, is re-pointed at the same location under TO. Use this after\n\
+            moving or renaming a project directory.\n\
+            \n\
+            On Linux/BSD the .trashinfo metadata is rewritten in place. On Windows\n\
+            the item is restored to its new location and trashed again, which\n\
+            resets its deletion time.\n\
+            \n\
+            Examples:\n\
+            \x20 --trash-rename ~/old/notes.txt ~/new/notes.txt\n\
+            \x20 --trash-rename ~/src/proj ~/work/proj"
+    )]
+    rename: Option<Vec<PathBuf>>,
+
+    /// Move SRC to DST like mv, recording it so --trash-undo-op can move it back
+    #[arg(
+        long = "mv",
+        num_args = 2,
+        value_names = ["SRC", "DST"],
+        long_help = "Move SRC to DST like mv, and record the move in a journal so\n\
+            --trash-undo-op can put SRC back where it was.\n\
+            \n\
+            If DST is an existing directory, SRC is moved into it. An existing\n\
+            file at the destination is never replaced. Moves across file systems\n\
+            copy and then delete SRC.\n\
+            \n\
+            The journal lives in ~/.local/state/trache/moves.jsonl\n\
+            (%LOCALAPPDATA%\\trache\\moves.jsonl on Windows)."
+    )]
+    mv: Option<Vec<PathBuf>>,
+
+    /// Move back what --mv moved: the latest move, or the one with ID
+    #[arg(
+        long = "trash-undo-op",
+        value_name = "ID",
+        num_args = 0..=1,
+        require_equals = true
+    )]
+    undo_op: Option<Option<u64>>,
+
+    /// Keep items matching PATTERN from being purged by any means (see --help)
+    #[arg(
+        long = "trash-pin",
+        value_name = "PATTERN",
+        add = ArgValueCompleter::new(complete_items),
+        long_help = "Pin the trashed items matching PATTERN (as for --trash-purge), so\n\
+            --trash-empty, --trash-purge, --trash-purge-n and the [retention] rules\n\
+            of --trash-clean leave them in the trash. Pinned items still count\n\
+            towards max_total_size. Restoring an item drops its pin.\n\
+            \n\
+            Pins are kept in the state directory (pins.jsonl), not in the trash.\n\
+            \n\
+            Example: trache --trash-pin 'full:old-design.sketch'"
+    )]
+    pin: Option<String>,
+
+    /// Let items matching PATTERN be purged again
+    #[arg(
+        long = "trash-unpin",
+        value_name = "PATTERN",
+        add = ArgValueCompleter::new(complete_items)
+    )]
+    unpin: Option<String>,
+
+    /// Apply the [retention] rules from config.toml (see --help)
+    #[arg(
+        long = "trash-clean",
+        long_help = "Purge items according to the [retention] section of config.toml.\n\n\
+            Intended to be run from cron or a systemd timer: nothing is printed\n\
+            unless something is purged.\n\
+            \n\
+            \x20 [retention]\n\
+            \x20 max_age = \"30d\"          purge items deleted more than 30 days ago\n\
+            \x20 keep_per_path = 3        then all but the 3 newest from each path\n\
+            \x20 max_total_size = \"10G\"   then purge oldest items until the trash fits\n\
+            \x20 protect = [\"*.keep\"]     never purge items matching these patterns\n\
+            \n\
+            \x20 [[retention.mount]]      override max_age/max_total_size/\n\
+            \x20 path = \"/mnt/ssd\"        keep_per_path for the trash on one volume;\n\
+            \x20 max_age = \"3d\"           size limits are counted per volume\n\
+            \n\
+            \x20 [[retention.rule]]       override max_age/keep_per_path for items\n\
+            \x20 pattern = \"*.log\"        matching a pattern (the first matching\n\
+            \x20 max_age = \"2d\"           rule applies)\n\
+            \n\
+            Durations take s, m, h, d or w; sizes take k, M, G or T (powers of 1024).\n\
+            Protect and rule patterns use the same syntax as --trash-purge.\n\
+            \n\
+            Use --porcelain for a JSON summary suitable for monitoring."
+    )]
+    clean: bool,
+
+    /// With --trash-clean: print a JSON summary instead of human-readable output
+    #[arg(long, requires = "clean", conflicts_with_all = ["list", "empty", "undo", "purge"])]
+    porcelain: bool,
+
+    /// With --trash-undo: restore into a fresh temp directory, leaving original locations alone
+    #[arg(
+        long = "to-temp",
+        requires = "undo",
+        conflicts_with_all = ["list", "empty", "purge"],
+        long_help = "With --trash-undo: restore matching items into a fresh temporary\n\
+            directory instead of their original locations, and print its path.\n\
+            \n\
+            Use this to inspect recovered files before moving them into place\n\
+            yourself. Items with the same name are restored as\n\
+            <name>-untrash_N.<ext>.\n\
+            \n\
+            Sandboxes older than `to_temp_ttl` in config.toml (default: 7d) are\n\
+            permanently removed the next time --to-temp is used."
+    )]
+    to_temp: bool,
+
+    /// With --trash-undo: restore the single matching item under NAME in its original directory
+    #[arg(
+        long = "restore-as",
+        value_name = "NAME",
+        requires = "undo",
+        conflicts_with_all = ["list", "empty", "purge", "to_temp"],
+        long_help = "With --trash-undo: restore the matching item under a different\n\
+            basename in its original directory, e.g. to bring back an old copy\n\
+            of a file next to its replacement.\n\
+            \n\
+            The pattern must match exactly one item; narrow it with full:,\n\
+            path: or string: if it matches more. NAME must not already exist.\n\
+            \n\
+            Example:\n\
+            \x20 --trash-undo 'string:path:/home/me/notes.txt' --restore-as notes.old.txt"
+    )]
+    restore_as: Option<OsString>,
+
+    /// With --trash-undo: restore only the files matching GLOB from inside trashed directories
+    #[arg(
+        long = "inner",
+        value_name = "GLOB",
+        requires = "undo",
+        conflicts_with_all = ["list", "empty", "purge", "to_temp", "restore_as"],
+        long_help = "With --trash-undo: instead of restoring matching directories whole,\n\
+            extract only the entries inside them that match GLOB, moving each one\n\
+            to its place under the directory's original path. The rest of the\n\
+            directory stays in the trash.\n\
+            \n\
+            A GLOB without '/' matches entry names at any depth; one with '/'\n\
+            matches paths relative to the trashed directory. A matching\n\
+            subdirectory is extracted with everything in it. Entries whose\n\
+            destination already exists are skipped.\n\
+            \n\
+            Examples:\n\
+            \x20 --trash-undo full:myproject --inner Cargo.toml\n\
+            \x20 --trash-undo full:myproject --inner 'src/**/*.rs'"
+    )]
+    inner: Option<String>,
+
+    /// With --trash-undo: restore items that can't go back in place under DIR (see --help)
+    #[arg(
+        long,
+        value_name = "DIR",
+        num_args = 0..=1,
+        require_equals = true,
+        requires = "undo",
+        long_help = "With --trash-undo: when an item can't be restored to its original\n\
+            path because that volume is full or is no longer the one it was\n\
+            trashed from, restore it under DIR instead of leaving it in the\n\
+            trash. The original path is recreated below DIR, e.g.\n\
+            /mnt/usb/a.txt is rescued as DIR/mnt/usb/a.txt.\n\
+            \n\
+            DIR defaults to `rescue_dir` in config.toml, else ~/trache-rescue."
+    )]
+    rescue: Option<Option<PathBuf>>,
+
+    /// With --trash-undo: restore into <original parent>/restored-YYYYMMDD/ instead of in place
+    #[arg(
+        long = "dated-dir",
+        requires = "undo",
+        conflicts_with_all = ["list", "empty", "purge", "to_temp", "restore_as", "inner"],
+        long_help = "With --trash-undo: restore each item into a restored-YYYYMMDD\n\
+            directory (today's date) next to where it was trashed from, instead\n\
+            of its original path. Nothing in place is ever overwritten, and the\n\
+            results stay easy to find. Names already taken in the dated\n\
+            directory get the usual -untrash_N suffix."
+    )]
+    dated_dir: bool,
+
+    /// Show what would be done without doing it
+    #[arg(long = "trash-dry-run", global = true)]
+    dry_run: bool,
+
+    /// Use DIR as the trash instead of the system trash (Linux/BSD)
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        conflicts_with = "sandbox",
+        long_help = "Use DIR as the trash instead of the system trash: items are moved\n\
+            into DIR/files with a record of each in DIR/info, as in a freedesktop\n\
+            trash, and every listing, restore and purge sees only what is there.\n\
+            DIR is created if need be. Defaults to trash_dir in the config file.\n\
+            Only supported on Linux/BSD."
+    )]
+    trash_dir: Option<PathBuf>,
+
+    /// Use a throwaway trash and sample files instead of the real ones (see --help)
+    #[arg(
+        long,
+        global = true,
+        long_help = "Run against a throwaway sandbox instead of the real trash: a\n\
+            private directory under the system temp dir with its own trash and a\n\
+            playground of sample files. Relative FILE arguments resolve inside the\n\
+            playground, and files outside it are refused, so undo, purge and\n\
+            empty can be tried safely. The audit log, [report] and read-only mode\n\
+            are ignored in the sandbox.\n\
+            \n\
+            The sandbox persists between runs; --tutorial resets it.\n\
+            Only supported on Linux/BSD."
+    )]
+    sandbox: bool,
+
+    /// Guided walkthrough of trashing, undo and purge, in a fresh --sandbox
+    #[arg(long)]
+    tutorial: bool,
+
+    /// Print the versioned JSON schema of every machine-readable output
+    #[arg(long)]
+    schema: bool,
+
+    /// Output format for per-file results when trashing
+    #[arg(
+        long = "log-format",
+        value_name = "FORMAT",
+        global = true,
+        default_value = "text",
+        long_help = "Output format for per-file results when trashing.\n\n\
+            With json, one object per line is written to stdout for every action:\n\
+            \x20 {\"event\":\"trashed\",\"path\":\"a.txt\",\"dry_run\":false}\n\
+            \x20 {\"event\":\"skipped\",\"path\":\"b.txt\",\"reason\":\"declined\"}\n\
+            \x20 {\"event\":\"prompted\",\"path\":\"b.txt\",\"prompt\":\"...\",\"answer\":false}\n\
+            \x20 {\"event\":\"errored\",\"path\":\"c\",\"error\":\"Is a directory\"}\n\
+            \n\
+            Prompts and diagnostics still go to stderr; --verbose and --trash-dry-run\n\
+            messages are replaced by the events."
+    )]
+    log_format: LogFormat,
+
+    /// Record prompts, answers and actions of this run in FILE (see --help)
+    #[arg(
+        long,
+        value_name = "FILE",
+        global = true,
+        long_help = "Write a transcript of this run to FILE: the command line, every\n\
+            prompt shown with the answer given, and every action taken with its\n\
+            outcome. Review it afterwards or attach it to a bug report.\n\
+            \n\
+            The home directory is written as ~. Path components matching a\n\
+            --transcript-redact glob are written as ***; the words around the\n\
+            paths are left as they are. FILE is readable only by you."
+    )]
+    transcript: Option<PathBuf>,
+
+    /// With --transcript: write path components matching GLOB as *** (repeatable)
+    #[arg(
+        long = "transcript-redact",
+        value_name = "GLOB",
+        global = true,
+        requires = "transcript",
+        long_help = "With --transcript: write every path component matching GLOB as ***,\n\
+            e.g. --transcript-redact 'client-*' --transcript-redact '*.key'.\n\
+            Can be given more than once."
+    )]
+    transcript_redact: Vec<String>,
+
+    /// Show a desktop notification summing up big or slow runs (see --help)
+    #[arg(
+        long,
+        global = true,
+        long_help = "When the run has trashed, restored or purged 10 or more items, or\n\
+            taken 5 seconds or more, show a desktop notification summing it up\n\
+            (with notify-send on Linux and BSD, osascript on macOS, a toast on\n\
+            Windows). Handy when trache runs from a file manager or a timer.\n\
+            The thresholds are min_items and min_seconds under [notify] in\n\
+            config.toml."
+    )]
+    notify: bool,
+
+    /// Remap exit codes: MAP is NAME=CODE,..., e.g. partial=1 (see --help)
+    #[arg(
+        long = "status-map",
+        value_name = "MAP",
+        global = true,
+        long_help = "Exit with other codes than the defaults, for tools that give codes a\n\
+            meaning of their own. Overrides [exit_codes] in config.toml.\n\
+            \n\
+            \x20 success      0  everything asked for was done\n\
+            \x20 failure      1  nothing was done because of errors\n\
+            \x20 (usage)      2  bad command line; can't be remapped\n\
+            \x20 partial      3  some items were handled, others failed\n\
+            \x20 unsupported  4  not available on this platform\n\
+            \x20 cancelled    5  quit at a prompt\n\
+            \x20 declined     0  every prompt was answered no\n\
+            \x20 not_found       failed: a path or trashed item wasn't there\n\
+            \x20 is_directory    failed: a path was a directory\n\
+            \x20 cross_device    failed: a move would cross file systems\n\
+            \n\
+            The last three exit as failure does unless given codes of their own.\n\
+            \n\
+            e.g. --status-map partial=1,declined=6. Invoked as rm, trache exits\n\
+            0 or 1 like rm does."
+    )]
+    status_map: Option<String>,
+
+    /// Color --trash-list and -v output using LS_COLORS
+    #[arg(
+        long,
+        global = true,
+        value_name = "WHEN",
+        num_args = 0..=1,
+        require_equals = true,
+        default_value = "auto",
+        default_missing_value = "always"
+    )]
+    color: color::ColorMode,
+
+    /// How to show deletion times: iso, long-iso, full-iso, relative or +FORMAT (see --help)
+    #[arg(
+        long,
+        value_name = "STYLE",
+        global = true,
+        value_parser = timestyle::TimeStyle::parse,
+        long_help = "How to show deletion times in listings and prompts:\n\
+            \n\
+            \x20 iso        05-01 14:30 within the last six months, else 2023-11-20\n\
+            \x20 long-iso   2024-05-01 14:30 (the default)\n\
+            \x20 full-iso   2024-05-01 14:30:00.000000000 +0200\n\
+            \x20 relative   3 days ago\n\
+            \x20 +FORMAT    strftime FORMAT, e.g. +%d.%m.%Y\n\
+            \n\
+            The default can be set with `time_style` in config.toml."
+    )]
+    time_style: Option<timestyle::TimeStyle>,
+
+    /// Overwrite files PASSES times (default 3) and delete them, bypassing the trash
+    #[arg(
+        long,
+        value_name = "PASSES",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "3",
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with = "mode",
+        long_help = "Bypass the trash: overwrite each file's contents PASSES times\n\
+            (default 3) with pseudo-random data, then delete it. This cannot be\n\
+            undone.\n\
+            \n\
+            The usual rules still apply: directories need -r (every file inside is\n\
+            shredded) or -d (empty only), and -i/-I/--interactive prompt with\n\
+            \"shred\" instead of \"remove\". Symbolic links are deleted without\n\
+            touching their targets.\n\
+            \n\
+            Overwriting in place is not effective on copy-on-write or journaling\n\
+            file systems, SSDs with wear levelling, or files that have snapshots."
+    )]
+    shred: Option<u32>,
+
+    /// Delete permanently when a file's volume has no usable trash [default: never]
+    #[arg(
+        long,
+        value_name = "WHEN",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "prompt",
+        long_help = "What to do when a file lives on a volume with no usable trash\n\
+            (NFS mounts, containers, FAT sticks without .Trash-UID):\n\
+            \n\
+            \x20 never   report an error (the default)\n\
+            \x20 prompt  ask before deleting it permanently (--rm-fallback alone)\n\
+            \x20 always  delete it permanently without asking\n\
+            \n\
+            The default can be set with `rm_fallback` in config.toml."
+    )]
+    rm_fallback: Option<config::RmFallback>,
+
+    /// Refuse to remove more than N entries, counting directory contents; 0 for no limit
+    #[arg(
+        long = "max-delete",
+        value_name = "N",
+        long_help = "Refuse to remove more than N entries in this run, counting\n\
+            everything inside directories given with -r. Everything is counted\n\
+            before anything is removed, so a run that would go over the limit\n\
+            leaves all files alone. A blast-radius limit for scripts and cron.\n\
+            \n\
+            The default can be set with `max_delete` in config.toml;\n\
+            --max-delete 0 lifts it for one run."
+    )]
+    max_delete: Option<u64>,
+
+    /// Ask before removing any single file or tree bigger than SIZE; 0 for no limit (see --help)
+    #[arg(
+        long = "max-size-no-prompt",
+        value_name = "SIZE",
+        long_help = "Ask before removing any one argument bigger than SIZE (e.g. 100G),\n\
+            counting everything inside directories, even without -i. The\n\
+            prompt shows the size. A guard against trashing a huge dataset by\n\
+            mistake, which can fill the disk the trash lives on. -f doesn't ask.\n\
+            \n\
+            The default can be set with `max_size_no_prompt` in config.toml;\n\
+            --max-size-no-prompt 0 lifts it for one run."
+    )]
+    max_size_no_prompt: Option<String>,
+
+    /// Retry N times when a file is in use by another process (see --help)
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 0,
+        long_help = "When a file can't be trashed because another process is using it\n\
+            (a sharing violation on Windows, a busy mount point or running\n\
+            program on Unix), try again up to N times, waiting --retry-delay\n\
+            between tries, before giving up on it.\n\
+            \n\
+            On Linux, open files can be trashed; set `open_files` in config.toml\n\
+            to \"warn\" or \"prompt\" to hear which processes hold them first."
+    )]
+    retry: u32,
+
+    /// Record what this run trashes under NAME, for --trash-undo-label (see --help)
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with_all = ["mode", "shred"],
+        value_parser = clap::builder::NonEmptyStringValueParser::new(),
+        long_help = "Record every item this run trashes under the label NAME, so\n\
+            --trash-undo-label NAME can restore them all later, together with\n\
+            anything else trashed under the same label. Handy for scripted\n\
+            cleanups:\n\
+            \n\
+            \x20 trache -r --label build-cleanup target/ dist/\n\
+            \x20 trache --trash-undo-label build-cleanup\n\
+            \n\
+            Labels are kept in the state directory (labels.jsonl) and also appear\n\
+            in the audit log."
+    )]
+    label: Option<String>,
+
+    /// Milliseconds to wait between --retry tries
+    #[arg(long, value_name = "MS", default_value_t = 500, requires = "retry")]
+    retry_delay: u64,
+
+    /// Record a hash of each trashed item so restoring with --verify can check it
+    #[arg(
+        long,
+        conflicts_with_all = ["mode", "shred"],
+        long_help = "Record the SHA-256 of each file or directory this run trashes, taken\n\
+            just before it goes, in the state directory (checksums.jsonl). Restoring\n\
+            with --verify then warns about trashed copies that were corrupted or\n\
+            modified while in the trash. checksums = true in the config does this\n\
+            for every run."
+    )]
+    checksum: bool,
+
+    /// With --trash-undo*: warn about items that changed while in the trash
+    #[arg(
+        long,
+        requires = "restore",
+        long_help = "With --trash-undo, --trash-undo-since or --trash-undo-label: hash each\n\
+            matching item in the trash before restoring it and warn when it no\n\
+            longer matches the hash recorded when it was trashed with --checksum\n\
+            (or checksums = true). Items trashed without one are counted but not\n\
+            checked. The restore goes ahead either way."
+    )]
+    verify: bool,
+
+    // --- rm-compatible flags ---
+    /// Remove empty directories
+    #[arg(short = 'd', long = "dir", global = true, overrides_with = "dir")]
+    dir: bool,
+
+    /// Remove directories and their contents recursively
+    #[arg(
+        short = 'r',
+        visible_short_alias = 'R',
+        long,
+        global = true,
+        overrides_with = "recursive"
+    )]
+    recursive: bool,
+
+    /// Prompt before every removal; also prompts during --trash-undo and --trash-purge
+    #[arg(short = 'i', global = true, overrides_with_all = ["force", "prompt_once", "interactive", "prompt_always"])]
+    prompt_always: bool,
+
+    /// Prompt once before removing >3 files or recursively; remember first choice during --trash-undo
+    #[arg(short = 'I', global = true, overrides_with_all = ["force", "prompt_always", "interactive", "prompt_once"])]
+    prompt_once: bool,
+
+    /// Prompt according to WHEN: never, once, or always; also affects --trash-undo (see --help)
+    #[arg(
+        long = "interactive",
+        value_name = "WHEN",
+        global = true,
+        default_missing_value = "always",
+        num_args = 0..=1,
+        overrides_with_all = ["force", "prompt_always", "prompt_once", "interactive"],
+        long_help = "Prompt according to WHEN: never (default), once, or always.\n\n\
+            When trashing files:\n\
+            \x20 always (-i)  prompt before each file\n\
+            \x20 once (-I)    prompt once before >3 files or recursive removal\n\
+            \x20 smart        prompt only for risky removals: directories that are\n\
+            \x20              not empty (with -r), files owned by someone else,\n\
+            \x20              paths outside the current directory, and '/'\n\
+            \x20              lookalikes: top-level directories and your home\n\
+            \x20 never        no prompts (default)\n\
+            The 3 is bulk_prompt_count in config.toml; with bulk_prompt_size\n\
+            (e.g. \"1G\") once also prompts when the files add up to more.\n\
+            \n\
+            When restoring (--trash-undo):\n\
+            \x20 always (-i)  prompt at every conflict (collision or twin group)\n\
+            \x20 once (-I)    prompt on first conflict of each type, remember for the rest\n\
+            \x20 never        restore without prompting; skip items whose path already exists\n\
+            \n\
+            When purging (--trash-purge), once, always or smart lists the\n\
+            matches by number and purges only the ones selected (e.g. 1,3-5).\n\
+            When restoring, smart prompts like always: conflicts are risky.\n\
+            \n\
+            Type ? at any prompt to have its choices and their consequences\n\
+            explained.\n\
+            \n\
+            -f / --force overrides all interactive flags."
+    )]
+    interactive: Option<InteractiveMode>,
+
+    /// Ignore nonexistent files, never prompt
+    #[arg(short = 'f', long, global = true, overrides_with_all = ["prompt_always", "prompt_once", "interactive", "force"])]
+    force: bool,
+
+    /// Explain what is being done
+    #[arg(short = 'v', long, global = true, overrides_with = "verbose")]
+    verbose: bool,
+
+    /// Print totals when done: items trashed and their size, skipped, failed (see --help)
+    #[arg(
+        long,
+        long_help = "When done trashing, print a line of totals to stderr, such as\n\
+            'trashed 42 item(s) (1.3 GiB), skipped 3, failed 1'. Items are\n\
+            measured before they go, which takes a while for big trees.\n\
+            -v prints it too when given more than one FILE."
+    )]
+    summary: bool,
+
+    /// Word messages and prompts as another rm does; 'gnu' is GNU rm (see --help)
+    #[arg(
+        long,
+        value_name = "MODE",
+        long_help = "Word diagnostics and prompts as GNU rm does, for tools that read\n\
+            rm's stderr: 'rm: cannot remove ...', 'rm: remove write-protected\n\
+            regular file ...?'. A non-empty directory is still asked about\n\
+            whole ('remove directory ... recursively?'), as it's trashed as\n\
+            one item. Write-protected files are asked about at a terminal, as\n\
+            rm does, and the exit status is rm's 0 or 1. Always on when\n\
+            trache is run as rm; --compat gnu turns it on under trache's own\n\
+            name, with trache's options still available."
+    )]
+    compat: Option<Compat>,
+
+    /// Do not remove '/'; 'all' also rejects arguments on separate devices
+    #[arg(
+        long = "preserve-root",
+        value_name = "MODE",
+        global = true,
+        default_missing_value = "yes",
+        num_args = 0..=1,
+        overrides_with_all = ["no_preserve_root", "preserve_root"],
+        long_help = "Do not remove '/' (the default). On Windows, drive roots such as C:\\,\n\
+            the Windows folder (%SystemRoot%) and your user profile\n\
+            (%UserProfile%) are refused the same way.\n\
+            'all' also rejects arguments on a separate device from their parent."
+    )]
+    preserve_root: Option<PreserveRoot>,
+
+    /// Do not treat '/' specially
+    #[arg(long = "no-preserve-root", global = true, overrides_with_all = ["preserve_root", "no_preserve_root"])]
+    no_preserve_root: bool,
+
+    /// Skip directories on different file systems
+    #[arg(
+        short = 'x',
+        long = "one-file-system",
+        global = true,
+        overrides_with = "one_file_system"
+    )]
+    one_file_system: bool,
+
+    /// This flag has no effect.  It is kept only for backwards compatibility with BSD.
+    #[arg(short = 'P', hide = true, overrides_with = "_compat_p")]
+    _compat_p: bool,
+
+    /// Unsupported (use --trash-undo instead)
+    #[arg(short = 'W', hide = true, overrides_with = "compat_w")]
+    compat_w: bool,
+
+    /// Trash everything under DIR that MANIFEST doesn't list (see --help)
+    #[arg(
+        long = "sync-with",
+        value_name = "MANIFEST",
+        conflicts_with = "mode",
+        long_help = "Make a directory match an expected set of files: trash everything\n\
+            under the single DIR argument that MANIFEST doesn't list, so the\n\
+            cleanup can be undone. Directories with nothing listed inside are\n\
+            trashed whole; a listed directory is kept with all its contents.\n\
+            \n\
+            MANIFEST has one path per line, or NUL-separated paths (as from\n\
+            `find -print0`) if it contains any NUL byte; \"-\" reads stdin. Paths\n\
+            are relative to DIR, or absolute paths inside it.\n\
+            \n\
+            Example:\n\
+            \x20 git -C site ls-files -z | trache --sync-with - --trash-dry-run site"
+    )]
+    sync_with: Option<PathBuf>,
+
+    /// Trash what under the DIR arguments matches --name, --mtime, --size and --empty (see --help)
+    #[arg(
+        long,
+        conflicts_with_all = ["mode", "sync_with"],
+        long_help = "Walk the DIR arguments like find(1) and trash every entry matching all\n\
+            of the predicates given, with the usual prompts, -v and --trash-dry-run:\n\
+            \n\
+            \x20 --name GLOB   base name matches GLOB, e.g. '*.log'\n\
+            \x20 --mtime N     last modified N whole days ago; +N more, -N fewer\n\
+            \x20 --size N      file size N bytes, or with a unit: 512k, 1G;\n\
+            \x20               +N bigger, -N smaller; never matches directories\n\
+            \x20 --empty       empty file or empty directory\n\
+            \n\
+            Directories are looked inside rather than trashed whole, so only an\n\
+            empty one can match. The DIR arguments themselves are never trashed;\n\
+            symlinks are not followed. Entries that can't be read are reported\n\
+            and passed over, and the run then ends with an error.\n\
+            \n\
+            Example:\n\
+            \x20 trache --find --name '*.log' --mtime +30 --trash-dry-run /var/tmp/app"
+    )]
+    find: bool,
+
+    /// With --find: match entries whose name matches GLOB
+    #[arg(long = "name", value_name = "GLOB", requires = "find")]
+    find_name: Option<String>,
+
+    /// With --find: match entries modified N days ago (+N more, -N fewer)
+    #[arg(
+        long = "mtime",
+        value_name = "N",
+        requires = "find",
+        allow_hyphen_values = true
+    )]
+    find_mtime: Option<String>,
+
+    /// With --find: match files of size N (+N bigger, -N smaller), e.g. +1G
+    #[arg(
+        long = "size",
+        value_name = "N",
+        requires = "find",
+        allow_hyphen_values = true
+    )]
+    find_size: Option<String>,
+
+    /// With --find: match empty files and directories
+    #[arg(long = "empty", requires = "find")]
+    find_empty: bool,
+
+    /// Trash every empty directory under the DIR arguments, deepest first (see --help)
+    #[arg(
+        long = "prune-empty-dirs",
+        conflicts_with_all = ["mode", "sync_with", "find"],
+        long_help = "Trash every directory under the DIR arguments that is empty, or\n\
+            becomes empty once the empty directories inside it are gone. The\n\
+            deepest go first, so each is trashed on its own; a directory whose\n\
+            subdirectory is kept at a -i prompt stays too. The DIR arguments\n\
+            themselves are never trashed. Works with -i, -I, -v and\n\
+            --trash-dry-run."
+    )]
+    prune_empty_dirs: bool,
+
+    /// Files to trash
+    #[arg(required_unless_present = "mode")]
+    files: Vec<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Subcommand spellings of the main modes. Each one is turned into the
+/// equivalent flags by [`Cli::apply_command`], so both styles share one
+/// implementation.
+#[derive(Subcommand)]
+enum Command {
+    /// List items in trash (same as --trash-list)
+    List {
+        /// Only items matching PATTERN, as for --trash-purge
+        #[arg(add = ArgValueCompleter::new(complete_items))]
+        pattern: Option<String>,
+
+        /// Show only items whose contents are identical to another item
+        #[arg(long)]
+        duplicates: bool,
+
+        /// Print each item as TEMPLATE; see --format in `trache --help`
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "duplicates")]
+        format: Option<String>,
+
+        /// Print items as CSV, TSV or JSON lines (see --schema)
+        #[arg(
+            long,
+            value_enum,
+            value_name = "FORMAT",
+            conflicts_with_all = ["duplicates", "format"]
+        )]
+        output: Option<table::OutputFormat>,
+    },
+
+    /// Restore items matching PATTERN from trash (same as --trash-undo)
+    Restore {
+        /// Which items to restore, as for --trash-undo
+        #[arg(add = ArgValueCompleter::new(complete_items))]
+        pattern: String,
+
+        /// Restore into a fresh temp directory, leaving original locations alone
+        #[arg(long = "to-temp")]
+        to_temp: bool,
+
+        /// Restore the single matching item under NAME in its original directory
+        #[arg(long = "restore-as", value_name = "NAME", conflicts_with = "to_temp")]
+        restore_as: Option<OsString>,
+
+        /// Restore only the files matching GLOB from inside trashed directories
+        #[arg(long, value_name = "GLOB", conflicts_with_all = ["to_temp", "restore_as"])]
+        inner: Option<String>,
+
+        /// Restore items that can't go back in place under DIR
+        #[arg(long, value_name = "DIR", num_args = 0..=1, require_equals = true)]
+        rescue: Option<Option<PathBuf>>,
+
+        /// Restore into <original parent>/restored-YYYYMMDD/ instead of in place
+        #[arg(long = "dated-dir", conflicts_with_all = ["to_temp", "restore_as", "inner"])]
+        dated_dir: bool,
+    },
+
+    /// Permanently delete items matching PATTERN from trash (same as --trash-purge)
+    Purge {
+        /// Which items to purge, as for --trash-purge
+        #[arg(add = ArgValueCompleter::new(complete_items))]
+        pattern: String,
+
+        /// Only purge matches trashed from these paths or from inside them
+        paths: Vec<PathBuf>,
+
+        /// Purge duplicate copies among matching items, keeping the newest
+        #[arg(long)]
+        duplicates_keep_newest: bool,
+    },
+
+    /// Empty the entire trash (same as --trash-empty)
+    Empty {
+        /// Only purge items trashed from these paths or from inside them
+        paths: Vec<PathBuf>,
+    },
+
+    /// Move FILES to trash (same as `trache FILES`)
+    Rm {
+        /// Files to trash
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+}
+
+impl Command {
+    fn name(&self) -> &'static str {
+        match self {
+            Command::List { .. } => "list",
+            Command::Restore { .. } => "restore",
+            Command::Purge { .. } => "purge",
+            Command::Empty { .. } => "empty",
+            Command::Rm { .. } => "rm",
+        }
+    }
+}
+
+fn main() {
+    // Reset SIGPIPE to default behavior (terminate silently) so piping to
+    // tools like `head` or `grep` doesn't cause a panic
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+
+    // Under TRACHE_COMPLETE=<shell>, print the shell's completion script or
+    // answer a completion request, and exit
+    CompleteEnv::with_factory(cli_command)
+        .var("TRACHE_COMPLETE")
+        .complete();
+
+    let mut args: Vec<OsString> = std::env::args_os().collect();
+    if args
+        .first()
+        .is_some_and(|argv0| rmcompat::invoked_as_rm(argv0))
+    {
+        rmcompat::enable();
+        args = rmcompat::translate(args);
+    }
+    let matches = cli_command().get_matches_from(args);
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if cli.compat == Some(Compat::Gnu) {
+        rmcompat::enable();
+    }
+    if let Err(e) = cli.apply_command(matches.contains_id("mode")) {
+        eprintln!("trache: {e}");
+        status::exit(Status::Failure);
+    }
+    // --trash-purge and --trash-empty take paths to narrow them down
+    if let Some(id) = given_mode(&matches)
+        && !cli.files.is_empty()
+        && !matches!(id.as_str(), "purge" | "empty")
+    {
+        eprintln!("{}", mode_with_files_error(&id, &cli.files));
+        status::exit(Status::Failure);
+    }
+    if cli.patterns_from.is_some() && cli.undo.is_none() && cli.purge.is_none() {
+        eprintln!("trache: --patterns-from only works with --trash-undo or --trash-purge");
+        status::exit(Status::Failure);
+    }
+    if cli.filter.is_some() && cli.list.is_none() && cli.undo.is_none() && cli.purge.is_none() {
+        eprintln!("trache: --filter only works with --trash-list, --trash-undo or --trash-purge");
+        status::exit(Status::Failure);
+    }
+
+    let config = config::load().unwrap_or_else(|e| {
+        eprintln!("{}: {e}", rmcompat::prog());
+        status::exit(Status::Failure);
+    });
+    if let Err(e) = status::init(&config.exit_codes, cli.status_map.as_deref()) {
+        eprintln!("{}: {e}", rmcompat::prog());
+        status::exit(Status::Failure);
+    }
+    sizing::init(config.size_strategy);
+    answers::init(&config.yes_answers);
+    color::init(cli.color);
+    let time_style = match (&cli.time_style, &config.time_style) {
+        (Some(style), _) => style.clone(),
+        (None, Some(style)) => timestyle::TimeStyle::parse(style).unwrap_or_else(|e| {
+            eprintln!("{}: time_style: {e}", rmcompat::prog());
+            status::exit(Status::Failure);
+        }),
+        (None, None) => timestyle::TimeStyle::default(),
+    };
+    timestyle::init(time_style);
+
+    let sandboxed = cli.sandbox || cli.tutorial;
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    let sandbox = sandboxed.then(|| enter_sandbox(&cli));
+    #[cfg(not(all(unix, not(target_os = "macos"), not(target_os = "ios"))))]
+    if sandboxed {
+        eprintln!("trache: --sandbox is not supported on this platform");
+        status::exit(Status::Unsupported);
+    }
+
+    if !sandboxed && let Some(dir) = cli.trash_dir.as_ref().or(config.trash_dir.as_ref()) {
+        use_trash_dir(dir);
+    }
+    // For tests that mustn't touch any real trash
+    if std::env::var_os("TRACHE_BACKEND").is_some_and(|b| b == "mock") {
+        backend::set(Box::new(backend::Mock::default()));
+    }
+
+    if !sandboxed {
+        if let Err(e) = audit::init(&config.audit) {
+            eprintln!("{}: {e}", rmcompat::prog());
+            status::exit(Status::Failure);
+        }
+        if let Err(e) = report::init(&config.report) {
+            eprintln!("{}: {e}", rmcompat::prog());
+            status::exit(Status::Failure);
+        }
+        hooks::init(&config.hooks);
+    }
+    if cli.notify {
+        notify::init(&config.notify);
+    }
+    if let Some(path) = &cli.transcript {
+        let args: Vec<String> = std::env::args_os()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect();
+        if let Err(e) = transcript::init(path, &cli.transcript_redact, &args) {
+            eprintln!("{}: {e}", rmcompat::prog());
+            status::exit(Status::Failure);
+        }
+    }
+    #[cfg(any(
+        target_os = "windows",
+        all(unix, not(target_os = "macos"), not(target_os = "ios"))
+    ))]
+    if let Err(e) = items::set_ignored_mounts(&config.ignore_mounts) {
+        eprintln!("{}: {e}", rmcompat::prog());
+        status::exit(Status::Failure);
+    }
+
+    if cli.compat_w {
+        eprintln!("trache: -W is not supported; use --trash-undo <pattern> to restore from trash");
+        status::exit(Status::Failure);
+    }
+
+    let dry_run = cli.dry_run;
+    let plan = cli.list.is_none() && cli.output.is_some();
+    if plan {
+        let planned = match cli.destructive_op() {
+            Some("trash files" | "shred files" | "restore items") => true,
+            Some("purge items") => cli.purge.is_some(),
+            _ => false,
+        };
+        if !dry_run || cli.output != Some(table::OutputFormat::Json) || !planned {
+            eprintln!(
+                "trache: --output: needs --trash-list, or --output json with --trash-dry-run \
+                 when trashing, restoring or purging"
+            );
+            status::exit(Status::Failure);
+        }
+        if cli.log_format == LogFormat::Json {
+            eprintln!("trache: --output json and --log-format json cannot be used together");
+            status::exit(Status::Failure);
+        }
+    }
+    let hidden = if cli.no_hidden {
+        false
+    } else {
+        cli.hidden || config.hidden.unwrap_or(true)
+    };
+
+    // The sandbox's own files are fair game, but --batch names its paths
+    // only as it runs, so it's held to read-only mode like anything else.
+    // The services still list, and refuse changes call by call.
+    if !dry_run
+        && (!sandboxed || cli.batch)
+        && !cli.daemon
+        && !cli.serve_stdio
+        && let Some(op) = cli.destructive_op()
+        && let Some(source) = read_only_source(&config)
+    {
+        eprintln!(
+            "{}: refusing to {op}: read-only mode ({source})",
+            rmcompat::prog()
+        );
+        status::exit(Status::Failure);
+    }
+
+    let interactive = if cli.force {
+        InteractiveMode::Never
+    } else if cli.prompt_always {
+        InteractiveMode::Always
+    } else if cli.prompt_once {
+        InteractiveMode::Once
+    } else if let Some(mode) = cli.interactive {
+        mode
+    } else {
+        InteractiveMode::Never
+    };
+
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    if let (Some(sandbox), true) = (&sandbox, cli.tutorial) {
+        if let Err(e) = sandbox::tutorial(sandbox, &mut input) {
+            eprintln!("Error: {e}");
+            status::exit(Status::Failure);
+        }
+        return;
+    }
+
+    let result = if cli.schema {
+        serde_json::to_string_pretty(&schema::document())
+            .map(|text| println!("{text}"))
+            .map_err(Into::into)
+    } else if let Some(ref raw) = cli.list {
+        let mut filter = raw.as_deref().map(|raw| {
+            let parsed = parse_pattern(raw);
+            let matcher = compile_matcher(&parsed, hidden).unwrap_or_else(|e| {
+                eprintln!("trache: {e}");
+                status::exit(Status::Failure);
+            });
+            (matcher, parsed.target)
+        });
+        if let Some(ref expr) = cli.filter {
+            let (matcher, target) = filter.unwrap_or_else(|| {
+                let everything =
+                    compile_matcher(&parse_pattern("full:*"), true).expect("'*' is a valid glob");
+                (everything, PatternTarget::Name)
+            });
+            let matcher = filtered(matcher, expr).unwrap_or_else(|e| {
+                eprintln!("trache: {e}");
+                status::exit(Status::Failure);
+            });
+            filter = Some((matcher, target));
+        }
+        if cli.duplicates {
+            list_duplicates(filter.as_ref())
+        } else {
+            let format = cli.format.as_deref().map(|t| {
+                template::Template::parse(t).unwrap_or_else(|e| {
+                    eprintln!("trache: --format: {e}");
+                    status::exit(Status::Failure);
+                })
+            });
+            let layout = if cli.tree {
+                Some(Layout::Tree)
+            } else {
+                cli.group_by.map(Layout::Grouped)
+            };
+            list_trash(filter.as_ref(), format.as_ref(), cli.output, layout)
+        }
+    } else if cli.dirs {
+        list_trash_dirs()
+    } else if cli.stats {
+        trash_stats(&config.retention)
+    } else if cli.analyze || cli.check {
+        let adopt_dir = config
+            .rescue_dir
+            .clone()
+            .or_else(config::default_rescue_dir);
+        if cli.analyze {
+            analyze_trash(&config.retention, adopt_dir)
+        } else {
+            check_trash(cli.repair, dry_run, adopt_dir)
+        }
+    } else if cli.daemon {
+        serve_daemon(
+            trash_options(&cli, &config, InteractiveMode::Never),
+            read_only_source(&config),
+        )
+    } else if cli.batch {
+        let opts = trash_options(&cli, &config, interactive);
+        run_batch(&mut input, &mut terminal_input(), &opts, hidden)
+    } else if cli.serve_stdio {
+        serve_stdio(
+            &mut input,
+            trash_options(&cli, &config, InteractiveMode::Never),
+            read_only_source(&config),
+        )
+    } else if let Some(action) = cli.resume {
+        interrupt::install();
+        resume_batches(
+            &mut input,
+            action,
+            trash_options(&cli, &config, interactive),
+        )
+    } else if let Some(ref raw) = cli.info {
+        let parsed = parse_pattern(raw);
+        let matcher = compile_matcher(&parsed, hidden).unwrap_or_else(|e| {
+            eprintln!("trache: {e}");
+            status::exit(Status::Failure);
+        });
+        info_items(parsed.pattern, &matcher, parsed.target)
+    } else if let Some(ref raw) = cli.cat {
+        let parsed = parse_pattern(raw);
+        let matcher = compile_matcher(&parsed, hidden).unwrap_or_else(|e| {
+            eprintln!("trache: {e}");
+            status::exit(Status::Failure);
+        });
+        cat_item(parsed.pattern, &matcher, parsed.target, cli.head)
+    } else if let Some(ref raw) = cli.diff {
+        let parsed = parse_pattern(raw);
+        let matcher = compile_matcher(&parsed, hidden).unwrap_or_else(|e| {
+            eprintln!("trache: {e}");
+            status::exit(Status::Failure);
+        });
+        diff_item(parsed.pattern, &matcher, parsed.target)
+    } else if let Some(ref raw) = cli.export {
+        let parsed = parse_pattern(raw);
+        let matcher = compile_matcher(&parsed, hidden).unwrap_or_else(|e| {
+            eprintln!("trache: {e}");
+            status::exit(Status::Failure);
+        });
+        let to = cli.to.as_deref().expect("--trash-export requires --to");
+        export_items(
+            parsed.pattern,
+            &matcher,
+            parsed.target,
+            to,
+            dry_run,
+            cli.force,
+        )
+    } else if let Some(ref archive) = cli.import {
+        import_items(archive, cli.in_place, dry_run)
+    } else if cli.empty {
+        empty_trash(&cli.files, dry_run)
+    } else if let Some(ref raw) = cli.undo {
+        let (matcher, target, pattern) = pattern_matcher(
+            "--trash-undo",
+            raw.as_deref(),
+            cli.patterns_from.as_deref(),
+            cli.filter.as_deref(),
+            hidden,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("trache: {e}");
+            status::exit(Status::Failure);
+        });
+        let to_temp_ttl = match config.to_temp_ttl.as_deref() {
+            Some(ttl) => units::parse_duration(ttl).unwrap_or_else(|e| {
+                eprintln!("trache: to_temp_ttl: {e}");
+                status::exit(Status::Failure);
+            }),
+            None => DEFAULT_TO_TEMP_TTL,
+        };
+        #[cfg(any(
+            target_os = "windows",
+            all(unix, not(target_os = "macos"), not(target_os = "ios"))
+        ))]
+        if let Some(ref dir) = cli.rescue {
+            let dir = dir
+                .clone()
+                .or_else(|| config.rescue_dir.clone())
+                .or_else(config::default_rescue_dir);
+            let Some(dir) = dir else {
+                eprintln!(
+                    "trache: --rescue: no home directory; give a directory with --rescue=DIR"
+                );
+                status::exit(Status::Failure);
+            };
+            rescue::enable(std::path::absolute(&dir).unwrap_or(dir));
+        }
+        let opts = RestoreOptions {
+            dry_run,
+            interactive,
+            to_temp: cli.to_temp,
+            to_temp_ttl,
+            restore_as: cli.restore_as.clone(),
+            inner: cli.inner.clone(),
+            since: None,
+            label: None,
+            dated_dir: cli.dated_dir,
+            plan,
+            verify: cli.verify,
+        };
+        restore_items(&mut input, &pattern, &matcher, target, &opts)
+    } else if let Some(ref when) = cli.undo_since {
+        let since = units::parse_since(when, chrono::Local::now()).unwrap_or_else(|e| {
+            eprintln!("trache: --trash-undo-since: {e}");
+            status::exit(Status::Failure);
+        });
+        let everything =
+            compile_matcher(&parse_pattern("full:*"), true).expect("'*' is a valid glob");
+        let opts = RestoreOptions {
+            dry_run,
+            interactive,
+            to_temp: false,
+            to_temp_ttl: DEFAULT_TO_TEMP_TTL,
+            restore_as: None,
+            inner: None,
+            since: Some(since),
+            label: None,
+            dated_dir: false,
+            plan,
+            verify: cli.verify,
+        };
+        restore_items(&mut input, when, &everything, PatternTarget::Name, &opts)
+    } else if let Some(ref label) = cli.undo_label {
+        let everything =
+            compile_matcher(&parse_pattern("full:*"), true).expect("'*' is a valid glob");
+        let opts = RestoreOptions {
+            dry_run,
+            interactive,
+            to_temp: false,
+            to_temp_ttl: DEFAULT_TO_TEMP_TTL,
+            restore_as: None,
+            inner: None,
+            since: None,
+            label: Some(label.clone()),
+            dated_dir: false,
+            plan,
+            verify: cli.verify,
+        };
+        restore_items(&mut input, label, &everything, PatternTarget::Name, &opts).and_then(|()| {
+            if !dry_run && let Some(file) = labels::file_path() {
+                labels::prune(&file, label, &items::list()?)?;
+            }
+            Ok(())
+        })
+    } else if let Some(ref paths) = cli.rename {
+        rename_items(&paths[0], &paths[1], dry_run)
+    } else if let Some(ref paths) = cli.mv {
+        move_recorded(&paths[0], &paths[1], dry_run)
+    } else if let Some(id) = cli.undo_op {
+        undo_move(id, dry_run)
+    } else if let Some(raw) = cli.pin.as_ref().or(cli.unpin.as_ref()) {
+        let parsed = parse_pattern(raw);
+        let matcher = compile_matcher(&parsed, hidden).unwrap_or_else(|e| {
+            eprintln!("trache: {e}");
+            status::exit(Status::Failure);
+        });
+        pin_items(
+            parsed.pattern,
+            &matcher,
+            parsed.target,
+            cli.pin.is_some(),
+            dry_run,
+        )
+    } else if cli.clean {
+        clean_trash(&config.retention, dry_run, cli.porcelain)
+    } else if let Some(ref raw) = cli.purge {
+        let (matcher, target, pattern) = pattern_matcher(
+            "--trash-purge",
+            raw.as_deref(),
+            cli.patterns_from.as_deref(),
+            cli.filter.as_deref(),
+            hidden,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("trache: {e}");
+            status::exit(Status::Failure);
+        });
+        let opts = PurgeOptions {
+            dry_run,
+            interactive,
+            keep_newest_duplicates: cli.duplicates_keep_newest,
+            keep: cli.keep,
+            scope: &cli.files,
+            plan,
+        };
+        purge_items(&mut input, &pattern, &matcher, target, &opts)
+    } else if let Some(ref indices) = cli.purge_n {
+        purge_by_index(indices, dry_run)
+    } else if let Some(ref dir) = cli.purge_dir {
+        purge_dir(&mut input, dir, dry_run, cli.force)
+    } else {
+        // Entries --find had to pass over, each already reported
+        let mut unreadable = 0;
+        let files = if let Some(ref manifest) = cli.sync_with {
+            sync_targets(manifest, &cli.files).unwrap_or_else(|e| {
+                eprintln!("trache: --sync-with: {e}");
+                status::exit(Status::Failure);
+            })
+        } else if cli.find {
+            let found = find_targets(&cli).unwrap_or_else(|e| {
+                eprintln!("trache: --find: {e}");
+                status::exit(Status::Failure);
+            });
+            for e in &found.errors {
+                eprintln!("trache: --find: {e}");
+            }
+            unreadable = found.errors.len();
+            found.paths
+        } else if cli.prune_empty_dirs {
+            finder::empty_dirs(&cli.files).unwrap_or_else(|e| {
+                eprintln!("trache: --prune-empty-dirs: {e}");
+                status::exit(Status::Failure);
+            })
+        } else {
+            cli.files.clone()
+        };
+
+        let opts = trash_options(&cli, &config, interactive);
+
+        let result = if cli.sync_with.is_some() && files.is_empty() {
+            println!("Nothing to trash: everything is listed in the manifest.");
+            Ok(())
+        } else if cli.find && files.is_empty() {
+            println!("Nothing to trash: no entry matches.");
+            Ok(())
+        } else if cli.prune_empty_dirs && files.is_empty() {
+            println!("Nothing to trash: no empty directories.");
+            Ok(())
+        } else {
+            if !rmcompat::enabled() {
+                interrupt::install();
+            }
+            if let Some(ref label) = cli.label
+                && !dry_run
+                && let Err(e) = labels::init(label)
+            {
+                eprintln!("trache: --label: {e}");
+                status::exit(Status::Failure);
+            }
+            if cli.summary || (cli.verbose && files.len() > 1 && !rmcompat::enabled()) {
+                tally::enable();
+            }
+            let result = trash_files(&mut input, &files, &opts);
+            if let Some(line) = tally::summary() {
+                eprintln!("{}: {line}", rmcompat::prog());
+            }
+            result
+        };
+        if result.is_ok() && unreadable > 0 {
+            Err(TracheError::Incomplete(format!(
+                "--find: {unreadable} unreadable entries were passed over"
+            )))
+        } else {
+            result
+        }
+    };
+    status::finish(result.as_ref().err().map(|e| e.to_string()).as_deref());
+
+    let outcome = status::of(&result);
+    // Quitting at a prompt is its own answer
+    if let Err(e) = result
+        && !matches!(e, TracheError::PromptAborted)
+    {
+        // rm has already named each file it couldn't remove
+        if !rmcompat::enabled() {
+            eprintln!("Error: {e}");
+        } else if !matches!(e, TracheError::NotRemoved(_)) {
+            eprintln!("rm: {e}");
+        }
+    }
+    if status::code(outcome) != 0 {
+        status::exit(outcome);
+    }
+}
+
+/// The options for trashing files, from the command line and config.toml.
+fn trash_options(cli: &Cli, config: &config::Config, interactive: InteractiveMode) -> TrashOptions {
+    let preserve_root = if cli.no_preserve_root {
+        PreserveRoot::No
+    } else if let Some(mode) = cli.preserve_root {
+        mode
+    } else {
+        PreserveRoot::Yes // default
+    };
+
+    TrashOptions {
+        dir: cli.dir || cli.prune_empty_dirs,
+        recursive: cli.recursive || cli.sync_with.is_some() || cli.find,
+        force: cli.force,
+        interactive,
+        verbose: cli.verbose,
+        dry_run: cli.dry_run,
+        preserve_root,
+        one_file_system: cli.one_file_system,
+        log_format: cli.log_format,
+        shred: cli.shred,
+        rm_fallback: cli.rm_fallback.or(config.rm_fallback).unwrap_or_default(),
+        max_delete: cli.max_delete.or(config.max_delete).filter(|&n| n > 0),
+        max_size_no_prompt: max_size_no_prompt(cli, config).unwrap_or_else(|e| {
+            eprintln!("{}: {e}", rmcompat::prog());
+            status::exit(Status::Failure);
+        }),
+        // Like rm: unless -f or --interactive=never, and only when someone
+        // is there to answer
+        prompt_protected: rmcompat::enabled()
+            && !cli.force
+            && cli.interactive != Some(InteractiveMode::Never)
+            && io::stdin().is_terminal(),
+        prune: cli.prune_empty_dirs,
+        bulk: bulk_limits(config).unwrap_or_else(|e| {
+            eprintln!("{}: {e}", rmcompat::prog());
+            status::exit(Status::Failure);
+        }),
+        retry: cli.retry,
+        retry_delay: Duration::from_millis(cli.retry_delay),
+        open_files: config.open_files,
+        plan: cli.list.is_none() && cli.output.is_some(),
+        checksum: cli.checksum || config.checksums,
+    }
+}
+
+/// The entries --sync-with should trash: everything under the one directory
+/// in `files` that `manifest` doesn't list.
+fn sync_targets(manifest: &Path, files: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    let [dir] = files else {
+        return Err("expected exactly one DIR argument".into());
+    };
+    if !dir.is_dir() {
+        return Err(format!("'{}' is not a directory", dir.display()));
+    }
+    let dir = std::path::absolute(dir).map_err(|e| format!("'{}': {e}", dir.display()))?;
+    manifest::Manifest::load(manifest, &dir)?
+        .extras(&dir)
+        .map_err(|e| format!("cannot read '{}': {e}", dir.display()))
+}
+
+/// The entries --find should trash.
+fn find_targets(cli: &Cli) -> Result<finder::Found, String> {
+    finder::Predicates::new(
+        cli.find_name.as_deref(),
+        cli.find_mtime.as_deref(),
+        cli.find_size.as_deref(),
+        cli.find_empty,
+    )?
+    .ok_or("give at least one of --name, --mtime, --size and --empty")?
+    .find(&cli.files)
+}
+
+/// Make `dir` the trash for this run (--trash-dir).
+fn use_trash_dir(dir: &Path) {
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    match backend::Directory::open(dir) {
+        Ok(trash) => backend::set(Box::new(trash)),
+        Err(e) => {
+            eprintln!("trache: cannot use '{}' as the trash: {e}", dir.display());
+            status::exit(Status::Failure);
+        }
+    }
+    #[cfg(not(all(unix, not(target_os = "macos"), not(target_os = "ios"))))]
+    {
+        let _ = dir;
+        eprintln!("trache: --trash-dir is not supported on this platform");
+        status::exit(Status::Unsupported);
+    }
+}
+
+/// Why nothing may be changed, if TRACHE_READONLY or the config says so.
+fn read_only_source(config: &config::Config) -> Option<&'static str> {
+    if config::env_flag("TRACHE_READONLY") {
+        Some("TRACHE_READONLY is set")
+    } else if config.readonly {
+        Some("readonly = true in config")
+    } else {
+        None
+    }
+}
+
+/// Set up --sandbox (or --tutorial), refusing paths that would escape it.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+fn enter_sandbox(cli: &Cli) -> sandbox::Sandbox {
+    let sandbox = sandbox::Sandbox::new();
+    if let Err(e) = sandbox.enter(cli.tutorial) {
+        eprintln!("trache: cannot set up sandbox: {e}");
+        status::exit(Status::Failure);
+    }
+    items::restrict_to_trash_dir(sandbox.trash_dir());
+
+    // Items are restored to their recorded paths, so --trash-rename must
+    // stay inside too
+    let renamed_to = cli.rename.iter().filter_map(|paths| paths.get(1));
+    let moved = cli.mv.iter().flatten();
+    for path in cli.files.iter().chain(renamed_to).chain(moved) {
+        if let Err(e) = sandbox::check(path) {
+            eprintln!("trache: {e}");
+            status::exit(Status::Failure);
+        }
+    }
+    sandbox
+}
+
+/// The command line as clap parses it, with the help text that is built
+/// rather than written out: --trash-undo's ends with the restore prompts'
+/// choices, taken from the tables the prompts use.
+fn cli_command() -> clap::Command {
+    Cli::command().mut_arg("undo", |arg| {
+        let help = arg
+            .get_long_help()
+            .map(|h| h.to_string())
+            .unwrap_or_default();
+        arg.long_help(format!("{help}\n{}", interact::restore_help().trim_end()))
+    })
+}
+
+/// The id of the mode flag given on the command line, if any.
+fn given_mode(matches: &clap::ArgMatches) -> Option<String> {
+    let command = Cli::command();
+    let group = command.get_groups().find(|g| g.get_id() == "mode")?;
+    group
+        .get_args()
+        .find(|id| {
+            matches.value_source(id.as_str()) == Some(clap::parser::ValueSource::CommandLine)
+        })
+        .map(|id| id.to_string())
+}
+
+/// Explain that the mode `id` acts on the trash rather than on `files`, and
+/// point at what was probably meant.
+fn mode_with_files_error(id: &str, files: &[PathBuf]) -> String {
+    let command = Cli::command();
+    let flag = command
+        .get_arguments()
+        .find(|a| a.get_id() == id)
+        .and_then(|a| a.get_long())
+        .map_or_else(|| id.to_string(), |long| format!("--{long}"));
+    let first = files[0].display();
+    let hint = match id {
+        "undo" | "cat" | "diff" | "info" | "export" => format!(
+            "to match items trashed from a path, put it in the pattern: {flag} 'string:path:{first}'"
+        ),
+        "purge_n" => format!("to purge items trashed from a path, use --trash-purge '*' {first}"),
+        _ => format!("to trash the files, leave out {flag}"),
+    };
+    let files = files
+        .iter()
+        .map(|f| format!("'{}'", f.display()))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        "trache: {flag} acts on the trash and doesn't take FILES (got {files}); nothing was done\n\
+         trache: hint: {hint}"
+    )
+}
+
+impl Cli {
+    /// Turn a subcommand into the flags it stands for. `mode_given` is set
+    /// when a --trash-* mode flag was given as well.
+    fn apply_command(&mut self, mode_given: bool) -> Result<(), String> {
+        let Some(command) = self.command.take() else {
+            return Ok(());
+        };
+        let name = command.name();
+        if mode_given || !self.files.is_empty() {
+            return Err(format!(
+                "'{name}' can't be combined with a --trash-* mode or FILES before it"
+            ));
+        }
+        // `rm empty` must never empty the trash just because a file has the
+        // name of a command
+        if Path::new(name).symlink_metadata().is_ok() {
+            return Err(format!(
+                "'{name}' is both a command and a file here; use './{name}' to trash the file"
+            ));
+        }
+        // Nor `trache -f empty` or `trache -rf purge '*'`: with rm's flags
+        // the words are more likely files that didn't exist, so the commands
+        // that change the trash must then be spelled as flags
+        let rm_flags = self.force
+            || self.recursive
+            || self.dir
+            || self.prompt_always
+            || self.prompt_once
+            || self.interactive.is_some();
+        if rm_flags && !matches!(command, Command::List { .. } | Command::Rm { .. }) {
+            return Err(format!(
+                "'{name}' isn't taken as a command together with -f, -r, -d, -i or -I; \
+                 use --trash-{flag} for the command, or './{name}' to trash a file",
+                flag = match command {
+                    Command::Restore { .. } => "undo",
+                    _ => name,
+                }
+            ));
+        }
+
+        match command {
+            Command::List {
+                pattern,
+                duplicates,
+                format,
+                output,
+            } => {
+                self.list = Some(pattern);
+                self.duplicates = duplicates;
+                self.format = format;
+                self.output = output;
+            }
+            Command::Restore {
+                pattern,
+                to_temp,
+                restore_as,
+                inner,
+                rescue,
+                dated_dir,
+            } => {
+                self.undo = Some(Some(pattern));
+                self.to_temp = to_temp;
+                self.restore_as = restore_as;
+                self.inner = inner;
+                self.rescue = rescue;
+                self.dated_dir = dated_dir;
+            }
+            Command::Purge {
+                pattern,
+                paths,
+                duplicates_keep_newest,
+            } => {
+                self.purge = Some(Some(pattern));
+                self.files = paths;
+                self.duplicates_keep_newest = duplicates_keep_newest;
+            }
+            Command::Empty { paths } => {
+                self.empty = true;
+                self.files = paths;
+            }
+            Command::Rm { files } => self.files = files,
+        }
+        Ok(())
+    }
+
+    /// What this invocation would change, or None if it only reads.
+    fn destructive_op(&self) -> Option<&'static str> {
+        if self.list.is_some()
+            || self.dirs
+            || self.stats
+            || self.analyze
+            || (self.check && !self.repair)
+            || self.cat.is_some()
+            || self.diff.is_some()
+            || self.export.is_some()
+            || self.info.is_some()
+            || self.tutorial
+            || self.schema
+            // Pins only change what later purges may touch
+            || self.pin.is_some()
+            || self.unpin.is_some()
+        {
+            None
+        } else if self.empty {
+            Some("empty the trash")
+        } else if self.undo.is_some() || self.undo_since.is_some() || self.undo_label.is_some() {
+            Some("restore items")
+        } else if self.import.is_some() {
+            Some("import items")
+        } else if self.rename.is_some() {
+            Some("rename items")
+        } else if self.mv.is_some() {
+            Some("move files")
+        } else if self.undo_op.is_some() {
+            Some("undo a move")
+        } else if self.clean {
+            Some("clean the trash")
+        } else if self.check {
+            Some("repair the trash")
+        } else if self.resume.is_some() {
+            Some("resume a batch")
+        } else if self.daemon || self.serve_stdio {
+            Some("serve trash operations")
+        } else if self.batch {
+            Some("run batch commands")
+        } else if self.purge.is_some() || self.purge_n.is_some() || self.purge_dir.is_some() {
+            Some("purge items")
+        } else if self.shred.is_some() {
+            Some("shred files")
+        } else {
+            Some("trash files")
+        }
+    }
+}
+
+fn trash_files(
+    input: &mut dyn BufRead,
+    files: &[PathBuf],
+    opts: &TrashOptions,
+) -> Result<(), TracheError> {
+    // Check -x/--one-file-system support on this platform
+    #[cfg(not(unix))]
+    if opts.one_file_system {
+        return Err(TracheError::Unsupported(
+            "--one-file-system is not supported on this platform".into(),
+        ));
+    }
+
+    // Count everything first, so a runaway glob in a cron job stops before
+    // anything is touched
+    if let Some(limit) = opts.max_delete {
+        let mut count = 0;
+        for file in files {
+            count += match file.symlink_metadata() {
+                Ok(meta) if meta.is_dir() && opts.recursive => {
+                    units::count_entries(file, limit.saturating_sub(count))
+                }
+                Ok(_) => 1,
+                Err(_) => 0,
+            };
+            if count > limit {
+                return Err(TracheError::Refused(format!(
+                    "refusing to {} more than {limit} entries (--max-delete); nothing was touched",
+                    opts.verb()
+                )));
+            }
+        }
+    }
+
+    let prog = rmcompat::prog();
+    // The cause of each failure, where it has one
+    let mut failures: Vec<Option<io::ErrorKind>> = Vec::new();
+
+    // -I: prompt once if many or big files, or recursive
+    let bulk_prompt = planner::bulk_prompt(&opts.flags(), &opts.bulk, files.len(), || {
+        files.iter().map(|file| sizing::size(file)).sum()
+    });
+    if let Some(ref msg) = bulk_prompt
+        && !confirm(input, opts, None, msg, assess_risk(files).as_ref())
+    {
+        return Ok(());
+    }
+
+    if !opts.dry_run {
+        let paths: Vec<PathBuf> = files
+            .iter()
+            .filter(|file| file.symlink_metadata().is_ok())
+            .map(|file| std::path::absolute(file).unwrap_or_else(|_| file.clone()))
+            .collect();
+        hooks::pre(hooks::Kind::Trash, &paths)?;
+    }
+    let journaled = !opts.dry_run
+        && opts.shred.is_none()
+        && batch::begin(batch::Kind::Trash, files, opts.recursive)?;
+
+    for file in files {
+        if interrupt::interrupted() {
+            break;
+        }
+        if let Some(e) = planner::refuse_by_name(file) {
+            eprintln!("{prog}: {e}: skipping '{}'", file.display());
+            opts.emit(events::Event::Errored {
+                path: file,
+                error: e.into(),
+            });
+            failures.push(None);
+            continue;
+        }
+
+        // Check preserve-root; rm only guards '/' when recursing
+        if (opts.recursive || !rmcompat::enabled())
+            && let Err(e) = check_preserve_root(file, opts.preserve_root)
+        {
+            for line in e.lines() {
+                eprintln!("{prog}: {line}");
+            }
+            opts.emit(events::Event::Errored {
+                path: file,
+                error: e,
+            });
+            failures.push(None);
+            continue;
+        }
+
+        // Check one-file-system
+        if opts.one_file_system
+            && let Err(e) = check_one_file_system(file)
+        {
+            eprintln!("{prog}: {e}");
+            opts.emit(events::Event::Errored {
+                path: file,
+                error: e,
+            });
+            failures.push(None);
+            continue;
+        }
+
+        // The rest of a read-only mount fails like its first file did
+        if let Some(mount) = readonly::skip(file) {
+            if rmcompat::enabled() {
+                eprintln!(
+                    "{prog}: cannot remove '{}': Read-only file system",
+                    file.display()
+                );
+            }
+            opts.emit(events::Event::Errored {
+                path: file,
+                error: readonly::describe(&mount),
+            });
+            failures.push(None);
+            continue;
+        }
+
+        if let Err(e) = trash_single(input, file, opts, bulk_prompt.is_some())
+            && (!opts.force || file.symlink_metadata().is_ok())
+        {
+            let mount = readonly::detect(file);
+            let shown = match &mount {
+                Some(_) if rmcompat::enabled() => "Read-only file system".to_string(),
+                Some(mount) => readonly::describe(mount),
+                None => rmcompat::describe(&e),
+            };
+            eprintln!("{prog}: cannot remove '{}': {shown}", file.display());
+            opts.emit(events::Event::Errored {
+                path: file,
+                error: mount.map_or_else(|| e.to_string(), |m| readonly::describe(&m)),
+            });
+            failures.push(e.cause());
+        }
+    }
+    if !rmcompat::enabled() {
+        for line in readonly::summary() {
+            eprintln!("{prog}: {line}");
+        }
+    }
+    if interrupt::interrupted() {
+        return Err(TracheError::Interrupted(interrupt::summary()));
+    }
+    if journaled {
+        batch::finish();
+    }
+    #[cfg(any(
+        target_os = "windows",
+        all(unix, not(target_os = "macos"), not(target_os = "ios"))
+    ))]
+    checksum::flush();
+
+    // Failures that all had one cause fail the run with it
+    match failures.split_first() {
+        None => Ok(()),
+        Some((first, rest)) => {
+            let shared = rest.iter().all(|cause| cause == first);
+            Err(TracheError::NotRemoved(first.filter(|_| shared)))
+        }
+    }
+}
+
+/// Examine one FILE argument and carry out the planner's actions for it.
+fn trash_single(
+    input: &mut dyn BufRead,
+    file: &Path,
+    opts: &TrashOptions,
+    bulk_prompted: bool,
+) -> Result<(), TracheError> {
+    let mut error = None;
+    let found = match file.symlink_metadata() {
+        Ok(meta) if meta.is_dir() => planner::Found::Dir {
+            empty: match is_dir_empty(file) {
+                Ok(empty) => Some(empty),
+                Err(e) => {
+                    error = Some(e);
+                    None
+                }
+            },
+        },
+        Ok(meta) if meta.is_symlink() => planner::Found::Symlink,
+        Ok(meta) => planner::Found::File {
+            empty: meta.len() == 0,
+            write_protected: opts.prompt_protected && is_write_protected(file),
+        },
+        Err(e) => {
+            let missing = e.kind() == io::ErrorKind::NotFound;
+            error = Some(e);
+            planner::Found::Unreadable { missing }
+        }
+    };
+
+    let concern = if opts.interactive == InteractiveMode::Smart {
+        smart_concern(file)
+    } else {
+        None
+    };
+    for action in planner::plan(&opts.flags(), file, found, bulk_prompted, concern) {
+        match action {
+            planner::Action::Confirm(prompt) => {
+                let risk = assess_risk(&[file.to_path_buf()]);
+                if !confirm(input, opts, Some(file), &prompt, risk.as_ref()) {
+                    return Ok(());
+                }
+            }
+            planner::Action::Trash | planner::Action::Preview => {
+                let is_dir = matches!(found, planner::Found::Dir { .. });
+                if is_dir && opts.recursive && !confirm_uncommitted(input, file, opts) {
+                    return Ok(());
+                }
+                if !confirm_large(input, file, opts) {
+                    return Ok(());
+                }
+                // Measured while it's still here, for --summary
+                let size = tally::enabled().then(|| sizing::size(file));
+                if is_dir && opts.one_file_system {
+                    trash_one_file_system(input, file, opts)?
+                } else if action == planner::Action::Trash {
+                    trash_path(input, file, opts)?
+                } else {
+                    preview_path(file, opts)
+                }
+                tally::add_bytes(size.unwrap_or(0));
+            }
+            planner::Action::Skip(reason) => {
+                opts.emit(events::Event::Skipped { path: file, reason })
+            }
+            planner::Action::Unreadable => {
+                return Err(error
+                    .expect("planner only fails on examination errors")
+                    .into());
+            }
+            planner::Action::Refuse(kind, e) => return Err(TracheError::io_kind(kind, e.into())),
+        }
+    }
+    Ok(())
+}
+
+/// Warn about checkouts with uncommitted changes in the directory `file`, and
+/// ask whether to remove it all the same. -f removes it without looking; as
+/// rm, only someone at a terminal is asked.
+fn confirm_uncommitted(input: &mut dyn BufRead, file: &Path, opts: &TrashOptions) -> bool {
+    if opts.force || (rmcompat::enabled() && !io::stdin().is_terminal()) {
+        return true;
+    }
+    let repos = vcs::dirty_repos(file);
+    if repos.is_empty() {
+        return true;
+    }
+    let prog = rmcompat::prog();
+    for repo in &repos {
+        eprintln!(
+            "{prog}: warning: '{}' is a {} checkout with uncommitted changes",
+            repo.path.display(),
+            repo.kind.name()
+        );
+    }
+    if opts.dry_run {
+        return true;
+    }
+    let prompt = format!(
+        "{prog}: {} directory '{}' anyway? ",
+        opts.verb(),
+        file.display()
+    );
+    confirm(input, opts, Some(file), &prompt, None)
+}
+
+/// Ask whether to remove `file` if it's bigger than --max-size-no-prompt,
+/// showing how big. -f removes it without measuring.
+fn confirm_large(input: &mut dyn BufRead, file: &Path, opts: &TrashOptions) -> bool {
+    let Some(limit) = opts.max_size_no_prompt else {
+        return true;
+    };
+    if opts.force || opts.dry_run {
+        return true;
+    }
+    let size = sizing::size(file);
+    if size <= limit {
+        return true;
+    }
+    let prompt = format!(
+        "{}: {} '{}', which is {}? ",
+        rmcompat::prog(),
+        opts.verb(),
+        file.display(),
+        units::format_size(size)
+    );
+    confirm(input, opts, Some(file), &prompt, None)
+}
+
+/// Trash (or preview) the directory `file` without what's mounted inside it,
+/// for -x: the other file systems are skipped, and the directories leading
+/// to them kept.
+fn trash_one_file_system(
+    input: &mut dyn BufRead,
+    file: &Path,
+    opts: &TrashOptions,
+) -> Result<(), TracheError> {
+    let each = |input: &mut dyn BufRead, path: &Path| {
+        if opts.dry_run {
+            preview_path(path, opts);
+            Ok(())
+        } else {
+            trash_path(input, path, opts)
+        }
+    };
+    let Some(split) = onefs::split(file)? else {
+        return each(input, file);
+    };
+    let prog = rmcompat::prog();
+    let what = if rmcompat::enabled() {
+        "device"
+    } else {
+        "file system"
+    };
+    for path in &split.foreign {
+        let e = format!(
+            "skipping '{}', since it's on a different {what}",
+            path.display()
+        );
+        eprintln!("{prog}: {e}");
+        opts.emit(events::Event::Skipped {
+            path,
+            reason: "on a different file system",
+        });
+    }
+    let mut failed = false;
+    for path in &split.parts {
+        if let Err(e) = each(input, path) {
+            eprintln!(
+                "{prog}: cannot remove '{}': {}",
+                path.display(),
+                rmcompat::describe(&e)
+            );
+            opts.emit(events::Event::Errored {
+                path,
+                error: e.to_string(),
+            });
+            failed = true;
+        }
+    }
+    if opts.dry_run && !failed {
+        return Ok(());
+    }
+    Err(TracheError::Incomplete(if rmcompat::enabled() {
+        "Directory not empty".into()
+    } else {
+        "other file systems inside it were left in place".into()
+    }))
+}
+
+/// Ask a yes/no question while trashing, reporting it as an event.
+/// A "no" for a specific path also reports that path as skipped.
+fn confirm(
+    input: &mut dyn BufRead,
+    opts: &TrashOptions,
+    path: Option<&Path>,
+    prompt: &str,
+    risk: Option<&risk::Risk>,
+) -> bool {
+    let answer = match risk {
+        Some(risk) => prompt_yes(input, &risk.decorate(prompt)),
+        None => prompt_yes(input, prompt),
+    };
+    opts.emit(events::Event::Prompted {
+        path,
+        prompt: prompt
+            .trim_start_matches(rmcompat::prog())
+            .trim_start_matches(": ")
+            .trim_end(),
+        answer,
+    });
+    if let (false, Some(path)) = (answer, path) {
+        opts.emit(events::Event::Skipped {
+            path,
+            reason: "declined",
+        });
+    }
+    answer
+}
+
+/// Entries a prompt counts before calling the number a lower bound
+const RISK_COUNT_LIMIT: u64 = 100_000;
+
+/// What makes removing `file` risky enough for --interactive=smart to ask,
+/// besides recursing into a directory that isn't empty (the planner's call).
+/// Not being able to tell where it is counts as a reason to ask.
+fn smart_concern(file: &Path) -> Option<&'static str> {
+    const UNKNOWN: &str = "can't tell where it is";
+    // Nothing there to remove; trashing it reports that
+    let meta = file.symlink_metadata().ok()?;
+    // The path itself, not where a symlink points
+    let canonical = match (file.parent(), file.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            parent.canonicalize().map(|parent| parent.join(name))
+        }
+        _ => file.canonicalize(),
+    };
+    let Ok(canonical) = canonical else {
+        return Some(UNKNOWN);
+    };
+    let home = config::home_dir().and_then(|h| h.canonicalize().ok());
+    if home.as_deref() == Some(&*canonical) {
+        return Some("your home directory");
+    }
+    if canonical.parent() == Some(Path::new("/")) {
+        return Some("a top-level directory");
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        // SAFETY: geteuid has no preconditions
+        if meta.uid() != unsafe { libc::geteuid() } {
+            return Some("not owned by you");
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = meta;
+    let Ok(cwd) = std::env::current_dir().and_then(|d| d.canonicalize()) else {
+        return Some(UNKNOWN);
+    };
+    if !canonical.starts_with(&cwd) {
+        return Some("outside the current directory");
+    }
+    None
+}
+
+/// The size above which each argument is asked about, from
+/// --max-size-no-prompt or config.toml; None when unset or 0.
+fn max_size_no_prompt(cli: &Cli, config: &config::Config) -> Result<Option<u64>, String> {
+    let (name, size) = match (&cli.max_size_no_prompt, &config.max_size_no_prompt) {
+        (Some(size), _) => ("--max-size-no-prompt", size),
+        (None, Some(size)) => ("max_size_no_prompt", size),
+        (None, None) => return Ok(None),
+    };
+    let size = units::parse_size(size).map_err(|e| format!("{name}: {e}"))?;
+    Ok(Some(size).filter(|&n| n > 0))
+}
+
+/// -I's thresholds from config.toml, rm's where unset.
+fn bulk_limits(config: &config::Config) -> Result<planner::Bulk, String> {
+    let mut bulk = planner::Bulk::default();
+    if let Some(count) = config.bulk_prompt_count {
+        bulk.count = count;
+    }
+    if let Some(size) = &config.bulk_prompt_size {
+        bulk.size = Some(units::parse_size(size).map_err(|e| format!("bulk_prompt_size: {e}"))?);
+    }
+    Ok(bulk)
+}
+
+/// What removing `paths` involves, for the prompt about them. None when
+/// acting as rm, whose prompts carry nothing extra.
+fn assess_risk(paths: &[PathBuf]) -> Option<risk::Risk> {
+    if rmcompat::enabled() {
+        return None;
+    }
+    let home = config::home_dir().and_then(|h| h.canonicalize().ok());
+    let mut risk = risk::Risk::default();
+    for path in paths {
+        let Ok(meta) = path.symlink_metadata() else {
+            continue;
+        };
+        let canonical = path.canonicalize().ok();
+        if meta.is_file() && is_write_protected(path) {
+            risk.overridden("write-protected");
+        }
+        // Only reachable with --no-preserve-root
+        if canonical.as_deref() == Some(Path::new("/")) {
+            risk.overridden("'/'");
+        }
+        if canonical.is_some() && canonical == home {
+            risk.overridden("home directory");
+        }
+        let limit = RISK_COUNT_LIMIT.saturating_sub(risk.entries);
+        let entries = units::count_entries(path, limit);
+        risk.entries += entries;
+        risk.more |= risk.entries > RISK_COUNT_LIMIT;
+        risk.recursive |= meta.is_dir() && entries > 1;
+        risk.bytes += sizing::size(path);
+    }
+    Some(risk)
+}
+
+/// `path` for messages, colored by what it is (while it still exists).
+fn painted(path: &Path) -> String {
+    let text = path.display().to_string();
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    color::paint(&text, color::kind(path), &name).into_owned()
+}
+
+/// Report what trashing (or shredding) a path would do, for --trash-dry-run.
+fn preview_path(file: &Path, opts: &TrashOptions) {
+    let text = opts.log_format == LogFormat::Text && !opts.plan;
+    let shown = painted(file);
+    if opts.plan {
+        let action = if opts.shred.is_some() {
+            dryrun::Action::Shred
+        } else {
+            dryrun::Action::Trash
+        };
+        dryrun::Step::new(action, file).print();
+    }
+    if opts.shred.is_some() {
+        if text {
+            println!("would shred '{shown}'");
+        }
+        opts.emit(events::Event::Shredded {
+            path: file,
+            dry_run: true,
+        });
+        return;
+    }
+
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    topdir::check(file);
+
+    if text {
+        println!("would trash '{shown}'");
+    }
+    opts.emit(events::Event::Trashed {
+        path: file,
+        dry_run: true,
+    });
+}
+
+/// Move a single path to the trash.
+fn trash_path(
+    input: &mut dyn BufRead,
+    file: &Path,
+    opts: &TrashOptions,
+) -> Result<(), TracheError> {
+    if !opts.force && opts.open_files != config::OpenFiles::Ignore {
+        let holders = inuse::holders(file);
+        if !holders.is_empty() {
+            let prog = rmcompat::prog();
+            let what = format!(
+                "'{}' is open in {}",
+                file.display(),
+                inuse::describe(&holders)
+            );
+            if opts.open_files == config::OpenFiles::Warn {
+                eprintln!("{prog}: warning: {what}");
+            } else {
+                let prompt = format!("{prog}: {what}; {} it anyway? ", opts.verb());
+                if !confirm(input, opts, Some(file), &prompt, None) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    if let Some(passes) = opts.shred {
+        return shred_path(file, passes, opts);
+    }
+
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    topdir::check(file);
+
+    let text = opts.log_format == LogFormat::Text;
+    let shown = painted(file);
+    let size = report_size(file);
+    let is_dir = file.symlink_metadata().is_ok_and(|m| m.is_dir());
+    #[cfg(any(
+        target_os = "windows",
+        all(unix, not(target_os = "macos"), not(target_os = "ios"))
+    ))]
+    let link = fs::read_link(file).ok();
+    let checksum = opts
+        .checksum
+        .then(|| {
+            checksum::hash(file)
+                .map_err(|e| {
+                    eprintln!(
+                        "{}: warning: cannot hash '{}': {e}",
+                        rmcompat::prog(),
+                        file.display()
+                    );
+                })
+                .ok()
+        })
+        .flatten();
+    // Deletion times are whole seconds, so this may be one more than the item's
+    let started = chrono::Local::now().timestamp() - 1;
+    let mut result = backend::get().delete(file);
+    let mut retries = 0;
+    while retries < opts.retry && result.as_ref().is_err_and(inuse::is_busy) {
+        std::thread::sleep(opts.retry_delay);
+        retries += 1;
+        result = backend::get().delete(file);
+    }
+    audit::record(audit::Op::Trash, file, &result);
+    if let Err(e) = &result
+        && inuse::is_busy(e)
+    {
+        let busy = inuse::busy_error(file, retries);
+        return Err(TracheError::io_kind(io::ErrorKind::ResourceBusy, busy));
+    }
+    if let Err(trash::Error::UnsupportedTrashVolume { .. }) = result
+        && opts.rm_fallback != config::RmFallback::Never
+    {
+        return remove_path(input, file, opts);
+    }
+    result?;
+    report::record(audit::Op::Trash, file, size);
+    #[cfg(any(
+        target_os = "windows",
+        all(unix, not(target_os = "macos"), not(target_os = "ios"))
+    ))]
+    if let Some(target) = link
+        && let Err(e) = links::record(file, &target)
+    {
+        eprintln!(
+            "{}: warning: cannot record link target of '{}': {e}",
+            rmcompat::prog(),
+            file.display()
+        );
+    }
+
+    if let Some(sha256) = checksum {
+        checksum::note(file, sha256, started);
+    }
+
+    if opts.verbose && text {
+        match (rmcompat::enabled(), is_dir) {
+            (false, _) => println!("trashed '{shown}'"),
+            (true, false) => println!("removed '{shown}'"),
+            (true, true) => println!("removed directory '{shown}'"),
+        }
+    }
+    opts.emit(events::Event::Trashed {
+        path: file,
+        dry_run: false,
+    });
+    Ok(())
+}
+
+/// Permanently delete a path that couldn't be trashed, for --rm-fallback.
+fn remove_path(
+    input: &mut dyn BufRead,
+    file: &Path,
+    opts: &TrashOptions,
+) -> Result<(), TracheError> {
+    if opts.rm_fallback == config::RmFallback::Prompt {
+        let prompt = format!(
+            "{}: cannot trash '{}' (no usable trash on its volume); delete it permanently? ",
+            rmcompat::prog(),
+            file.display()
+        );
+        let risk = assess_risk(&[file.to_path_buf()]).map(|mut risk| {
+            risk.overridden("permanent");
+            risk
+        });
+        if !confirm(input, opts, Some(file), &prompt, risk.as_ref()) {
+            return Ok(());
+        }
+    }
+
+    let size = report_size(file);
+    let shown = painted(file);
+    let result = if file.symlink_metadata()?.is_dir() {
+        fs::remove_dir_all(file)
+    } else {
+        fs::remove_file(file)
+    };
+    audit::record(audit::Op::Delete, file, &result);
+    result?;
+    report::record(audit::Op::Delete, file, size);
+
+    if opts.verbose && opts.log_format == LogFormat::Text {
+        if rmcompat::enabled() {
+            println!("removed '{shown}'");
+        } else {
+            println!("removed '{shown}' (no usable trash)");
+        }
+    }
+    opts.emit(events::Event::Deleted { path: file });
+    Ok(())
+}
+
+/// Overwrite and delete a path for --shred.
+fn shred_path(file: &Path, passes: u32, opts: &TrashOptions) -> Result<(), TracheError> {
+    let text = opts.log_format == LogFormat::Text;
+    let shown = painted(file);
+    let size = report_size(file);
+    let result = shred::shred_tree(file, passes);
+    audit::record(audit::Op::Shred, file, &result);
+    result?;
+    report::record(audit::Op::Shred, file, size);
+
+    if opts.verbose && text {
+        println!("shredded '{shown}'");
+    }
+    opts.emit(events::Event::Shredded {
+        path: file,
+        dry_run: false,
+    });
+    Ok(())
+}
+
+/// Size of `path` for the report, measured only when reporting is on.
+fn report_size(path: &Path) -> u64 {
+    if report::enabled() {
+        sizing::size(path)
+    } else {
+        0
+    }
+}
+
+fn is_dir_empty(path: &Path) -> io::Result<bool> {
+    Ok(fs::read_dir(path)?.next().is_none())
+}
+
+/// Whether we may not write to `path`, the test rm prompts on.
+#[cfg(unix)]
+fn is_write_protected(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(c_path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: c_path is a valid NUL-terminated string
+    unsafe { libc::access(c_path.as_ptr(), libc::W_OK) != 0 }
+}
+
+#[cfg(not(unix))]
+fn is_write_protected(path: &Path) -> bool {
+    path.metadata().is_ok_and(|m| m.permissions().readonly())
+}
+
+fn check_preserve_root(path: &Path, mode: PreserveRoot) -> Result<(), String> {
+    if mode == PreserveRoot::No {
+        return Ok(());
+    }
+
+    // Normalize the path to check for root
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+    // Check if it's the root directory
+    if canonical == Path::new("/") {
+        return Err("it is dangerous to operate recursively on '/'\n\
+             use --no-preserve-root to override this failsafe"
+            .to_string());
+    }
+    #[cfg(windows)]
+    if let Some(what) = windows_protected(&canonical) {
+        return Err(format!(
+            "it is dangerous to operate recursively on '{}' ({what})\n\
+             use --no-preserve-root to override this failsafe",
+            path.display()
+        ));
+    }
+
+    // For --preserve-root=all, also check if path is on a different device than its parent
+    if mode == PreserveRoot::All
+        && let Err(e) = check_same_device_as_parent(&canonical)
+    {
+        return Err(format!(
+            "'{}' is on a different device from its parent; refusing to operate\n{}",
+            path.display(),
+            e
+        ));
+    }
+
+    Ok(())
+}
+
+/// What makes `canonical` one of Windows' '/'s: a drive or share root, the
+/// Windows folder or the user profile.
+#[cfg(windows)]
+fn windows_protected(canonical: &Path) -> Option<&'static str> {
+    let mut components = canonical.components();
+    if matches!(components.next(), Some(Component::Prefix(_)))
+        && matches!(components.next(), Some(Component::RootDir))
+        && components.next().is_none()
+    {
+        return Some("a drive root");
+    }
+    for (var, what) in [
+        ("SystemRoot", "the Windows folder"),
+        ("USERPROFILE", "your user profile"),
+    ] {
+        // Canonical on both sides, so C:\Windows and \\?\C:\WINDOWS compare equal
+        if let Some(dir) = std::env::var_os(var)
+            && Path::new(&dir)
+                .canonicalize()
+                .is_ok_and(|dir| dir == canonical)
+        {
+            return Some(what);
+        }
+    }
+    None
+}
+
+#[cfg(unix)]
+fn check_same_device_as_parent(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let path_meta = path.symlink_metadata().map_err(|e| e.to_string())?;
+
+    if let Some(parent) = path.parent() {
+        if parent.as_os_str().is_empty() {
+            return Ok(()); // No parent to compare
+        }
+        let parent_meta = parent.symlink_metadata().map_err(|e| e.to_string())?;
+
+        if path_meta.dev() != parent_meta.dev() {
+            return Err("use --no-preserve-root to override this failsafe".to_string());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_same_device_as_parent(_path: &Path) -> Result<(), String> {
+    // On non-Unix platforms, skip the device check
+    Ok(())
+}
+
+#[cfg(unix)]
+fn check_one_file_system(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let path_meta = canonical.symlink_metadata().map_err(|e| e.to_string())?;
+
+    if let Some(parent) = canonical.parent() {
+        if parent.as_os_str().is_empty() {
+            return Ok(()); // No parent to compare
+        }
+        let parent_meta = parent.symlink_metadata().map_err(|e| e.to_string())?;
+
+        if path_meta.dev() != parent_meta.dev() {
+            let what = if rmcompat::enabled() {
+                "device"
+            } else {
+                "file system"
+            };
+            return Err(format!(
+                "skipping '{}', since it's on a different {what}",
+                path.display()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_one_file_system(_path: &Path) -> Result<(), String> {
+    // This shouldn't be called on non-Unix - we error earlier
+    Ok(())
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn list_trash(
+    filter: Option<&(CompiledMatcher, PatternTarget)>,
+    format: Option<&template::Template>,
+    output: Option<table::OutputFormat>,
+    layout: Option<Layout>,
+) -> Result<(), TracheError> {
+    use std::io::Write;
+
+    // Numbered before filtering, so numbers match the full listing. That takes
+    // the whole trash, sorted; past that, items are matched and printed one
+    // at a time rather than gathered first, so output starts straight away
+    let numbered = items::list_numbered()?;
+    let keep =
+        |item: &trash::TrashItem| filter.is_none_or(|(m, target)| item_matches(item, m, *target));
+    let matching = || {
+        numbered
+            .iter()
+            .enumerate()
+            .map(|(i, item)| (i + 1, item))
+            .filter(|(_, item)| keep(item))
+    };
+    let mut out = io::BufWriter::new(io::stdout().lock());
+
+    if let Some(output) = output {
+        if let Some(header) = table::header(output) {
+            writeln!(out, "{header}")?;
+        }
+        for (n, item) in matching() {
+            let listed = table::ListedItem {
+                n,
+                id: items::short_id(item),
+                deleted: DateTime::from_timestamp(item.time_deleted, 0)
+                    .map(|t| t.with_timezone(&Local).to_rfc3339())
+                    .unwrap_or_default(),
+                deleted_unix: item.time_deleted,
+                name: item.name.to_string_lossy().into_owned(),
+                path: item.original_path().to_string_lossy().into_owned(),
+                trash_path: items::item_path(item).to_string_lossy().into_owned(),
+            };
+            writeln!(out, "{}", table::line(output, &listed))?;
+        }
+        out.flush()?;
+        return Ok(());
+    }
+
+    if let Some(format) = format {
+        for (n, item) in matching() {
+            writeln!(out, "{}", render_item(format, n, item))?;
+        }
+        out.flush()?;
+        return Ok(());
+    }
+
+    // The last match sets the width of the number column
+    let Some(last) = (1..=numbered.len()).rev().find(|&n| keep(&numbered[n - 1])) else {
+        if filter.is_some() {
+            println!("No matching items in trash.");
+        } else {
+            println!("Trash is empty.");
+        }
+        return Ok(());
+    };
+    let width = last.to_string().len();
+    match layout {
+        Some(Layout::Grouped(GroupBy::Dir)) => {
+            drop(out);
+            list_grouped_by_dir(&matching().collect::<Vec<_>>(), width);
+            return Ok(());
+        }
+        Some(Layout::Tree) => {
+            let labelled: Vec<(PathBuf, String)> = matching()
+                .map(|(n, item)| {
+                    let label = format!(
+                        "#{n} {} {}",
+                        items::short_id(item),
+                        format_timestamp(item.time_deleted)
+                    );
+                    (item.original_path(), label)
+                })
+                .collect();
+            for line in tree::render(&labelled) {
+                writeln!(out, "{line}")?;
+            }
+            out.flush()?;
+            return Ok(());
+        }
+        None => {}
+    }
+    for (n, item) in matching() {
+        let time = format_timestamp(item.time_deleted);
+        let name = item.name.to_string_lossy();
+        let kind = color::kind(&items::item_path(item));
+        writeln!(
+            out,
+            "{:>width$} {} {} {} {}",
+            n,
+            items::short_id(item),
+            time,
+            color::paint(&name, kind, &name),
+            item.original_path().display()
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// --group-by dir: the listing split by original directory, each group
+/// headed by its directory, item count and total size.
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn list_grouped_by_dir(items: &[(usize, &trash::TrashItem)], width: usize) {
+    let mut groups: std::collections::BTreeMap<&Path, Vec<&(usize, &trash::TrashItem)>> =
+        std::collections::BTreeMap::new();
+    for entry in items {
+        groups
+            .entry(entry.1.original_parent.as_path())
+            .or_default()
+            .push(entry);
+    }
+    for (i, (dir, group)) in groups.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        let size: u64 = group.iter().map(|(_, item)| items::item_size(item)).sum();
+        println!(
+            "{} ({} item(s), {})",
+            dir.display(),
+            group.len(),
+            units::format_size(size)
+        );
+        for (n, item) in group {
+            let name = item.name.to_string_lossy();
+            let kind = color::kind(&items::item_path(item));
+            println!(
+                "  {:>width$} {} {} {}",
+                n,
+                items::short_id(item),
+                format_timestamp(item.time_deleted),
+                color::paint(&name, kind, &name)
+            );
+        }
+    }
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn render_item(format: &template::Template, n: usize, item: &trash::TrashItem) -> String {
+    use template::Field;
+
+    let size = if format.uses(Field::Size) || format.uses(Field::SizeBytes) {
+        items::item_size(item)
+    } else {
+        0
+    };
+    let deleted = DateTime::from_timestamp(item.time_deleted, 0).map(|t| t.with_timezone(&Local));
+    format.render(|field| match field {
+        Field::Index => n.to_string(),
+        Field::Id => items::short_id(item),
+        Field::Name => {
+            let name = item.name.to_string_lossy();
+            let kind = color::kind(&items::item_path(item));
+            color::paint(&name, kind, &name).into_owned()
+        }
+        Field::Path => item.original_path().display().to_string(),
+        Field::Dir => item.original_parent.display().to_string(),
+        Field::TrashPath => items::item_path(item).display().to_string(),
+        Field::Size => units::format_size(size),
+        Field::SizeBytes => size.to_string(),
+        Field::Deleted => format_timestamp(item.time_deleted),
+        Field::DeletedIso => deleted.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        Field::DeletedUnix => item.time_deleted.to_string(),
+    })
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn list_duplicates(filter: Option<&(CompiledMatcher, PatternTarget)>) -> Result<(), TracheError> {
+    let mut items = items::list()?;
+    if let Some((matcher, target)) = filter {
+        items.retain(|item| item_matches(item, matcher, *target));
+    }
+    let groups = dupes::groups(&items);
+    if groups.is_empty() {
+        println!("No duplicates in trash.");
+        return Ok(());
+    }
+
+    for group in &groups {
+        let size = items::item_size(&items[group[0]]);
+        println!(
+            "{} identical copies, {} each:",
+            group.len(),
+            units::format_size(size)
+        );
+        for &i in group {
+            let item = &items[i];
+            println!(
+                "  {} {} {}",
+                format_timestamp(item.time_deleted),
+                item.name.to_string_lossy(),
+                item.original_path().display()
+            );
+        }
+    }
+    println!(
+        "Reclaimable: {} (trache --trash-purge PATTERN --duplicates-keep-newest)",
+        units::format_size(dupes::reclaimable(&items, &groups))
+    );
+    Ok(())
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn list_duplicates(_filter: Option<&(CompiledMatcher, PatternTarget)>) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "Listing trash is not supported on this platform".into(),
+    ))
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn cat_item(
+    pattern: &str,
+    matcher: &CompiledMatcher,
+    target: PatternTarget,
+    head: Option<usize>,
+) -> Result<(), TracheError> {
+    use std::io::Write;
+
+    let matching: Vec<_> = items::list()?
+        .into_iter()
+        .filter(|item| item_matches(item, matcher, target))
+        .collect();
+    let Some(item) = matching.iter().max_by_key(|item| item.time_deleted) else {
+        return Err(TracheError::NoMatch(pattern.to_string()));
+    };
+    if matching.len() > 1 {
+        eprintln!(
+            "trache: {} items match; showing {} deleted {}",
+            matching.len(),
+            item.original_path().display(),
+            format_timestamp(item.time_deleted)
+        );
+    }
+
+    let path = items::item_path(item);
+    if path.is_dir() {
+        let original = item.original_path();
+        let e = format!("'{}' is a directory", original.display());
+        return Err(TracheError::io_kind(io::ErrorKind::IsADirectory, e));
+    }
+    let mut reader = io::BufReader::new(fs::File::open(&path)?);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    match head {
+        Some(n) => {
+            let mut line = Vec::new();
+            for _ in 0..n {
+                line.clear();
+                if reader.read_until(b'\n', &mut line)? == 0 {
+                    break;
+                }
+                out.write_all(&line)?;
+            }
+        }
+        None => {
+            io::copy(&mut reader, &mut out)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn cat_item(
+    _pattern: &str,
+    _matcher: &CompiledMatcher,
+    _target: PatternTarget,
+    _head: Option<usize>,
+) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "Reading trashed items is not supported on this platform".into(),
+    ))
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn diff_item(
+    pattern: &str,
+    matcher: &CompiledMatcher,
+    target: PatternTarget,
+) -> Result<(), TracheError> {
+    use std::io::Write;
+
+    let matching: Vec<_> = items::list()?
+        .into_iter()
+        .filter(|item| item_matches(item, matcher, target))
+        .collect();
+    let Some(item) = matching.iter().max_by_key(|item| item.time_deleted) else {
+        return Err(TracheError::NoMatch(pattern.to_string()));
+    };
+    let original = item.original_path();
+    let deleted = format_timestamp(item.time_deleted);
+    if matching.len() > 1 {
+        eprintln!(
+            "trache: {} items match; comparing {} deleted {deleted}",
+            matching.len(),
+            original.display(),
+        );
+    }
+
+    let trashed = items::item_path(item);
+    if trashed.is_dir() {
+        let e = format!("'{}' is a directory", original.display());
+        return Err(TracheError::io_kind(io::ErrorKind::IsADirectory, e));
+    }
+    let current = match fs::read(&original) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            println!(
+                "Nothing exists at '{}'; restoring it would not collide.",
+                original.display()
+            );
+            return Ok(());
+        }
+        Err(_) if original.is_dir() => {
+            let e = format!("'{}' is now a directory", original.display());
+            return Err(TracheError::io_kind(io::ErrorKind::IsADirectory, e));
+        }
+        Err(e) => return Err(TracheError::io(format!("'{}'", original.display()), e)),
+    };
+    let old = fs::read(&trashed)?;
+    let diff = diff::unified(
+        &old,
+        &current,
+        &format!("{} (trashed {deleted})", original.display()),
+        &original.display().to_string(),
+    );
+    if diff.is_empty() {
+        println!("The trashed copy is identical to '{}'.", original.display());
+        return Ok(());
+    }
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    out.write_all(&diff)?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn export_items(
+    pattern: &str,
+    matcher: &CompiledMatcher,
+    target: PatternTarget,
+    archive: &Path,
+    dry_run: bool,
+    force: bool,
+) -> Result<(), TracheError> {
+    let mut matching: Vec<_> = items::list()?
+        .into_iter()
+        .filter(|item| item_matches(item, matcher, target))
+        .collect();
+    if matching.is_empty() {
+        return Err(TracheError::NoMatch(pattern.to_string()));
+    }
+    if !force && archive.symlink_metadata().is_ok() {
+        let e = format!(
+            "'{}' already exists; use -f to replace it",
+            archive.display()
+        );
+        return Err(TracheError::io_kind(io::ErrorKind::AlreadyExists, e));
+    }
+    matching.sort_by_key(|item| (item.original_path(), item.time_deleted));
+
+    if dry_run {
+        print_items(&matching, "would export");
+        return Ok(());
+    }
+    let packed: Vec<_> = matching
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            (
+                items::item_path(item),
+                export::Entry::new(i + 1, item.original_path(), item.time_deleted),
+            )
+        })
+        .collect();
+    export::write(archive, &packed)
+        .map_err(|e| TracheError::io(format!("cannot write '{}'", archive.display()), e))?;
+    println!(
+        "Exported {} item(s) to {}.",
+        packed.len(),
+        archive.display()
+    );
+    Ok(())
+}
+
+/// Unpack the items of an archive from --trash-export into the trash, or
+/// with `in_place` to their original paths.
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn import_items(archive: &Path, in_place: bool, dry_run: bool) -> Result<(), TracheError> {
+    let invalid = |e: io::Error| TracheError::io(format!("'{}'", archive.display()), e);
+    if dry_run {
+        let prefix = if in_place {
+            "would restore"
+        } else {
+            "would import"
+        };
+        for entry in import::manifest(archive).map_err(invalid)?.items {
+            println!(
+                "{prefix}: {} (deleted {})",
+                entry.original_path.display(),
+                format_timestamp(entry.deleted)
+            );
+        }
+        return Ok(());
+    }
+
+    let target = if in_place {
+        import::Target::InPlace
+    } else {
+        let trash = match backend::get().directory() {
+            Some(dir) => dir.to_path_buf(),
+            None => import::home_trash()
+                .ok_or(TracheError::NoLocation("cannot determine the home trash"))?,
+        };
+        import::Target::Trash(trash)
+    };
+    let mut failed = 0;
+    for outcome in import::import(archive, &target).map_err(invalid)? {
+        let original = &outcome.entry.original_path;
+        if in_place {
+            audit::record(audit::Op::Restore, original, &outcome.result);
+        }
+        match outcome.result {
+            Ok(_) if in_place => println!("Restored: {}", original.display()),
+            Ok(_) => println!("Imported: {}", original.display()),
+            Err(e) => {
+                eprintln!("trache: skipping '{}': {e}", original.display());
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(TracheError::Incomplete(format!(
+            "{failed} item(s) could not be imported"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn import_items(_archive: &Path, _in_place: bool, _dry_run: bool) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "Importing trashed items is not supported on this platform".into(),
+    ))
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn export_items(
+    _pattern: &str,
+    _matcher: &CompiledMatcher,
+    _target: PatternTarget,
+    _archive: &Path,
+    _dry_run: bool,
+    _force: bool,
+) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "Reading trashed items is not supported on this platform".into(),
+    ))
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn diff_item(
+    _pattern: &str,
+    _matcher: &CompiledMatcher,
+    _target: PatternTarget,
+) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "Reading trashed items is not supported on this platform".into(),
+    ))
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn info_items(
+    pattern: &str,
+    matcher: &CompiledMatcher,
+    target: PatternTarget,
+) -> Result<(), TracheError> {
+    let matching: Vec<_> = items::list_numbered()?
+        .into_iter()
+        .filter(|item| item_matches(item, matcher, target))
+        .collect();
+    if matching.is_empty() {
+        return Err(TracheError::NoMatch(pattern.to_string()));
+    }
+    let pinned = pins::pinned_ids()?;
+
+    for (i, item) in matching.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        let path = items::item_path(item);
+        let deleted = DateTime::from_timestamp(item.time_deleted, 0)
+            .map(|t| {
+                t.with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M:%S %z")
+                    .to_string()
+            })
+            .unwrap_or_else(|| item.time_deleted.to_string());
+        println!("ID:        {}", items::short_id(item));
+        println!("Name:      {}", item.name.to_string_lossy());
+        println!("Original:  {}", item.original_path().display());
+        println!("In trash:  {}", path.display());
+        println!("Deleted:   {deleted}");
+        if pinned.contains(&*item.id.to_string_lossy()) {
+            println!("Pinned:    yes");
+        }
+        match path.symlink_metadata() {
+            Ok(meta) => {
+                let size = units::disk_usage(&path);
+                println!("Size:      {} ({size} bytes)", units::format_size(size));
+                let kind = if meta.is_symlink() {
+                    let target = fs::read_link(&path).unwrap_or_default();
+                    format!("symbolic link to {}", target.display())
+                } else if meta.is_dir() {
+                    format!(
+                        "directory, {} entries",
+                        units::count_entries(&path, u64::MAX) - 1
+                    )
+                } else {
+                    "file".to_string()
+                };
+                println!("Type:      {kind}");
+                println!("Mode:      {}", describe_permissions(&meta));
+            }
+            Err(e) => println!("Content:   missing ({e})"),
+        }
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+        match fs::read_to_string(&item.id) {
+            Ok(text) => {
+                println!("{}:", Path::new(&item.id).display());
+                for line in text.lines() {
+                    println!("  {line}");
+                }
+            }
+            Err(e) => println!("Info file: unreadable ({e})"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn info_items(
+    _pattern: &str,
+    _matcher: &CompiledMatcher,
+    _target: PatternTarget,
+) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "Inspecting trashed items is not supported on this platform".into(),
+    ))
+}
+
+/// Permissions as `ls -l` shows them, with the octal mode.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+fn describe_permissions(meta: &fs::Metadata) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = meta.permissions().mode();
+    let mut text = String::with_capacity(9);
+    for (bit, c) in [
+        (0o400, 'r'),
+        (0o200, 'w'),
+        (0o100, 'x'),
+        (0o040, 'r'),
+        (0o020, 'w'),
+        (0o010, 'x'),
+        (0o004, 'r'),
+        (0o002, 'w'),
+        (0o001, 'x'),
+    ] {
+        text.push(if mode & bit != 0 { c } else { '-' });
+    }
+    format!("{text} ({:04o})", mode & 0o7777)
+}
+
+#[cfg(target_os = "windows")]
+fn describe_permissions(meta: &fs::Metadata) -> String {
+    if meta.permissions().readonly() {
+        "read-only".to_string()
+    } else {
+        "read-write".to_string()
+    }
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn list_trash_dirs() -> Result<(), TracheError> {
+    let items = items::list()?;
+
+    let mut dirs: std::collections::BTreeMap<&Path, usize> = std::collections::BTreeMap::new();
+    for item in &items {
+        *dirs.entry(item.original_parent.as_path()).or_default() += 1;
+    }
+    for (dir, count) in dirs {
+        println!("{count}\t{}", dir.display());
+    }
+    Ok(())
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn trash_stats(rules: &config::Retention) -> Result<(), TracheError> {
+    struct VolumeStats {
+        count: usize,
+        size: u64,
+        oldest: i64,
+    }
+
+    let items = items::list()?;
+    if items.is_empty() {
+        println!("Trash is empty.");
+        return Ok(());
+    }
+
+    let mut volumes: std::collections::BTreeMap<PathBuf, VolumeStats> =
+        std::collections::BTreeMap::new();
+    for item in &items {
+        let stats = volumes
+            .entry(items::trash_dir(item))
+            .or_insert(VolumeStats {
+                count: 0,
+                size: 0,
+                oldest: i64::MAX,
+            });
+        stats.count += 1;
+        stats.size += items::item_size(item);
+        stats.oldest = stats.oldest.min(item.time_deleted);
+    }
+
+    let mounts: Vec<&Path> = rules.mounts.iter().map(|m| m.path.as_path()).collect();
+    let mut total_size = 0;
+    for (dir, stats) in &volumes {
+        total_size += stats.size;
+        println!(
+            "{}: {} item(s), {}, oldest {}",
+            dir.display(),
+            stats.count,
+            units::format_size(stats.size),
+            format_timestamp(stats.oldest)
+        );
+        let policy = retention::mount_policy(&mounts, dir);
+        println!("  retention: {}", retention::describe_policy(rules, policy));
+    }
+    println!(
+        "Total: {} item(s), {}",
+        items.len(),
+        units::format_size(total_size)
+    );
+    Ok(())
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn trash_stats(_rules: &config::Retention) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "Trash statistics are not supported on this platform".into(),
+    ))
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+#[cfg_attr(target_os = "windows", allow(unused_variables))]
+fn analyze_trash(rules: &config::Retention, adopt_dir: Option<PathBuf>) -> Result<(), TracheError> {
+    use analyze::shell_quote;
+
+    let all = items::list()?;
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    let trash_dirs = items::trash_dirs(&all);
+    let pinned = pins::pinned_ids()?;
+    let items: Vec<_> = all
+        .into_iter()
+        .filter(|item| !pinned.contains(&*item.id.to_string_lossy()))
+        .collect();
+    // Items already counted, so overlapping findings aren't added twice
+    let mut counted = std::collections::HashSet::new();
+    let mut total = 0;
+    let mut found = false;
+
+    let groups = dupes::groups(&items);
+    let copies: Vec<usize> = groups
+        .iter()
+        .flat_map(|group| group.iter().skip(1).copied())
+        .collect();
+    if !copies.is_empty() {
+        let size = dupes::reclaimable(&items, &groups);
+        println!(
+            "Duplicates: {} older copies of {} item(s), {}",
+            copies.len(),
+            groups.len(),
+            units::format_size(size)
+        );
+        println!("  trache --trash-purge --duplicates-keep-newest '*'");
+        counted.extend(copies);
+        total += size;
+        found = true;
+    }
+
+    let cutoff = (Local::now() - chrono::Duration::days(analyze::COLD_DAYS))
+        .format("%Y-%m-%d")
+        .to_string();
+    let pattern = format!("deleted:<{cutoff}:*");
+    let before = units::DateFilter::parse(&format!("<{cutoff}")).map_err(TracheError::Invalid)?;
+    let cold: Vec<usize> = (0..items.len())
+        .filter(|&i| before.accepts(items[i].time_deleted))
+        .collect();
+    if !cold.is_empty() {
+        let size: u64 = cold.iter().map(|&i| items::item_size(&items[i])).sum();
+        println!(
+            "Cold items: {} trashed before {cutoff}, {}",
+            cold.len(),
+            units::format_size(size)
+        );
+        println!("  trache --trash-purge {}", shell_quote(&pattern));
+        if rules.max_age.is_none() {
+            println!(
+                "  or keep it that way: max_age = \"{}d\" under [retention] in config.toml, then trache --trash-clean",
+                analyze::COLD_DAYS
+            );
+        }
+        total += cold
+            .iter()
+            .filter(|&&i| counted.insert(i))
+            .map(|&i| items::item_size(&items[i]))
+            .sum::<u64>();
+        found = true;
+    }
+
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    {
+        for dir in trash_dirs {
+            let orphans = analyze::orphans(&dir)
+                .map_err(|e| TracheError::io(format!("cannot read '{}'", dir.display()), e))?;
+            if !orphans.contents.is_empty() {
+                let size: u64 = orphans.contents.iter().map(|p| units::disk_usage(p)).sum();
+                println!(
+                    "Orphaned contents in '{}': {} path(s) with no .trashinfo record, {}",
+                    dir.display(),
+                    orphans.contents.len(),
+                    units::format_size(size)
+                );
+                for path in &orphans.contents {
+                    println!("  {}", path.display());
+                }
+                // --repair records them as trashed from the rescue directory,
+                // after which they can be purged like any other item
+                println!("  trache --trash-check --repair");
+                let adopted = orphans
+                    .contents
+                    .iter()
+                    .filter_map(|path| Some(adopt_dir.as_ref()?.join(path.file_name()?)));
+                for original in adopted {
+                    let pattern = format!("path:string:full:{}", original.display());
+                    println!("  trache --trash-purge {}", shell_quote(&pattern));
+                }
+                total += size;
+                found = true;
+            }
+            if !orphans.records.is_empty() {
+                println!(
+                    "Orphaned records in '{}': {} .trashinfo with no contents",
+                    dir.display(),
+                    orphans.records.len()
+                );
+                for path in &orphans.records {
+                    println!("  {}", path.display());
+                }
+                println!("  trache --trash-check --repair");
+                found = true;
+            }
+        }
+    }
+
+    if found {
+        println!("Reclaimable: {}", units::format_size(total));
+    } else {
+        println!("No wasted space found.");
+    }
+    Ok(())
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn analyze_trash(
+    _rules: &config::Retention,
+    _adopt_dir: Option<PathBuf>,
+) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "Trash analysis is not supported on this platform".into(),
+    ))
+}
+
+/// --trash-check: report what's broken in each trash directory, items in it
+/// or not, and with `repair` fix it. Orphaned contents are recorded as
+/// trashed from `adopt_dir`.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+fn check_trash(repair: bool, dry_run: bool, adopt_dir: Option<PathBuf>) -> Result<(), TracheError> {
+    // Broken records are skipped when listing, so the rest still count. If
+    // even that fails, the trash directories that exist are still checked
+    let (dirs, unlisted) = match items::list() {
+        Ok(items) => (items::trash_dirs(&items), None),
+        Err(e) => {
+            let dirs = items::trash_dirs(&[]);
+            eprintln!(
+                "trache: warning: cannot list the trash, so only {} is checked",
+                dirs.iter()
+                    .map(|dir| format!("'{}'", dir.display()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            (dirs, Some(e))
+        }
+    };
+    let adopt_dir = adopt_dir.ok_or(TracheError::NoLocation(
+        "no home directory for the rescue directory; set rescue_dir",
+    ))?;
+
+    let mut found = 0;
+    let mut left = 0;
+    for dir in dirs {
+        let problems = check::scan(&dir)
+            .map_err(|e| TracheError::io(format!("cannot read '{}'", dir.display()), e))?;
+        if problems.is_empty() {
+            continue;
+        }
+        println!("Problems in '{}':", dir.display());
+        for problem in problems {
+            found += 1;
+            println!("  {problem}");
+            let Some(fix) = check::plan(&problem, &adopt_dir) else {
+                left += 1;
+                continue;
+            };
+            if !repair {
+                left += 1;
+            } else if dry_run {
+                println!("    would {fix}");
+            } else {
+                match check::apply(&dir, &problem, &fix) {
+                    Ok(()) => println!("    {fix}: done"),
+                    Err(e) => {
+                        println!("    {fix}: {e}");
+                        left += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    if found == 0 && unlisted.is_none() {
+        println!("No problems found.");
+    } else if left > 0 && !repair {
+        return Err(TracheError::Incomplete(format!(
+            "{found} problem(s) found; --repair fixes what it can"
+        )));
+    } else if left > 0 {
+        return Err(TracheError::Incomplete(format!(
+            "{left} of {found} problem(s) could not be repaired"
+        )));
+    }
+    // What was checked is reported; the listing's failure still counts
+    unlisted.map_or(Ok(()), |e| Err(e.into()))
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"), not(target_os = "ios"))))]
+fn check_trash(
+    _repair: bool,
+    _dry_run: bool,
+    _adopt_dir: Option<PathBuf>,
+) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "Checking the trash is only supported on Linux/BSD".into(),
+    ))
+}
+
+/// --batch: run the commands in `commands`, one per line, asking any
+/// questions on `prompts`. `opts` hold the flags given with --batch, which
+/// restoring and purging take their dry run and interactivity from.
+fn run_batch(
+    commands: &mut dyn BufRead,
+    prompts: &mut dyn BufRead,
+    opts: &TrashOptions,
+    hidden: bool,
+) -> Result<(), TracheError> {
+    let mut ran = 0;
+    let mut failed = 0;
+    for (n, line) in commands.lines().enumerate() {
+        let line = line?;
+        let line = line.trim_end_matches('\r').trim_start();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        ran += 1;
+        let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+        match run_batch_command(prompts, command, arg, opts, hidden) {
+            Ok(()) => {}
+            // Quitting or Ctrl-C stops the batch, not just the command
+            Err(e @ (TracheError::PromptAborted | TracheError::Interrupted(_))) => return Err(e),
+            Err(e) => {
+                eprintln!("trache: line {}: {e}", n + 1);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(TracheError::Incomplete(format!(
+            "{failed} of {ran} batch command(s) failed"
+        )));
+    }
+    println!("Ran {ran} batch command(s).");
+    Ok(())
+}
+
+/// One line of --batch input, split into its command word and argument.
+fn run_batch_command(
+    prompts: &mut dyn BufRead,
+    command: &str,
+    arg: &str,
+    opts: &TrashOptions,
+    hidden: bool,
+) -> Result<(), TracheError> {
+    if arg.is_empty() {
+        return Err(TracheError::Invalid(match command {
+            "trash" => "trash needs a PATH".into(),
+            "undo" | "purge" => format!("{command} needs a PATTERN"),
+            _ => format!("unknown command '{command}'"),
+        }));
+    }
+    let compile = || {
+        let parsed = parse_pattern(arg);
+        compile_matcher(&parsed, hidden)
+            .map(|matcher| (matcher, parsed.target, parsed.pattern))
+            .map_err(TracheError::Invalid)
+    };
+    match command {
+        "trash" => {
+            #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+            sandbox::check(Path::new(arg)).map_err(TracheError::Refused)?;
+            trash_files(prompts, &[PathBuf::from(arg)], opts)
+        }
+        "undo" => {
+            let (matcher, target, pattern) = compile()?;
+            let restore = RestoreOptions {
+                dry_run: opts.dry_run,
+                interactive: opts.interactive,
+                to_temp: false,
+                to_temp_ttl: DEFAULT_TO_TEMP_TTL,
+                restore_as: None,
+                inner: None,
+                since: None,
+                label: None,
+                dated_dir: false,
+                plan: false,
+                verify: false,
+            };
+            restore_items(prompts, pattern, &matcher, target, &restore)
+        }
+        "purge" => {
+            let (matcher, target, pattern) = compile()?;
+            let purge = PurgeOptions {
+                dry_run: opts.dry_run,
+                interactive: opts.interactive,
+                keep_newest_duplicates: false,
+                keep: None,
+                scope: &[],
+                plan: false,
+            };
+            purge_items(prompts, pattern, &matcher, target, &purge)
+        }
+        _ => Err(TracheError::Invalid(format!("unknown command '{command}'"))),
+    }
+}
+
+/// Where --batch answers prompts, its stdin being taken: the terminal if
+/// there is one, or else nothing, which declines every prompt.
+fn terminal_input() -> Box<dyn BufRead> {
+    let terminal = if cfg!(windows) { "CONIN$" } else { "/dev/tty" };
+    match fs::File::open(terminal) {
+        Ok(file) => Box::new(io::BufReader::new(file)),
+        Err(_) => Box::new(io::empty()),
+    }
+}
+
+/// --daemon: serve trash operations on the session bus until stopped.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+fn serve_daemon(opts: TrashOptions, read_only: Option<&str>) -> Result<(), TracheError> {
+    daemon::serve(ServiceBroker::service(opts, read_only))
+        .map_err(|e| TracheError::Other(format!("cannot serve on the session bus: {e}")))
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"), not(target_os = "ios"))))]
+fn serve_daemon(_opts: TrashOptions, _read_only: Option<&str>) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "--daemon is only supported on Linux/BSD".into(),
+    ))
+}
+
+/// --serve-stdio: answer JSON-RPC requests from `input` until it ends.
+/// Responses get stdout to themselves; what trache would otherwise print
+/// there goes to stderr.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+fn serve_stdio(
+    input: &mut dyn BufRead,
+    opts: TrashOptions,
+    read_only: Option<&str>,
+) -> Result<(), TracheError> {
+    use std::os::fd::AsFd;
+
+    let mut responses = fs::File::from(io::stdout().as_fd().try_clone_to_owned()?);
+    // SAFETY: dup2 on the standard descriptors touches no Rust-owned memory
+    if unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    rpc::serve(
+        &ServiceBroker::service(opts, read_only),
+        input,
+        &mut responses,
+    )?;
+    Ok(())
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"), not(target_os = "ios"))))]
+fn serve_stdio(
+    _input: &mut dyn BufRead,
+    _opts: TrashOptions,
+    _read_only: Option<&str>,
+) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "--serve-stdio is only supported on Linux/BSD".into(),
+    ))
+}
+
+/// The command line's ways of trashing, restoring and purging, for --daemon
+/// and --serve-stdio
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+struct ServiceBroker {
+    opts: TrashOptions,
+}
+
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+impl ServiceBroker {
+    /// The service, trashing paths with `opts` as -rf would have them, or
+    /// changing nothing when `read_only` says why not.
+    fn service(opts: TrashOptions, read_only: Option<&str>) -> service::Service {
+        let opts = TrashOptions {
+            force: true,
+            recursive: true,
+            dir: true,
+            ..opts
+        };
+        let service = service::Service::new(Box::new(ServiceBroker { opts }));
+        match read_only {
+            Some(why) => service.read_only(why),
+            None => service,
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+impl service::Broker for ServiceBroker {
+    fn trash(&self, path: &Path) -> Result<(), String> {
+        // -f would pass over a missing path without a word
+        path.symlink_metadata().map_err(|e| e.to_string())?;
+        if let Some(e) = planner::refuse_by_name(path) {
+            return Err(e.to_string());
+        }
+        check_preserve_root(path, self.opts.preserve_root)?;
+        hooks::pre(hooks::Kind::Trash, &[path.to_path_buf()]).map_err(|e| e.to_string())?;
+        trash_single(&mut io::empty(), path, &self.opts, false).map_err(|e| e.to_string())
+    }
+
+    fn list(&self) -> Result<Vec<trash::TrashItem>, String> {
+        items::list_numbered().map_err(|e| e.to_string())
+    }
+
+    fn restore(&self, item: trash::TrashItem) -> Result<PathBuf, String> {
+        let original = item.original_path();
+        if original.symlink_metadata().is_err() {
+            restore_audited(vec![item]).map_err(|e| e.to_string())?;
+            return Ok(original);
+        }
+        let _names = lock::names(lock::Names::Untrash);
+        let target = untrash_name(&original, find_untrash_range(&original, 1));
+        restore_one_as(item, &target).map_err(|e| e.to_string())?;
+        Ok(target)
+    }
+
+    fn purge(&self, item: trash::TrashItem) -> Result<(), String> {
+        let mut kept = unpinned(vec![item]).map_err(|e| e.to_string())?;
+        let Some(item) = kept.pop() else {
+            return Err("the item is pinned".to_string());
+        };
+        purge_audited([item], audit::Op::Purge).map_err(|e| e.to_string())
+    }
+
+    fn size(&self, item: &trash::TrashItem) -> u64 {
+        items::item_size(item)
+    }
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+/// --resume: finish or undo each batch a killed or interrupted run left
+/// behind, oldest first. A batch's journal goes once it has been dealt with.
+fn resume_batches(
+    input: &mut dyn BufRead,
+    action: ResumeAction,
+    mut opts: TrashOptions,
+) -> Result<(), TracheError> {
+    let journals = batch::unfinished()?;
+    if journals.is_empty() {
+        println!("Nothing to resume.");
+        return Ok(());
+    }
+    let recursive = opts.recursive;
+    for journal in journals {
+        let started = format_timestamp(journal.time);
+        match (journal.kind, action) {
+            (batch::Kind::Trash, ResumeAction::Continue) => {
+                let rest: Vec<PathBuf> = journal
+                    .remaining()
+                    .into_iter()
+                    .filter(|path| path.symlink_metadata().is_ok())
+                    .collect();
+                println!(
+                    "Trashing the {} item(s) left by the batch of {started}",
+                    rest.len()
+                );
+                opts.recursive = recursive || journal.recursive;
+                if opts.dry_run {
+                    trash_files(input, &rest, &opts)?;
+                    continue;
+                }
+                batch::adopt(&journal)?;
+                let result = trash_files(input, &rest, &opts);
+                if interrupt::interrupted() {
+                    return result;
+                }
+                batch::finish();
+                result?;
+            }
+            (batch::Kind::Trash, ResumeAction::Rollback) => {
+                let items = newest_items(|item| {
+                    journal.done.contains(&item.original_path())
+                        && item.time_deleted >= journal.time
+                })?;
+                println!(
+                    "Restoring the {} item(s) trashed by the batch of {started}",
+                    items.len()
+                );
+                if opts.dry_run {
+                    print_items(&items, "would restore");
+                    continue;
+                }
+                restore_audited(items)?;
+            }
+            (batch::Kind::Restore, ResumeAction::Continue) => {
+                let items = newest_items(|item| {
+                    let path = item.original_path();
+                    journal.paths.contains(&path)
+                        && item.time_deleted <= journal.time
+                        && path.symlink_metadata().is_err()
+                })?;
+                println!(
+                    "Restoring the {} item(s) left by the batch of {started}",
+                    items.len()
+                );
+                if opts.dry_run {
+                    print_items(&items, "would restore");
+                    continue;
+                }
+                restore_audited(items)?;
+            }
+            (batch::Kind::Restore, ResumeAction::Rollback) => {
+                // Restored: back in place, and no longer in the trash
+                let still_trashed: Vec<PathBuf> = items::list()?
+                    .iter()
+                    .filter(|item| item.time_deleted <= journal.time)
+                    .map(|item| item.original_path())
+                    .collect();
+                let restored: Vec<PathBuf> = journal
+                    .paths
+                    .iter()
+                    .filter(|path| path.symlink_metadata().is_ok() && !still_trashed.contains(path))
+                    .cloned()
+                    .collect();
+                println!(
+                    "Trashing again the {} item(s) restored by the batch of {started}",
+                    restored.len()
+                );
+                opts.recursive = true;
+                trash_files(input, &restored, &opts)?;
+                if opts.dry_run {
+                    continue;
+                }
+            }
+        }
+        batch::discard(&journal)?;
+    }
+    Ok(())
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+/// The newest item in the trash for each original path that `wanted` accepts.
+fn newest_items(
+    wanted: impl Fn(&trash::TrashItem) -> bool,
+) -> Result<Vec<trash::TrashItem>, trash::Error> {
+    let mut newest: std::collections::BTreeMap<PathBuf, trash::TrashItem> =
+        std::collections::BTreeMap::new();
+    for item in items::list()?.into_iter().filter(|item| wanted(item)) {
+        let path = item.original_path();
+        if newest
+            .get(&path)
+            .is_none_or(|kept| kept.time_deleted < item.time_deleted)
+        {
+            newest.insert(path, item);
+        }
+    }
+    Ok(newest.into_values().collect())
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn resume_batches(
+    _input: &mut dyn BufRead,
+    _action: ResumeAction,
+    _opts: TrashOptions,
+) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "--resume is not supported on this platform".into(),
+    ))
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn list_trash(
+    _filter: Option<&(CompiledMatcher, PatternTarget)>,
+    _format: Option<&template::Template>,
+    _output: Option<table::OutputFormat>,
+    _layout: Option<Layout>,
+) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "Listing trash is not supported on this platform".into(),
+    ))
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn list_trash_dirs() -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "Listing trash is not supported on this platform".into(),
+    ))
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn format_timestamp(time_deleted: i64) -> String {
+    timestyle::format(time_deleted)
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+/// Build a map of original_path -> count for duplicate detection.
+fn path_counts(items: &[trash::TrashItem]) -> std::collections::HashMap<PathBuf, usize> {
+    let mut counts = std::collections::HashMap::new();
+    for item in items {
+        *counts.entry(item.original_path()).or_insert(0) += 1;
+    }
+    counts
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+/// Print each item with disambiguation when multiple items share the same original path.
+fn print_items(items: &[trash::TrashItem], prefix: &str) {
+    let counts = path_counts(items);
+    let mut seen: std::collections::HashMap<PathBuf, usize> = std::collections::HashMap::new();
+
+    for item in items {
+        let path = item.original_path();
+        let total = counts[&path];
+        if total > 1 {
+            let idx = seen.entry(path.clone()).or_insert(0);
+            *idx += 1;
+            let ts = format_timestamp(item.time_deleted);
+            println!("{prefix} ({}/{total}, {ts}): {}", *idx, path.display());
+        } else {
+            println!("{prefix}: {}", path.display());
+        }
+    }
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn item_matches(item: &trash::TrashItem, matcher: &CompiledMatcher, target: PatternTarget) -> bool {
+    let haystack = || match target {
+        PatternTarget::Name => item.name.to_string_lossy().into_owned(),
+        PatternTarget::Path => item.original_path().to_string_lossy().into_owned(),
+        PatternTarget::Dir => item.original_parent.to_string_lossy().into_owned(),
+        PatternTarget::Id => items::short_id(item),
+    };
+    // Sizes are only measured for items that match otherwise
+    match matcher {
+        CompiledMatcher::Text(text) => text.is_match(&haystack()),
+        CompiledMatcher::Sized(inner, filter) => {
+            item_matches(item, inner, target) && filter.accepts(items::item_size(item))
+        }
+        CompiledMatcher::Dated(inner, filter) => {
+            item_matches(item, inner, target) && filter.accepts(item.time_deleted)
+        }
+        CompiledMatcher::Typed(inner, kind) => {
+            item_matches(item, inner, target)
+                && items::item_path(item)
+                    .symlink_metadata()
+                    .is_ok_and(|meta| ItemKind::of(&meta) == *kind)
+        }
+        CompiledMatcher::Not(inner) => !item_matches(item, inner, target),
+        CompiledMatcher::Visible(inner) => {
+            !has_hidden_component(&haystack()) && item_matches(item, inner, target)
+        }
+        CompiledMatcher::Any(matchers) => matchers.iter().any(|(m, t)| item_matches(item, m, *t)),
+        CompiledMatcher::Filtered(inner, expr, now) => {
+            item_matches(item, inner, target) && expr.accepts(&ItemFacts(item), *now)
+        }
+    }
+}
+
+/// A trash item as a --filter expression sees it
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+struct ItemFacts<'a>(&'a trash::TrashItem);
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+impl filter::Facts for ItemFacts<'_> {
+    fn name(&self) -> String {
+        self.0.name.to_string_lossy().into_owned()
+    }
+
+    fn path(&self) -> String {
+        self.0.original_path().to_string_lossy().into_owned()
+    }
+
+    fn dir(&self) -> String {
+        self.0.original_parent.to_string_lossy().into_owned()
+    }
+
+    fn id(&self) -> String {
+        items::short_id(self.0)
+    }
+
+    fn size(&self) -> u64 {
+        items::item_size(self.0)
+    }
+
+    fn deleted(&self) -> i64 {
+        self.0.time_deleted
+    }
+
+    fn kind(&self) -> Option<&'static str> {
+        let meta = items::item_path(self.0).symlink_metadata().ok()?;
+        Some(ItemKind::of(&meta).name())
+    }
+}
+
+/// Completions for a PATTERN argument: what's in the trash that starts with
+/// `current`, after any prefixes it has. Names are offered by default, and
+/// original paths, parent directories or IDs after path:, dir: or id:.
+/// Newest first, and nothing if the trash can't be listed.
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn complete_items(current: &std::ffi::OsStr) -> Vec<CompletionCandidate> {
+    let Some(current) = current.to_str() else {
+        return Vec::new();
+    };
+    let parsed = parse_pattern(current);
+    let prefixes = &current[..current.len() - parsed.pattern.len()];
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    if let Some(dir) = config::load().ok().and_then(|config| config.trash_dir)
+        && dir.is_dir()
+        && let Ok(trash) = backend::Directory::open(&dir)
+    {
+        backend::set(Box::new(trash));
+    }
+    let Ok(mut items) = items::list() else {
+        return Vec::new();
+    };
+    items.sort_by_key(|item| std::cmp::Reverse(item.time_deleted));
+
+    let mut seen = std::collections::HashSet::new();
+    let mut candidates = Vec::new();
+    for item in &items {
+        let path = item.original_path().to_string_lossy().into_owned();
+        let (value, help) = match parsed.target {
+            PatternTarget::Name => (item.name.to_string_lossy().into_owned(), Some(path)),
+            PatternTarget::Path => (path, None),
+            PatternTarget::Dir => (item.original_parent.to_string_lossy().into_owned(), None),
+            PatternTarget::Id => (items::short_id(item), Some(path)),
+        };
+        if !value.starts_with(parsed.pattern) || !seen.insert(value.clone()) {
+            continue;
+        }
+        // A name like "notes[1].txt" has to match itself as a glob
+        let value = if parsed.match_type == "glob" {
+            globset::escape(&value)
+        } else {
+            value
+        };
+        candidates.push(
+            CompletionCandidate::new(format!("{prefixes}{value}")).help(help.map(Into::into)),
+        );
+    }
+    candidates
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn complete_items(_current: &std::ffi::OsStr) -> Vec<CompletionCandidate> {
+    Vec::new()
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+/// Restore the items matching `matcher`. The matches are gathered before
+/// anything is restored, since several options look at all of them at
+/// once (--plan, --restore-as, the interactive menu).
+fn restore_items(
+    input: &mut dyn BufRead,
+    pattern: &str,
+    matcher: &CompiledMatcher,
+    target: PatternTarget,
+    opts: &RestoreOptions,
+) -> Result<(), TracheError> {
+    let mut items = items::list()?;
+    if let Some(ref label) = opts.label {
+        let file = labels::file_path().ok_or(TracheError::NoLocation(
+            "cannot determine the state directory",
+        ))?;
+        items = labels::select(&labels::load(&file)?, label, items);
+    }
+    let matching: Vec<_> = items
+        .into_iter()
+        .filter(|item| item_matches(item, matcher, target))
+        .filter(|item| opts.since.is_none_or(|since| item.time_deleted >= since))
+        .collect();
+
+    if matching.is_empty() {
+        if opts.label.is_some() {
+            println!("No items trashed with label '{pattern}' found in trash.");
+        } else if opts.since.is_some() {
+            println!("No items trashed since '{pattern}' found in trash.");
+        } else {
+            println!("No items matching '{pattern}' found in trash.");
+        }
+        return Ok(());
+    }
+
+    let dry_run = opts.dry_run;
+
+    if opts.verify {
+        verify_items(&matching);
+    }
+
+    if dry_run && opts.plan {
+        if opts.inner.is_some() || opts.restore_as.is_some() || opts.to_temp || opts.dated_dir {
+            return Err(TracheError::Invalid(
+                "--output json cannot plan --inner, --restore-as, --to-temp or --dated-dir".into(),
+            ));
+        }
+        restore_plan(matching);
+        return Ok(());
+    }
+
+    if let Some(ref glob) = opts.inner {
+        return extract_inner(matching, glob, dry_run);
+    }
+
+    if let Some(ref name) = opts.restore_as {
+        return restore_item_as(pattern, matching, name, dry_run);
+    }
 
-    let stdin = io::stdin();
-    let mut input = stdin.lock();
+    if opts.to_temp {
+        return restore_items_to_temp(matching, dry_run, opts.to_temp_ttl);
+    }
 
-    let result = if cli.list {
-        list_trash()
-    } else if cli.empty {
-        if dry_run {
-            println!("would empty trash");
-            Ok(())
+    if opts.dated_dir {
+        return restore_items_dated(matching, dry_run);
+    }
+
+    #[cfg(windows)]
+    let (matching, unrestorable) = restore_invalid_names(input, matching, opts)?;
+    #[cfg(not(windows))]
+    let unrestorable = 0;
+
+    if matching.is_empty() {
+        // Everything had an unusable name and was dealt with already
+    } else if opts.interactive == InteractiveMode::Never {
+        let prefix = if dry_run {
+            "would restore"
         } else {
-            empty_trash()
+            "Restoring"
+        };
+        print_items(&matching, prefix);
+
+        if !dry_run {
+            restore_audited(matching)?;
+            println!("Restored item(s).");
         }
-    } else if let Some(ref raw) = cli.undo {
-        let parsed = parse_pattern(raw);
-        let matcher = compile_matcher(parsed.pattern, parsed.match_type, parsed.full)
-            .unwrap_or_else(|e| {
-                eprintln!("trache: {e}");
-                std::process::exit(1);
-            });
-        restore_items(
-            &mut input,
-            parsed.pattern,
-            &matcher,
-            parsed.target,
-            dry_run,
-            interactive,
-        )
-    } else if let Some(ref raw) = cli.purge {
-        let parsed = parse_pattern(raw);
-        let matcher = compile_matcher(parsed.pattern, parsed.match_type, parsed.full)
-            .unwrap_or_else(|e| {
-                eprintln!("trache: {e}");
-                std::process::exit(1);
-            });
-        purge_items(parsed.pattern, &matcher, parsed.target, dry_run)
     } else {
-        let preserve_root = if cli.no_preserve_root {
-            PreserveRoot::No
-        } else if let Some(mode) = cli.preserve_root {
-            mode
-        } else {
-            PreserveRoot::Yes // default
-        };
+        restore_items_interactive(input, matching, dry_run, opts.interactive)?;
+    }
 
-        let opts = TrashOptions {
-            dir: cli.dir,
-            recursive: cli.recursive,
-            force: cli.force,
-            interactive,
-            verbose: cli.verbose,
-            dry_run: cli.dry_run,
-            preserve_root,
-            one_file_system: cli.one_file_system,
-        };
+    if unrestorable > 0 {
+        return Err(TracheError::Incomplete(format!(
+            "{unrestorable} item(s) have names Windows cannot use"
+        )));
+    }
+    Ok(())
+}
 
-        trash_files(&mut input, &cli.files, &opts)
+/// For --verify: warn about each item in `items` whose trashed copy no
+/// longer matches the hash recorded when it was trashed.
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn verify_items(items: &[trash::TrashItem]) {
+    let recorded = match checksum::recorded() {
+        Ok(recorded) => recorded,
+        Err(e) => {
+            eprintln!("trache: warning: cannot read the recorded checksums: {e}");
+            return;
+        }
     };
-
-    if let Err(e) = result {
-        eprintln!("Error: {e}");
-        std::process::exit(1);
+    let (mut intact, mut unrecorded) = (0, 0);
+    for item in items {
+        let original = item.original_path();
+        match checksum::verify(item, &recorded) {
+            Ok(checksum::Verdict::Intact) => intact += 1,
+            Ok(checksum::Verdict::Unrecorded) => unrecorded += 1,
+            Ok(checksum::Verdict::Changed) => eprintln!(
+                "trache: warning: '{}' changed while in the trash (deleted {}): \
+                 it no longer matches its checksum",
+                original.display(),
+                format_timestamp(item.time_deleted)
+            ),
+            Err(e) => eprintln!(
+                "trache: warning: cannot verify '{}': {e}",
+                original.display()
+            ),
+        }
+    }
+    if intact > 0 {
+        eprintln!("trache: {intact} item(s) match their checksums");
+    }
+    if unrecorded > 0 {
+        eprintln!("trache: {unrecorded} item(s) have no recorded checksum (see --checksum)");
     }
 }
 
-fn new_trash_ctx() -> TrashContext {
-    #[allow(unused_mut)]
-    let mut ctx = TrashContext::new();
-    #[cfg(target_os = "macos")]
-    ctx.set_delete_method(trash::macos::DeleteMethod::NsFileManager);
-    ctx
+/// Print the --output json plan for restoring `items` to their original
+/// paths, with what's in the way of each.
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn restore_plan(mut items: Vec<trash::TrashItem>) {
+    items.sort_by_key(|item| item.original_path());
+    let mut twins: std::collections::HashMap<PathBuf, usize> = std::collections::HashMap::new();
+    for item in &items {
+        *twins
+            .entry(casefold::collision_key(&item.original_path()))
+            .or_default() += 1;
+    }
+    for item in &items {
+        let path = item.original_path();
+        let mut step =
+            dryrun::Step::new(dryrun::Action::Restore, &items::item_path(item)).to(&path);
+        if path.symlink_metadata().is_ok() || casefold::existing_variant(&path).is_some() {
+            step.conflicts.push(dryrun::Conflict::Collision);
+        }
+        if twins[&casefold::collision_key(&path)] > 1 {
+            step.conflicts.push(dryrun::Conflict::Twin);
+        }
+        step.print();
+    }
 }
 
-fn trash_files(
-    input: &mut dyn BufRead,
-    files: &[PathBuf],
-    opts: &TrashOptions,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Check -x/--one-file-system support on this platform
-    #[cfg(not(unix))]
-    if opts.one_file_system {
-        return Err("--one-file-system is not supported on this platform".into());
-    }
+/// Move the entries matching `glob` out of each trashed directory in
+/// `matching` to the same place under the directory's original path.
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn extract_inner(
+    mut matching: Vec<trash::TrashItem>,
+    glob: &str,
+    dry_run: bool,
+) -> Result<(), TracheError> {
+    let by_path = glob.contains('/');
+    let matcher = globset::GlobBuilder::new(glob)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| TracheError::Invalid(format!("invalid glob pattern: {e}")))?
+        .compile_matcher();
+    matching.sort_by_key(|item| item.original_path());
 
+    let mut extracted = 0;
     let mut had_error = false;
+    for item in &matching {
+        let root = items::item_path(item);
+        if !root.symlink_metadata().is_ok_and(|m| m.is_dir()) {
+            eprintln!(
+                "trache: skipping '{}': not a directory",
+                item.original_path().display()
+            );
+            continue;
+        }
+        let mut found = Vec::new();
+        find_inner(&root, Path::new(""), &matcher, by_path, &mut found)?;
 
-    // -I: prompt once if >3 files or recursive
-    let prompt_once_triggered =
-        opts.interactive == InteractiveMode::Once && (files.len() > 3 || opts.recursive);
+        let original = item.original_path();
+        for rel in found {
+            let dest = original.join(&rel);
+            if dest.symlink_metadata().is_ok() {
+                eprintln!("trache: skipping '{}': already exists", dest.display());
+                had_error = true;
+                continue;
+            }
+            if dry_run {
+                println!("would extract: {}", dest.display());
+                continue;
+            }
+            let result = dest
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|()| move_path(&root.join(&rel), &dest));
+            audit::record(audit::Op::Restore, &dest, &result);
+            match result {
+                Ok(()) => {
+                    println!("Extracted: {}", dest.display());
+                    extracted += 1;
+                }
+                Err(e) => {
+                    eprintln!("trache: cannot extract '{}': {e}", dest.display());
+                    had_error = true;
+                }
+            }
+        }
+    }
+
+    if extracted == 0 && !had_error && !dry_run {
+        println!("No entries matching '{glob}' found inside matching items.");
+    }
+    if had_error {
+        return Err(TracheError::Incomplete(
+            "some entries could not be extracted".into(),
+        ));
+    }
+    Ok(())
+}
 
-    if prompt_once_triggered {
-        let msg = if opts.recursive {
-            format!("trache: remove {} argument(s) recursively? ", files.len())
+/// Collect paths under `dir` (relative to the trashed directory) matching
+/// `matcher`, not descending into matches.
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn find_inner(
+    dir: &Path,
+    rel: &Path,
+    matcher: &globset::GlobMatcher,
+    by_path: bool,
+    found: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    let mut entries: Vec<_> = fs::read_dir(dir)?.collect::<io::Result<_>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let entry_rel = rel.join(entry.file_name());
+        let hit = if by_path {
+            matcher.is_match(&entry_rel)
         } else {
-            format!("trache: remove {} argument(s)? ", files.len())
+            matcher.is_match(entry.file_name())
         };
-        if !prompt_yes(input, &msg) {
-            return Ok(());
+        if hit {
+            found.push(entry_rel);
+        } else if entry.file_type()?.is_dir() {
+            find_inner(&entry.path(), &entry_rel, matcher, by_path, found)?;
         }
     }
+    Ok(())
+}
 
-    for file in files {
-        // Reject paths ending in . or ..
-        match file.components().next_back() {
-            Some(Component::CurDir) | Some(Component::ParentDir) => {
-                eprintln!(
-                    "trache: refusing to remove '.' or '..' directory: skipping '{}'",
-                    file.display()
-                );
-                had_error = true;
-                continue;
-            }
-            _ => {}
-        }
+/// Restore the one item in `matching` as `name` next to its original path.
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn restore_item_as(
+    pattern: &str,
+    mut matching: Vec<trash::TrashItem>,
+    name: &std::ffi::OsStr,
+    dry_run: bool,
+) -> Result<(), TracheError> {
+    let mut components = Path::new(name).components();
+    if !matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) {
+        return Err(TracheError::Invalid(format!(
+            "--restore-as takes a plain file name, not '{}'",
+            name.to_string_lossy()
+        )));
+    }
+    #[cfg(windows)]
+    if let Some(problem) = winnames::name_problem(&name.to_string_lossy()) {
+        return Err(TracheError::Invalid(format!(
+            "--restore-as: '{}' {problem}",
+            name.to_string_lossy()
+        )));
+    }
+    if matching.len() > 1 {
+        print_items(&matching, "matching");
+        return Err(TracheError::Invalid(format!(
+            "--restore-as needs exactly one matching item, but '{pattern}' matches {}",
+            matching.len()
+        )));
+    }
+
+    let item = matching.remove(0);
+    let original = item.original_path();
+    let target = original.with_file_name(name);
+    if target.symlink_metadata().is_ok() {
+        let e = format!("'{}' already exists", target.display());
+        return Err(TracheError::io_kind(io::ErrorKind::AlreadyExists, e));
+    }
+    if dry_run {
+        println!(
+            "would restore {} as: {}",
+            original.display(),
+            target.display()
+        );
+        return Ok(());
+    }
+    restore_one_as(item, &target)?;
+    println!("Restored {} as: {}", original.display(), target.display());
+    Ok(())
+}
 
-        // Check preserve-root
-        if let Err(e) = check_preserve_root(file, opts.preserve_root) {
-            eprintln!("trache: {}", e);
-            had_error = true;
+/// Deal with items whose original names Windows can't hold (typically
+/// trashed on another OS and carried over). Interactively each one is offered
+/// under a sanitized name; otherwise it is left in the trash. Returns the
+/// remaining items and how many were left behind.
+#[cfg(windows)]
+fn restore_invalid_names(
+    input: &mut dyn BufRead,
+    matching: Vec<trash::TrashItem>,
+    opts: &RestoreOptions,
+) -> Result<(Vec<trash::TrashItem>, usize), TracheError> {
+    let mut valid = Vec::new();
+    let mut unrestorable = 0;
+    for item in matching {
+        let original = item.original_path();
+        let Some(problem) = winnames::path_problem(&original) else {
+            valid.push(item);
+            continue;
+        };
+        let sanitized = original.with_file_name(winnames::sanitize(&item.name.to_string_lossy()));
+        let fixable = winnames::path_problem(&sanitized).is_none() && !sanitized.exists();
+        if !fixable {
+            eprintln!("trache: cannot restore '{}': {problem}", original.display());
+            unrestorable += 1;
             continue;
         }
-
-        // Check one-file-system
-        if opts.one_file_system
-            && let Err(e) = check_one_file_system(file)
-        {
-            eprintln!("trache: {}", e);
-            had_error = true;
+        if opts.interactive == InteractiveMode::Never {
+            eprintln!(
+                "trache: cannot restore '{}': {problem} (use -i to restore it as '{}')",
+                original.display(),
+                sanitized.display()
+            );
+            unrestorable += 1;
+            continue;
+        }
+        let prompt = format!(
+            "trache: cannot restore '{}': {problem}; restore as '{}'? ",
+            original.display(),
+            sanitized.display()
+        );
+        if !prompt_yes(input, &prompt) {
             continue;
         }
+        if opts.dry_run {
+            println!(
+                "would restore {} as: {}",
+                original.display(),
+                sanitized.display()
+            );
+        } else {
+            restore_one_as(item, &sanitized)?;
+            println!(
+                "Restored {} as: {}",
+                original.display(),
+                sanitized.display()
+            );
+        }
+    }
+    Ok((valid, unrestorable))
+}
 
-        if let Err(e) = trash_single(input, file, opts, prompt_once_triggered)
-            && (!opts.force || file.symlink_metadata().is_ok())
-        {
-            eprintln!("trache: cannot remove '{}': {}", file.display(), e);
-            had_error = true;
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn restore_items_to_temp(
+    mut matching: Vec<trash::TrashItem>,
+    dry_run: bool,
+    ttl: Duration,
+) -> Result<(), TracheError> {
+    matching.sort_by_key(|item| item.original_path());
+
+    if dry_run {
+        for item in &matching {
+            println!("would restore to temp: {}", item.original_path().display());
         }
+        return Ok(());
     }
 
-    if had_error {
-        Err("some files could not be removed".into())
-    } else {
-        Ok(())
+    clean_expired_sandboxes(ttl);
+
+    let sandbox = create_sandbox()?;
+    for item in matching {
+        let original = item.original_path();
+        let _names = lock::names(lock::Names::Untrash);
+        let mut target = sandbox.join(&item.name);
+        if target.symlink_metadata().is_ok() {
+            target = untrash_name(&target, find_untrash_range(&target, 1));
+        }
+        restore_one_as(item, &target)?;
+        println!("Restored {} as: {}", original.display(), target.display());
     }
+    println!("Restored item(s) to {}", sandbox.display());
+    Ok(())
 }
 
-fn trash_single(
-    input: &mut dyn BufRead,
-    file: &PathBuf,
-    opts: &TrashOptions,
-    already_prompted: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let metadata = match file.symlink_metadata() {
-        Ok(m) => m,
-        Err(e) => {
-            if opts.force && e.kind() == io::ErrorKind::NotFound {
-                return Ok(()); // -f ignores nonexistent files
-            }
-            return Err(e.into());
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn restore_items_dated(
+    mut matching: Vec<trash::TrashItem>,
+    dry_run: bool,
+) -> Result<(), TracheError> {
+    matching.sort_by_key(|item| item.original_path());
+    let dir_name = format!("restored-{}", Local::now().format("%Y%m%d"));
+
+    for item in matching {
+        let original = item.original_path();
+        let dir = item.original_parent.join(&dir_name);
+        let _names = lock::names(lock::Names::Untrash);
+        let mut target = dir.join(&item.name);
+        if target.symlink_metadata().is_ok() {
+            target = untrash_name(&target, find_untrash_range(&target, 1));
         }
-    };
+        if dry_run {
+            println!(
+                "would restore {} as: {}",
+                original.display(),
+                target.display()
+            );
+            continue;
+        }
+        fs::create_dir_all(&dir)?;
+        restore_one_as(item, &target)?;
+        println!("Restored {} as: {}", original.display(), target.display());
+    }
+    Ok(())
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+const SANDBOX_PREFIX: &str = "trache-undo-";
 
-    // Prompt if -i (always) and we haven't already done a bulk prompt
-    let should_prompt = opts.interactive == InteractiveMode::Always && !already_prompted;
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn create_sandbox() -> io::Result<PathBuf> {
+    let stamp = Local::now().format("%Y%m%d-%H%M%S");
+    let pid = std::process::id();
+    let dir = std::env::temp_dir().join(format!("{SANDBOX_PREFIX}{stamp}-{pid}"));
+    fs::create_dir(&dir)?;
+    Ok(dir)
+}
 
-    if metadata.is_dir() {
-        if opts.recursive {
-            if should_prompt {
-                let prompt = format!(
-                    "trache: remove directory '{}' recursively? ",
-                    file.display()
-                );
-                if !prompt_yes(input, &prompt) {
-                    return Ok(());
-                }
-            }
-            if opts.dry_run {
-                println!("would trash '{}'", file.display());
-            } else {
-                new_trash_ctx().delete(file)?;
-                if opts.verbose {
-                    println!("trashed '{}'", file.display());
-                }
-            }
-        } else if opts.dir {
-            if is_dir_empty(file)? {
-                if should_prompt {
-                    let prompt = format!("trache: remove directory '{}'? ", file.display());
-                    if !prompt_yes(input, &prompt) {
-                        return Ok(());
-                    }
-                }
-                if opts.dry_run {
-                    println!("would trash '{}'", file.display());
-                } else {
-                    new_trash_ctx().delete(file)?;
-                    if opts.verbose {
-                        println!("trashed '{}'", file.display());
-                    }
-                }
-            } else {
-                return Err("Directory not empty".into());
-            }
-        } else {
-            return Err("Is a directory".into());
+/// Remove --to-temp sandboxes that have outlived their TTL.
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn clean_expired_sandboxes(ttl: Duration) {
+    let Ok(entries) = fs::read_dir(std::env::temp_dir()) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        if !entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with(SANDBOX_PREFIX)
+        {
+            continue;
         }
-    } else {
-        if should_prompt {
-            let file_type = if metadata.is_symlink() {
-                "symbolic link"
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.elapsed().ok())
+            .is_some_and(|age| age > ttl);
+        if expired && entry.path().is_dir() {
+            let _ = fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn temp_path(path: &Path) -> PathBuf {
+    let pid = std::process::id();
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let parent = path.parent().unwrap_or(Path::new(""));
+    parent.join(format!(".trache_tmp_{pid}_{name}"))
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+/// Rename `from` to `to`, falling back to copy-and-delete across file systems.
+fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            copy_recursive(from, to)?;
+            let meta = from.symlink_metadata()?;
+            if meta.is_dir() {
+                fs::remove_dir_all(from)
             } else {
-                "regular file"
-            };
-            let prompt = format!("trache: remove {} '{}'? ", file_type, file.display());
-            if !prompt_yes(input, &prompt) {
-                return Ok(());
+                fs::remove_file(from)
             }
         }
-        if opts.dry_run {
-            println!("would trash '{}'", file.display());
+        other => other,
+    }
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    let meta = from.symlink_metadata()?;
+    if meta.is_symlink() {
+        let link = fs::read_link(from)?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&link, to)?;
+        #[cfg(windows)]
+        if from.is_dir() {
+            std::os::windows::fs::symlink_dir(&link, to)?;
         } else {
-            new_trash_ctx().delete(file)?;
-            if opts.verbose {
-                println!("trashed '{}'", file.display());
-            }
+            std::os::windows::fs::symlink_file(&link, to)?;
         }
+    } else if meta.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(from, to)?;
     }
-
+    warn_attr_problems(attrs::copy(from, to)?);
     Ok(())
 }
 
-fn is_dir_empty(path: &PathBuf) -> Result<bool, Box<dyn std::error::Error>> {
-    Ok(fs::read_dir(path)?.next().is_none())
-}
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn restore_one_as(item: trash::TrashItem, target: &Path) -> Result<(), TracheError> {
+    let original = item.original_path();
 
-fn check_preserve_root(path: &Path, mode: PreserveRoot) -> Result<(), String> {
-    if mode == PreserveRoot::No {
+    if *target == *original && !target.exists() {
+        restore_audited(vec![item])?;
         return Ok(());
     }
 
-    // Normalize the path to check for root
-    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let occupied = original.exists();
+    let tmp = if occupied {
+        let t = temp_path(&original);
+        fs::rename(&original, &t)?;
+        Some(t)
+    } else {
+        None
+    };
 
-    // Check if it's the root directory
-    if canonical == Path::new("/") {
-        return Err("it is dangerous to operate recursively on '/'\n\
-             use --no-preserve-root to override this failsafe"
-            .to_string());
+    // Restore to original path (now free)
+    if let Err(e) = restore_audited(vec![item]) {
+        if let Some(ref t) = tmp {
+            let _ = fs::rename(t, &original);
+        }
+        return Err(e);
     }
 
-    // For --preserve-root=all, also check if path is on a different device than its parent
-    if mode == PreserveRoot::All
-        && let Err(e) = check_same_device_as_parent(&canonical)
-    {
-        return Err(format!(
-            "'{}' is on a different device from its parent; refusing to operate\n{}",
-            path.display(),
-            e
-        ));
+    // Rename restored file to target
+    if let Err(e) = move_path(&original, target) {
+        if let Some(ref t) = tmp {
+            eprintln!(
+                "warning: could not rename restored file, original file left at {}",
+                t.display()
+            );
+        }
+        return Err(e.into());
+    }
+
+    // Put existing file back at original path
+    if let Some(ref t) = tmp {
+        fs::rename(t, &original)?;
     }
 
     Ok(())
 }
 
-#[cfg(unix)]
-fn check_same_device_as_parent(path: &Path) -> Result<(), String> {
-    use std::os::unix::fs::MetadataExt;
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn handle_collision(
+    input: &mut dyn BufRead,
+    item: trash::TrashItem,
+    path: &Path,
+    dry_run: bool,
+    once: bool,
+    remembered_collision: &mut Option<CollisionChoice>,
+) -> Result<(), TracheError> {
+    if let Some(existing) = casefold::existing_variant(path) {
+        eprintln!(
+            "{} differs only in case from the existing {}",
+            path.display(),
+            existing.display()
+        );
+    }
 
-    let path_meta = path.symlink_metadata().map_err(|e| e.to_string())?;
+    let choice = if let Some(c) = *remembered_collision {
+        eprintln!(
+            "{} already exists \u{2192} {} (remembered)",
+            path.display(),
+            collision_choice_name(c)
+        );
+        c
+    } else {
+        let f = find_untrash_range(path, 1);
+        let keep_name = untrash_name(path, f);
+        let c = prompt_collision(input, path, &keep_name, once);
+        if once && c != CollisionChoice::Quit {
+            *remembered_collision = Some(c);
+        }
+        c
+    };
 
-    if let Some(parent) = path.parent() {
-        if parent.as_os_str().is_empty() {
-            return Ok(()); // No parent to compare
+    if dry_run {
+        match choice {
+            CollisionChoice::Overwrite => println!("would overwrite: {}", path.display()),
+            CollisionChoice::KeepBoth => {
+                let f = find_untrash_range(path, 1);
+                println!("would restore as: {}", untrash_name(path, f).display());
+            }
+            CollisionChoice::None => {}
+            CollisionChoice::Quit => return Err(TracheError::PromptAborted),
         }
-        let parent_meta = parent.symlink_metadata().map_err(|e| e.to_string())?;
+        return Ok(());
+    }
 
-        if path_meta.dev() != parent_meta.dev() {
-            return Err("use --no-preserve-root to override this failsafe".to_string());
+    match choice {
+        CollisionChoice::Quit => return Err(TracheError::PromptAborted),
+        CollisionChoice::None => {}
+        CollisionChoice::Overwrite => {
+            let removed = if path.is_dir() {
+                fs::remove_dir_all(path)
+            } else {
+                fs::remove_file(path)
+            };
+            audit::record(audit::Op::Overwrite, path, &removed);
+            removed?;
+            restore_audited(vec![item])?;
+            println!("Overwritten: {}", path.display());
+        }
+        CollisionChoice::KeepBoth => {
+            let _names = lock::names(lock::Names::Untrash);
+            let f = find_untrash_range(path, 1);
+            let target = untrash_name(path, f);
+            restore_one_as(item, &target)?;
+            println!("Restored as: {}", target.display());
         }
     }
 
     Ok(())
 }
 
-#[cfg(not(unix))]
-fn check_same_device_as_parent(_path: &Path) -> Result<(), String> {
-    // On non-Unix platforms, skip the device check
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn restore_twins_renamed(
+    twins: Vec<trash::TrashItem>,
+    path: &Path,
+    dry_run: bool,
+) -> Result<(), TracheError> {
+    // Names shown in a prompt may have been taken since; pick them afresh
+    let _names = lock::names(lock::Names::Untrash);
+    let start = find_untrash_range(path, twins.len());
+    for (i, twin) in twins.into_iter().enumerate() {
+        let n = start + i;
+        let target = untrash_name(path, n);
+        if dry_run {
+            println!("would restore as: {}", target.display());
+        } else {
+            restore_one_as(twin, &target)?;
+            println!("Restored as: {}", target.display());
+        }
+    }
     Ok(())
 }
 
-#[cfg(unix)]
-fn check_one_file_system(path: &Path) -> Result<(), String> {
-    use std::os::unix::fs::MetadataExt;
-
-    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
-    let path_meta = canonical.symlink_metadata().map_err(|e| e.to_string())?;
-
-    if let Some(parent) = canonical.parent() {
-        if parent.as_os_str().is_empty() {
-            return Ok(()); // No parent to compare
-        }
-        let parent_meta = parent.symlink_metadata().map_err(|e| e.to_string())?;
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn handle_twin_selected(
+    input: &mut dyn BufRead,
+    selections: Vec<usize>,
+    twins: Vec<trash::TrashItem>,
+    path: &Path,
+    dry_run: bool,
+    once: bool,
+    remembered_collision: &mut Option<CollisionChoice>,
+) -> Result<(), TracheError> {
+    let selected: Vec<trash::TrashItem> =
+        selections.iter().map(|&i| twins[i - 1].clone()).collect();
 
-        if path_meta.dev() != parent_meta.dev() {
-            return Err(format!(
-                "skipping '{}', since it's on a different file system",
-                path.display()
-            ));
+    if selected.len() > 1 {
+        restore_twins_renamed(selected, path, dry_run)?;
+    } else {
+        let item = selected.into_iter().next().unwrap();
+        if path.exists() {
+            handle_collision(input, item, path, dry_run, once, remembered_collision)?;
+        } else if dry_run {
+            println!("would restore: {}", path.display());
+        } else {
+            restore_audited(vec![item])?;
+            println!("Restored: {}", path.display());
         }
     }
 
     Ok(())
 }
 
-#[cfg(not(unix))]
-fn check_one_file_system(_path: &Path) -> Result<(), String> {
-    // This shouldn't be called on non-Unix - we error earlier
-    Ok(())
-}
-
 #[cfg(any(
     target_os = "windows",
     all(unix, not(target_os = "macos"), not(target_os = "ios"))
 ))]
-fn list_trash() -> Result<(), Box<dyn std::error::Error>> {
-    let items = list()?;
+fn handle_twin_group(
+    input: &mut dyn BufRead,
+    path: &Path,
+    mut twins: Vec<trash::TrashItem>,
+    dry_run: bool,
+    once: bool,
+    remembered_twin: &mut Option<TwinChoice>,
+    remembered_collision: &mut Option<CollisionChoice>,
+) -> Result<(), TracheError> {
+    twins.sort_by_key(|t| t.time_deleted);
+    let count = twins.len();
+    let start = find_untrash_range(path, count);
+    let end = start + count - 1;
+    let range_desc = format_untrash_range(path, start, end);
 
-    if items.is_empty() {
-        println!("Trash is empty.");
-        return Ok(());
+    let choice = if let Some(ref remembered) = *remembered_twin {
+        let desc = match remembered {
+            TwinChoice::All => format!("all (remembered): restoring as {range_desc}"),
+            TwinChoice::None => "none (remembered)".to_string(),
+            TwinChoice::Some(_) => "some (remembered)".to_string(),
+            TwinChoice::Quit => unreachable!(),
+        };
+        eprintln!("{} trashed {count} times \u{2192} {desc}", path.display());
+        remembered.clone()
+    } else {
+        let twin_infos: Vec<TwinInfo> = twins
+            .iter()
+            .map(|t| TwinInfo {
+                name: t.name.to_string_lossy().into_owned(),
+                timestamp: format_timestamp(t.time_deleted),
+            })
+            .collect();
+        let c = prompt_twins(input, path, &twin_infos, &range_desc, once);
+        if once && !matches!(c, TwinChoice::Quit) {
+            *remembered_twin = Some(match &c {
+                TwinChoice::Some(_) => TwinChoice::Some(vec![]),
+                other => other.clone(),
+            });
+        }
+        c
+    };
+
+    match choice {
+        TwinChoice::Quit => return Err(TracheError::PromptAborted),
+        TwinChoice::None => {}
+        TwinChoice::All => {
+            restore_twins_renamed(twins, path, dry_run)?;
+        }
+        TwinChoice::Some(selections) => {
+            if selections.is_empty() {
+                // Remembered "some" — re-prompt for selection
+                for (i, twin) in twins.iter().enumerate() {
+                    let ts = format_timestamp(twin.time_deleted);
+                    eprintln!("  {}: {} ({})", i + 1, twin.name.to_string_lossy(), ts);
+                }
+                if let Some(sel) = prompt_selection(input, count) {
+                    handle_twin_selected(
+                        input,
+                        sel,
+                        twins,
+                        path,
+                        dry_run,
+                        once,
+                        remembered_collision,
+                    )?;
+                }
+            } else {
+                handle_twin_selected(
+                    input,
+                    selections,
+                    twins,
+                    path,
+                    dry_run,
+                    once,
+                    remembered_collision,
+                )?;
+            }
+        }
     }
 
-    for item in items {
-        let time = format_timestamp(item.time_deleted);
-        println!(
-            "{} {} {}",
-            time,
-            item.name.to_string_lossy(),
-            item.original_path().display()
-        );
-    }
     Ok(())
 }
 
-#[cfg(any(target_os = "macos", target_os = "ios"))]
-fn list_trash() -> Result<(), Box<dyn std::error::Error>> {
-    Err("Listing trash is not supported on this platform".into())
-}
-
 #[cfg(any(
     target_os = "windows",
     all(unix, not(target_os = "macos"), not(target_os = "ios"))
 ))]
-fn format_timestamp(time_deleted: i64) -> String {
-    DateTime::from_timestamp(time_deleted, 0)
-        .map(|t| t.with_timezone(&Local))
-        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
-        .unwrap_or_else(|| "????-??-?? ??:??".to_string())
-}
+/// Collisions needed before an interactive restore asks for a policy up front.
+const BATCH_CONFLICT_PROMPT_MIN: usize = 2;
 
 #[cfg(any(
     target_os = "windows",
     all(unix, not(target_os = "macos"), not(target_os = "ios"))
 ))]
-/// Build a map of original_path -> count for duplicate detection.
-fn path_counts(items: &[trash::TrashItem]) -> std::collections::HashMap<PathBuf, usize> {
-    let mut counts = std::collections::HashMap::new();
-    for item in items {
-        *counts.entry(item.original_path()).or_insert(0) += 1;
+fn restore_items_interactive(
+    input: &mut dyn BufRead,
+    matching: Vec<trash::TrashItem>,
+    dry_run: bool,
+    interactive: InteractiveMode,
+) -> Result<(), TracheError> {
+    // Items that would land on the same destination are twins, which on
+    // case-insensitive file systems includes names differing only in case
+    let mut groups: std::collections::HashMap<PathBuf, Vec<trash::TrashItem>> =
+        std::collections::HashMap::new();
+    for item in matching {
+        let key = casefold::collision_key(&item.original_path());
+        groups.entry(key).or_default().push(item);
     }
-    counts
-}
 
-#[cfg(any(
-    target_os = "windows",
-    all(unix, not(target_os = "macos"), not(target_os = "ios"))
-))]
-/// Print each item with disambiguation when multiple items share the same original path.
-fn print_items(items: &[trash::TrashItem], prefix: &str) {
-    let counts = path_counts(items);
-    let mut seen: std::collections::HashMap<PathBuf, usize> = std::collections::HashMap::new();
+    let mut twin_groups: Vec<(PathBuf, Vec<trash::TrashItem>)> = Vec::new();
+    let mut singletons: Vec<trash::TrashItem> = Vec::new();
+    for items in groups.into_values() {
+        if items.len() > 1 {
+            twin_groups.push((items[0].original_path(), items));
+        } else {
+            singletons.extend(items);
+        }
+    }
 
-    for item in items {
+    twin_groups.sort_by(|a, b| a.0.cmp(&b.0));
+    singletons.sort_by_key(|a| a.original_path());
+    // Both are taken in path order together below, so a directory is back
+    // before anything trashed from inside it, which then goes into it
+    // rather than colliding with it
+
+    let once = interactive == InteractiveMode::Once;
+    let mut remembered_twin: Option<TwinChoice> = None;
+    let mut remembered_collision: Option<CollisionChoice> = None;
+
+    // With several collisions ahead, offer to settle them all up front
+    let conflicts = twin_groups
+        .iter()
+        .map(|(path, _)| path.clone())
+        .chain(singletons.iter().map(|item| item.original_path()))
+        .filter(|path| path.exists())
+        .count();
+    if conflicts >= BATCH_CONFLICT_PROMPT_MIN {
+        remembered_collision = prompt_conflict_policy(input, conflicts);
+        if remembered_collision == Some(CollisionChoice::Quit) {
+            return Ok(());
+        }
+    }
+
+    let mut twin_groups = twin_groups.into_iter().peekable();
+    let mut singletons = singletons.into_iter().peekable();
+    loop {
+        let twins_next = match (twin_groups.peek(), singletons.peek()) {
+            (Some((path, _)), Some(item)) => *path <= item.original_path(),
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => break,
+        };
+        if twins_next {
+            let (path, twins) = twin_groups.next().unwrap();
+            handle_twin_group(
+                input,
+                &path,
+                twins,
+                dry_run,
+                once,
+                &mut remembered_twin,
+                &mut remembered_collision,
+            )?;
+            continue;
+        }
+        let item = singletons.next().unwrap();
         let path = item.original_path();
-        let total = counts[&path];
-        if total > 1 {
-            let idx = seen.entry(path.clone()).or_insert(0);
-            *idx += 1;
-            let ts = format_timestamp(item.time_deleted);
-            println!("{prefix} ({}/{total}, {ts}): {}", *idx, path.display());
+        if path.exists() {
+            handle_collision(input, item, &path, dry_run, once, &mut remembered_collision)?;
+        } else if dry_run {
+            println!("would restore: {}", path.display());
         } else {
-            println!("{prefix}: {}", path.display());
+            restore_audited(vec![item])?;
+            println!("Restored: {}", path.display());
         }
     }
+
+    Ok(())
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn restore_items(
+    _input: &mut dyn BufRead,
+    _pattern: &str,
+    _matcher: &CompiledMatcher,
+    _target: PatternTarget,
+    _opts: &RestoreOptions,
+) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "Restoring from trash is not supported on this platform".into(),
+    ))
 }
 
 #[cfg(any(
     target_os = "windows",
     all(unix, not(target_os = "macos"), not(target_os = "ios"))
 ))]
-fn restore_items(
+/// Purge the items matching `matcher`. The matches are gathered first, for
+/// --keep, duplicates and the interactive menu to look at together; only
+/// the purge itself goes in chunks.
+fn purge_items(
     input: &mut dyn BufRead,
     pattern: &str,
     matcher: &CompiledMatcher,
     target: PatternTarget,
-    dry_run: bool,
-    interactive: InteractiveMode,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let items = list()?;
-    let matching: Vec<_> = items
+    opts: &PurgeOptions,
+) -> Result<(), TracheError> {
+    let PurgeOptions {
+        dry_run,
+        interactive,
+        keep_newest_duplicates,
+        keep,
+        scope,
+        plan,
+    } = *opts;
+    let roots = items::scope_roots(scope)?;
+    let items = items::list()?;
+    let mut matching: Vec<_> = items
         .into_iter()
-        .filter(|item| {
-            let haystack = match target {
-                PatternTarget::Name => item.name.to_string_lossy().into_owned(),
-                PatternTarget::Path => item.original_path().to_string_lossy().into_owned(),
-            };
-            matcher.is_match(&haystack)
-        })
+        .filter(|item| items::in_scope(&item.original_path(), &roots))
+        .filter(|item| item_matches(item, matcher, target))
         .collect();
+    if let Some(keep) = keep {
+        matching = beyond_newest(matching, keep);
+        if matching.is_empty() {
+            println!("No path matching '{pattern}' was trashed more than {keep} time(s).");
+            return Ok(());
+        }
+    }
+    matching = unpinned(matching)?;
+
+    if keep_newest_duplicates {
+        let older_copies: std::collections::HashSet<usize> = dupes::groups(&matching)
+            .into_iter()
+            .flat_map(|group| group.into_iter().skip(1))
+            .collect();
+        matching = matching
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| older_copies.contains(i))
+            .map(|(_, item)| item)
+            .collect();
+        if matching.is_empty() {
+            println!("No duplicate items matching '{pattern}' found in trash.");
+            return Ok(());
+        }
+    }
 
     if matching.is_empty() {
         println!("No items matching '{pattern}' found in trash.");
         return Ok(());
     }
 
-    if interactive == InteractiveMode::Never {
-        let prefix = if dry_run {
-            "would restore"
-        } else {
-            "Restoring"
-        };
-        print_items(&matching, prefix);
-
-        if !dry_run {
-            restore_all(matching)?;
-            println!("Restored item(s).");
+    if dry_run && plan {
+        matching.sort_by_key(|item| (item.original_path(), item.time_deleted));
+        for item in &matching {
+            dryrun::Step::new(dryrun::Action::Purge, &items::item_path(item)).print();
         }
         return Ok(());
     }
 
-    restore_items_interactive(input, matching, dry_run, interactive)
+    if interactive != InteractiveMode::Never {
+        matching.sort_by(|a, b| {
+            (a.original_path(), a.time_deleted).cmp(&(b.original_path(), b.time_deleted))
+        });
+        for (i, item) in matching.iter().enumerate() {
+            let ts = format_timestamp(item.time_deleted);
+            eprintln!("  {}: {} ({ts})", i + 1, item.original_path().display());
+        }
+        let Some(selected) = prompt_selection(input, matching.len()) else {
+            println!("Nothing purged.");
+            return Ok(());
+        };
+        matching = matching
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| selected.binary_search(&(i + 1)).is_ok())
+            .map(|(_, item)| item)
+            .collect();
+    }
+
+    let prefix = if dry_run { "would purge" } else { "Purging" };
+    print_items(&matching, prefix);
+
+    if !dry_run {
+        purge_audited(matching, audit::Op::Purge)?;
+        println!("Permanently deleted item(s).");
+    }
+    Ok(())
 }
 
+/// For --keep: the items in `items` older than the `keep` newest trashed
+/// from the same path.
 #[cfg(any(
     target_os = "windows",
     all(unix, not(target_os = "macos"), not(target_os = "ios"))
 ))]
-fn temp_path(path: &Path) -> PathBuf {
-    let pid = std::process::id();
-    let name = path.file_name().unwrap_or_default().to_string_lossy();
-    let parent = path.parent().unwrap_or(Path::new(""));
-    parent.join(format!(".trache_tmp_{pid}_{name}"))
+fn beyond_newest(items: Vec<trash::TrashItem>, keep: usize) -> Vec<trash::TrashItem> {
+    let mut twins: std::collections::BTreeMap<PathBuf, Vec<trash::TrashItem>> =
+        std::collections::BTreeMap::new();
+    for item in items {
+        twins.entry(item.original_path()).or_default().push(item);
+    }
+    twins
+        .into_values()
+        .flat_map(|mut group| {
+            group.sort_by_key(|item| std::cmp::Reverse(item.time_deleted));
+            group.into_iter().skip(keep)
+        })
+        .collect()
 }
 
 #[cfg(any(
     target_os = "windows",
     all(unix, not(target_os = "macos"), not(target_os = "ios"))
 ))]
-fn restore_one_as(item: trash::TrashItem, target: &Path) -> Result<(), Box<dyn std::error::Error>> {
-    let original = item.original_path();
-
-    if *target == *original && !target.exists() {
-        restore_all(vec![item])?;
+fn purge_by_index(indices: &str, dry_run: bool) -> Result<(), TracheError> {
+    let items = items::list_numbered()?;
+    if items.is_empty() {
+        println!("Trash is empty.");
         return Ok(());
     }
-
-    let occupied = original.exists();
-    let tmp = if occupied {
-        let t = temp_path(&original);
-        fs::rename(&original, &t)?;
-        Some(t)
-    } else {
-        None
-    };
-
-    // Restore to original path (now free)
-    if let Err(e) = restore_all(vec![item]) {
-        if let Some(ref t) = tmp {
-            let _ = fs::rename(t, &original);
-        }
-        return Err(e.into());
+    let selected = interact::parse_selection(indices, items.len())
+        .map_err(|e| TracheError::Invalid(format!("--trash-purge-n: {e}")))?;
+    if selected.is_empty() {
+        return Err(TracheError::Invalid(
+            "--trash-purge-n: no items selected".into(),
+        ));
     }
 
-    // Rename restored file to target
-    if let Err(e) = fs::rename(&original, target) {
-        if let Some(ref t) = tmp {
-            eprintln!(
-                "warning: could not rename restored file, original file left at {}",
-                t.display()
-            );
-        }
-        return Err(e.into());
+    let matching: Vec<_> = items
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| selected.binary_search(&(i + 1)).is_ok())
+        .map(|(_, item)| item)
+        .collect();
+    let matching = unpinned(matching)?;
+    if matching.is_empty() {
+        return Ok(());
     }
 
-    // Put existing file back at original path
-    if let Some(ref t) = tmp {
-        fs::rename(t, &original)?;
-    }
+    let prefix = if dry_run { "would purge" } else { "Purging" };
+    print_items(&matching, prefix);
 
+    if !dry_run {
+        purge_audited(matching, audit::Op::Purge)?;
+        println!("Permanently deleted item(s).");
+    }
     Ok(())
 }
 
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn purge_by_index(_indices: &str, _dry_run: bool) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "Purging trash is not supported on this platform".into(),
+    ))
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn purge_items(
+    _input: &mut dyn BufRead,
+    _pattern: &str,
+    _matcher: &CompiledMatcher,
+    _target: PatternTarget,
+    _opts: &PurgeOptions,
+) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "Purging trash is not supported on this platform".into(),
+    ))
+}
+
 #[cfg(any(
     target_os = "windows",
     all(unix, not(target_os = "macos"), not(target_os = "ios"))
 ))]
-fn handle_collision(
+/// --trash-purge-dir: purge everything trashed from `dir` or from inside it,
+/// after asking once unless `force`.
+fn purge_dir(
     input: &mut dyn BufRead,
-    item: trash::TrashItem,
-    path: &Path,
+    dir: &Path,
     dry_run: bool,
-    once: bool,
-    remembered_collision: &mut Option<CollisionChoice>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let choice = if let Some(c) = *remembered_collision {
-        eprintln!(
-            "{} already exists \u{2192} {} (remembered)",
-            path.display(),
-            collision_choice_name(c)
-        );
-        c
-    } else {
-        let f = find_untrash_range(path, 1);
-        let keep_name = untrash_name(path, f);
-        let c = prompt_collision(input, path, &keep_name, once);
-        if once && c != CollisionChoice::Quit {
-            *remembered_collision = Some(c);
-        }
-        c
-    };
+    force: bool,
+) -> Result<(), TracheError> {
+    let roots = items::scope_roots(std::slice::from_ref(&dir.to_path_buf()))?;
+    let mut items = items::list()?;
+    items.retain(|item| items::in_scope(&item.original_path(), &roots));
+    let items = unpinned(items)?;
+    if items.is_empty() {
+        println!("Nothing in the trash came from '{}'.", dir.display());
+        return Ok(());
+    }
 
     if dry_run {
-        match choice {
-            CollisionChoice::Overwrite => println!("would overwrite: {}", path.display()),
-            CollisionChoice::KeepBoth => {
-                let f = find_untrash_range(path, 1);
-                println!("would restore as: {}", untrash_name(path, f).display());
-            }
-            CollisionChoice::None => {}
-            CollisionChoice::Quit => std::process::exit(0),
-        }
+        print_items(&items, "would purge");
         return Ok(());
     }
-
-    match choice {
-        CollisionChoice::Quit => std::process::exit(0),
-        CollisionChoice::None => {}
-        CollisionChoice::Overwrite => {
-            if path.is_dir() {
-                fs::remove_dir_all(path)?;
-            } else {
-                fs::remove_file(path)?;
-            }
-            restore_all(vec![item])?;
-            println!("Overwritten: {}", path.display());
+    if !force {
+        for item in &items {
+            let ts = format_timestamp(item.time_deleted);
+            eprintln!("  {} ({ts})", item.original_path().display());
         }
-        CollisionChoice::KeepBoth => {
-            let f = find_untrash_range(path, 1);
-            let target = untrash_name(path, f);
-            restore_one_as(item, &target)?;
-            println!("Restored as: {}", target.display());
+        let question = format!(
+            "trache: permanently delete these {} item(s) trashed from '{}'? ",
+            items.len(),
+            dir.display()
+        );
+        if !prompt_yes(input, &question) {
+            println!("Nothing purged.");
+            return Ok(());
         }
     }
-
+    let count = items.len();
+    purge_audited(items, audit::Op::Purge)?;
+    println!("Permanently deleted {count} item(s).");
     Ok(())
 }
 
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn purge_dir(
+    _input: &mut dyn BufRead,
+    _dir: &Path,
+    _dry_run: bool,
+    _force: bool,
+) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "Purging trash is not supported on this platform".into(),
+    ))
+}
+
 #[cfg(any(
     target_os = "windows",
-    all(unix, not(target_os = "macos"), not(target_os = "ios"))
-))]
-fn restore_twins_renamed(
-    twins: Vec<trash::TrashItem>,
-    path: &Path,
-    start: usize,
-    dry_run: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
-    for (i, twin) in twins.into_iter().enumerate() {
-        let n = start + i;
-        let target = untrash_name(path, n);
-        if dry_run {
-            println!("would restore as: {}", target.display());
-        } else {
-            restore_one_as(twin, &target)?;
-            println!("Restored as: {}", target.display());
-        }
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+/// `items` without the pinned ones, saying how many were left alone.
+fn unpinned(items: Vec<trash::TrashItem>) -> io::Result<Vec<trash::TrashItem>> {
+    let pinned = pins::pinned_ids()?;
+    if pinned.is_empty() {
+        return Ok(items);
     }
-    Ok(())
+    let (kept, items): (Vec<_>, Vec<_>) = items
+        .into_iter()
+        .partition(|item| pinned.contains(&*item.id.to_string_lossy()));
+    if !kept.is_empty() {
+        eprintln!(
+            "trache: leaving {} pinned item(s) in the trash (see --trash-unpin)",
+            kept.len()
+        );
+    }
+    Ok(items)
 }
 
 #[cfg(any(
     target_os = "windows",
     all(unix, not(target_os = "macos"), not(target_os = "ios"))
 ))]
-fn handle_twin_selected(
-    input: &mut dyn BufRead,
-    selections: Vec<usize>,
-    twins: Vec<trash::TrashItem>,
-    path: &Path,
+/// Pin or unpin the items matching. Pins of items no longer in the trash
+/// are dropped along the way.
+fn pin_items(
+    pattern: &str,
+    matcher: &CompiledMatcher,
+    target: PatternTarget,
+    pin: bool,
     dry_run: bool,
-    once: bool,
-    remembered_collision: &mut Option<CollisionChoice>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let selected: Vec<trash::TrashItem> =
-        selections.iter().map(|&i| twins[i - 1].clone()).collect();
+) -> Result<(), TracheError> {
+    let file = pins::file_path().ok_or(TracheError::NoLocation(
+        "cannot determine where to keep pins",
+    ))?;
+    let items = items::list()?;
+    let matching: Vec<_> = items
+        .iter()
+        .filter(|item| item_matches(item, matcher, target))
+        .collect();
+    if matching.is_empty() {
+        return Err(TracheError::NoMatch(pattern.to_string()));
+    }
 
-    if selected.len() > 1 {
-        let start = find_untrash_range(path, selected.len());
-        restore_twins_renamed(selected, path, start, dry_run)?;
+    let present: std::collections::HashSet<_> =
+        items.iter().map(|item| item.id.to_string_lossy()).collect();
+    let mut pins = pins::load(&file)?;
+    pins.retain(|p| present.contains(p.id.as_str()));
+
+    let (done, would) = if pin {
+        ("Pinned", "would pin")
     } else {
-        let item = selected.into_iter().next().unwrap();
-        if path.exists() {
-            handle_collision(input, item, path, dry_run, once, remembered_collision)?;
-        } else if dry_run {
-            println!("would restore: {}", path.display());
-        } else {
-            restore_all(vec![item])?;
-            println!("Restored: {}", path.display());
+        ("Unpinned", "would unpin")
+    };
+    for item in matching {
+        let id = item.id.to_string_lossy();
+        let path = item.original_path();
+        let at = pins.iter().position(|p| *p.id == *id);
+        match (pin, at) {
+            (true, None) => pins.push(pins::Pin {
+                id: id.into_owned(),
+                path: path.clone(),
+            }),
+            (false, Some(at)) => {
+                pins.remove(at);
+            }
+            (true, Some(_)) => {
+                println!("Already pinned: {}", path.display());
+                continue;
+            }
+            (false, None) => {
+                println!("Not pinned: {}", path.display());
+                continue;
+            }
         }
+        let prefix = if dry_run { would } else { done };
+        println!("{prefix}: {}", path.display());
     }
 
+    if !dry_run {
+        pins::save(&file, &pins)?;
+    }
     Ok(())
 }
 
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn pin_items(
+    _pattern: &str,
+    _matcher: &CompiledMatcher,
+    _target: PatternTarget,
+    _pin: bool,
+    _dry_run: bool,
+) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "Pinning trashed items is not supported on this platform".into(),
+    ))
+}
+
 #[cfg(any(
     target_os = "windows",
     all(unix, not(target_os = "macos"), not(target_os = "ios"))
 ))]
-fn handle_twin_group(
-    input: &mut dyn BufRead,
-    path: &Path,
-    mut twins: Vec<trash::TrashItem>,
-    dry_run: bool,
-    once: bool,
-    remembered_twin: &mut Option<TwinChoice>,
-    remembered_collision: &mut Option<CollisionChoice>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    twins.sort_by_key(|t| t.time_deleted);
-    let count = twins.len();
-    let start = find_untrash_range(path, count);
-    let end = start + count - 1;
-    let range_desc = format_untrash_range(path, start, end);
+fn rename_items(from: &Path, to: &Path, dry_run: bool) -> Result<(), TracheError> {
+    let from = std::path::absolute(from)?;
+    let to = std::path::absolute(to)?;
 
-    let choice = if let Some(ref remembered) = *remembered_twin {
-        let desc = match remembered {
-            TwinChoice::All => format!("all (remembered): restoring as {range_desc}"),
-            TwinChoice::None => "none (remembered)".to_string(),
-            TwinChoice::Some(_) => "some (remembered)".to_string(),
-            TwinChoice::Quit => unreachable!(),
-        };
-        eprintln!("{} trashed {count} times \u{2192} {desc}", path.display());
-        remembered.clone()
-    } else {
-        let twin_infos: Vec<TwinInfo> = twins
-            .iter()
-            .map(|t| TwinInfo {
-                name: t.name.to_string_lossy().into_owned(),
-                timestamp: format_timestamp(t.time_deleted),
-            })
-            .collect();
-        let c = prompt_twins(input, path, &twin_infos, &range_desc, once);
-        if once && !matches!(c, TwinChoice::Quit) {
-            *remembered_twin = Some(match &c {
-                TwinChoice::Some(_) => TwinChoice::Some(vec![]),
-                other => other.clone(),
-            });
-        }
-        c
-    };
+    let mut matching: Vec<(trash::TrashItem, PathBuf)> = items::list()?
+        .into_iter()
+        .filter_map(|item| {
+            let rest = item.original_path().strip_prefix(&from).ok()?.to_path_buf();
+            let new_path = if rest.as_os_str().is_empty() {
+                to.clone()
+            } else {
+                to.join(rest)
+            };
+            Some((item, new_path))
+        })
+        .collect();
 
-    match choice {
-        TwinChoice::Quit => std::process::exit(0),
-        TwinChoice::None => {}
-        TwinChoice::All => {
-            restore_twins_renamed(twins, path, start, dry_run)?;
+    if matching.is_empty() {
+        println!("No items from '{}' found in trash.", from.display());
+        return Ok(());
+    }
+    matching.sort_by_key(|(item, _)| item.original_path());
+
+    let mut had_error = false;
+    for (item, new_path) in matching {
+        let old_path = item.original_path();
+        if dry_run {
+            println!(
+                "would rename: {} -> {}",
+                old_path.display(),
+                new_path.display()
+            );
+            continue;
         }
-        TwinChoice::Some(selections) => {
-            if selections.is_empty() {
-                // Remembered "some" — re-prompt for selection
-                for (i, twin) in twins.iter().enumerate() {
-                    let ts = format_timestamp(twin.time_deleted);
-                    eprintln!("  {}: {} ({})", i + 1, twin.name.to_string_lossy(), ts);
-                }
-                if let Some(sel) = prompt_selection(input, count) {
-                    handle_twin_selected(
-                        input,
-                        sel,
-                        twins,
-                        path,
-                        dry_run,
-                        once,
-                        remembered_collision,
-                    )?;
-                }
-            } else {
-                handle_twin_selected(
-                    input,
-                    selections,
-                    twins,
-                    path,
-                    dry_run,
-                    once,
-                    remembered_collision,
-                )?;
+
+        let result = set_original_path(item, &new_path);
+        audit::record(audit::Op::Rename, &old_path, &result);
+        match result {
+            Ok(()) => println!("Renamed: {} -> {}", old_path.display(), new_path.display()),
+            Err(e) => {
+                eprintln!("trache: cannot rename '{}': {e}", old_path.display());
+                had_error = true;
             }
         }
     }
 
+    if had_error {
+        Err(TracheError::Incomplete(
+            "some items could not be renamed".into(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Re-point a trash item at a new original path.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+fn set_original_path(item: trash::TrashItem, new_path: &Path) -> Result<(), TracheError> {
+    items::set_original_path(&item, new_path)?;
+    Ok(())
+}
+
+/// Re-point a trash item at a new original path.
+#[cfg(target_os = "windows")]
+fn set_original_path(item: trash::TrashItem, new_path: &Path) -> Result<(), TracheError> {
+    // Recycle Bin metadata can't be edited, so re-adopt the item instead:
+    // restore it at its new location and trash it again from there
+    match new_path.parent() {
+        Some(parent) if parent.is_dir() => {}
+        _ => {
+            let e = format!("'{}' does not exist", new_path.display());
+            return Err(TracheError::io_kind(io::ErrorKind::NotFound, e));
+        }
+    }
+    if new_path.symlink_metadata().is_ok() {
+        let e = format!("'{}' already exists", new_path.display());
+        return Err(TracheError::io_kind(io::ErrorKind::AlreadyExists, e));
+    }
+    restore_one_as(item, new_path)?;
+    backend::get().delete(new_path)?;
     Ok(())
 }
 
+/// --mv: move `src` to `dst` and record it in the move journal.
 #[cfg(any(
     target_os = "windows",
     all(unix, not(target_os = "macos"), not(target_os = "ios"))
 ))]
-fn restore_items_interactive(
-    input: &mut dyn BufRead,
-    matching: Vec<trash::TrashItem>,
-    dry_run: bool,
-    interactive: InteractiveMode,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut groups: std::collections::HashMap<PathBuf, Vec<trash::TrashItem>> =
-        std::collections::HashMap::new();
-    for item in matching {
-        groups.entry(item.original_path()).or_default().push(item);
+fn move_recorded(src: &Path, dst: &Path, dry_run: bool) -> Result<(), TracheError> {
+    let journal = moves::journal_path().ok_or(TracheError::NoLocation(
+        "cannot determine the move journal location",
+    ))?;
+    let src = std::path::absolute(src)?;
+    let mut dst = std::path::absolute(dst)?;
+    if src.symlink_metadata().is_err() {
+        let e = format!("cannot stat '{}': No such file or directory", src.display());
+        return Err(TracheError::io_kind(io::ErrorKind::NotFound, e));
     }
-
-    let mut twin_groups: Vec<(PathBuf, Vec<trash::TrashItem>)> = Vec::new();
-    let mut singletons: Vec<trash::TrashItem> = Vec::new();
-    for (path, items) in groups {
-        if items.len() > 1 {
-            twin_groups.push((path, items));
-        } else {
-            singletons.extend(items);
-        }
+    if dst.is_dir() {
+        let Some(name) = src.file_name() else {
+            return Err(TracheError::Invalid(format!(
+                "cannot move '{}'",
+                src.display()
+            )));
+        };
+        dst.push(name);
+    }
+    if dst.symlink_metadata().is_ok() {
+        let e = format!("'{}' already exists", dst.display());
+        return Err(TracheError::io_kind(io::ErrorKind::AlreadyExists, e));
+    }
+    if dst.starts_with(&src) {
+        return Err(TracheError::Invalid(format!(
+            "cannot move '{}' into itself ('{}')",
+            src.display(),
+            dst.display()
+        )));
     }
 
-    twin_groups.sort_by(|a, b| a.0.cmp(&b.0));
-    singletons.sort_by_key(|a| a.original_path());
+    if dry_run {
+        println!("would move: {} -> {}", src.display(), dst.display());
+        return Ok(());
+    }
 
-    let once = interactive == InteractiveMode::Once;
-    let mut remembered_twin: Option<TwinChoice> = None;
-    let mut remembered_collision: Option<CollisionChoice> = None;
+    let result = move_path(&src, &dst);
+    audit::record(audit::Op::Move, &src, &result);
+    result.map_err(|e| TracheError::io(format!("cannot move '{}'", src.display()), e))?;
+    let id =
+        moves::record(&journal, &src, &dst, chrono::Local::now().timestamp()).map_err(|e| {
+            let context = format!(
+                "moved '{}', but cannot record it in '{}'",
+                src.display(),
+                journal.display()
+            );
+            TracheError::io(context, e)
+        })?;
+    println!(
+        "Moved: {} -> {} (undo with --trash-undo-op={id})",
+        src.display(),
+        dst.display()
+    );
+    Ok(())
+}
 
-    for (path, twins) in twin_groups {
-        handle_twin_group(
-            input,
-            &path,
-            twins,
-            dry_run,
-            once,
-            &mut remembered_twin,
-            &mut remembered_collision,
-        )?;
+/// --trash-undo-op: move a recorded --mv back to where it came from.
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"))
+))]
+fn undo_move(id: Option<u64>, dry_run: bool) -> Result<(), TracheError> {
+    let journal = moves::journal_path().ok_or(TracheError::NoLocation(
+        "cannot determine the move journal location",
+    ))?;
+    let recorded = moves::load(&journal)
+        .map_err(|e| TracheError::io(format!("cannot read '{}'", journal.display()), e))?;
+    let entry = moves::select(&recorded, id).map_err(TracheError::Invalid)?;
+    if entry.to.symlink_metadata().is_err() {
+        let e = format!(
+            "'{}' no longer exists; cannot undo move {}",
+            entry.to.display(),
+            entry.id
+        );
+        return Err(TracheError::io_kind(io::ErrorKind::NotFound, e));
+    }
+    if entry.from.symlink_metadata().is_ok() {
+        let e = format!(
+            "'{}' already exists; cannot undo move {}",
+            entry.from.display(),
+            entry.id
+        );
+        return Err(TracheError::io_kind(io::ErrorKind::AlreadyExists, e));
     }
 
-    for item in singletons {
-        let path = item.original_path();
-        if path.exists() {
-            handle_collision(input, item, &path, dry_run, once, &mut remembered_collision)?;
-        } else if dry_run {
-            println!("would restore: {}", path.display());
-        } else {
-            restore_all(vec![item])?;
-            println!("Restored: {}", path.display());
-        }
+    if dry_run {
+        println!(
+            "would move back: {} -> {}",
+            entry.to.display(),
+            entry.from.display()
+        );
+        return Ok(());
     }
 
+    if let Some(parent) = entry.from.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let result = move_path(&entry.to, &entry.from);
+    audit::record(audit::Op::Move, &entry.to, &result);
+    result.map_err(|e| TracheError::io(format!("cannot move '{}'", entry.to.display()), e))?;
+    moves::forget(&journal, entry.id)
+        .map_err(|e| TracheError::io(format!("cannot update '{}'", journal.display()), e))?;
+    println!(
+        "Moved back: {} -> {}",
+        entry.to.display(),
+        entry.from.display()
+    );
     Ok(())
 }
 
 #[cfg(any(target_os = "macos", target_os = "ios"))]
-fn restore_items(
-    _input: &mut dyn BufRead,
-    _pattern: &str,
-    _matcher: &CompiledMatcher,
-    _target: PatternTarget,
-    _dry_run: bool,
-    _interactive: InteractiveMode,
-) -> Result<(), Box<dyn std::error::Error>> {
-    Err("Restoring from trash is not supported on this platform".into())
+fn move_recorded(_src: &Path, _dst: &Path, _dry_run: bool) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "--mv is not supported on this platform".into(),
+    ))
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn undo_move(_id: Option<u64>, _dry_run: bool) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "--trash-undo-op is not supported on this platform".into(),
+    ))
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn rename_items(_from: &Path, _to: &Path, _dry_run: bool) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "Renaming trash items is not supported on this platform".into(),
+    ))
 }
 
 #[cfg(any(
     target_os = "windows",
     all(unix, not(target_os = "macos"), not(target_os = "ios"))
 ))]
-fn purge_items(
-    pattern: &str,
-    matcher: &CompiledMatcher,
-    target: PatternTarget,
+fn clean_trash(
+    rules: &config::Retention,
     dry_run: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let items = list()?;
-    let matching: Vec<_> = items
-        .into_iter()
-        .filter(|item| {
-            let haystack = match target {
-                PatternTarget::Name => item.name.to_string_lossy().into_owned(),
-                PatternTarget::Path => item.original_path().to_string_lossy().into_owned(),
-            };
-            matcher.is_match(&haystack)
+    porcelain: bool,
+) -> Result<(), TracheError> {
+    let policies = retention::policies(rules).map_err(TracheError::Invalid)?;
+    let overrides = retention::overrides(rules).map_err(TracheError::Invalid)?;
+    if policies.iter().all(retention::Rules::is_empty) && overrides.is_empty() {
+        return Err(TracheError::Invalid(
+            "no retention rules configured (see --help for [retention])".into(),
+        ));
+    }
+    let mounts: Vec<&Path> = rules.mounts.iter().map(|m| m.path.as_path()).collect();
+
+    let mut protect = Vec::new();
+    for raw in &rules.protect {
+        let parsed = parse_pattern(raw);
+        // Protection errs on the side of keeping dotfiles
+        let matcher = compile_matcher(&parsed, true)
+            .map_err(|e| TracheError::Invalid(format!("retention.protect '{raw}': {e}")))?;
+        protect.push((matcher, parsed.target));
+    }
+    let mut patterns = Vec::new();
+    for rule in &rules.rules {
+        let parsed = parse_pattern(&rule.pattern);
+        let matcher = compile_matcher(&parsed, true)
+            .map_err(|e| TracheError::Invalid(format!("retention.rule '{}': {e}", rule.pattern)))?;
+        patterns.push((matcher, parsed.target));
+    }
+
+    let items = items::list()?;
+    let pinned = pins::pinned_ids()?;
+    let need_sizes = policies.iter().any(|p| p.max_total_size.is_some()) || porcelain;
+    let candidates: Vec<retention::Candidate> = items
+        .iter()
+        .map(|item| retention::Candidate {
+            time_deleted: item.time_deleted,
+            size: if need_sizes {
+                items::item_size(item)
+            } else {
+                0
+            },
+            protected: pinned.contains(&*item.id.to_string_lossy())
+                || protect.iter().any(|(m, t)| item_matches(item, m, *t)),
+            path: item.original_path(),
+            policy: retention::mount_policy(&mounts, &items::trash_dir(item)),
+            rule: patterns.iter().position(|(m, t)| item_matches(item, m, *t)),
         })
         .collect();
 
-    if matching.is_empty() {
-        println!("No items matching '{pattern}' found in trash.");
-        return Ok(());
+    let now = chrono::Utc::now().timestamp();
+    let plan = retention::plan(&candidates, &policies, &overrides, now);
+
+    let mut summary = retention::CleanSummary {
+        dry_run,
+        purged: plan.len(),
+        purged_bytes: plan.iter().map(|&(i, _)| candidates[i].size).sum(),
+        remaining: items.len() - plan.len(),
+        protected: candidates.iter().filter(|c| c.protected).count(),
+        items: Vec::new(),
+    };
+
+    let prefix = if dry_run { "would purge" } else { "Purged" };
+    for &(i, reason) in &plan {
+        let path = items[i].original_path();
+        if porcelain {
+            summary.items.push(retention::CleanedItem {
+                path: path.to_string_lossy().into_owned(),
+                size: candidates[i].size,
+                reason,
+            });
+        } else {
+            println!("{prefix} ({}): {}", reason.describe(), path.display());
+        }
     }
 
-    let prefix = if dry_run { "would purge" } else { "Purging" };
-    print_items(&matching, prefix);
+    if !dry_run && !plan.is_empty() {
+        purge_audited(plan.iter().map(|&(i, _)| &items[i]), audit::Op::Purge)?;
+    }
 
-    if !dry_run {
-        purge_all(matching)?;
-        println!("Permanently deleted item(s).");
+    if porcelain {
+        println!("{}", serde_json::to_string(&summary)?);
     }
     Ok(())
 }
 
 #[cfg(any(target_os = "macos", target_os = "ios"))]
-fn purge_items(
-    _pattern: &str,
-    _matcher: &CompiledMatcher,
-    _target: PatternTarget,
+fn clean_trash(
+    _rules: &config::Retention,
     _dry_run: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
-    Err("Purging trash is not supported on this platform".into())
+    _porcelain: bool,
+) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "Cleaning trash is not supported on this platform".into(),
+    ))
 }
 
 #[cfg(any(
     target_os = "windows",
     all(unix, not(target_os = "macos"), not(target_os = "ios"))
 ))]
-fn empty_trash() -> Result<(), Box<dyn std::error::Error>> {
-    let items = list()?;
+fn empty_trash(scope: &[PathBuf], dry_run: bool) -> Result<(), TracheError> {
+    if scope.is_empty() && dry_run {
+        println!("would empty trash");
+        return Ok(());
+    }
+    let roots = items::scope_roots(scope)?;
+    let mut items = items::list()?;
+    items.retain(|item| items::in_scope(&item.original_path(), &roots));
+    let pinned = items.len();
+    let items = unpinned(items)?;
+    let pinned = pinned - items.len();
 
+    if items.is_empty() && pinned > 0 {
+        return Ok(());
+    }
     if items.is_empty() {
-        println!("Trash is already empty.");
+        if scope.is_empty() {
+            println!("Trash is already empty.");
+        } else {
+            println!("Nothing in the trash came from there.");
+        }
         return Ok(());
     }
 
+    if dry_run {
+        print_items(&items, "would purge");
+        return Ok(());
+    }
     let count = items.len();
-    purge_all(items)?;
+    purge_audited(items, audit::Op::Empty)?;
     println!("Permanently deleted {count} item(s).");
     Ok(())
 }
 
 #[cfg(target_os = "macos")]
-fn empty_trash() -> Result<(), Box<dyn std::error::Error>> {
+fn empty_trash(scope: &[PathBuf], dry_run: bool) -> Result<(), TracheError> {
+    if !scope.is_empty() {
+        return Err(TracheError::Unsupported(
+            "emptying only part of the trash is not supported on this platform".into(),
+        ));
+    }
+    if dry_run {
+        println!("would empty trash");
+        return Ok(());
+    }
     let output = std::process::Command::new("osascript")
         .arg("-e")
         .arg("tell application \"Finder\" to empty trash")
         .output()?;
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("osascript failed: {stderr}").into());
+        return Err(TracheError::Other(format!("osascript failed: {stderr}")));
     }
     println!("Trash emptied.");
     Ok(())
 }
 
 #[cfg(target_os = "ios")]
-fn empty_trash() -> Result<(), Box<dyn std::error::Error>> {
-    Err("Emptying trash is not supported on this platform".into())
+fn empty_trash(_scope: &[PathBuf], _dry_run: bool) -> Result<(), TracheError> {
+    Err(TracheError::Unsupported(
+        "Emptying trash is not supported on this platform".into(),
+    ))
 }
@@ -0,0 +1,180 @@
+// --sync-with: find what under a directory isn't in a list of expected
+// files, so it can be trashed. The list is one path per line, or
+// NUL-separated (as from `find -print0`) if it contains any NUL bytes.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+pub struct Manifest {
+    /// Listed paths, relative to the directory being synced
+    listed: HashSet<PathBuf>,
+    /// Every directory that has something listed below it
+    ancestors: HashSet<PathBuf>,
+}
+
+impl Manifest {
+    /// Read a manifest file ("-" for stdin). Relative entries are taken
+    /// relative to `dir`; absolute ones must lie inside it.
+    pub fn load(path: &Path, dir: &Path) -> Result<Self, String> {
+        let mut bytes = Vec::new();
+        let read = if path == Path::new("-") {
+            io::stdin().read_to_end(&mut bytes).map(|_| ())
+        } else {
+            fs::File::open(path).and_then(|mut f| f.read_to_end(&mut bytes).map(|_| ()))
+        };
+        read.map_err(|e| format!("cannot read manifest '{}': {e}", path.display()))?;
+        Self::parse(&bytes, dir)
+    }
+
+    fn parse(bytes: &[u8], dir: &Path) -> Result<Self, String> {
+        let separator = if bytes.contains(&0) { 0 } else { b'\n' };
+        let mut manifest = Manifest {
+            listed: HashSet::new(),
+            ancestors: HashSet::new(),
+        };
+        for entry in bytes.split(|&b| b == separator) {
+            let entry = entry.strip_suffix(b"\r").unwrap_or(entry);
+            if entry.is_empty() {
+                continue;
+            }
+            let path = entry_path(entry);
+            let relative = if path.is_absolute() {
+                path.strip_prefix(dir).map_err(|_| {
+                    format!(
+                        "manifest entry '{}' is outside '{}'",
+                        path.display(),
+                        dir.display()
+                    )
+                })?
+            } else {
+                &path
+            };
+            let relative = normalize(relative).ok_or_else(|| {
+                format!("manifest entry '{}' escapes the directory", path.display())
+            })?;
+            for ancestor in relative.ancestors().skip(1) {
+                manifest.ancestors.insert(ancestor.to_path_buf());
+            }
+            manifest.listed.insert(relative);
+        }
+        Ok(manifest)
+    }
+
+    /// Everything under `dir` that isn't listed: whole directories when
+    /// nothing inside them is listed, individual entries otherwise.
+    pub fn extras(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut found = Vec::new();
+        self.walk(dir, Path::new(""), &mut found)?;
+        Ok(found)
+    }
+
+    fn walk(&self, dir: &Path, rel: &Path, found: &mut Vec<PathBuf>) -> io::Result<()> {
+        let mut entries: Vec<_> = fs::read_dir(dir)?.collect::<io::Result<_>>()?;
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries {
+            let entry_rel = rel.join(entry.file_name());
+            if self.listed.contains(&entry_rel) {
+                continue;
+            }
+            if self.ancestors.contains(&entry_rel) && entry.file_type()?.is_dir() {
+                self.walk(&entry.path(), &entry_rel, found)?;
+            } else {
+                found.push(entry.path());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A manifest entry as a path. Names are bytes on unix, so they're taken
+/// as they are; elsewhere they must be UTF-8.
+#[cfg(unix)]
+fn entry_path(entry: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(entry))
+}
+
+#[cfg(not(unix))]
+fn entry_path(entry: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(entry).into_owned())
+}
+
+/// `path` without `.` components and with `..` resolved, or None if it
+/// climbs out of its base.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => out.push(name),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !out.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::tree;
+
+    fn extras(tmp: &tempfile::TempDir, manifest: &str) -> Vec<String> {
+        let manifest = Manifest::parse(manifest.as_bytes(), tmp.path()).unwrap();
+        manifest
+            .extras(tmp.path())
+            .unwrap()
+            .iter()
+            .map(|p| p.strip_prefix(tmp.path()).unwrap().display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_extras_lines() {
+        let tmp = tree(&[
+            "keep.txt",
+            "junk.txt",
+            "app/bin",
+            "app/old.log",
+            "cache/a",
+            "cache/b",
+        ]);
+        assert_eq!(
+            extras(&tmp, "keep.txt\napp/bin\n"),
+            ["app/old.log", "cache", "junk.txt"]
+        );
+    }
+
+    #[test]
+    fn test_extras_nul_separated_and_dirs() {
+        let tmp = tree(&["a b.txt", "lib/x", "lib/y", "tmp/"]);
+        assert_eq!(extras(&tmp, "./a b.txt\0lib\0"), ["tmp"]);
+    }
+
+    #[test]
+    fn test_absolute_entries() {
+        let tmp = tree(&["a", "b"]);
+        let listed = format!("{}\n", tmp.path().join("a").display());
+        assert_eq!(extras(&tmp, &listed), ["b"]);
+        assert!(Manifest::parse(b"/elsewhere/a\n", tmp.path()).is_err());
+        assert!(Manifest::parse(b"../a\n", tmp.path()).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_names_that_are_not_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+        let tmp = tree(&["b"]);
+        let name = std::ffi::OsStr::from_bytes(b"caf\xe9");
+        fs::write(tmp.path().join(name), "").unwrap();
+        let manifest = Manifest::parse(b"caf\xe9\n", tmp.path()).unwrap();
+        let extras = manifest.extras(tmp.path()).unwrap();
+        assert_eq!(extras, [tmp.path().join("b")]);
+    }
+}
@@ -0,0 +1,185 @@
+// Journal of moves made with --mv, so --trash-undo-op can put them back.
+// One JSON object per line in the state directory; undone moves are removed.
+// IDs are never reused: the last one given out is kept beside the journal,
+// so undoing the newest move doesn't hand its ID to the next.
+#![cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config, lock};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Move {
+    pub id: u64,
+    /// Seconds since the epoch
+    pub time: i64,
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// Journal location: $XDG_STATE_HOME/trache/moves.jsonl (or the platform equivalent).
+pub fn journal_path() -> Option<PathBuf> {
+    config::state_dir().map(|d| d.join("moves.jsonl"))
+}
+
+/// Every recorded move, oldest first. A missing journal has no moves.
+pub fn load(journal: &Path) -> io::Result<Vec<Move>> {
+    let text = match fs::read_to_string(journal) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn parse(text: &str) -> Result<Vec<Move>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| serde_json::from_str(line).map_err(|e| format!("line {}: {e}", i + 1)))
+        .collect()
+}
+
+/// Where the last ID given out is kept, next to `journal`.
+fn last_id_path(journal: &Path) -> PathBuf {
+    journal.with_extension("last-id")
+}
+
+/// The last ID given out. Journals from before there was a record of it
+/// go by the highest ID in them.
+fn last_id(journal: &Path, moves: &[Move]) -> io::Result<u64> {
+    let recorded = match fs::read_to_string(last_id_path(journal)) {
+        Ok(text) => text.trim().parse().unwrap_or(0),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+        Err(e) => return Err(e),
+    };
+    Ok(moves.iter().map(|m| m.id).fold(recorded, u64::max))
+}
+
+/// Append a move to the journal and return its ID.
+pub fn record(journal: &Path, from: &Path, to: &Path, time: i64) -> io::Result<u64> {
+    let _lock = lock::file(journal)?;
+    let id = last_id(journal, &load(journal)?)? + 1;
+    let entry = Move {
+        id,
+        time,
+        from: from.to_path_buf(),
+        to: to.to_path_buf(),
+    };
+    if let Some(dir) = journal.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let line = serde_json::to_string(&entry).map_err(io::Error::other)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(journal)?;
+    writeln!(file, "{line}")?;
+    let tmp = journal.with_extension("last-id.tmp");
+    fs::write(&tmp, format!("{id}\n"))?;
+    fs::rename(&tmp, last_id_path(journal))?;
+    Ok(id)
+}
+
+/// Drop the move with `id` from the journal.
+pub fn forget(journal: &Path, id: u64) -> io::Result<()> {
+    let _lock = lock::file(journal)?;
+    let mut text = String::new();
+    for entry in load(journal)?.into_iter().filter(|m| m.id != id) {
+        text.push_str(&serde_json::to_string(&entry).map_err(io::Error::other)?);
+        text.push('\n');
+    }
+    // Write a sibling and rename it over, so a crash can't truncate the journal
+    let tmp = journal.with_extension("jsonl.tmp");
+    fs::write(&tmp, text)?;
+    fs::rename(&tmp, journal)
+}
+
+/// The move to undo: the one with `id`, or the most recent.
+pub fn select(moves: &[Move], id: Option<u64>) -> Result<&Move, String> {
+    match id {
+        Some(id) => moves
+            .iter()
+            .find(|m| m.id == id)
+            .ok_or_else(|| format!("no recorded move with ID {id}")),
+        None => moves
+            .last()
+            .ok_or_else(|| "no recorded moves to undo".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_assigns_increasing_ids() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let journal = tmp.path().join("state").join("moves.jsonl");
+
+        assert_eq!(
+            record(&journal, Path::new("/a"), Path::new("/b"), 10).unwrap(),
+            1
+        );
+        assert_eq!(
+            record(&journal, Path::new("/c"), Path::new("/d"), 20).unwrap(),
+            2
+        );
+        // An undone move's ID isn't given out again
+        forget(&journal, 2).unwrap();
+        assert_eq!(
+            record(&journal, Path::new("/e"), Path::new("/f"), 30).unwrap(),
+            3
+        );
+        forget(&journal, 1).unwrap();
+        forget(&journal, 3).unwrap();
+        assert_eq!(
+            record(&journal, Path::new("/g"), Path::new("/h"), 40).unwrap(),
+            4
+        );
+
+        let moves = load(&journal).unwrap();
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].from, Path::new("/g"));
+    }
+
+    #[test]
+    fn test_forget_keeps_other_moves() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let journal = tmp.path().join("moves.jsonl");
+        record(&journal, Path::new("/a"), Path::new("/b"), 10).unwrap();
+        record(&journal, Path::new("/c"), Path::new("/d"), 20).unwrap();
+
+        forget(&journal, 1).unwrap();
+
+        let ids: Vec<u64> = load(&journal).unwrap().iter().map(|m| m.id).collect();
+        assert_eq!(ids, [2]);
+    }
+
+    #[test]
+    fn test_missing_journal_is_empty() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        assert!(load(&tmp.path().join("moves.jsonl")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_reports_bad_lines() {
+        let err =
+            parse("{\"id\":1,\"time\":0,\"from\":\"/a\",\"to\":\"/b\"}\nnot json\n").unwrap_err();
+        assert!(err.starts_with("line 2:"), "{err}");
+    }
+
+    #[test]
+    fn test_select_defaults_to_latest() {
+        let moves = parse(
+            "{\"id\":1,\"time\":0,\"from\":\"/a\",\"to\":\"/b\"}\n\
+             {\"id\":3,\"time\":5,\"from\":\"/c\",\"to\":\"/d\"}\n",
+        )
+        .unwrap();
+        assert_eq!(select(&moves, None).unwrap().id, 3);
+        assert_eq!(select(&moves, Some(1)).unwrap().from, Path::new("/a"));
+        assert!(select(&moves, Some(2)).is_err());
+        assert!(select(&[], None).is_err());
+    }
+}
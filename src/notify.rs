@@ -0,0 +1,157 @@
+// Desktop notifications for --notify: once a run has trashed, restored or
+// purged enough items, or taken long enough, a summary is shown with the
+// desktop's own notifier (notify-send, osascript or a PowerShell toast), so
+// runs from a file manager or a timer don't finish unseen. The thresholds
+// come from [notify] in config.toml.
+
+use std::collections::BTreeMap;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::audit::Op;
+use crate::config;
+
+pub const DEFAULT_MIN_ITEMS: usize = 10;
+pub const DEFAULT_MIN_SECONDS: u64 = 5;
+
+struct Notifier {
+    start: Instant,
+    min_items: usize,
+    min_time: Duration,
+    /// Items done so far, by what was done to them
+    counts: BTreeMap<&'static str, usize>,
+}
+
+static NOTIFIER: Mutex<Option<Notifier>> = Mutex::new(None);
+
+/// Start timing the run, for a notification at the end.
+pub fn init(cfg: &config::Notify) {
+    *NOTIFIER.lock().unwrap() = Some(Notifier {
+        start: Instant::now(),
+        min_items: cfg.min_items.unwrap_or(DEFAULT_MIN_ITEMS),
+        min_time: Duration::from_secs(cfg.min_seconds.unwrap_or(DEFAULT_MIN_SECONDS)),
+        counts: BTreeMap::new(),
+    });
+}
+
+/// Count an item `op` succeeded on.
+pub fn record(op: Op) {
+    let done = match op {
+        Op::Trash => "Trashed",
+        Op::Restore => "Restored",
+        Op::Purge | Op::Empty => "Purged",
+        Op::Shred => "Shredded",
+        Op::Delete => "Deleted",
+        _ => return,
+    };
+    if let Some(notifier) = NOTIFIER.lock().unwrap().as_mut() {
+        *notifier.counts.entry(done).or_default() += 1;
+    }
+}
+
+/// Show the summary if the run was big or long enough. `error` is how the
+/// run failed, if it did. Only the first call does anything; called by
+/// status::finish.
+pub fn finish(error: Option<&str>) {
+    let Some(notifier) = NOTIFIER.lock().unwrap().take() else {
+        return;
+    };
+    let elapsed = notifier.start.elapsed();
+    let items: usize = notifier.counts.values().sum();
+    if items < notifier.min_items.max(1) && elapsed < notifier.min_time {
+        return;
+    }
+    let body = summary(&notifier.counts, elapsed, error);
+    if let Err(e) = send("trache", &body) {
+        eprintln!("trache: warning: cannot show notification: {e}");
+    }
+}
+
+fn summary(counts: &BTreeMap<&str, usize>, elapsed: Duration, error: Option<&str>) -> String {
+    let mut parts: Vec<String> = counts
+        .iter()
+        .map(|(done, n)| format!("{done} {n} item(s)"))
+        .collect();
+    if parts.is_empty() {
+        parts.push("Nothing done".to_string());
+    }
+    let mut body = format!("{} in {}s", parts.join(", "), elapsed.as_secs());
+    if let Some(e) = error {
+        body.push_str(&format!("; {e}"));
+    }
+    body
+}
+
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+fn notifier(title: &str, body: &str) -> Command {
+    let mut cmd = Command::new("notify-send");
+    cmd.args(["--app-name=trache", title, body]);
+    cmd
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn notifier(title: &str, body: &str) -> Command {
+    let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+    let mut cmd = Command::new("osascript");
+    cmd.arg("-e").arg(format!(
+        "display notification {} with title {}",
+        quote(body),
+        quote(title)
+    ));
+    cmd
+}
+
+#[cfg(windows)]
+fn notifier(title: &str, body: &str) -> Command {
+    // The texts go in as environment variables, so nothing needs quoting
+    const SCRIPT: &str = "[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] > $null; \
+        $xml = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02); \
+        $texts = $xml.GetElementsByTagName('text'); \
+        $texts.Item(0).AppendChild($xml.CreateTextNode($env:TRACHE_TITLE)) > $null; \
+        $texts.Item(1).AppendChild($xml.CreateTextNode($env:TRACHE_BODY)) > $null; \
+        [Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('trache').Show([Windows.UI.Notifications.ToastNotification]::new($xml))";
+    let mut cmd = Command::new("powershell");
+    cmd.args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+        .env("TRACHE_TITLE", title)
+        .env("TRACHE_BODY", body);
+    cmd
+}
+
+fn send(title: &str, body: &str) -> Result<(), String> {
+    let mut cmd = notifier(title, body);
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let status = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .status()
+        .map_err(|e| format!("{program}: {e}"))?;
+    if !status.success() {
+        return Err(format!("{program} exited with {status}"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let mut counts = BTreeMap::new();
+        counts.insert("Trashed", 12);
+        counts.insert("Deleted", 1);
+        assert_eq!(
+            summary(&counts, Duration::from_millis(7400), None),
+            "Deleted 1 item(s), Trashed 12 item(s) in 7s"
+        );
+        assert_eq!(
+            summary(
+                &BTreeMap::new(),
+                Duration::from_secs(9),
+                Some("some files could not be removed")
+            ),
+            "Nothing done in 9s; some files could not be removed"
+        );
+    }
+}
@@ -0,0 +1,104 @@
+// -x/--one-file-system inside the tree: like GNU rm, a recursive removal
+// descends into a directory argument and leaves alone the subtrees mounted
+// from other file systems, removing everything else. Only the directories
+// leading to such a subtree are kept; every other entry is trashed whole.
+#![cfg_attr(not(unix), allow(dead_code))]
+
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A directory argument split at the file systems mounted inside it.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Split {
+    /// Entries wholly on the argument's file system, to trash one by one
+    pub parts: Vec<PathBuf>,
+    /// Directories on other file systems, left alone
+    pub foreign: Vec<PathBuf>,
+}
+
+/// How `dir` splits; None when nothing inside it is on another file system,
+/// so it can be trashed whole.
+#[cfg(unix)]
+pub fn split(dir: &Path) -> io::Result<Option<Split>> {
+    use std::os::unix::fs::MetadataExt;
+
+    split_by(dir, &|_, meta| meta.dev())
+}
+
+#[cfg(not(unix))]
+pub fn split(_dir: &Path) -> io::Result<Option<Split>> {
+    Ok(None)
+}
+
+/// The device of a path, given its metadata
+type Device<'a> = &'a dyn Fn(&Path, &Metadata) -> u64;
+
+fn split_by(dir: &Path, device: Device) -> io::Result<Option<Split>> {
+    let dev = device(dir, &dir.symlink_metadata()?);
+    let mut split = Split::default();
+    Ok((!collect(dir, dev, device, &mut split)?).then_some(split))
+}
+
+/// Add what's inside `dir` to `split`; whether all of it is on `dev`, in
+/// which case `split` is left as it was.
+fn collect(dir: &Path, dev: u64, device: Device, split: &mut Split) -> io::Result<bool> {
+    let mut entries: Vec<_> = fs::read_dir(dir)?.collect::<io::Result<_>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    let mut inside = Split::default();
+    let mut whole = true;
+    for entry in entries {
+        let path = entry.path();
+        let meta = fs::symlink_metadata(&path)?;
+        if !meta.is_dir() {
+            inside.parts.push(path);
+        } else if device(&path, &meta) != dev {
+            inside.foreign.push(path);
+            whole = false;
+        } else if collect(&path, dev, device, &mut inside)? {
+            inside.parts.push(path);
+        } else {
+            whole = false;
+        }
+    }
+    if !whole {
+        split.parts.append(&mut inside.parts);
+        split.foreign.append(&mut inside.foreign);
+    }
+    Ok(whole)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::tree;
+
+    /// Splits `tmp` as if every directory named "mnt" were a mount.
+    fn split_at_mnt(tmp: &tempfile::TempDir) -> Option<(Vec<String>, Vec<String>)> {
+        let root = tmp.path();
+        let device = |path: &Path, _: &Metadata| u64::from(path.ends_with("mnt"));
+        let relative = |paths: Vec<PathBuf>| -> Vec<String> {
+            paths
+                .iter()
+                .map(|p| p.strip_prefix(root).unwrap().display().to_string())
+                .collect()
+        };
+        split_by(root, &device)
+            .unwrap()
+            .map(|s| (relative(s.parts), relative(s.foreign)))
+    }
+
+    #[test]
+    fn test_whole_tree_is_not_split() {
+        let tmp = tree(&["a/x", "b/c/y", "z"]);
+        assert_eq!(split_at_mnt(&tmp), None);
+    }
+
+    #[test]
+    fn test_split_keeps_the_way_to_mounts() {
+        let tmp = tree(&["a/mnt/inside", "a/x", "b/c/mnt/", "b/c/y", "b/d/z", "top"]);
+        let (parts, foreign) = split_at_mnt(&tmp).unwrap();
+        assert_eq!(parts, ["a/x", "b/c/y", "b/d", "top"]);
+        assert_eq!(foreign, ["a/mnt", "b/c/mnt"]);
+    }
+}
@@ -0,0 +1,100 @@
+// Items pinned with --trash-pin, which --trash-empty, --trash-purge and
+// --trash-clean leave where they are. The pins are kept beside the trash
+// rather than in it, so other trash tools never see them: one JSON object
+// per line in the state directory, keyed by item ID. Pins whose item has
+// left the trash are dropped the next time the file is written.
+#![cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pin {
+    pub id: String,
+    /// The item's original path, for whoever reads the file
+    pub path: PathBuf,
+}
+
+/// Pin file location: $XDG_STATE_HOME/trache/pins.jsonl (or the platform equivalent).
+pub fn file_path() -> Option<PathBuf> {
+    config::state_dir().map(|d| d.join("pins.jsonl"))
+}
+
+/// Every pin. A missing file has none.
+pub fn load(file: &Path) -> io::Result<Vec<Pin>> {
+    let text = match fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn parse(text: &str) -> Result<Vec<Pin>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| serde_json::from_str(line).map_err(|e| format!("line {}: {e}", i + 1)))
+        .collect()
+}
+
+/// Replace the pin file's contents with `pins`.
+pub fn save(file: &Path, pins: &[Pin]) -> io::Result<()> {
+    let mut text = String::new();
+    for pin in pins {
+        text.push_str(&serde_json::to_string(pin).map_err(io::Error::other)?);
+        text.push('\n');
+    }
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Write a sibling and rename it over, so a crash can't lose every pin
+    let tmp = file.with_extension("jsonl.tmp");
+    fs::write(&tmp, text)?;
+    fs::rename(&tmp, file)
+}
+
+/// IDs of the pinned items, from the default location.
+pub fn pinned_ids() -> io::Result<HashSet<String>> {
+    let Some(file) = file_path() else {
+        return Ok(HashSet::new());
+    };
+    Ok(load(&file)?.into_iter().map(|pin| pin.id).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let file = tmp.path().join("state").join("pins.jsonl");
+        assert!(load(&file).unwrap().is_empty());
+
+        let pins = vec![
+            Pin {
+                id: "/t/info/a.trashinfo".into(),
+                path: "/home/me/a".into(),
+            },
+            Pin {
+                id: "/t/info/b\nc.trashinfo".into(),
+                path: "/home/me/b\nc".into(),
+            },
+        ];
+        save(&file, &pins).unwrap();
+        assert_eq!(load(&file).unwrap(), pins);
+    }
+
+    #[test]
+    fn test_parse_reports_line() {
+        let err = parse("{\"id\":\"a\",\"path\":\"/a\"}\n\nnot json\n").unwrap_err();
+        assert!(err.starts_with("line 3:"), "{err}");
+    }
+}
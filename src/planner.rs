@@ -0,0 +1,564 @@
+// Decides what trashing one FILE argument involves: which prompts to ask,
+// what to refuse, what -f skips and what --trash-dry-run only shows. It never
+// touches the file system, so every flag combination can be tested directly;
+// trash_files gathers the facts and carries out the actions.
+
+use std::io;
+use std::path::{Component, Path};
+
+use crate::InteractiveMode;
+
+/// The flags that shape the plan.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Flags {
+    pub interactive: InteractiveMode,
+    pub force: bool,
+    pub recursive: bool,
+    pub dir: bool,
+    pub dry_run: bool,
+    pub shred: bool,
+    /// Running as rm, or --compat gnu: GNU rm's wording
+    pub gnu: bool,
+    /// Ask before removing write-protected files, as rm does on a terminal
+    pub prompt_protected: bool,
+    /// --prune-empty-dirs: directories given are empty once their empty
+    /// subdirectories, given before them, are gone
+    pub prune: bool,
+}
+
+impl Flags {
+    /// The verb used in prompts
+    pub fn verb(&self) -> &'static str {
+        if self.shred { "shred" } else { "remove" }
+    }
+
+    /// The program name prompts start with
+    pub fn prog(&self) -> &'static str {
+        if self.gnu { "rm" } else { "trache" }
+    }
+}
+
+/// When -I asks its one question.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bulk {
+    /// Ask for more arguments than this
+    pub count: usize,
+    /// Also ask when the arguments add up to more bytes than this
+    pub size: Option<u64>,
+}
+
+impl Default for Bulk {
+    /// rm's: more than three arguments, whatever their size
+    fn default() -> Bulk {
+        Bulk {
+            count: 3,
+            size: None,
+        }
+    }
+}
+
+/// What a FILE argument turned out to be.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Found {
+    /// It couldn't be examined; `missing` when it doesn't exist
+    Unreadable {
+        missing: bool,
+    },
+    File {
+        empty: bool,
+        write_protected: bool,
+    },
+    Symlink,
+    /// `empty` is None when the directory couldn't be read
+    Dir {
+        empty: Option<bool>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Ask this first; leave the path alone if the answer is no
+    Confirm(String),
+    /// Move the path to the trash, or shred it
+    Trash,
+    /// Say what would be done, for --trash-dry-run
+    Preview,
+    /// Leave the path alone without it being an error
+    Skip(&'static str),
+    /// Fail with the error met while examining the path
+    Unreadable,
+    /// Fail with an error of this kind and message, as rm does
+    Refuse(io::ErrorKind, &'static str),
+}
+
+/// The -I question asked once before anything is removed, if any. `size`
+/// measures the arguments, and is only called when `bulk` has a size limit
+/// that decides it.
+pub fn bulk_prompt(
+    flags: &Flags,
+    bulk: &Bulk,
+    count: usize,
+    size: impl FnOnce() -> u64,
+) -> Option<String> {
+    if flags.interactive != InteractiveMode::Once {
+        return None;
+    }
+    let asked = count > bulk.count || flags.recursive || bulk.size.is_some_and(|max| size() > max);
+    if !asked {
+        return None;
+    }
+    let (prog, verb) = (flags.prog(), flags.verb());
+    let arguments = match (flags.gnu, count) {
+        (false, _) => "argument(s)",
+        (true, 1) => "argument",
+        (true, _) => "arguments",
+    };
+    Some(if flags.recursive {
+        format!("{prog}: {verb} {count} {arguments} recursively? ")
+    } else {
+        format!("{prog}: {verb} {count} {arguments}? ")
+    })
+}
+
+/// Why `path` is refused by name alone: rm never removes '.' or '..'.
+pub fn refuse_by_name(path: &Path) -> Option<&'static str> {
+    match path.components().next_back() {
+        Some(Component::CurDir | Component::ParentDir) => {
+            Some("refusing to remove '.' or '..' directory")
+        }
+        _ => None,
+    }
+}
+
+/// The actions for one path, in order. `bulk_prompted` is set when the -I
+/// question was already asked and answered yes. `concern` is what makes the
+/// path risky to remove, for --interactive=smart.
+pub fn plan(
+    flags: &Flags,
+    path: &Path,
+    found: Found,
+    bulk_prompted: bool,
+    concern: Option<&str>,
+) -> Vec<Action> {
+    let protected = flags.prompt_protected
+        && matches!(
+            found,
+            Found::File {
+                write_protected: true,
+                ..
+            }
+        );
+    let smart = flags.interactive == InteractiveMode::Smart
+        && (concern.is_some()
+            || (flags.recursive && matches!(found, Found::Dir { empty } if empty != Some(true))));
+    let prompt =
+        (flags.interactive == InteractiveMode::Always && !bulk_prompted) || protected || smart;
+    let (prog, verb) = (flags.prog(), flags.verb());
+    let shown = path.display();
+    let question = match found {
+        Found::Unreadable { missing: true } if flags.force => {
+            return vec![Action::Skip("nonexistent")];
+        }
+        Found::Unreadable { .. } => return vec![Action::Unreadable],
+        // rm would descend into a directory and ask about each entry, but it
+        // goes as one item here, so the question has to say that it's all
+        // of it; only an empty one is asked about as rm asks
+        Found::Dir { empty: Some(true) } if flags.recursive && flags.gnu => {
+            format!("{prog}: {verb} directory '{shown}'? ")
+        }
+        Found::Dir { .. } if flags.recursive => {
+            format!("{prog}: {verb} directory '{shown}' recursively? ")
+        }
+        // A dry run leaves the subdirectories in place; otherwise one was
+        // kept, so the directory stays too
+        Found::Dir { empty: Some(false) } if flags.prune && !flags.dry_run => {
+            return vec![Action::Skip("not empty")];
+        }
+        Found::Dir { empty: Some(_) } if flags.prune => {
+            format!("{prog}: {verb} directory '{shown}'? ")
+        }
+        Found::Dir { empty: Some(true) } if flags.dir => {
+            format!("{prog}: {verb} directory '{shown}'? ")
+        }
+        Found::Dir { empty: Some(false) } if flags.dir => {
+            return vec![Action::Refuse(
+                io::ErrorKind::DirectoryNotEmpty,
+                "Directory not empty",
+            )];
+        }
+        Found::Dir { empty: None } if flags.dir => return vec![Action::Unreadable],
+        Found::Dir { .. } => {
+            return vec![Action::Refuse(
+                io::ErrorKind::IsADirectory,
+                "Is a directory",
+            )];
+        }
+        Found::Symlink => format!("{prog}: {verb} symbolic link '{shown}'? "),
+        Found::File {
+            empty,
+            write_protected,
+        } if flags.gnu => {
+            let protection = if write_protected {
+                "write-protected "
+            } else {
+                ""
+            };
+            let empty = if empty { "empty " } else { "" };
+            format!("{prog}: {verb} {protection}regular {empty}file '{shown}'? ")
+        }
+        Found::File { .. } => format!("{prog}: {verb} regular file '{shown}'? "),
+    };
+
+    let question = match concern {
+        Some(concern) if smart => format!("{} ({concern})? ", question.trim_end_matches("? ")),
+        _ => question,
+    };
+    let mut actions = Vec::new();
+    if prompt {
+        actions.push(Action::Confirm(question));
+    }
+    actions.push(if flags.dry_run {
+        Action::Preview
+    } else {
+        Action::Trash
+    });
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODES: [InteractiveMode; 4] = [
+        InteractiveMode::Never,
+        InteractiveMode::Once,
+        InteractiveMode::Always,
+        InteractiveMode::Smart,
+    ];
+
+    const FOUND: [Found; 8] = [
+        Found::Unreadable { missing: true },
+        Found::Unreadable { missing: false },
+        Found::File {
+            empty: false,
+            write_protected: false,
+        },
+        Found::File {
+            empty: true,
+            write_protected: true,
+        },
+        Found::Symlink,
+        Found::Dir { empty: Some(true) },
+        Found::Dir { empty: Some(false) },
+        Found::Dir { empty: None },
+    ];
+
+    /// Every combination of flags.
+    fn all_flags() -> Vec<Flags> {
+        let mut all = Vec::new();
+        for interactive in MODES {
+            for bits in 0..256u16 {
+                all.push(Flags {
+                    interactive,
+                    force: bits & 1 != 0,
+                    recursive: bits & 2 != 0,
+                    dir: bits & 4 != 0,
+                    dry_run: bits & 8 != 0,
+                    shred: bits & 16 != 0,
+                    gnu: bits & 32 != 0,
+                    prompt_protected: bits & 64 != 0,
+                    prune: bits & 128 != 0,
+                });
+            }
+        }
+        all
+    }
+
+    /// What happens to the path, ignoring prompts.
+    fn outcome(actions: &[Action]) -> &Action {
+        actions.last().unwrap()
+    }
+
+    fn done(flags: &Flags) -> Action {
+        if flags.dry_run {
+            Action::Preview
+        } else {
+            Action::Trash
+        }
+    }
+
+    #[test]
+    fn test_outcome_table() {
+        for flags in all_flags() {
+            for found in FOUND {
+                let actions = plan(&flags, Path::new("x"), found, false, None);
+                let expected = match found {
+                    Found::Unreadable { missing: true } if flags.force => {
+                        Action::Skip("nonexistent")
+                    }
+                    Found::Unreadable { .. } => Action::Unreadable,
+                    Found::File { .. } | Found::Symlink => done(&flags),
+                    Found::Dir { .. } if flags.recursive => done(&flags),
+                    Found::Dir { empty: Some(false) } if flags.prune && !flags.dry_run => {
+                        Action::Skip("not empty")
+                    }
+                    Found::Dir { empty: Some(_) } if flags.prune => done(&flags),
+                    Found::Dir { .. } if !flags.dir => {
+                        Action::Refuse(io::ErrorKind::IsADirectory, "Is a directory")
+                    }
+                    Found::Dir { empty: Some(true) } => done(&flags),
+                    Found::Dir { empty: Some(false) } => {
+                        Action::Refuse(io::ErrorKind::DirectoryNotEmpty, "Directory not empty")
+                    }
+                    Found::Dir { empty: None } => Action::Unreadable,
+                };
+                assert_eq!(outcome(&actions), &expected, "{flags:?} {found:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_prompts_with_dash_i_or_when_write_protected() {
+        for flags in all_flags() {
+            for found in FOUND {
+                for bulk_prompted in [false, true] {
+                    let actions = plan(&flags, Path::new("x"), found, bulk_prompted, None);
+                    let removes = matches!(outcome(&actions), Action::Trash | Action::Preview);
+                    let protected = flags.prompt_protected
+                        && found
+                            == Found::File {
+                                empty: true,
+                                write_protected: true,
+                            };
+                    let risky = flags.interactive == InteractiveMode::Smart
+                        && flags.recursive
+                        && matches!(found, Found::Dir { empty } if empty != Some(true));
+                    let expected = removes
+                        && ((flags.interactive == InteractiveMode::Always && !bulk_prompted)
+                            || protected
+                            || risky);
+                    let prompted = matches!(actions.first(), Some(Action::Confirm(_)));
+                    assert_eq!(prompted, expected, "{flags:?} {found:?} {bulk_prompted}");
+                    assert!(actions.len() <= 2);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_prompt_wording() {
+        let flags = Flags {
+            interactive: InteractiveMode::Always,
+            recursive: true,
+            ..Flags::default()
+        };
+        let question =
+            |found, flags: &Flags| match &plan(flags, Path::new("a b"), found, false, None)[0] {
+                Action::Confirm(q) => q.clone(),
+                other => panic!("{other:?}"),
+            };
+        let file = Found::File {
+            empty: true,
+            write_protected: true,
+        };
+        assert_eq!(
+            question(file, &flags),
+            "trache: remove regular file 'a b'? "
+        );
+        assert_eq!(
+            question(Found::Symlink, &flags),
+            "trache: remove symbolic link 'a b'? "
+        );
+        assert_eq!(
+            question(Found::Dir { empty: Some(false) }, &flags),
+            "trache: remove directory 'a b' recursively? "
+        );
+        let flags = Flags {
+            recursive: false,
+            dir: true,
+            shred: true,
+            ..flags
+        };
+        assert_eq!(
+            question(Found::Dir { empty: Some(true) }, &flags),
+            "trache: shred directory 'a b'? "
+        );
+    }
+
+    #[test]
+    fn test_smart_prompts_only_with_a_concern() {
+        let flags = Flags {
+            interactive: InteractiveMode::Smart,
+            ..Flags::default()
+        };
+        let file = Found::File {
+            empty: false,
+            write_protected: false,
+        };
+        assert_eq!(
+            plan(&flags, Path::new("a"), file, false, None),
+            [Action::Trash]
+        );
+        assert_eq!(
+            plan(
+                &flags,
+                Path::new("a"),
+                file,
+                false,
+                Some("not owned by you")
+            ),
+            [
+                Action::Confirm("trache: remove regular file 'a' (not owned by you)? ".into()),
+                Action::Trash
+            ]
+        );
+        let recursive = Flags {
+            recursive: true,
+            ..flags
+        };
+        let empty_dir = Found::Dir { empty: Some(true) };
+        assert_eq!(
+            plan(&recursive, Path::new("d"), empty_dir, false, None),
+            [Action::Trash]
+        );
+        assert_eq!(
+            plan(
+                &recursive,
+                Path::new("d"),
+                Found::Dir { empty: Some(false) },
+                false,
+                None
+            )[0],
+            Action::Confirm("trache: remove directory 'd' recursively? ".into())
+        );
+        // Other modes ignore concerns
+        let always = Flags {
+            interactive: InteractiveMode::Always,
+            ..flags
+        };
+        assert_eq!(
+            plan(
+                &always,
+                Path::new("a"),
+                file,
+                false,
+                Some("not owned by you")
+            )[0],
+            Action::Confirm("trache: remove regular file 'a'? ".into())
+        );
+    }
+
+    #[test]
+    fn test_gnu_prompt_wording() {
+        let flags = Flags {
+            interactive: InteractiveMode::Always,
+            gnu: true,
+            ..Flags::default()
+        };
+        let question = |found| match &plan(&flags, Path::new("a"), found, false, None)[0] {
+            Action::Confirm(q) => q.clone(),
+            other => panic!("{other:?}"),
+        };
+        let file = |empty, write_protected| Found::File {
+            empty,
+            write_protected,
+        };
+        assert_eq!(
+            question(file(false, false)),
+            "rm: remove regular file 'a'? "
+        );
+        assert_eq!(
+            question(file(true, false)),
+            "rm: remove regular empty file 'a'? "
+        );
+        assert_eq!(
+            question(file(false, true)),
+            "rm: remove write-protected regular file 'a'? "
+        );
+        let rflags = Flags {
+            recursive: true,
+            ..flags
+        };
+        let rquestion = |found| match &plan(&rflags, Path::new("d"), found, false, None)[0] {
+            Action::Confirm(q) => q.clone(),
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(
+            rquestion(Found::Dir { empty: Some(false) }),
+            "rm: remove directory 'd' recursively? "
+        );
+        assert_eq!(
+            rquestion(Found::Dir { empty: None }),
+            "rm: remove directory 'd' recursively? "
+        );
+        assert_eq!(
+            rquestion(Found::Dir { empty: Some(true) }),
+            "rm: remove directory 'd'? "
+        );
+        let once = Flags {
+            interactive: InteractiveMode::Once,
+            ..flags
+        };
+        assert_eq!(
+            bulk_prompt(&once, &Bulk::default(), 4, || 0).unwrap(),
+            "rm: remove 4 arguments? "
+        );
+        let recursive = Flags {
+            recursive: true,
+            ..once
+        };
+        assert_eq!(
+            bulk_prompt(&recursive, &Bulk::default(), 1, || 0).unwrap(),
+            "rm: remove 1 argument recursively? "
+        );
+    }
+
+    #[test]
+    fn test_bulk_prompt_table() {
+        for flags in all_flags() {
+            for count in [1, 3, 4] {
+                let expected =
+                    flags.interactive == InteractiveMode::Once && (count > 3 || flags.recursive);
+                assert_eq!(
+                    bulk_prompt(&flags, &Bulk::default(), count, || u64::MAX).is_some(),
+                    expected,
+                    "{flags:?} {count}"
+                );
+            }
+        }
+        let flags = Flags {
+            interactive: InteractiveMode::Once,
+            ..Flags::default()
+        };
+        assert_eq!(
+            bulk_prompt(&flags, &Bulk::default(), 5, || 0).unwrap(),
+            "trache: remove 5 argument(s)? "
+        );
+    }
+
+    #[test]
+    fn test_bulk_prompt_limits() {
+        let once = Flags {
+            interactive: InteractiveMode::Once,
+            ..Flags::default()
+        };
+        let bulk = Bulk {
+            count: 1,
+            size: Some(1000),
+        };
+        let unmeasured = || panic!("size measured needlessly");
+        assert!(bulk_prompt(&once, &bulk, 2, unmeasured).is_some());
+        assert!(bulk_prompt(&once, &bulk, 1, || 1001).is_some());
+        assert!(bulk_prompt(&once, &bulk, 1, || 1000).is_none());
+        assert!(bulk_prompt(&Flags::default(), &bulk, 1, unmeasured).is_none());
+        assert!(bulk_prompt(&once, &Bulk::default(), 1, unmeasured).is_none());
+    }
+
+    #[test]
+    fn test_refuses_dot_and_dot_dot() {
+        assert!(refuse_by_name(Path::new(".")).is_some());
+        assert!(refuse_by_name(Path::new("a/..")).is_some());
+        assert!(refuse_by_name(Path::new("a/.b")).is_none());
+        assert!(refuse_by_name(Path::new("..a")).is_none());
+    }
+}
@@ -0,0 +1,169 @@
+// Trashing onto read-only file systems. Once a file can't be trashed because
+// its file system is mounted read-only, that mount is named with a hint to
+// remount it, and the remaining files on it are skipped without trying each
+// one. Only Unix has read-only mounts to detect; elsewhere this does nothing.
+#![cfg_attr(not(unix), allow(dead_code))]
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// A read-only mount met during this run
+struct Mount {
+    dev: u64,
+    path: PathBuf,
+    /// Files skipped on it after the first failure
+    skipped: usize,
+}
+
+static MOUNTS: Mutex<Vec<Mount>> = Mutex::new(Vec::new());
+
+/// After trashing `file` failed: the mount point of its file system if that
+/// is mounted read-only, which then explains the failure.
+pub fn detect(file: &Path) -> Option<PathBuf> {
+    // A device already found read-only isn't looked into again
+    let known = device(file).and_then(|dev| {
+        let mounts = MOUNTS.lock().unwrap();
+        mounts.iter().find(|m| m.dev == dev).map(|m| m.path.clone())
+    });
+    if known.is_some() {
+        return known;
+    }
+    let (dev, path) = read_only_mount(file)?;
+    let mut mounts = MOUNTS.lock().unwrap();
+    if !mounts.iter().any(|m| m.dev == dev) {
+        mounts.push(Mount {
+            dev,
+            path: path.clone(),
+            skipped: 0,
+        });
+    }
+    Some(path)
+}
+
+/// Whether `file` is on a mount already found read-only, so it needn't be
+/// tried. The file is counted for [`summary`].
+pub fn skip(file: &Path) -> Option<PathBuf> {
+    let mut mounts = MOUNTS.lock().unwrap();
+    // Most runs meet none, and then no file needs looking at
+    if mounts.is_empty() {
+        return None;
+    }
+    let dev = device(file)?;
+    let mount = mounts.iter_mut().find(|m| m.dev == dev)?;
+    mount.skipped += 1;
+    Some(mount.path.clone())
+}
+
+/// Why trashing onto the read-only `mount` failed, with the fix.
+pub fn describe(mount: &Path) -> String {
+    format!(
+        "read-only file system at '{}' (remount it read-write to trash here, \
+         e.g. mount -o remount,rw '{}')",
+        mount.display(),
+        mount.display()
+    )
+}
+
+/// One line per read-only mount with files skipped on it.
+pub fn summary() -> Vec<String> {
+    MOUNTS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|m| m.skipped > 0)
+        .map(|m| {
+            format!(
+                "skipped {} more item(s) on read-only '{}'",
+                m.skipped,
+                m.path.display()
+            )
+        })
+        .collect()
+}
+
+/// The device of the file system `file` is removed from: its parent's.
+#[cfg(unix)]
+fn device(file: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    let absolute = std::path::absolute(file).ok()?;
+    Some(absolute.parent()?.metadata().ok()?.dev())
+}
+
+#[cfg(not(unix))]
+fn device(_file: &Path) -> Option<u64> {
+    None
+}
+
+fn parent(file: &Path) -> Option<PathBuf> {
+    let absolute = std::path::absolute(file).ok()?;
+    absolute.parent()?.canonicalize().ok()
+}
+
+/// The device and mount point of `file`'s parent, if mounted read-only.
+#[cfg(unix)]
+fn read_only_mount(file: &Path) -> Option<(u64, PathBuf)> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let parent = parent(file)?;
+    let c_path = CString::new(parent.as_os_str().as_bytes()).ok()?;
+    // SAFETY: statvfs only writes into the zeroed struct we pass
+    // (f_flag and ST_RDONLY have different types on different platforms)
+    #[allow(clippy::unnecessary_cast)]
+    let read_only = unsafe {
+        let mut stat: libc::statvfs = std::mem::zeroed();
+        libc::statvfs(c_path.as_ptr(), &mut stat) == 0
+            && (stat.f_flag as u64) & (libc::ST_RDONLY as u64) != 0
+    };
+    if !read_only {
+        return None;
+    }
+    let dev = device(file)?;
+    Some((dev, mount_point(&parent, dev)))
+}
+
+#[cfg(not(unix))]
+fn read_only_mount(_file: &Path) -> Option<(u64, PathBuf)> {
+    None
+}
+
+/// The topmost ancestor of `dir` still on device `dev`.
+#[cfg(unix)]
+fn mount_point(dir: &Path, dev: u64) -> PathBuf {
+    use std::os::unix::fs::MetadataExt;
+
+    dir.ancestors()
+        .take_while(|a| a.metadata().is_ok_and(|m| m.dev() == dev))
+        .last()
+        .unwrap_or(dir)
+        .to_path_buf()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+
+    #[test]
+    fn test_mount_point_is_on_the_same_device() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path().canonicalize().unwrap();
+        let dev = dir.metadata().unwrap().dev();
+        let mount = mount_point(&dir, dev);
+        assert!(dir.starts_with(&mount));
+        assert_eq!(mount.metadata().unwrap().dev(), dev);
+        assert!(
+            mount
+                .parent()
+                .is_none_or(|p| p.metadata().unwrap().dev() != dev)
+        );
+    }
+
+    #[test]
+    fn test_writable_is_not_detected() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        assert!(detect(&tmp.path().join("file")).is_none());
+        assert!(skip(&tmp.path().join("file")).is_none());
+    }
+}
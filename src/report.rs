@@ -0,0 +1,202 @@
+// Optional reporting of deleted items to an external command, for tracking
+// data lifecycle. Enabled via [report] in config.toml; a no-op otherwise.
+//
+// Events are batched and written to the command's stdin as JSON lines, so a
+// large operation spawns one process per batch rather than per file.
+
+use std::env;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::audit::Op;
+use crate::config;
+
+pub const DEFAULT_BATCH_SIZE: usize = 100;
+
+struct Reporter {
+    command: Vec<String>,
+    batch_size: usize,
+    user: String,
+    host: String,
+    pending: Vec<String>,
+}
+
+static REPORTER: Mutex<Option<Reporter>> = Mutex::new(None);
+
+/// One line written to the report command
+#[derive(Serialize, JsonSchema)]
+struct Entry<'a> {
+    time: String,
+    op: Op,
+    path: &'a str,
+    size: u64,
+    user: &'a str,
+    host: &'a str,
+}
+
+/// JSON schema of a line written to the report command, for --schema.
+pub fn schema() -> schemars::Schema {
+    schemars::schema_for!(Entry<'static>)
+}
+
+/// Set up reporting if the config names a command.
+pub fn init(cfg: &config::Report) -> Result<(), String> {
+    if cfg.command.is_empty() {
+        return Ok(());
+    }
+    let batch_size = cfg.batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
+    if batch_size == 0 {
+        return Err("report.batch_size must be at least 1".into());
+    }
+    *REPORTER.lock().unwrap() = Some(Reporter {
+        command: cfg.command.clone(),
+        batch_size,
+        user: user_name(),
+        host: host_name(),
+        pending: Vec::new(),
+    });
+    Ok(())
+}
+
+/// Whether a command is configured; lets callers skip measuring sizes.
+pub fn enabled() -> bool {
+    REPORTER.lock().unwrap().is_some()
+}
+
+/// Queue an event for `path`, sending the batch once it is full.
+pub fn record(op: Op, path: &Path, size: u64) {
+    let mut guard = REPORTER.lock().unwrap();
+    let Some(reporter) = guard.as_mut() else {
+        return;
+    };
+
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let entry = Entry {
+        time: chrono::Local::now().to_rfc3339(),
+        op,
+        path: &path.to_string_lossy(),
+        size,
+        user: &reporter.user,
+        host: &reporter.host,
+    };
+    reporter
+        .pending
+        .push(serde_json::to_string(&entry).unwrap_or_default());
+
+    if reporter.pending.len() >= reporter.batch_size {
+        send(&mut guard);
+    }
+}
+
+/// Send any queued events. Call once before exiting.
+pub fn flush() {
+    let mut guard = REPORTER.lock().unwrap();
+    if guard.as_ref().is_some_and(|r| !r.pending.is_empty()) {
+        send(&mut guard);
+    }
+}
+
+fn send(guard: &mut Option<Reporter>) {
+    let Some(reporter) = guard.as_mut() else {
+        return;
+    };
+    let lines = std::mem::take(&mut reporter.pending);
+    if let Err(e) = run(&reporter.command, &lines) {
+        eprintln!("trache: report command failed: {e}");
+        // Don't repeat the warning for every remaining batch
+        *guard = None;
+    }
+}
+
+fn run(command: &[String], lines: &[String]) -> Result<(), String> {
+    let mut child = Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("{}: {e}", command[0]))?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    for line in lines {
+        if let Err(e) = writeln!(stdin, "{line}") {
+            let _ = child.wait();
+            return Err(e.to_string());
+        }
+    }
+    drop(stdin);
+
+    let status = child.wait().map_err(|e| e.to_string())?;
+    if !status.success() {
+        return Err(format!("{} exited with {status}", command[0]));
+    }
+    Ok(())
+}
+
+fn user_name() -> String {
+    ["USER", "USERNAME", "LOGNAME"]
+        .iter()
+        .find_map(|var| env::var(var).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_default()
+}
+
+#[cfg(unix)]
+fn host_name() -> String {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer is valid for buf.len() bytes
+    let rc = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) };
+    if rc != 0 {
+        return String::new();
+    }
+    let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..end]).into_owned()
+}
+
+#[cfg(not(unix))]
+fn host_name() -> String {
+    env::var("COMPUTERNAME").unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_json() {
+        let entry = Entry {
+            time: "2024-01-15T10:30:00+00:00".into(),
+            op: Op::Purge,
+            path: "/tmp/a.txt",
+            size: 5,
+            user: "u",
+            host: "h",
+        };
+        assert_eq!(
+            serde_json::to_string(&entry).unwrap(),
+            r#"{"time":"2024-01-15T10:30:00+00:00","op":"purge","path":"/tmp/a.txt","size":5,"user":"u","host":"h"}"#
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_writes_lines_to_stdin() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let out = tmp.path().join("out");
+        let command = vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            format!("cat > '{}'", out.display()),
+        ];
+        run(&command, &["a".into(), "b".into()]).unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "a\nb\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_reports_failure() {
+        assert!(run(&["false".to_string()], &[]).is_err());
+    }
+}
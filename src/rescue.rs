@@ -0,0 +1,100 @@
+// Restoring somewhere else when the original volume can't take an item back:
+// it is full, or it has gone away and the path now leads to a different file
+// system. Without this the item stays stuck in the trash.
+
+use std::ffi::OsString;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+static DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Send items that can't be restored in place to `dir` for the rest of the run.
+pub fn enable(dir: PathBuf) {
+    let _ = DIR.set(dir);
+}
+
+/// The rescue directory, if --rescue is on.
+pub fn dir() -> Option<&'static Path> {
+    DIR.get().map(PathBuf::as_path)
+}
+
+/// The cause of a failed restore, if it is one that a different
+/// destination could fix.
+pub fn rescuable_cause(err: &trash::Error) -> Option<&io::Error> {
+    match err {
+        trash::Error::FileSystem { source, .. }
+            if matches!(
+                source.kind(),
+                io::ErrorKind::CrossesDevices
+                    | io::ErrorKind::StorageFull
+                    | io::ErrorKind::QuotaExceeded
+            ) =>
+        {
+            Some(source)
+        }
+        _ => None,
+    }
+}
+
+/// Where `original` goes under `dir`: its whole path, re-rooted, so items
+/// rescued from different places don't land on top of each other.
+pub fn destination(dir: &Path, original: &Path) -> PathBuf {
+    let mut dest = dir.to_path_buf();
+    for component in original.components() {
+        match component {
+            // C: becomes a C directory
+            Component::Prefix(prefix) => {
+                let drive: String = prefix
+                    .as_os_str()
+                    .to_string_lossy()
+                    .chars()
+                    .filter(|c| c.is_alphanumeric())
+                    .collect();
+                dest.push(OsString::from(drive));
+            }
+            Component::Normal(name) => dest.push(name),
+            Component::RootDir | Component::CurDir | Component::ParentDir => {}
+        }
+    }
+    dest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_destination_reroots_path() {
+        assert_eq!(
+            destination(
+                Path::new("/home/me/trache-rescue"),
+                Path::new("/mnt/usb/a/b.txt")
+            ),
+            PathBuf::from("/home/me/trache-rescue/mnt/usb/a/b.txt")
+        );
+    }
+
+    #[test]
+    fn test_destination_ignores_parent_components() {
+        assert_eq!(
+            destination(Path::new("/r"), Path::new("/a/../b")),
+            PathBuf::from("/r/a/b")
+        );
+    }
+
+    #[test]
+    fn test_rescuable_cause() {
+        let fs_error = |kind| trash::Error::FileSystem {
+            path: PathBuf::from("/x"),
+            source: io::Error::from(kind),
+        };
+        assert!(rescuable_cause(&fs_error(io::ErrorKind::StorageFull)).is_some());
+        assert!(rescuable_cause(&fs_error(io::ErrorKind::CrossesDevices)).is_some());
+        assert!(rescuable_cause(&fs_error(io::ErrorKind::PermissionDenied)).is_none());
+        let unknown = trash::Error::Unknown {
+            description: String::new(),
+        };
+        assert!(rescuable_cause(&unknown).is_none());
+    }
+}
@@ -0,0 +1,514 @@
+// Retention planning for --trash-clean.
+// Decides which items to purge; the caller does the listing and purging.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::config;
+use crate::units;
+
+pub struct Candidate {
+    pub time_deleted: i64,
+    pub size: u64,
+    pub protected: bool,
+    /// Original path; items trashed from the same one count together for
+    /// keep_per_path
+    pub path: PathBuf,
+    /// Index into the policy list passed to [`plan`]
+    pub policy: usize,
+    /// Index into the overrides passed to [`plan`] of the first
+    /// [[retention.rule]] matching the item
+    pub rule: Option<usize>,
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct Rules {
+    pub max_age: Option<Duration>,
+    pub max_total_size: Option<u64>,
+    pub keep_per_path: Option<usize>,
+}
+
+/// The rules a [[retention.rule]] sets; what it leaves unset comes from the
+/// item's [`Rules`].
+#[derive(Default, Clone, Copy)]
+pub struct Override {
+    pub max_age: Option<Duration>,
+    pub keep_per_path: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    MaxAge,
+    KeepPerPath,
+    MaxTotalSize,
+}
+
+impl Rules {
+    pub fn is_empty(&self) -> bool {
+        self.max_age.is_none() && self.max_total_size.is_none() && self.keep_per_path.is_none()
+    }
+}
+
+/// Pick the policy for an item stored at `path`: 0 for the global rules,
+/// or 1 + the index of the longest mount path containing it.
+pub fn mount_policy(mounts: &[&Path], path: &Path) -> usize {
+    mounts
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| path.starts_with(m))
+        .max_by_key(|(_, m)| m.components().count())
+        .map(|(i, _)| i + 1)
+        .unwrap_or(0)
+}
+
+/// The global policy (index 0) followed by one per [[retention.mount]],
+/// with unset mount fields inherited from the global rules.
+pub fn policies(cfg: &config::Retention) -> Result<Vec<Rules>, String> {
+    let mut global = parse_rules(
+        cfg.max_age.as_deref(),
+        cfg.max_total_size.as_deref(),
+        "retention",
+    )?;
+    global.keep_per_path = cfg.keep_per_path;
+    let mut policies = vec![global];
+    for mount in &cfg.mounts {
+        let context = format!("retention.mount '{}'", mount.path.display());
+        let mut rules = parse_rules(
+            mount.max_age.as_deref().or(cfg.max_age.as_deref()),
+            mount
+                .max_total_size
+                .as_deref()
+                .or(cfg.max_total_size.as_deref()),
+            &context,
+        )?;
+        rules.keep_per_path = mount.keep_per_path.or(cfg.keep_per_path);
+        policies.push(rules);
+    }
+    Ok(policies)
+}
+
+/// One override per [[retention.rule]], in order.
+pub fn overrides(cfg: &config::Retention) -> Result<Vec<Override>, String> {
+    cfg.rules
+        .iter()
+        .map(|rule| {
+            Ok(Override {
+                max_age: rule
+                    .max_age
+                    .as_deref()
+                    .map(units::parse_duration)
+                    .transpose()
+                    .map_err(|e| format!("retention.rule '{}': max_age: {e}", rule.pattern))?,
+                keep_per_path: rule.keep_per_path,
+            })
+        })
+        .collect()
+}
+
+fn parse_rules(
+    max_age: Option<&str>,
+    max_total_size: Option<&str>,
+    context: &str,
+) -> Result<Rules, String> {
+    Ok(Rules {
+        max_age: max_age
+            .map(units::parse_duration)
+            .transpose()
+            .map_err(|e| format!("{context}: max_age: {e}"))?,
+        max_total_size: max_total_size
+            .map(units::parse_size)
+            .transpose()
+            .map_err(|e| format!("{context}: max_total_size: {e}"))?,
+        keep_per_path: None,
+    })
+}
+
+/// Human-readable summary of policy `index` from [`policies`].
+pub fn describe_policy(cfg: &config::Retention, index: usize) -> String {
+    let (max_age, max_total_size, keep_per_path, source) = match index.checked_sub(1) {
+        None => (
+            cfg.max_age.as_deref(),
+            cfg.max_total_size.as_deref(),
+            cfg.keep_per_path,
+            None,
+        ),
+        Some(i) => {
+            let m = &cfg.mounts[i];
+            (
+                m.max_age.as_deref().or(cfg.max_age.as_deref()),
+                m.max_total_size
+                    .as_deref()
+                    .or(cfg.max_total_size.as_deref()),
+                m.keep_per_path.or(cfg.keep_per_path),
+                Some(m.path.display().to_string()),
+            )
+        }
+    };
+
+    let mut parts = Vec::new();
+    if let Some(age) = max_age {
+        parts.push(format!("max_age={age}"));
+    }
+    if let Some(size) = max_total_size {
+        parts.push(format!("max_total_size={size}"));
+    }
+    if let Some(keep) = keep_per_path {
+        parts.push(format!("keep_per_path={keep}"));
+    }
+    let mut desc = if parts.is_empty() {
+        "none".to_string()
+    } else {
+        parts.join(" ")
+    };
+    if let Some(source) = source {
+        desc.push_str(&format!(" (mount {source})"));
+    }
+    desc
+}
+
+impl Reason {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Reason::MaxAge => "older than max_age",
+            Reason::KeepPerPath => "beyond keep_per_path",
+            Reason::MaxTotalSize => "over max_total_size",
+        }
+    }
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct CleanedItem {
+    pub path: String,
+    pub size: u64,
+    pub reason: Reason,
+}
+
+/// Summary printed by --trash-clean --porcelain
+#[derive(Serialize, JsonSchema)]
+pub struct CleanSummary {
+    pub dry_run: bool,
+    pub purged: usize,
+    pub purged_bytes: u64,
+    pub remaining: usize,
+    pub protected: usize,
+    pub items: Vec<CleanedItem>,
+}
+
+/// Return the indices of candidates to purge, oldest first, with the rule
+/// that selected each one. Each candidate is judged by `policies[c.policy]`,
+/// or for max_age and keep_per_path by `overrides[c.rule]` where that sets
+/// them, and size limits are per policy. Protected candidates are never
+/// selected but still count towards their policy's total size and their
+/// path's keep_per_path.
+pub fn plan(
+    candidates: &[Candidate],
+    policies: &[Rules],
+    overrides: &[Override],
+    now: i64,
+) -> Vec<(usize, Reason)> {
+    let mut selected = Vec::new();
+    let mut chosen = vec![false; candidates.len()];
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by_key(|&i| candidates[i].time_deleted);
+    let rule = |c: &Candidate| c.rule.map(|r| overrides[r]).unwrap_or_default();
+
+    for &i in &order {
+        let c = &candidates[i];
+        let Some(max_age) = rule(c).max_age.or(policies[c.policy].max_age) else {
+            continue;
+        };
+        let cutoff = now.saturating_sub(max_age.as_secs() as i64);
+        if !c.protected && c.time_deleted < cutoff {
+            selected.push((i, Reason::MaxAge));
+            chosen[i] = true;
+        }
+    }
+
+    // Twins newest first
+    let mut twins: HashMap<&Path, Vec<usize>> = HashMap::new();
+    for &i in order.iter().rev() {
+        twins.entry(&candidates[i].path).or_default().push(i);
+    }
+    for group in twins.values() {
+        for (rank, &i) in group.iter().enumerate() {
+            let c = &candidates[i];
+            let keep = rule(c).keep_per_path.or(policies[c.policy].keep_per_path);
+            if keep.is_some_and(|keep| rank >= keep) && !c.protected && !chosen[i] {
+                selected.push((i, Reason::KeepPerPath));
+                chosen[i] = true;
+            }
+        }
+    }
+
+    for (policy, rules) in policies.iter().enumerate() {
+        let Some(max_total) = rules.max_total_size else {
+            continue;
+        };
+        let group: Vec<usize> = order
+            .iter()
+            .copied()
+            .filter(|&i| candidates[i].policy == policy)
+            .collect();
+        let mut total: u64 = group
+            .iter()
+            .filter(|&&i| !chosen[i])
+            .map(|&i| candidates[i].size)
+            .sum();
+        for &i in &group {
+            if total <= max_total {
+                break;
+            }
+            let c = &candidates[i];
+            if c.protected || chosen[i] {
+                continue;
+            }
+            total -= c.size;
+            selected.push((i, Reason::MaxTotalSize));
+            chosen[i] = true;
+        }
+    }
+
+    selected.sort_by_key(|&(i, _)| (candidates[i].time_deleted, i));
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn cand(age_days: i64, size: u64, protected: bool) -> Candidate {
+        Candidate {
+            time_deleted: 100 * DAY - age_days * DAY,
+            size,
+            protected,
+            path: PathBuf::from(format!("/home/u/{age_days}")),
+            policy: 0,
+            rule: None,
+        }
+    }
+
+    fn from(path: &str, mut c: Candidate) -> Candidate {
+        c.path = path.into();
+        c
+    }
+
+    fn on_mount(mut c: Candidate) -> Candidate {
+        c.policy = 1;
+        c
+    }
+
+    #[test]
+    fn test_plan_no_rules() {
+        let c = vec![cand(50, 10, false)];
+        assert!(plan(&c, &[Rules::default()], &[], 100 * DAY).is_empty());
+    }
+
+    #[test]
+    fn test_plan_max_age() {
+        let c = vec![cand(40, 10, false), cand(5, 10, false), cand(31, 10, false)];
+        let rules = Rules {
+            max_age: Some(Duration::from_secs(30 * DAY as u64)),
+            ..Default::default()
+        };
+        assert_eq!(
+            plan(&c, &[rules], &[], 100 * DAY),
+            vec![(0, Reason::MaxAge), (2, Reason::MaxAge)]
+        );
+    }
+
+    #[test]
+    fn test_plan_max_age_skips_protected() {
+        let c = vec![cand(40, 10, true), cand(35, 10, false)];
+        let rules = Rules {
+            max_age: Some(Duration::from_secs(30 * DAY as u64)),
+            ..Default::default()
+        };
+        assert_eq!(
+            plan(&c, &[rules], &[], 100 * DAY),
+            vec![(1, Reason::MaxAge)]
+        );
+    }
+
+    #[test]
+    fn test_plan_max_total_size_oldest_first() {
+        let c = vec![cand(1, 50, false), cand(3, 50, false), cand(2, 50, false)];
+        let rules = Rules {
+            max_total_size: Some(60),
+            ..Default::default()
+        };
+        assert_eq!(
+            plan(&c, &[rules], &[], 100 * DAY),
+            vec![(1, Reason::MaxTotalSize), (2, Reason::MaxTotalSize)]
+        );
+    }
+
+    #[test]
+    fn test_plan_max_total_size_counts_protected() {
+        let c = vec![cand(3, 100, true), cand(2, 10, false), cand(1, 10, false)];
+        let rules = Rules {
+            max_total_size: Some(100),
+            ..Default::default()
+        };
+        // Protected item alone fills the budget, so every unprotected item goes
+        assert_eq!(
+            plan(&c, &[rules], &[], 100 * DAY),
+            vec![(1, Reason::MaxTotalSize), (2, Reason::MaxTotalSize)]
+        );
+    }
+
+    #[test]
+    fn test_plan_age_then_size() {
+        let c = vec![cand(40, 100, false), cand(2, 50, false), cand(1, 50, false)];
+        let rules = Rules {
+            max_age: Some(Duration::from_secs(30 * DAY as u64)),
+            max_total_size: Some(60),
+            keep_per_path: None,
+        };
+        assert_eq!(
+            plan(&c, &[rules], &[], 100 * DAY),
+            vec![(0, Reason::MaxAge), (1, Reason::MaxTotalSize)]
+        );
+    }
+
+    #[test]
+    fn test_plan_per_mount_rules() {
+        let c = vec![
+            cand(10, 10, false),
+            on_mount(cand(10, 10, false)),
+            on_mount(cand(1, 10, false)),
+        ];
+        let global = Rules {
+            max_age: Some(Duration::from_secs(30 * DAY as u64)),
+            ..Default::default()
+        };
+        let ssd = Rules {
+            max_age: Some(Duration::from_secs(3 * DAY as u64)),
+            ..Default::default()
+        };
+        assert_eq!(
+            plan(&c, &[global, ssd], &[], 100 * DAY),
+            vec![(1, Reason::MaxAge)]
+        );
+    }
+
+    #[test]
+    fn test_plan_size_limit_is_per_mount() {
+        let c = vec![
+            cand(3, 100, false),
+            on_mount(cand(2, 50, false)),
+            on_mount(cand(1, 50, false)),
+        ];
+        let ssd = Rules {
+            max_total_size: Some(60),
+            ..Default::default()
+        };
+        // The big item on the default volume doesn't count against the SSD quota
+        assert_eq!(
+            plan(&c, &[Rules::default(), ssd], &[], 100 * DAY),
+            vec![(1, Reason::MaxTotalSize)]
+        );
+    }
+
+    #[test]
+    fn test_plan_keep_per_path() {
+        let c = vec![
+            from("/a", cand(4, 10, false)),
+            from("/a", cand(3, 10, true)),
+            from("/a", cand(2, 10, false)),
+            from("/a", cand(1, 10, false)),
+            from("/b", cand(5, 10, false)),
+        ];
+        let rules = Rules {
+            keep_per_path: Some(2),
+            ..Default::default()
+        };
+        // The protected copy counts as one of the two kept, and is kept anyway
+        assert_eq!(
+            plan(&c, &[rules], &[], 100 * DAY),
+            vec![(0, Reason::KeepPerPath)]
+        );
+    }
+
+    #[test]
+    fn test_plan_rule_overrides_volume() {
+        let mut logs = from("/a.log", cand(5, 10, false));
+        logs.rule = Some(0);
+        let c = vec![logs, from("/b.txt", cand(5, 10, false))];
+        let global = Rules {
+            max_age: Some(Duration::from_secs(30 * DAY as u64)),
+            ..Default::default()
+        };
+        let log_rule = Override {
+            max_age: Some(Duration::from_secs(2 * DAY as u64)),
+            ..Default::default()
+        };
+        assert_eq!(
+            plan(&c, &[global], &[log_rule], 100 * DAY),
+            vec![(0, Reason::MaxAge)]
+        );
+    }
+
+    fn sample_config() -> config::Retention {
+        config::Retention {
+            max_age: Some("30d".into()),
+            max_total_size: Some("10G".into()),
+            mounts: vec![config::MountRetention {
+                path: "/mnt/ssd".into(),
+                max_age: Some("3d".into()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_policies_inherit_global() {
+        let p = policies(&sample_config()).unwrap();
+        assert_eq!(p.len(), 2);
+        assert_eq!(p[1].max_age, Some(Duration::from_secs(3 * DAY as u64)));
+        assert_eq!(p[1].max_total_size, Some(10 * 1024 * 1024 * 1024));
+    }
+
+    #[test]
+    fn test_policies_invalid_mount_value() {
+        let mut cfg = sample_config();
+        cfg.mounts[0].max_age = Some("soon".into());
+        let err = policies(&cfg).err().unwrap();
+        assert!(err.contains("/mnt/ssd"));
+    }
+
+    #[test]
+    fn test_describe_policy() {
+        let cfg = sample_config();
+        assert_eq!(describe_policy(&cfg, 0), "max_age=30d max_total_size=10G");
+        assert_eq!(
+            describe_policy(&cfg, 1),
+            "max_age=3d max_total_size=10G (mount /mnt/ssd)"
+        );
+        assert_eq!(describe_policy(&config::Retention::default(), 0), "none");
+    }
+
+    #[test]
+    fn test_mount_policy_longest_prefix() {
+        let mounts = [Path::new("/mnt"), Path::new("/mnt/ssd")];
+        assert_eq!(
+            mount_policy(&mounts, Path::new("/mnt/ssd/.Trash-1000/files/a")),
+            2
+        );
+        assert_eq!(
+            mount_policy(&mounts, Path::new("/mnt/hdd/.Trash-1000/files/a")),
+            1
+        );
+        assert_eq!(
+            mount_policy(&mounts, Path::new("/home/u/.local/share/Trash")),
+            0
+        );
+    }
+}
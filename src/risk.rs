@@ -0,0 +1,142 @@
+// How much a prompt is about to remove. The question shows what went into
+// the judgment (entries, bytes, protections overridden) and is colored by
+// severity, so a reflexive "y" has something to catch on: green for a single
+// small file, yellow for directories and big files, red for many entries or
+// anything a protection would normally stop.
+
+use crate::units;
+
+/// More entries than this makes a removal high-risk.
+pub const MANY_ENTRIES: u64 = 100;
+
+/// A single file this big is no longer low-risk.
+const LARGE_BYTES: u64 = 100 << 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Low,
+    Medium,
+    High,
+}
+
+impl Level {
+    /// SGR code for prompts at this level
+    fn code(self) -> &'static str {
+        match self {
+            Level::Low => "32",
+            Level::Medium => "33",
+            Level::High => "1;31",
+        }
+    }
+}
+
+/// What a prompt's removal involves.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Risk {
+    /// Entries at and below the paths
+    pub entries: u64,
+    /// Set when counting stopped early; `entries` is then a lower bound
+    pub more: bool,
+    pub bytes: u64,
+    /// Whether a directory with contents is among the paths
+    pub recursive: bool,
+    /// Protections this removal goes past, e.g. "write-protected"
+    pub overrides: Vec<&'static str>,
+}
+
+impl Risk {
+    /// Note a protection this removal goes past, once.
+    pub fn overridden(&mut self, what: &'static str) {
+        if !self.overrides.contains(&what) {
+            self.overrides.push(what);
+        }
+    }
+
+    pub fn level(&self) -> Level {
+        if self.entries > MANY_ENTRIES || !self.overrides.is_empty() {
+            Level::High
+        } else if self.recursive || self.entries > 1 || self.bytes >= LARGE_BYTES {
+            Level::Medium
+        } else {
+            Level::Low
+        }
+    }
+
+    /// The inputs, e.g. "12 entries, 3.4 MiB, write-protected".
+    pub fn summary(&self) -> String {
+        let plus = if self.more { "+" } else { "" };
+        let noun = if self.entries == 1 && !self.more {
+            "entry"
+        } else {
+            "entries"
+        };
+        let mut parts = vec![
+            format!("{}{plus} {noun}", self.entries),
+            units::format_size(self.bytes),
+        ];
+        parts.extend(self.overrides.iter().map(|o| o.to_string()));
+        parts.join(", ")
+    }
+
+    /// `prompt` ("...? ") with the summary before its question mark, colored
+    /// by level when stderr is a color terminal.
+    pub fn decorate(&self, prompt: &str) -> String {
+        let question = prompt.trim_end().trim_end_matches('?');
+        let text = format!("{question} [{}]?", self.summary());
+        format!(
+            "{} ",
+            crate::color::paint_stderr(&text, self.level().code())
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn risk(entries: u64, bytes: u64) -> Risk {
+        Risk {
+            entries,
+            bytes,
+            ..Risk::default()
+        }
+    }
+
+    #[test]
+    fn test_levels() {
+        assert_eq!(risk(1, 30).level(), Level::Low);
+        assert_eq!(risk(1, LARGE_BYTES).level(), Level::Medium);
+        assert_eq!(risk(5, 30).level(), Level::Medium);
+        assert_eq!(
+            Risk {
+                recursive: true,
+                ..risk(1, 0)
+            }
+            .level(),
+            Level::Medium
+        );
+        assert_eq!(risk(MANY_ENTRIES + 1, 0).level(), Level::High);
+        assert_eq!(
+            Risk {
+                overrides: vec!["write-protected"],
+                ..risk(1, 0)
+            }
+            .level(),
+            Level::High
+        );
+    }
+
+    #[test]
+    fn test_decorate_puts_inputs_before_the_question_mark() {
+        assert_eq!(
+            risk(1, 30).decorate("trache: remove regular file 'a'? "),
+            "trache: remove regular file 'a' [1 entry, 30 B]? "
+        );
+        let many = Risk {
+            more: true,
+            overrides: vec!["write-protected"],
+            ..risk(100_000, 2048)
+        };
+        assert_eq!(many.summary(), "100000+ entries, 2.0 KiB, write-protected");
+    }
+}
@@ -0,0 +1,292 @@
+// Running as a drop-in `rm`. When the binary is invoked as rm (through a
+// symlink or hard link placed earlier in PATH), only GNU rm's options are
+// accepted, usage errors and diagnostics read like GNU rm's, and nothing
+// trache-specific (--trash-* modes, subcommands, extra options) can
+// be reached from the command line. The accepted arguments are translated
+// into the equivalent trache command line, so trashing itself is shared.
+// `trache --compat gnu` gets the same wording and exit statuses without
+// giving up trache's own options.
+
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use clap::error::{ContextKind, ContextValue, ErrorKind};
+use clap::{ArgAction, Parser};
+
+use crate::{InteractiveMode, PreserveRoot};
+
+static ENABLED: OnceLock<bool> = OnceLock::new();
+
+/// GNU rm's options, and nothing else.
+#[derive(Parser)]
+#[command(
+    name = "rm",
+    version = concat!("(trache) ", env!("CARGO_PKG_VERSION")),
+    override_usage = "rm [OPTION]... [FILE]...",
+    about = "Remove (move to the trash) the FILE(s).",
+    disable_help_flag = true,
+    disable_version_flag = true,
+    infer_long_args = true
+)]
+struct RmCli {
+    /// Ignore nonexistent files and arguments, never prompt
+    #[arg(short = 'f', long, overrides_with_all = ["prompt_always", "prompt_once", "interactive", "force"])]
+    force: bool,
+
+    /// Prompt before every removal
+    #[arg(short = 'i', overrides_with_all = ["force", "prompt_once", "interactive", "prompt_always"])]
+    prompt_always: bool,
+
+    /// Prompt once before removing more than three files, or when removing recursively
+    #[arg(short = 'I', overrides_with_all = ["force", "prompt_always", "interactive", "prompt_once"])]
+    prompt_once: bool,
+
+    /// Prompt according to WHEN: never, once (-I), or always (-i)
+    #[arg(
+        long,
+        value_name = "WHEN",
+        default_missing_value = "always",
+        num_args = 0..=1,
+        require_equals = true,
+        overrides_with_all = ["force", "prompt_always", "prompt_once", "interactive"]
+    )]
+    interactive: Option<InteractiveMode>,
+
+    /// Skip any directory on a file system different from its parent's
+    #[arg(long = "one-file-system", overrides_with = "one_file_system")]
+    one_file_system: bool,
+
+    /// Do not treat '/' specially
+    #[arg(long = "no-preserve-root", overrides_with_all = ["preserve_root", "no_preserve_root"])]
+    no_preserve_root: bool,
+
+    /// Do not remove '/' (default); with 'all', reject any argument on a separate device from its parent
+    #[arg(
+        long = "preserve-root",
+        value_name = "all",
+        default_missing_value = "yes",
+        num_args = 0..=1,
+        require_equals = true,
+        overrides_with_all = ["no_preserve_root", "preserve_root"]
+    )]
+    preserve_root: Option<PreserveRoot>,
+
+    /// Remove directories and their contents recursively
+    #[arg(short = 'r', short_alias = 'R', long, overrides_with = "recursive")]
+    recursive: bool,
+
+    /// Remove empty directories
+    #[arg(short = 'd', long, overrides_with = "dir")]
+    dir: bool,
+
+    /// Explain what is being done
+    #[arg(short = 'v', long, overrides_with = "verbose")]
+    verbose: bool,
+
+    /// Display this help and exit
+    #[arg(long, action = ArgAction::Help)]
+    help: Option<bool>,
+
+    /// Output version information and exit
+    #[arg(long, action = ArgAction::Version)]
+    version: Option<bool>,
+
+    #[arg(value_name = "FILE")]
+    files: Vec<OsString>,
+}
+
+/// Whether `argv0` names rm, ignoring the directory and any .exe suffix.
+pub fn invoked_as_rm(argv0: &OsStr) -> bool {
+    Path::new(argv0).file_stem() == Some(OsStr::new("rm"))
+}
+
+/// Act as rm for the rest of the run.
+pub fn enable() {
+    let _ = ENABLED.set(true);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.get().copied().unwrap_or(false)
+}
+
+/// The program name diagnostics start with.
+pub fn prog() -> &'static str {
+    if enabled() { "rm" } else { "trache" }
+}
+
+/// An error as rm would word it: without Rust's " (os error N)" suffix.
+pub fn describe(error: &dyn std::fmt::Display) -> String {
+    let text = error.to_string();
+    if !enabled() {
+        return text;
+    }
+    match text.rfind(" (os error ") {
+        Some(at) if text.ends_with(')') => text[..at].to_string(),
+        _ => text,
+    }
+}
+
+/// Parse rm's command line and return the trache command line doing the
+/// same. Exits the way GNU rm does on usage errors, --help and --version.
+pub fn translate(args: Vec<OsString>) -> Vec<OsString> {
+    let rm = RmCli::try_parse_from(&args).unwrap_or_else(|e| {
+        if matches!(e.kind(), ErrorKind::DisplayHelp | ErrorKind::DisplayVersion) {
+            e.exit();
+        }
+        for line in usage_error(&e) {
+            eprintln!("{line}");
+        }
+        std::process::exit(1);
+    });
+    if rm.files.is_empty() {
+        if rm.force {
+            std::process::exit(0);
+        }
+        eprintln!("rm: missing operand");
+        eprintln!("Try 'rm --help' for more information.");
+        std::process::exit(1);
+    }
+    trache_args(rm)
+}
+
+fn trache_args(rm: RmCli) -> Vec<OsString> {
+    let mut out: Vec<OsString> = vec!["trache".into(), "--color=never".into()];
+    let flags = [
+        (rm.force, "-f"),
+        (rm.prompt_always, "-i"),
+        (rm.prompt_once, "-I"),
+        (
+            rm.interactive == Some(InteractiveMode::Never),
+            "--interactive=never",
+        ),
+        (
+            rm.interactive == Some(InteractiveMode::Once),
+            "--interactive=once",
+        ),
+        (
+            rm.interactive == Some(InteractiveMode::Always),
+            "--interactive=always",
+        ),
+        (
+            rm.interactive == Some(InteractiveMode::Smart),
+            "--interactive=smart",
+        ),
+        (rm.one_file_system, "--one-file-system"),
+        (rm.no_preserve_root, "--no-preserve-root"),
+        (
+            rm.preserve_root == Some(PreserveRoot::All),
+            "--preserve-root=all",
+        ),
+        (rm.recursive, "-r"),
+        (rm.dir, "-d"),
+        (rm.verbose, "-v"),
+    ];
+    out.extend(flags.iter().filter(|(set, _)| *set).map(|(_, f)| f.into()));
+    // Everything after this is a FILE, even `list` or `--trash-empty`
+    out.push("--".into());
+    out.extend(rm.files);
+    out
+}
+
+/// GNU rm's wording for a usage error, one line per item.
+fn usage_error(e: &clap::Error) -> Vec<String> {
+    let context = |kind| match e.get(kind) {
+        Some(ContextValue::String(s)) => Some(s.clone()),
+        _ => None,
+    };
+    let mut lines = Vec::new();
+    match (e.kind(), context(ContextKind::InvalidArg)) {
+        (ErrorKind::UnknownArgument, Some(arg)) => {
+            let option = arg.split('=').next().unwrap_or(&arg);
+            if option.starts_with("--") {
+                lines.push(format!("rm: unrecognized option '{option}'"));
+            } else {
+                let letter = option.trim_start_matches('-');
+                lines.push(format!("rm: invalid option -- '{letter}'"));
+            }
+            // Someone trying to remove a file whose name starts with '-'
+            if let Some(file) = std::env::args_os()
+                .skip(1)
+                .find(|a| a.to_string_lossy().starts_with(option))
+                .filter(|a| Path::new(a).symlink_metadata().is_ok())
+            {
+                let file = file.to_string_lossy();
+                lines.push(format!("Try 'rm ./{file}' to remove the file '{file}'."));
+            }
+        }
+        (ErrorKind::InvalidValue, Some(arg)) => {
+            let option = arg.split([' ', '=', '[']).next().unwrap_or(&arg);
+            let value = context(ContextKind::InvalidValue).unwrap_or_default();
+            lines.push(format!("rm: invalid argument '{value}' for '{option}'"));
+        }
+        _ => {
+            let message = e.kind().as_str().unwrap_or("invalid usage");
+            lines.push(format!("rm: {}", message.to_lowercase()));
+        }
+    }
+    lines.push("Try 'rm --help' for more information.".into());
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translated(args: &[&str]) -> Vec<String> {
+        let rm = RmCli::try_parse_from(args).unwrap();
+        trache_args(rm)
+            .into_iter()
+            .skip(2)
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn error(args: &[&str]) -> Vec<String> {
+        usage_error(&RmCli::try_parse_from(args).err().unwrap())
+    }
+
+    #[test]
+    fn test_recognizes_rm_names() {
+        assert!(invoked_as_rm(OsStr::new("rm")));
+        assert!(invoked_as_rm(OsStr::new("/usr/local/bin/rm")));
+        assert!(invoked_as_rm(OsStr::new("rm.exe")));
+        assert!(!invoked_as_rm(OsStr::new("trache")));
+        assert!(!invoked_as_rm(OsStr::new("rmdir")));
+    }
+
+    #[test]
+    fn test_translates_gnu_options() {
+        assert_eq!(translated(&["rm", "-rf", "a"]), ["-f", "-r", "--", "a"]);
+        assert_eq!(
+            translated(&["rm", "-R", "-v", "a"]),
+            ["-r", "-v", "--", "a"]
+        );
+        assert_eq!(translated(&["rm", "-f", "-i", "a"]), ["-i", "--", "a"]);
+        assert_eq!(
+            translated(&["rm", "--interactive=no", "--recur", "a"]),
+            ["--interactive=never", "-r", "--", "a"]
+        );
+        assert_eq!(
+            translated(&["rm", "--", "--trash-empty", "list"]),
+            ["--", "--trash-empty", "list"]
+        );
+    }
+
+    #[test]
+    fn test_usage_errors_read_like_gnu() {
+        assert_eq!(
+            error(&["rm", "--trash-empty"]),
+            [
+                "rm: unrecognized option '--trash-empty'",
+                "Try 'rm --help' for more information."
+            ]
+        );
+        assert_eq!(error(&["rm", "-rz", "a"])[0], "rm: invalid option -- 'z'");
+        assert_eq!(error(&["rm", "-h"])[0], "rm: invalid option -- 'h'");
+        assert_eq!(
+            error(&["rm", "--interactive=sometimes", "a"])[0],
+            "rm: invalid argument 'sometimes' for '--interactive'"
+        );
+    }
+}
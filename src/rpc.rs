@@ -0,0 +1,230 @@
+// --serve-stdio: the service operations as JSON-RPC 2.0 over stdin and
+// stdout, for editor plugins and front-ends that would rather keep one trache
+// running than start one per action. Each line of input is a request, or a
+// batch of them in an array, and each gets its response on one line of
+// output, sent once the request's post hooks and --report lines have run;
+// notifications (requests without an id) get none. Parameters are
+// given by name:
+//
+//   trash    {"paths": [...]}  -> {"failed": {path: why}}
+//   list                       -> {"items": [{"id", "path", "deleted"}]}
+//   restore  {"ids": [...]}    -> {"restored": {id: path}, "failed": {id: why}}
+//   purge    {"ids": [...]}    -> {"failed": {id: why}}
+//   stats                      -> {"count", "size"}
+//
+// The server stops at the end of input.
+
+use std::io::{self, BufRead, Write};
+
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+
+use crate::service::Service;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The operation itself failed, such as the trash not being readable
+const FAILED: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Paths {
+    paths: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Ids {
+    ids: Vec<String>,
+}
+
+/// Answer each request in `input` on `output` until input runs out.
+pub fn serve(service: &Service, input: &mut dyn BufRead, output: &mut dyn Write) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(reply) = answer(service, &line) {
+            writeln!(output, "{reply}")?;
+            output.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// The response to one line of input, if it wants one.
+fn answer(service: &Service, line: &str) -> Option<Value> {
+    match serde_json::from_str(line) {
+        Err(e) => Some(error(Value::Null, PARSE_ERROR, e.to_string())),
+        Ok(Value::Array(batch)) if batch.is_empty() => Some(error(
+            Value::Null,
+            INVALID_REQUEST,
+            "empty batch".to_string(),
+        )),
+        Ok(Value::Array(batch)) => {
+            let replies: Vec<Value> = batch
+                .into_iter()
+                .filter_map(|request| call(service, request))
+                .collect();
+            (!replies.is_empty()).then_some(Value::Array(replies))
+        }
+        Ok(request) => call(service, request),
+    }
+}
+
+/// Carry out one request, returning its response unless it's a
+/// notification.
+fn call(service: &Service, request: Value) -> Option<Value> {
+    let id = request.get("id").cloned();
+    let Some(method) = request.get("method").and_then(Value::as_str) else {
+        return Some(error(
+            id.unwrap_or(Value::Null),
+            INVALID_REQUEST,
+            "no method".to_string(),
+        ));
+    };
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+    let result = dispatch(service, method, params);
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err((code, message)) => error(id, code, message),
+    })
+}
+
+fn dispatch(service: &Service, method: &str, params: Value) -> Result<Value, (i64, String)> {
+    let failed = |e: String| (FAILED, e);
+    match method {
+        "trash" => {
+            let Paths { paths } = parse(params)?;
+            Ok(json!({"failed": service.trash(paths)}))
+        }
+        "list" => {
+            let items = service.list().map_err(failed)?;
+            Ok(json!({"items": items}))
+        }
+        "restore" => {
+            let Ids { ids } = parse(params)?;
+            let (restored, failures) = service.restore(ids).map_err(failed)?;
+            Ok(json!({"restored": restored, "failed": failures}))
+        }
+        "purge" => {
+            let Ids { ids } = parse(params)?;
+            let failures = service.purge(ids).map_err(failed)?;
+            Ok(json!({"failed": failures}))
+        }
+        "stats" => {
+            let (count, size) = service.stats().map_err(failed)?;
+            Ok(json!({"count": count, "size": size}))
+        }
+        _ => Err((METHOD_NOT_FOUND, format!("no method '{method}'"))),
+    }
+}
+
+fn parse<T: DeserializeOwned>(params: Value) -> Result<T, (i64, String)> {
+    serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, e.to_string()))
+}
+
+fn error(id: Value, code: i64, message: String) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::MockBroker;
+
+    fn session(input: &str) -> Vec<Value> {
+        let service = Service::new(Box::new(MockBroker::default()));
+        let mut output = Vec::new();
+        serve(&service, &mut input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_requests_get_responses() {
+        let replies = session(concat!(
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "trash", "params": {"paths": ["/a", "b"]}}"#,
+            "\n\n",
+            r#"{"jsonrpc": "2.0", "method": "trash", "params": {"paths": ["/c"]}}"#,
+            "\n",
+            r#"[{"jsonrpc": "2.0", "id": 2, "method": "stats"}, {"jsonrpc": "2.0", "id": 3, "method": "list"}]"#,
+            "\n",
+        ));
+        assert_eq!(replies.len(), 2);
+        assert_eq!(
+            replies[0],
+            json!({"jsonrpc": "2.0", "id": 1, "result": {"failed": {"b": "not an absolute path"}}})
+        );
+        // The notification was carried out all the same
+        assert_eq!(replies[1][0]["result"], json!({"count": 2, "size": 2}));
+        assert_eq!(replies[1][1]["result"]["items"][1]["path"], "/c");
+    }
+
+    /// Keeps, for each line written, which of `watched` had been through
+    /// the post_trash hook by then
+    struct Snapshots {
+        watched: Vec<std::path::PathBuf>,
+        seen: Vec<Vec<bool>>,
+    }
+
+    impl Write for Snapshots {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let hooked = self
+                .watched
+                .iter()
+                .map(|path| path.with_extension("hooked").exists());
+            self.seen.push(hooked.collect());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_hooks_run_before_each_response() {
+        crate::hooks::mark_trashed();
+        let tmp = tempfile::TempDir::new().unwrap();
+        let watched = vec![tmp.path().join("first"), tmp.path().join("second")];
+        let request = |id: usize| {
+            let paths = json!({"paths": [watched[id]]});
+            json!({"jsonrpc": "2.0", "id": id, "method": "trash", "params": paths}).to_string()
+        };
+        let input = format!("{}\n{}\n", request(0), request(1));
+        let service = Service::new(Box::new(MockBroker::default()));
+        let mut output = Snapshots {
+            watched: watched.clone(),
+            seen: Vec::new(),
+        };
+        serve(&service, &mut input.as_bytes(), &mut output).unwrap();
+        // The first response went out after the first call's hook and before
+        // the second call; the second after both
+        assert_eq!(output.seen.first().unwrap(), &[true, false]);
+        assert_eq!(output.seen.last().unwrap(), &[true, true]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let replies = session(concat!(
+            "{not json\n",
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "shred"}"#,
+            "\n",
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "purge", "params": {"paths": []}}"#,
+            "\n",
+            r#"{"jsonrpc": "2.0", "id": 3}"#,
+            "\n",
+        ));
+        let codes: Vec<&Value> = replies.iter().map(|r| &r["error"]["code"]).collect();
+        assert_eq!(codes, [-32700, -32601, -32602, -32600]);
+        assert_eq!(replies[0]["id"], Value::Null);
+        assert_eq!(replies[3]["id"], 3);
+    }
+}
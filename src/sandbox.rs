@@ -0,0 +1,243 @@
+// A throwaway trash for trying trache out: --sandbox points the trash at a
+// private temp directory and keeps every operation inside its playground.
+// Only the freedesktop trash can be redirected, so this is Linux/BSD only.
+
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::TracheError;
+
+/// Files the playground starts with.
+const SAMPLE_FILES: &[(&str, &str)] = &[
+    ("notes.txt", "Remember to water the plants.\n"),
+    ("todo.md", "- [ ] try trache\n- [ ] read the README\n"),
+    ("drafts/chapter1.txt", "It was a dark and stormy night.\n"),
+    ("drafts/chapter2.txt", "The storm passed.\n"),
+];
+
+pub struct Sandbox {
+    root: PathBuf,
+}
+
+/// Set once this process has entered the sandbox
+static ENTERED: AtomicBool = AtomicBool::new(false);
+
+/// Refuse `path` if the sandbox has been entered and it lies outside the
+/// playground. Paths named on the command line are checked up front; this
+/// is for those that arrive later, from --batch or a service call.
+pub fn check(path: &Path) -> Result<(), String> {
+    let sandbox = Sandbox::new();
+    if !ENTERED.load(Ordering::Relaxed) || sandbox.contains(path) {
+        return Ok(());
+    }
+    Err(format!(
+        "'{}' is outside the sandbox ({})",
+        path.display(),
+        sandbox.playground().display()
+    ))
+}
+
+impl Sandbox {
+    /// The per-user sandbox under the system temp directory.
+    pub fn new() -> Self {
+        let uid = unsafe { libc::getuid() };
+        Sandbox {
+            root: env::temp_dir().join(format!("trache-sandbox-{uid}")),
+        }
+    }
+
+    /// Where the sample files live; relative FILE arguments resolve here.
+    pub fn playground(&self) -> PathBuf {
+        self.root.join("playground")
+    }
+
+    fn data_home(&self) -> PathBuf {
+        self.root.join("data")
+    }
+
+    /// The directory the sandboxed trash lives in.
+    pub fn trash_dir(&self) -> PathBuf {
+        self.data_home().join("Trash")
+    }
+
+    /// Create the sandbox (seeding sample files the first time, or again when
+    /// `reset`), then point this process's trash and working directory at it.
+    pub fn enter(&self, reset: bool) -> io::Result<()> {
+        if reset && self.root.exists() {
+            fs::remove_dir_all(&self.root)?;
+        }
+        let fresh = !self.root.exists();
+        if fresh {
+            fs::DirBuilder::new().mode(0o700).create(&self.root)?;
+        }
+        // Refuse a directory someone else planted in the shared temp dir
+        let meta = self.root.symlink_metadata()?;
+        if !meta.is_dir() || meta.uid() != unsafe { libc::getuid() } {
+            return Err(io::Error::other(format!(
+                "'{}' is not a directory owned by you",
+                self.root.display()
+            )));
+        }
+        if fresh {
+            self.seed()?;
+        }
+        fs::create_dir_all(self.data_home())?;
+        ENTERED.store(true, Ordering::Relaxed);
+
+        // SAFETY: called from main before any other threads exist
+        unsafe {
+            env::set_var("XDG_DATA_HOME", self.data_home());
+            // Keeps the --mv journal in the sandbox too
+            env::set_var("XDG_STATE_HOME", self.root.join("state"));
+        }
+        env::set_current_dir(self.playground())
+    }
+
+    fn seed(&self) -> io::Result<()> {
+        for (name, contents) in SAMPLE_FILES {
+            let path = self.playground().join(name);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, contents)?;
+        }
+        Ok(())
+    }
+
+    /// Whether `path` lies inside the playground.
+    pub fn contains(&self, path: &Path) -> bool {
+        let Ok(absolute) = std::path::absolute(path) else {
+            return false;
+        };
+        // Resolve symlinks in the parent, but not the final component, which
+        // is what gets trashed
+        let resolved = match (absolute.parent(), absolute.file_name()) {
+            (Some(parent), Some(name)) => parent.canonicalize().map(|p| p.join(name)),
+            _ => return false,
+        };
+        let playground = self.playground().canonicalize();
+        match (resolved, playground) {
+            (Ok(path), Ok(playground)) => path.starts_with(playground),
+            _ => false,
+        }
+    }
+}
+
+/// One step of the --tutorial walkthrough.
+struct Step {
+    explain: &'static str,
+    args: &'static [&'static str],
+}
+
+const STEPS: &[Step] = &[
+    Step {
+        explain: "Trash a file. It leaves the playground but isn't gone yet.",
+        args: &["-v", "notes.txt"],
+    },
+    Step {
+        explain: "See what's in the trash.",
+        args: &["--trash-list"],
+    },
+    Step {
+        explain: "Changed your mind? Put it back where it was.",
+        args: &["--trash-undo", "notes.txt"],
+    },
+    Step {
+        explain: "Directories need -r, just like rm.",
+        args: &["-rv", "drafts"],
+    },
+    Step {
+        explain: "Check first what a purge would delete for good...",
+        args: &["--trash-dry-run", "--trash-purge", "drafts"],
+    },
+    Step {
+        explain: "...then purge it. Purged items cannot be restored.",
+        args: &["--trash-purge", "drafts"],
+    },
+];
+
+/// Walk through the basics, running each step against the sandbox.
+pub fn tutorial(sandbox: &Sandbox, input: &mut dyn BufRead) -> Result<(), TracheError> {
+    let exe = env::current_exe()?;
+    println!("Welcome to trache! Everything here happens in a sandbox:");
+    println!("  {}", sandbox.playground().display());
+    println!("Nothing outside it is touched. It contains:");
+    for (name, _) in SAMPLE_FILES {
+        println!("  {name}");
+    }
+
+    for (n, step) in STEPS.iter().enumerate() {
+        println!();
+        println!("{}. {}", n + 1, step.explain);
+        println!("   $ trache {}", step.args.join(" "));
+        print!("   [press Enter to run it] ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        input.read_line(&mut line)?;
+
+        let status = Command::new(&exe)
+            .arg("--sandbox")
+            .args(step.args)
+            .status()?;
+        if !status.success() {
+            return Err(TracheError::Other(format!("step {} failed", n + 1)));
+        }
+    }
+
+    println!();
+    println!("That's it. Keep experimenting with `trache --sandbox ...`, or run");
+    println!("`trache --tutorial` again to start over with fresh files.");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_sandbox() -> (tempfile::TempDir, Sandbox) {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let sandbox = Sandbox {
+            root: tmp.path().join("sandbox"),
+        };
+        fs::create_dir(&sandbox.root).unwrap();
+        sandbox.seed().unwrap();
+        (tmp, sandbox)
+    }
+
+    #[test]
+    fn test_seed_creates_samples() {
+        let (_tmp, sandbox) = temp_sandbox();
+        for (name, contents) in SAMPLE_FILES {
+            let text = fs::read_to_string(sandbox.playground().join(name)).unwrap();
+            assert_eq!(&text, contents);
+        }
+    }
+
+    #[test]
+    fn test_contains() {
+        let (tmp, sandbox) = temp_sandbox();
+        let playground = sandbox.playground();
+        assert!(sandbox.contains(&playground.join("notes.txt")));
+        assert!(sandbox.contains(&playground.join("drafts")));
+        assert!(sandbox.contains(&playground.join("drafts/../todo.md")));
+        assert!(!sandbox.contains(&playground.join("../data")));
+        assert!(!sandbox.contains(tmp.path()));
+        assert!(!sandbox.contains(Path::new("/etc/passwd")));
+    }
+
+    #[test]
+    fn test_contains_symlinked_parent() {
+        let (tmp, sandbox) = temp_sandbox();
+        let outside = tmp.path().join("outside");
+        fs::create_dir(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, sandbox.playground().join("escape")).unwrap();
+        assert!(!sandbox.contains(&sandbox.playground().join("escape/file")));
+        // The link itself is inside and may be trashed
+        assert!(sandbox.contains(&sandbox.playground().join("escape")));
+    }
+}
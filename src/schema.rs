@@ -0,0 +1,184 @@
+// The JSON schemas of every machine-readable output, printed by --schema.
+// They're generated from the serde types that produce the output, so they
+// can't drift from it. Renaming or removing a field, or changing its type, is
+// a breaking change: bump VERSION and update the pinned fields in the tests.
+// Adding a field is compatible.
+
+use serde_json::{Value, json};
+
+use crate::{audit, dryrun, events, retention, table};
+
+pub const VERSION: u32 = 1;
+
+/// The schema document: a version and one schema per output.
+pub fn document() -> Value {
+    #[allow(unused_mut)]
+    let mut outputs = json!({
+        "list": output(
+            "--trash-list --output json, one object per line",
+            schemars::schema_for!(table::ListedItem),
+        ),
+        "events": output(
+            "--log-format json, one object per line, including under --trash-dry-run",
+            schemars::schema_for!(events::Event<'static>),
+        ),
+        "plan": output(
+            "--trash-dry-run --output json, one step per line",
+            schemars::schema_for!(dryrun::Step),
+        ),
+        "clean": output(
+            "--trash-clean --porcelain, including under --trash-dry-run",
+            schemars::schema_for!(retention::CleanSummary),
+        ),
+        "audit": output(
+            "audit log lines with [audit] format = \"json\"",
+            audit::schema(),
+        ),
+    });
+    #[cfg(any(
+        target_os = "windows",
+        all(unix, not(target_os = "macos"), not(target_os = "ios"))
+    ))]
+    {
+        outputs["report"] = output(
+            "lines written to the [report] command's stdin",
+            crate::report::schema(),
+        );
+    }
+    json!({
+        "version": VERSION,
+        "outputs": outputs,
+    })
+}
+
+fn output(description: &str, schema: schemars::Schema) -> Value {
+    json!({
+        "description": description,
+        "schema": schema,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    /// Every property name anywhere in `schema`, including nested types and
+    /// enum variants.
+    fn fields(schema: &Value) -> BTreeSet<String> {
+        let mut found = BTreeSet::new();
+        collect(schema, &mut found);
+        found
+    }
+
+    fn collect(value: &Value, found: &mut BTreeSet<String>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::Object(props)) = map.get("properties") {
+                    found.extend(props.keys().cloned());
+                }
+                map.values().for_each(|v| collect(v, found));
+            }
+            Value::Array(values) => values.iter().for_each(|v| collect(v, found)),
+            _ => {}
+        }
+    }
+
+    fn pinned(output: &str) -> BTreeSet<String> {
+        fields(&document()["outputs"][output]["schema"])
+    }
+
+    fn set(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    // These pin the field names of schema version 1. If one fails, the
+    // output changed incompatibly: bump VERSION and update the list.
+
+    #[test]
+    fn test_list_fields_are_stable() {
+        assert_eq!(
+            pinned("list"),
+            set(&[
+                "n",
+                "id",
+                "deleted",
+                "deleted_unix",
+                "name",
+                "path",
+                "trash_path"
+            ])
+        );
+    }
+
+    #[test]
+    fn test_event_fields_are_stable() {
+        assert_eq!(
+            pinned("events"),
+            set(&[
+                "event", "path", "dry_run", "reason", "prompt", "answer", "error"
+            ])
+        );
+    }
+
+    #[test]
+    fn test_plan_fields_are_stable() {
+        assert_eq!(
+            pinned("plan"),
+            set(&["action", "source", "destination", "conflicts"])
+        );
+        let schema = serde_json::to_string(&document()["outputs"]["plan"]).unwrap();
+        for name in ["trash", "shred", "restore", "purge", "collision", "twin"] {
+            assert!(schema.contains(&format!("\"{name}\"")), "missing {name}");
+        }
+    }
+
+    #[test]
+    fn test_clean_fields_are_stable() {
+        assert_eq!(
+            pinned("clean"),
+            set(&[
+                "dry_run",
+                "purged",
+                "purged_bytes",
+                "remaining",
+                "protected",
+                "items",
+                "path",
+                "size",
+                "reason",
+            ])
+        );
+    }
+
+    #[test]
+    fn test_audit_fields_are_stable() {
+        assert_eq!(
+            pinned("audit"),
+            set(&["time", "pid", "op", "path", "outcome", "error", "label"])
+        );
+    }
+
+    #[test]
+    #[cfg(any(
+        target_os = "windows",
+        all(unix, not(target_os = "macos"), not(target_os = "ios"))
+    ))]
+    fn report_fields_are_stable() {
+        assert_eq!(
+            pinned("report"),
+            set(&["time", "op", "path", "size", "user", "host"])
+        );
+    }
+
+    #[test]
+    fn test_event_names_are_stable() {
+        let schema = serde_json::to_string(&document()["outputs"]["events"]).unwrap();
+        for name in [
+            "trashed", "shredded", "deleted", "skipped", "prompted", "errored",
+        ] {
+            assert!(schema.contains(&format!("\"{name}\"")), "missing {name}");
+        }
+    }
+}
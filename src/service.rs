@@ -0,0 +1,314 @@
+// The trash operations trache offers other programs, over D-Bus with --daemon
+// and as JSON-RPC with --serve-stdio: trash, list, restore, purge and stats.
+// Items are named by the short IDs --trash-list shows, and each call reports
+// what it couldn't do item by item rather than failing as a whole. The work
+// itself is done by a Broker, which main backs with the command line's own
+// code, so items are trashed as trache trashes them and restored beside
+// whatever has since taken their place as `name-untrash_N`. Read-only mode
+// and --sandbox are enforced here too, item by item, since the paths only
+// arrive once the service is running.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use trash::TrashItem;
+
+#[cfg(test)]
+use crate::backend::TrashBackend;
+
+/// What the service does for each call, carried out as the command line
+/// would carry it out
+pub trait Broker: Send + Sync {
+    fn trash(&self, path: &Path) -> Result<(), String>;
+    /// Every item in the trash
+    fn list(&self) -> Result<Vec<TrashItem>, String>;
+    /// Restore `item`, under a new name if its original path is taken, and
+    /// return where it went
+    fn restore(&self, item: TrashItem) -> Result<PathBuf, String>;
+    fn purge(&self, item: TrashItem) -> Result<(), String>;
+    fn size(&self, item: &TrashItem) -> u64;
+}
+
+/// What couldn't be done, by path or ID, each with why
+pub type Failures = HashMap<String, String>;
+
+/// An item as List reports it
+#[derive(Debug, Serialize)]
+pub struct Listed {
+    pub id: String,
+    pub path: String,
+    /// Seconds since the epoch
+    pub deleted: i64,
+}
+
+pub struct Service {
+    broker: Box<dyn Broker>,
+    /// Why nothing may be changed, in read-only mode
+    read_only: Option<String>,
+}
+
+impl Service {
+    pub fn new(broker: Box<dyn Broker>) -> Service {
+        Service {
+            broker,
+            read_only: None,
+        }
+    }
+
+    /// The same service, refusing to change anything because of `why`.
+    pub fn read_only(self, why: &str) -> Service {
+        Service {
+            read_only: Some(format!("read-only mode ({why})")),
+            ..self
+        }
+    }
+
+    /// Whether `path` may be changed: not in read-only mode, and inside the
+    /// sandbox if there is one.
+    fn allowed(&self, path: &Path) -> Result<(), String> {
+        if let Some(why) = &self.read_only {
+            return Err(why.clone());
+        }
+        crate::sandbox::check(path)
+    }
+
+    /// Trash each of `paths`, which must be absolute. Returns the paths
+    /// that couldn't be trashed.
+    pub fn trash(&self, paths: Vec<String>) -> Failures {
+        let mut failed = HashMap::new();
+        for path in paths {
+            let result = if Path::new(&path).is_absolute() {
+                self.allowed(Path::new(&path))
+                    .and_then(|()| self.broker.trash(Path::new(&path)))
+            } else {
+                Err("not an absolute path".to_string())
+            };
+            if let Err(e) = result {
+                failed.insert(path, e);
+            }
+        }
+        flush();
+        failed
+    }
+
+    /// The items in the trash, oldest first.
+    pub fn list(&self) -> Result<Vec<Listed>, String> {
+        let items = self.broker.list()?;
+        flush();
+        Ok(items
+            .iter()
+            .map(|item| Listed {
+                id: crate::items::short_id(item),
+                path: item.original_path().to_string_lossy().into_owned(),
+                deleted: item.time_deleted,
+            })
+            .collect())
+    }
+
+    /// Restore the items with the short IDs `ids`. Returns where each
+    /// restored item went, and the IDs that couldn't be restored.
+    pub fn restore(&self, ids: Vec<String>) -> Result<(HashMap<String, String>, Failures), String> {
+        let (found, mut failed) = self.resolve(ids)?;
+        let mut restored = HashMap::new();
+        for (id, item) in found {
+            let result = self
+                .allowed(&item.original_path())
+                .and_then(|()| self.broker.restore(item));
+            match result {
+                Ok(path) => {
+                    restored.insert(id, path.to_string_lossy().into_owned());
+                }
+                Err(e) => {
+                    failed.insert(id, e);
+                }
+            }
+        }
+        flush();
+        Ok((restored, failed))
+    }
+
+    /// Permanently delete the items with the short IDs `ids`. Returns the IDs
+    /// that couldn't be purged.
+    pub fn purge(&self, ids: Vec<String>) -> Result<Failures, String> {
+        let (found, mut failed) = self.resolve(ids)?;
+        for (id, item) in found {
+            let result = match &self.read_only {
+                Some(why) => Err(why.clone()),
+                None => self.broker.purge(item),
+            };
+            if let Err(e) = result {
+                failed.insert(id, e);
+            }
+        }
+        flush();
+        Ok(failed)
+    }
+
+    /// How many items are in the trash, and their total size in bytes.
+    pub fn stats(&self) -> Result<(u64, u64), String> {
+        let items = self.broker.list()?;
+        let size = items.iter().map(|item| self.broker.size(item)).sum();
+        flush();
+        Ok((items.len() as u64, size))
+    }
+
+    /// The items with the short IDs `ids`, and why any couldn't be found.
+    /// Short IDs are hashes, so two items may share one; such an ID names
+    /// neither, rather than whichever came first.
+    fn resolve(&self, ids: Vec<String>) -> Result<(Vec<(String, TrashItem)>, Failures), String> {
+        let mut items: HashMap<String, Vec<TrashItem>> = HashMap::new();
+        for item in self.broker.list()? {
+            items
+                .entry(crate::items::short_id(&item))
+                .or_default()
+                .push(item);
+        }
+        let mut found = Vec::new();
+        let mut missing = HashMap::new();
+        for id in ids {
+            match items.remove(&id) {
+                Some(mut matched) if matched.len() == 1 => found.push((id, matched.remove(0))),
+                Some(matched) => {
+                    let why = format!("ambiguous id: {} items share it", matched.len());
+                    missing.insert(id.clone(), why);
+                    items.insert(id, matched);
+                }
+                None => {
+                    missing.insert(id, "no such item in the trash".to_string());
+                }
+            }
+        }
+        Ok((found, missing))
+    }
+}
+
+/// Wrap up one operation: record checksums, write back the trash index, run
+/// the post hooks and send the --report lines, so each call's come before
+/// its reply rather than when the service stops.
+fn flush() {
+    crate::checksum::flush();
+    crate::cache::flush();
+    crate::hooks::flush();
+    crate::report::flush();
+}
+
+/// The broker for tests: what the command line does, less the checks, on a
+/// bookkeeping-only backend::Mock. Trashing a path with "busy" in it fails,
+/// and what is trashed is noted for the post hooks, as the audit log notes it
+/// for the real broker. An item's size is the length of its name.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MockBroker(pub crate::backend::Mock);
+
+#[cfg(test)]
+impl Broker for MockBroker {
+    fn trash(&self, path: &Path) -> Result<(), String> {
+        if path.to_string_lossy().contains("busy") {
+            return Err("in use".into());
+        }
+        self.0.delete(path).map_err(|e| e.to_string())?;
+        crate::hooks::record(crate::audit::Op::Trash, path);
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<TrashItem>, String> {
+        self.0.list().map_err(|e| e.to_string())
+    }
+
+    fn restore(&self, item: TrashItem) -> Result<PathBuf, String> {
+        let original = item.original_path();
+        self.0.restore(vec![item]).map_err(|e| e.to_string())?;
+        Ok(original)
+    }
+
+    fn purge(&self, item: TrashItem) -> Result<(), String> {
+        self.0.purge(&[item]).map_err(|e| e.to_string())
+    }
+
+    fn size(&self, item: &TrashItem) -> u64 {
+        item.name.len() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calls_go_through_the_broker() {
+        let service = Service::new(Box::new(MockBroker::default()));
+        let failed = service.trash(vec![
+            "/home/u/a.txt".into(),
+            "/home/u/busy.db".into(),
+            "rel".into(),
+            "/home/u/bb.txt".into(),
+        ]);
+        assert_eq!(failed.len(), 2);
+        assert_eq!(failed["/home/u/busy.db"], "in use");
+        assert_eq!(failed["rel"], "not an absolute path");
+        assert_eq!(service.stats().unwrap(), (2, 11));
+
+        let listed = service.list().unwrap();
+        let originals: Vec<&str> = listed.iter().map(|item| item.path.as_str()).collect();
+        assert_eq!(originals, ["/home/u/a.txt", "/home/u/bb.txt"]);
+        let (a, b) = (listed[0].id.clone(), listed[1].id.clone());
+
+        let (restored, failed) = service.restore(vec![a.clone(), "nope".into()]).unwrap();
+        assert_eq!(restored[&a], "/home/u/a.txt");
+        assert_eq!(failed["nope"], "no such item in the trash");
+
+        assert!(service.purge(vec![b]).unwrap().is_empty());
+        assert_eq!(service.stats().unwrap(), (0, 0));
+    }
+
+    #[test]
+    fn test_shared_short_id_is_ambiguous() {
+        // Two IDs whose short IDs collide
+        let items = ["/t/files/x34293", "/t/files/x40179"].map(|id| TrashItem {
+            id: id.into(),
+            name: "x".into(),
+            original_parent: "/home/u".into(),
+            time_deleted: 1,
+        });
+        let mock = crate::backend::Mock::holding(items.into());
+        let service = Service::new(Box::new(MockBroker(mock)));
+        let failed = service.purge(vec!["e074b14c".into()]).unwrap();
+        assert_eq!(failed["e074b14c"], "ambiguous id: 2 items share it");
+        assert_eq!(service.stats().unwrap().0, 2);
+    }
+
+    #[test]
+    fn test_post_hooks_run_after_each_call() {
+        crate::hooks::mark_trashed();
+        let tmp = tempfile::TempDir::new().unwrap();
+        let (first, second) = (tmp.path().join("first"), tmp.path().join("second"));
+        let hooked = |path: &Path| std::fs::read_to_string(path.with_extension("hooked"));
+        let service = Service::new(Box::new(MockBroker::default()));
+
+        service.trash(vec![first.to_string_lossy().into_owned()]);
+        assert_eq!(hooked(&first).unwrap(), "\n");
+
+        service.trash(vec![second.to_string_lossy().into_owned()]);
+        assert_eq!(hooked(&second).unwrap(), "\n");
+        // Not run again for the first call's path
+        assert_eq!(hooked(&first).unwrap(), "\n");
+    }
+
+    #[test]
+    fn test_read_only_changes_nothing() {
+        let broker = MockBroker::default();
+        broker.trash(Path::new("/home/u/a.txt")).unwrap();
+        let service = Service::new(Box::new(broker)).read_only("TRACHE_READONLY is set");
+        let id = service.list().unwrap()[0].id.clone();
+        let why = "read-only mode (TRACHE_READONLY is set)";
+
+        let failed = service.trash(vec!["/home/u/b.txt".into()]);
+        assert_eq!(failed["/home/u/b.txt"], why);
+        let (restored, failed) = service.restore(vec![id.clone()]).unwrap();
+        assert!(restored.is_empty());
+        assert_eq!(failed[&id], why);
+        assert_eq!(service.purge(vec![id.clone()]).unwrap()[&id], why);
+        assert_eq!(service.stats().unwrap(), (1, 5));
+    }
+}
@@ -0,0 +1,133 @@
+// Overwrite-then-unlink removal for --shred, bypassing the trash.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const CHUNK: usize = 64 * 1024;
+
+/// Overwrite a regular file `passes` times with pseudo-random data, then unlink it.
+/// Symlinks are unlinked without touching their target.
+pub fn shred_file(path: &Path, passes: u32) -> io::Result<()> {
+    let meta = path.symlink_metadata()?;
+    if meta.is_file() {
+        overwrite(path, meta.len(), passes)?;
+    }
+    fs::remove_file(path)
+}
+
+/// Shred every file below `path`, then remove the directories bottom-up.
+pub fn shred_tree(path: &Path, passes: u32) -> io::Result<()> {
+    let meta = path.symlink_metadata()?;
+    if !meta.is_dir() {
+        return shred_file(path, passes);
+    }
+    for entry in fs::read_dir(path)? {
+        shred_tree(&entry?.path(), passes)?;
+    }
+    fs::remove_dir(path)
+}
+
+fn overwrite(path: &Path, len: u64, passes: u32) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let mut rng = XorShift::seeded();
+    let mut buf = vec![0u8; CHUNK];
+
+    for _ in 0..passes {
+        file.seek(SeekFrom::Start(0))?;
+        let mut remaining = len;
+        while remaining > 0 {
+            let n = remaining.min(CHUNK as u64) as usize;
+            rng.fill(&mut buf[..n]);
+            file.write_all(&buf[..n])?;
+            remaining -= n as u64;
+        }
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Fast non-cryptographic generator; the goal is to destroy the old bytes,
+/// not to produce unpredictable output.
+struct XorShift(u64);
+
+impl XorShift {
+    fn seeded() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self(nanos ^ (u64::from(std::process::id()) << 32) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            let bytes = self.next().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overwrite_replaces_contents_keeps_length() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let f = tmp.path().join("secret.txt");
+        let original = vec![b'A'; CHUNK + 123];
+        fs::write(&f, &original).unwrap();
+
+        overwrite(&f, original.len() as u64, 2).unwrap();
+
+        let after = fs::read(&f).unwrap();
+        assert_eq!(after.len(), original.len());
+        assert_ne!(after, original);
+    }
+
+    #[test]
+    fn test_shred_file_removes() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let f = tmp.path().join("secret.txt");
+        fs::write(&f, "secret").unwrap();
+        shred_file(&f, 1).unwrap();
+        assert!(!f.exists());
+    }
+
+    #[test]
+    fn test_shred_tree_removes_everything() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path().join("dir");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a"), "a").unwrap();
+        fs::write(dir.join("sub").join("b"), "b").unwrap();
+        shred_tree(&dir, 1).unwrap();
+        assert!(!dir.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shred_symlink_spares_target() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let target = tmp.path().join("target");
+        let link = tmp.path().join("link");
+        fs::write(&target, "keep me").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        shred_file(&link, 1).unwrap();
+
+        assert!(link.symlink_metadata().is_err());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "keep me");
+    }
+}
@@ -0,0 +1,204 @@
+// Directory sizes for stats, reports and retention limits. Adding up every
+// file is exact but slow on huge trees, so the strategy is configurable
+// (`size_strategy` in config.toml). Anything that needs exact sizes, like
+// duplicate detection, calls units::disk_usage directly.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::config;
+use crate::units;
+
+pub trait SizeStrategy: Send + Sync {
+    /// Size of `path` in bytes; 0 if it can't be read.
+    fn size(&self, path: &Path) -> u64;
+}
+
+/// Add up every file below the path.
+pub struct Walk;
+
+impl SizeStrategy for Walk {
+    fn size(&self, path: &Path) -> u64 {
+        units::disk_usage(path)
+    }
+}
+
+/// Measure the first `sample` files and assume the rest average the same.
+/// Counting the rest only needs directory listings, not a stat per file.
+pub struct Estimate {
+    pub sample: usize,
+}
+
+impl SizeStrategy for Estimate {
+    fn size(&self, path: &Path) -> u64 {
+        let Ok(meta) = path.symlink_metadata() else {
+            return 0;
+        };
+        if !meta.is_dir() {
+            return meta.len();
+        }
+
+        let (mut measured, mut measured_bytes, mut unmeasured) = (0u64, 0u64, 0u64);
+        let mut dirs = VecDeque::from([path.to_path_buf()]);
+        while let Some(dir) = dirs.pop_front() {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let Ok(file_type) = entry.file_type() else {
+                    continue;
+                };
+                if file_type.is_dir() {
+                    dirs.push_back(entry.path());
+                } else if measured < self.sample as u64 {
+                    measured += 1;
+                    measured_bytes += entry.metadata().map_or(0, |m| m.len());
+                } else {
+                    unmeasured += 1;
+                }
+            }
+        }
+        match measured {
+            0 => 0,
+            n => measured_bytes + unmeasured * (measured_bytes / n),
+        }
+    }
+}
+
+/// Read a subvolume's referenced bytes from its btrfs quota group, which is
+/// instant however big the tree is. Paths that aren't subvolume roots, or
+/// where quotas are off, are walked.
+pub struct Btrfs;
+
+impl SizeStrategy for Btrfs {
+    fn size(&self, path: &Path) -> u64 {
+        #[cfg(target_os = "linux")]
+        if let Some(size) = btrfs_qgroup_size(path) {
+            return size;
+        }
+        Walk.size(path)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn btrfs_qgroup_size(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::MetadataExt;
+
+    const BTRFS_SUPER_MAGIC: i64 = 0x9123_683e;
+    // Every subvolume's root directory has this inode number
+    const BTRFS_FIRST_FREE_OBJECTID: u64 = 256;
+
+    let meta = path.symlink_metadata().ok()?;
+    if !meta.is_dir() || meta.ino() != BTRFS_FIRST_FREE_OBJECTID {
+        return None;
+    }
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } != 0
+        || stat.f_type as i64 != BTRFS_SUPER_MAGIC
+    {
+        return None;
+    }
+
+    let output = std::process::Command::new("btrfs")
+        .args(["qgroup", "show", "-f", "--raw"])
+        .arg(path)
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_qgroup_show(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Referenced bytes from the first qgroup row of `btrfs qgroup show --raw`.
+fn parse_qgroup_show(output: &str) -> Option<u64> {
+    output.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let id = fields.next()?;
+        if !id.starts_with("0/") {
+            return None;
+        }
+        fields.next()?.parse().ok()
+    })
+}
+
+static STRATEGY: OnceLock<Box<dyn SizeStrategy>> = OnceLock::new();
+static KIND: OnceLock<config::SizeStrategy> = OnceLock::new();
+
+/// Choose the strategy `size` uses for the rest of the process.
+pub fn init(kind: config::SizeStrategy) {
+    let strategy: Box<dyn SizeStrategy> = match kind {
+        config::SizeStrategy::Walk => Box::new(Walk),
+        config::SizeStrategy::Estimate => Box::new(Estimate {
+            sample: ESTIMATE_SAMPLE,
+        }),
+        config::SizeStrategy::Btrfs => Box::new(Btrfs),
+    };
+    let _ = STRATEGY.set(strategy);
+    let _ = KIND.set(kind);
+}
+
+/// The strategy chosen with [`init`].
+pub fn kind() -> config::SizeStrategy {
+    KIND.get().copied().unwrap_or_default()
+}
+
+/// Files measured before `estimate` starts extrapolating.
+const ESTIMATE_SAMPLE: usize = 1000;
+
+/// Size of `path` under the configured strategy (`Walk` if none was set).
+pub fn size(path: &Path) -> u64 {
+    match STRATEGY.get() {
+        Some(strategy) => strategy.size(path),
+        None => Walk.size(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::sized_tree as tree;
+
+    #[test]
+    fn test_estimate_exact_below_sample() {
+        let tmp = tree(&[("a", 5), ("sub/b", 3)]);
+        let estimate = Estimate { sample: 10 };
+        assert_eq!(estimate.size(tmp.path()), Walk.size(tmp.path()));
+    }
+
+    #[test]
+    fn test_estimate_extrapolates() {
+        let tmp = tree(&[("a", 10), ("b", 10), ("c", 10), ("sub/d", 10)]);
+        let estimate = Estimate { sample: 2 };
+        assert_eq!(estimate.size(tmp.path()), 40);
+    }
+
+    #[test]
+    fn test_estimate_file_and_missing() {
+        let tmp = tree(&[("a", 7)]);
+        let estimate = Estimate { sample: 1 };
+        assert_eq!(estimate.size(&tmp.path().join("a")), 7);
+        assert_eq!(estimate.size(&tmp.path().join("missing")), 0);
+    }
+
+    #[test]
+    fn test_btrfs_falls_back_to_walk() {
+        let tmp = tree(&[("a", 5), ("sub/b", 3)]);
+        assert_eq!(Btrfs.size(tmp.path()), 8);
+    }
+
+    #[test]
+    fn test_parse_qgroup_show() {
+        let output = "qgroupid         rfer         excl \n\
+                      --------         ----         ---- \n\
+                      0/257        1654784        16384 \n";
+        assert_eq!(parse_qgroup_show(output), Some(1_654_784));
+        assert_eq!(parse_qgroup_show("ERROR: quotas not enabled\n"), None);
+    }
+}
@@ -0,0 +1,337 @@
+// Exit statuses. Every run ends in one of the outcomes below, each with a
+// fixed default code; [exit_codes] in config.toml and --status-map remap
+// them for tools that give particular codes a meaning. Usage errors found by
+// the argument parser always exit 2, and rm mode keeps rm's own 0 and 1.
+
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::TracheError;
+use crate::{config, rmcompat};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Everything asked for was done
+    Success,
+    /// Some items were handled and others failed
+    Partial,
+    /// Nothing was done because of errors
+    Failure,
+    /// Every prompt was answered no, so nothing was done
+    Declined,
+    /// Quit at a prompt, leaving the rest undone
+    Cancelled,
+    /// The operation isn't available on this platform
+    Unsupported,
+    /// Nothing was done because a path or trashed item wasn't there
+    NotFound,
+    /// Nothing was done because a path was a directory
+    IsDirectory,
+    /// Nothing was done because a move would have crossed file systems
+    CrossDevice,
+}
+
+impl Status {
+    pub const ALL: [Status; 9] = [
+        Status::Success,
+        Status::Partial,
+        Status::Failure,
+        Status::Declined,
+        Status::Cancelled,
+        Status::Unsupported,
+        Status::NotFound,
+        Status::IsDirectory,
+        Status::CrossDevice,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Status::Success => "success",
+            Status::Partial => "partial",
+            Status::Failure => "failure",
+            Status::Declined => "declined",
+            Status::Cancelled => "cancelled",
+            Status::Unsupported => "unsupported",
+            Status::NotFound => "not_found",
+            Status::IsDirectory => "is_directory",
+            Status::CrossDevice => "cross_device",
+        }
+    }
+
+    /// None for the causes of a failure, which exit as failure does
+    /// unless given codes of their own
+    fn default_code(self) -> Option<u8> {
+        match self {
+            Status::Success => Some(0),
+            Status::Failure => Some(1),
+            Status::Partial => Some(3),
+            Status::Unsupported => Some(4),
+            Status::Cancelled => Some(5),
+            // Like rm -i answered no
+            Status::Declined => Some(0),
+            Status::NotFound | Status::IsDirectory | Status::CrossDevice => None,
+        }
+    }
+
+    fn parse(name: &str) -> Option<Status> {
+        Status::ALL.into_iter().find(|s| s.name() == name)
+    }
+}
+
+/// Exit code of each status, in Status::ALL order
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Codes([Option<u8>; 9]);
+
+impl Default for Codes {
+    fn default() -> Self {
+        Codes(Status::ALL.map(Status::default_code))
+    }
+}
+
+impl Codes {
+    fn code(&self, status: Status) -> u8 {
+        self.0[status as usize].unwrap_or_else(|| self.code(Status::Failure))
+    }
+
+    fn set(&mut self, status: Status, code: i64) -> Result<(), String> {
+        let code = u8::try_from(code)
+            .map_err(|_| format!("{}: exit code {code} is not in 0-255", status.name()))?;
+        self.0[status as usize] = Some(code);
+        Ok(())
+    }
+
+    /// Apply [exit_codes] from the config.
+    fn apply_config(&mut self, cfg: &config::ExitCodes) -> Result<(), String> {
+        let given = [
+            (Status::Success, cfg.success),
+            (Status::Partial, cfg.partial),
+            (Status::Failure, cfg.failure),
+            (Status::Declined, cfg.declined),
+            (Status::Cancelled, cfg.cancelled),
+            (Status::Unsupported, cfg.unsupported),
+            (Status::NotFound, cfg.not_found),
+            (Status::IsDirectory, cfg.is_directory),
+            (Status::CrossDevice, cfg.cross_device),
+        ];
+        for (status, code) in given {
+            if let Some(code) = code {
+                self.set(status, code)
+                    .map_err(|e| format!("exit_codes.{e}"))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply a --status-map value: comma-separated NAME=CODE pairs.
+    fn apply_map(&mut self, map: &str) -> Result<(), String> {
+        for pair in map.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (name, code) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("--status-map: '{pair}': expected NAME=CODE"))?;
+            let status = Status::parse(name.trim()).ok_or_else(|| {
+                format!(
+                    "--status-map: unknown status '{}' (expected {})",
+                    name.trim(),
+                    Status::ALL.map(Status::name).join(", ")
+                )
+            })?;
+            let code = code
+                .trim()
+                .parse()
+                .map_err(|_| format!("--status-map: '{pair}': CODE must be a number"))?;
+            self.set(status, code)
+                .map_err(|e| format!("--status-map: {e}"))?;
+        }
+        Ok(())
+    }
+}
+
+static CODES: OnceLock<Codes> = OnceLock::new();
+static DONE: AtomicBool = AtomicBool::new(false);
+static DECLINED: AtomicBool = AtomicBool::new(false);
+
+/// Set the codes from the config's [exit_codes], then `map` (--status-map)
+/// on top.
+pub fn init(cfg: &config::ExitCodes, map: Option<&str>) -> Result<(), String> {
+    let mut codes = Codes::default();
+    codes.apply_config(cfg)?;
+    if let Some(map) = map {
+        codes.apply_map(map)?;
+    }
+    let _ = CODES.set(codes);
+    Ok(())
+}
+
+/// Something was trashed, restored, purged or moved.
+pub fn note_done() {
+    DONE.store(true, Ordering::Relaxed);
+}
+
+/// A prompt was answered no.
+pub fn note_declined() {
+    DECLINED.store(true, Ordering::Relaxed);
+}
+
+/// How a run that returned `result` went.
+pub fn of(result: &Result<(), TracheError>) -> Status {
+    let done = DONE.load(Ordering::Relaxed);
+    classify(result, done, DECLINED.load(Ordering::Relaxed))
+}
+
+/// How a run went that returned `result`, did something if `done` and had
+/// a prompt answered no if `declined`.
+fn classify(result: &Result<(), TracheError>, done: bool, declined: bool) -> Status {
+    match result {
+        Ok(()) if !done && declined => Status::Declined,
+        Ok(()) => Status::Success,
+        Err(TracheError::Unsupported(_)) => Status::Unsupported,
+        Err(TracheError::Interrupted(_) | TracheError::PromptAborted) => Status::Cancelled,
+        Err(_) if done => Status::Partial,
+        Err(e) => match e.cause() {
+            Some(std::io::ErrorKind::NotFound) => Status::NotFound,
+            Some(std::io::ErrorKind::IsADirectory) => Status::IsDirectory,
+            Some(std::io::ErrorKind::CrossesDevices) => Status::CrossDevice,
+            _ => Status::Failure,
+        },
+    }
+}
+
+/// The code `status` exits with.
+pub fn code(status: Status) -> u8 {
+    if rmcompat::enabled() {
+        return match status {
+            Status::Success | Status::Declined | Status::Cancelled => 0,
+            _ => 1,
+        };
+    }
+    CODES.get_or_init(Codes::default).code(status)
+}
+
+/// Wrap up the run, however it ends: record pending checksums, send what's
+/// left of the report, save the trash index, run the post hooks and show the
+/// --notify summary.
+/// `error` is how the run failed, if it did. Each part is done once, so
+/// later calls do nothing.
+pub fn finish(error: Option<&str>) {
+    #[cfg(any(
+        target_os = "windows",
+        all(unix, not(target_os = "macos"), not(target_os = "ios"))
+    ))]
+    {
+        crate::checksum::flush();
+        crate::report::flush();
+    }
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    crate::cache::flush();
+    crate::hooks::finish();
+    crate::notify::finish(error);
+}
+
+/// End the process with `status`'s code, once the run is wrapped up.
+pub fn exit(status: Status) -> ! {
+    let failed = !matches!(status, Status::Success | Status::Declined);
+    finish(failed.then_some(status.name()));
+    std::process::exit(code(status).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_table() {
+        let codes = Codes::default();
+        assert_eq!(codes.code(Status::Success), 0);
+        assert_eq!(codes.code(Status::Failure), 1);
+        assert_eq!(codes.code(Status::Partial), 3);
+        assert_eq!(codes.code(Status::Unsupported), 4);
+        assert_eq!(codes.code(Status::Cancelled), 5);
+        assert_eq!(codes.code(Status::Declined), 0);
+    }
+
+    #[test]
+    fn test_map_overrides_config() {
+        let mut codes = Codes::default();
+        let cfg = config::ExitCodes {
+            partial: Some(2),
+            declined: Some(7),
+            ..Default::default()
+        };
+        codes.apply_config(&cfg).unwrap();
+        codes.apply_map("partial=9, cancelled=130").unwrap();
+        assert_eq!(codes.code(Status::Partial), 9);
+        assert_eq!(codes.code(Status::Declined), 7);
+        assert_eq!(codes.code(Status::Cancelled), 130);
+    }
+
+    #[test]
+    fn test_bad_maps() {
+        let mut codes = Codes::default();
+        assert!(
+            codes
+                .apply_map("partial")
+                .unwrap_err()
+                .contains("NAME=CODE")
+        );
+        assert!(
+            codes
+                .apply_map("oops=3")
+                .unwrap_err()
+                .contains("unknown status")
+        );
+        assert!(codes.apply_map("failure=x").unwrap_err().contains("number"));
+        assert!(
+            codes
+                .apply_map("failure=256")
+                .unwrap_err()
+                .contains("0-255")
+        );
+        let cfg = config::ExitCodes {
+            success: Some(-1),
+            ..Default::default()
+        };
+        assert_eq!(
+            codes.apply_config(&cfg).unwrap_err(),
+            "exit_codes.success: exit code -1 is not in 0-255"
+        );
+    }
+
+    #[test]
+    fn test_failures_by_cause() {
+        let kind = |kind| Err(TracheError::io_kind(kind, "x".into()));
+        let of = |result| classify(&result, false, false);
+        assert_eq!(of(kind(std::io::ErrorKind::NotFound)), Status::NotFound);
+        assert_eq!(
+            of(Err(TracheError::NoMatch("*.log".into()))),
+            Status::NotFound
+        );
+        assert_eq!(
+            of(kind(std::io::ErrorKind::IsADirectory)),
+            Status::IsDirectory
+        );
+        assert_eq!(
+            of(kind(std::io::ErrorKind::CrossesDevices)),
+            Status::CrossDevice
+        );
+        assert_eq!(
+            of(kind(std::io::ErrorKind::PermissionDenied)),
+            Status::Failure
+        );
+        // Files not removed fail the run with the cause they shared
+        let not_removed = |cause| classify(&Err(TracheError::NotRemoved(cause)), false, false);
+        assert_eq!(
+            not_removed(Some(std::io::ErrorKind::NotFound)),
+            Status::NotFound
+        );
+        assert_eq!(not_removed(None), Status::Failure);
+        // Once something was done, a failure only makes the run partial
+        let gone = kind(std::io::ErrorKind::NotFound);
+        assert_eq!(classify(&gone, true, false), Status::Partial);
+
+        let mut codes = Codes::default();
+        assert_eq!(codes.code(Status::NotFound), 1);
+        codes.apply_map("failure=9, cross_device=18").unwrap();
+        assert_eq!(codes.code(Status::NotFound), 9);
+        assert_eq!(codes.code(Status::CrossDevice), 18);
+    }
+}
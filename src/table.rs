@@ -0,0 +1,142 @@
+// Machine-readable output for --trash-list --output, for spreadsheets and
+// scripts. CSV quotes fields per RFC 4180; TSV can't quote, so it
+// backslash-escapes tabs, newlines and backslashes the way PostgreSQL's text
+// format does. JSON is one object per line, described by --schema.
+#![cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+
+use std::borrow::Cow;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Comma-separated, with "double quotes" around fields that need them
+    Csv,
+    /// Tab-separated, with \t, \n, \r and \\ escapes
+    Tsv,
+    /// One JSON object per line
+    Json,
+}
+
+/// One trash item as listed by --trash-list --output.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ListedItem {
+    /// Number, as used by --trash-purge-n
+    pub n: usize,
+    /// Item ID, as used by id: patterns
+    pub id: String,
+    /// Deletion time in RFC 3339
+    pub deleted: String,
+    /// Deletion time in seconds since the epoch
+    pub deleted_unix: i64,
+    pub name: String,
+    /// Original path
+    pub path: String,
+    /// Location inside the trash
+    pub trash_path: String,
+}
+
+/// The line before the items (without a trailing newline): column names,
+/// or nothing for JSON lines.
+pub fn header(format: OutputFormat) -> Option<String> {
+    (format != OutputFormat::Json).then(|| row(format, &["n", "id", "deleted", "name", "path"]))
+}
+
+/// The line for one item, so a listing can be written as it's produced.
+pub fn line(format: OutputFormat, item: &ListedItem) -> String {
+    if format == OutputFormat::Json {
+        return serde_json::to_string(item).unwrap_or_default();
+    }
+    let n = item.n.to_string();
+    row(
+        format,
+        &[&n, &item.id, &item.deleted, &item.name, &item.path],
+    )
+}
+
+/// One CSV or TSV line (without the trailing newline) holding `fields`.
+fn row(format: OutputFormat, fields: &[&str]) -> String {
+    let (sep, escape): (&str, fn(&str) -> Cow<'_, str>) = match format {
+        OutputFormat::Csv | OutputFormat::Json => (",", csv_field),
+        OutputFormat::Tsv => ("\t", tsv_field),
+    };
+    fields
+        .iter()
+        .map(|f| escape(f))
+        .collect::<Vec<_>>()
+        .join(sep)
+}
+
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+fn tsv_field(field: &str) -> Cow<'_, str> {
+    if !field.contains(['\\', '\t', '\n', '\r']) {
+        return Cow::Borrowed(field);
+    }
+    let mut out = String::with_capacity(field.len() + 2);
+    for c in field.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_quotes_only_when_needed() {
+        assert_eq!(row(OutputFormat::Csv, &["a", "b c"]), "a,b c");
+        assert_eq!(
+            row(OutputFormat::Csv, &["a,b", "say \"hi\"", "x\ny"]),
+            "\"a,b\",\"say \"\"hi\"\"\",\"x\ny\""
+        );
+        assert_eq!(row(OutputFormat::Csv, &["", "tab\there"]), ",tab\there");
+    }
+
+    #[test]
+    fn test_tsv_escapes_separators() {
+        assert_eq!(row(OutputFormat::Tsv, &["a", "b,c"]), "a\tb,c");
+        assert_eq!(
+            row(OutputFormat::Tsv, &["a\tb", "c\\d", "e\r\nf"]),
+            "a\\tb\tc\\\\d\te\\r\\nf"
+        );
+    }
+
+    #[test]
+    fn test_listing_has_header_except_for_json() {
+        let item = ListedItem {
+            n: 1,
+            id: "4fd0bfc1".into(),
+            deleted: "2024-05-01T14:30:00+00:00".into(),
+            deleted_unix: 1714573800,
+            name: "a.txt".into(),
+            path: "/tmp/a.txt".into(),
+            trash_path: "/trash/files/a.txt".into(),
+        };
+        assert_eq!(
+            header(OutputFormat::Csv).as_deref(),
+            Some("n,id,deleted,name,path")
+        );
+        assert_eq!(
+            line(OutputFormat::Csv, &item),
+            "1,4fd0bfc1,2024-05-01T14:30:00+00:00,a.txt,/tmp/a.txt"
+        );
+        assert_eq!(header(OutputFormat::Json), None);
+        let json = line(OutputFormat::Json, &item);
+        assert!(json.starts_with("{\"n\":1,\"id\":\"4fd0bfc1\","), "{json}");
+    }
+}
@@ -0,0 +1,138 @@
+// Totals for the summary line printed after trashing with --summary (or -v
+// with several arguments): "trashed 42 item(s) (1.3 GiB), skipped 3,
+// failed 1". Items are counted from the per-file events as they're emitted,
+// and sizes added by whoever measured the item before it went.
+
+use std::sync::Mutex;
+
+use crate::events::Event;
+use crate::units;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Tally {
+    trashed: u64,
+    shredded: u64,
+    deleted: u64,
+    skipped: u64,
+    failed: u64,
+    bytes: u64,
+    dry_run: bool,
+}
+
+/// None until enabled, so runs without a summary don't measure anything
+static TALLY: Mutex<Option<Tally>> = Mutex::new(None);
+
+/// Start counting, for a summary at the end.
+pub fn enable() {
+    TALLY.lock().unwrap().get_or_insert_with(Tally::default);
+}
+
+/// Whether there's a summary to count towards, and so sizes to measure.
+pub fn enabled() -> bool {
+    TALLY.lock().unwrap().is_some()
+}
+
+/// Count the outcome `event` reports.
+pub fn record(event: &Event) {
+    if let Some(tally) = TALLY.lock().unwrap().as_mut() {
+        tally.record(event);
+    }
+}
+
+/// Add `bytes` to the size of what was removed.
+pub fn add_bytes(bytes: u64) {
+    if let Some(tally) = TALLY.lock().unwrap().as_mut() {
+        tally.bytes += bytes;
+    }
+}
+
+/// The summary line, if counting was enabled.
+pub fn summary() -> Option<String> {
+    TALLY.lock().unwrap().map(|tally| tally.line())
+}
+
+impl Tally {
+    fn record(&mut self, event: &Event) {
+        match event {
+            Event::Trashed { dry_run, .. } => {
+                self.trashed += 1;
+                self.dry_run |= *dry_run;
+            }
+            Event::Shredded { dry_run, .. } => {
+                self.shredded += 1;
+                self.dry_run |= *dry_run;
+            }
+            Event::Deleted { .. } => self.deleted += 1,
+            Event::Skipped { .. } => self.skipped += 1,
+            Event::Errored { .. } => self.failed += 1,
+            Event::Prompted { .. } => {}
+        }
+    }
+
+    fn line(&self) -> String {
+        let size = units::format_size(self.bytes);
+        let mut parts = Vec::new();
+        let (trash, shred) = if self.dry_run {
+            ("would trash", "would shred")
+        } else {
+            ("trashed", "shredded")
+        };
+        if self.shredded > 0 {
+            parts.push(format!("{shred} {} item(s)", self.shredded));
+        }
+        if self.trashed > 0 || self.shredded == 0 {
+            parts.push(format!("{trash} {} item(s)", self.trashed));
+        }
+        if self.deleted > 0 {
+            parts.push(format!("deleted {} item(s) permanently", self.deleted));
+        }
+        parts[0].push_str(&format!(" ({size})"));
+        parts.push(format!("skipped {}", self.skipped));
+        parts.push(format!("failed {}", self.failed));
+        parts.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_line() {
+        let path = Path::new("/tmp/a");
+        let mut tally = Tally::default();
+        assert_eq!(tally.line(), "trashed 0 item(s) (0 B), skipped 0, failed 0");
+
+        for _ in 0..3 {
+            tally.record(&Event::Trashed {
+                path,
+                dry_run: false,
+            });
+        }
+        tally.record(&Event::Skipped {
+            path,
+            reason: "declined",
+        });
+        tally.record(&Event::Errored {
+            path,
+            error: "gone".into(),
+        });
+        tally.record(&Event::Deleted { path });
+        tally.bytes = 1536;
+        assert_eq!(
+            tally.line(),
+            "trashed 3 item(s) (1.5 KiB), deleted 1 item(s) permanently, skipped 1, failed 1"
+        );
+
+        let mut shred = Tally::default();
+        shred.record(&Event::Shredded {
+            path,
+            dry_run: true,
+        });
+        assert_eq!(
+            shred.line(),
+            "would shred 1 item(s) (0 B), skipped 0, failed 0"
+        );
+    }
+}
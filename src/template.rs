@@ -0,0 +1,175 @@
+// --format templates for --trash-list: text with {placeholders}, checked
+// up front so a typo fails before anything is printed. `{{` and `}}` stand
+// for literal braces, and `\t`, `\n` and `\\` for a tab, a newline and a
+// backslash, since shells don't turn those into the characters in quotes.
+// Rendering is only used by trash management, which macOS doesn't support yet.
+#![cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    /// Position in the listing, as used by --trash-purge-n
+    Index,
+    Id,
+    Name,
+    Path,
+    Dir,
+    TrashPath,
+    Size,
+    SizeBytes,
+    Deleted,
+    DeletedIso,
+    DeletedUnix,
+}
+
+impl Field {
+    const ALL: [(&'static str, Field); 11] = [
+        ("n", Field::Index),
+        ("id", Field::Id),
+        ("name", Field::Name),
+        ("path", Field::Path),
+        ("dir", Field::Dir),
+        ("trash_path", Field::TrashPath),
+        ("size", Field::Size),
+        ("size_bytes", Field::SizeBytes),
+        ("deleted", Field::Deleted),
+        ("deleted_iso", Field::DeletedIso),
+        ("deleted_unix", Field::DeletedUnix),
+    ];
+
+    fn from_name(name: &str) -> Option<Field> {
+        Self::ALL.iter().find(|(n, _)| *n == name).map(|(_, f)| *f)
+    }
+
+    /// Placeholder names, for error messages and --help.
+    pub fn names() -> String {
+        Self::ALL.map(|(n, _)| format!("{{{n}}}")).join(" ")
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Field(Field),
+}
+
+#[derive(Debug)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("unclosed placeholder '{{{name}'")),
+                        }
+                    }
+                    let field = Field::from_name(&name).ok_or_else(|| {
+                        format!("unknown placeholder {{{name}}}; use {}", Field::names())
+                    })?;
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Field(field));
+                }
+                '}' => return Err("unmatched '}' (write '}}' for a literal brace)".into()),
+                '\\' => match chars.peek() {
+                    Some('t') => {
+                        chars.next();
+                        literal.push('\t');
+                    }
+                    Some('n') => {
+                        chars.next();
+                        literal.push('\n');
+                    }
+                    Some('\\') => {
+                        chars.next();
+                        literal.push('\\');
+                    }
+                    // Any other backslash is kept, as in a Windows path
+                    _ => literal.push('\\'),
+                },
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(Template { segments })
+    }
+
+    /// Whether rendering needs `field`, e.g. to skip measuring sizes.
+    pub fn uses(&self, field: Field) -> bool {
+        self.segments.contains(&Segment::Field(field))
+    }
+
+    pub fn render(&self, mut value: impl FnMut(Field) -> String) -> String {
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => out.push_str(text),
+                Segment::Field(field) => out.push_str(&value(*field)),
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str) -> String {
+        Template::parse(template)
+            .unwrap()
+            .render(|field| match field {
+                Field::Name => "a.txt".into(),
+                Field::SizeBytes => "42".into(),
+                other => format!("<{other:?}>"),
+            })
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(render("{name}\t{size_bytes}"), "a.txt\t42");
+        // As typed in a shell
+        assert_eq!(render(r"{name}\t{size_bytes}\n"), "a.txt\t42\n");
+        assert_eq!(render(r"C:\x\\t"), r"C:\x\t");
+        assert_eq!(render("{{{name}}}"), "{a.txt}");
+        assert_eq!(render("plain"), "plain");
+        assert_eq!(render("{n}:{id}"), "<Index>:<Id>");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = Template::parse("{nmae}").unwrap_err();
+        assert!(err.contains("unknown placeholder {nmae}"));
+        assert!(err.contains("{deleted_iso}"));
+        assert!(Template::parse("{name").is_err());
+        assert!(Template::parse("a}b").is_err());
+    }
+
+    #[test]
+    fn test_uses() {
+        let template = Template::parse("{name} {size}").unwrap();
+        assert!(template.uses(Field::Size));
+        assert!(!template.uses(Field::SizeBytes));
+    }
+}
@@ -0,0 +1,25 @@
+// Fixtures shared by the unit tests.
+
+use std::fs;
+
+/// A temp directory holding `entries`: a name ending in '/' is a directory,
+/// anything else a file of that many bytes, its parents made as needed.
+pub fn sized_tree(entries: &[(&str, usize)]) -> tempfile::TempDir {
+    let tmp = tempfile::TempDir::new().unwrap();
+    for (name, len) in entries {
+        let path = tmp.path().join(name);
+        if name.ends_with('/') {
+            fs::create_dir_all(path).unwrap();
+        } else {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, vec![b'x'; *len]).unwrap();
+        }
+    }
+    tmp
+}
+
+/// [`sized_tree`] with every file one byte long.
+pub fn tree(entries: &[&str]) -> tempfile::TempDir {
+    let sized: Vec<_> = entries.iter().map(|name| (*name, 1)).collect();
+    sized_tree(&sized)
+}
@@ -0,0 +1,156 @@
+// How deletion times are shown in listings and prompts (--time-style), with
+// the same style names as GNU ls.
+#![cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+
+use std::sync::OnceLock;
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, TimeDelta};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum TimeStyle {
+    /// 05-01 14:30 for the last six months, 2023-11-20 before that
+    Iso,
+    /// 2024-05-01 14:30
+    #[default]
+    LongIso,
+    /// 2024-05-01 14:30:00.000000000 +0200
+    FullIso,
+    /// 3 days ago
+    Relative,
+    /// strftime format, from +FORMAT
+    Custom(String),
+}
+
+static STYLE: OnceLock<TimeStyle> = OnceLock::new();
+
+impl TimeStyle {
+    /// Parse a --time-style value: a style name or +FORMAT.
+    pub fn parse(input: &str) -> Result<TimeStyle, String> {
+        match input {
+            "iso" => Ok(TimeStyle::Iso),
+            "long-iso" => Ok(TimeStyle::LongIso),
+            "full-iso" => Ok(TimeStyle::FullIso),
+            "relative" => Ok(TimeStyle::Relative),
+            _ => {
+                let Some(format) = input.strip_prefix('+') else {
+                    return Err(format!(
+                        "invalid time style '{input}'; expected iso, long-iso, full-iso, relative or +FORMAT"
+                    ));
+                };
+                if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                    return Err(format!("invalid time format '{format}'"));
+                }
+                Ok(TimeStyle::Custom(format.to_string()))
+            }
+        }
+    }
+
+    fn render(&self, time: DateTime<Local>, now: DateTime<Local>) -> String {
+        match self {
+            TimeStyle::Iso if now - time < TimeDelta::days(182) && time <= now => {
+                time.format("%m-%d %H:%M").to_string()
+            }
+            TimeStyle::Iso => time.format("%Y-%m-%d").to_string(),
+            TimeStyle::LongIso => time.format("%Y-%m-%d %H:%M").to_string(),
+            TimeStyle::FullIso => time.format("%Y-%m-%d %H:%M:%S%.9f %z").to_string(),
+            TimeStyle::Relative => relative(now - time),
+            TimeStyle::Custom(format) => time.format(format).to_string(),
+        }
+    }
+}
+
+/// Use `style` for the rest of the run.
+pub fn init(style: TimeStyle) {
+    let _ = STYLE.set(style);
+}
+
+/// Render a deletion time (seconds since the epoch) in the chosen style.
+pub fn format(time_deleted: i64) -> String {
+    let style = STYLE.get_or_init(TimeStyle::default);
+    match DateTime::from_timestamp(time_deleted, 0) {
+        Some(t) => style.render(t.with_timezone(&Local), Local::now()),
+        None => "????-??-?? ??:??".to_string(),
+    }
+}
+
+/// "3 days ago", in the largest unit that fits.
+fn relative(age: TimeDelta) -> String {
+    const UNITS: &[(i64, &str)] = &[
+        (365 * 86400, "year"),
+        (30 * 86400, "month"),
+        (7 * 86400, "week"),
+        (86400, "day"),
+        (3600, "hour"),
+        (60, "minute"),
+        (1, "second"),
+    ];
+    let secs = age.num_seconds();
+    if secs.abs() < 10 {
+        return "just now".to_string();
+    }
+    let (n, unit) = UNITS
+        .iter()
+        .find(|(size, _)| secs.abs() >= *size)
+        .map(|(size, unit)| (secs.abs() / size, *unit))
+        .unwrap_or((secs.abs(), "second"));
+    let plural = if n == 1 { "" } else { "s" };
+    if secs < 0 {
+        format!("in {n} {unit}{plural}")
+    } else {
+        format!("{n} {unit}{plural} ago")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    #[test]
+    fn test_parse_names_and_formats() {
+        assert_eq!(TimeStyle::parse("iso"), Ok(TimeStyle::Iso));
+        assert_eq!(TimeStyle::parse("relative"), Ok(TimeStyle::Relative));
+        assert_eq!(
+            TimeStyle::parse("+%d.%m.%Y"),
+            Ok(TimeStyle::Custom("%d.%m.%Y".into()))
+        );
+        assert!(TimeStyle::parse("locale").is_err());
+        assert!(TimeStyle::parse("+%Q").is_err());
+    }
+
+    #[test]
+    fn test_render_styles() {
+        let now = at(2024, 5, 10, 12, 0);
+        let recent = at(2024, 5, 1, 14, 30);
+        let old = at(2023, 5, 1, 14, 30);
+        assert_eq!(TimeStyle::Iso.render(recent, now), "05-01 14:30");
+        assert_eq!(TimeStyle::Iso.render(old, now), "2023-05-01");
+        assert_eq!(TimeStyle::LongIso.render(recent, now), "2024-05-01 14:30");
+        assert!(
+            TimeStyle::FullIso
+                .render(recent, now)
+                .starts_with("2024-05-01 14:30:00.000000000 ")
+        );
+        assert_eq!(
+            TimeStyle::Custom("%d.%m.%Y".into()).render(recent, now),
+            "01.05.2024"
+        );
+    }
+
+    #[test]
+    fn test_relative_picks_largest_unit() {
+        assert_eq!(relative(TimeDelta::seconds(3)), "just now");
+        assert_eq!(relative(TimeDelta::seconds(45)), "45 seconds ago");
+        assert_eq!(relative(TimeDelta::minutes(1)), "1 minute ago");
+        assert_eq!(relative(TimeDelta::hours(5)), "5 hours ago");
+        assert_eq!(relative(TimeDelta::days(3)), "3 days ago");
+        assert_eq!(relative(TimeDelta::days(15)), "2 weeks ago");
+        assert_eq!(relative(TimeDelta::days(400)), "1 year ago");
+        assert_eq!(relative(TimeDelta::minutes(-2)), "in 2 minutes");
+    }
+}
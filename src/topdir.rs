@@ -0,0 +1,107 @@
+// Diagnostics for trashing onto volumes other than the home trash's. The
+// freedesktop spec prefers a shared, sticky $topdir/.Trash/$uid and falls back
+// to $topdir/.Trash-$uid; say which one is skipped and why, once per volume.
+
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use trash::os_limited::{TopdirTrashReport, topdir_trash_report};
+
+/// Devices already looked at during this run, so the mount table is read
+/// once per volume rather than once per file.
+static CHECKED: Mutex<Vec<u64>> = Mutex::new(Vec::new());
+
+/// Warn about problems with the trash on `path`'s volume, the first time a
+/// path on that volume is trashed.
+pub fn check(path: &Path) {
+    // The parent's device, followed through any symlinks, is the volume
+    let Some(dev) = std::path::absolute(path)
+        .ok()
+        .and_then(|path| path.parent()?.metadata().ok())
+        .map(|meta| meta.dev())
+    else {
+        return;
+    };
+    {
+        let mut checked = CHECKED.lock().unwrap();
+        if checked.contains(&dev) {
+            return;
+        }
+        checked.push(dev);
+    }
+    let Some(path) = canonical_parent(path) else {
+        return;
+    };
+    let Ok(Some(report)) = topdir_trash_report(&path) else {
+        return;
+    };
+    for warning in warnings(&report) {
+        eprintln!("{}: warning: {warning}", crate::rmcompat::prog());
+    }
+}
+
+/// `path` with its parent directories resolved, the way trashing sees it.
+fn canonical_parent(path: &Path) -> Option<PathBuf> {
+    let absolute = std::path::absolute(path).ok()?;
+    let parent = absolute.parent()?.canonicalize().ok()?;
+    Some(parent.join(absolute.file_name()?))
+}
+
+fn warnings(report: &TopdirTrashReport) -> Vec<String> {
+    let topdir = report.topdir.display();
+    match (&report.shared_problem, &report.private_problem) {
+        (None, None) => Vec::new(),
+        (Some(shared), None) => vec![format!(
+            "skipping the shared trash on '{topdir}': {shared}; using '{}'",
+            report.private_trash.display()
+        )],
+        (shared, Some(private)) => {
+            let mut problems = vec![format!("no usable trash on '{topdir}': {private}")];
+            if let Some(shared) = shared {
+                problems.push(format!(
+                    "also skipping the shared trash on '{topdir}': {shared}"
+                ));
+            }
+            problems
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(shared: Option<&str>, private: Option<&str>) -> TopdirTrashReport {
+        TopdirTrashReport {
+            topdir: PathBuf::from("/mnt/usb"),
+            shared_problem: shared.map(String::from),
+            private_trash: PathBuf::from("/mnt/usb/.Trash-1000"),
+            private_problem: private.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_usable_trash_is_quiet() {
+        assert!(warnings(&report(None, None)).is_empty());
+    }
+
+    #[test]
+    fn test_shared_problem_names_the_fallback() {
+        let warnings = warnings(&report(Some("'/mnt/usb/.Trash' is not sticky"), None));
+        assert_eq!(
+            warnings,
+            [
+                "skipping the shared trash on '/mnt/usb': '/mnt/usb/.Trash' is not sticky; \
+              using '/mnt/usb/.Trash-1000'"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_private_problem_means_no_trash() {
+        let warnings = warnings(&report(Some("shared"), Some("private")));
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0], "no usable trash on '/mnt/usb': private");
+    }
+}
@@ -0,0 +1,275 @@
+// Session transcript for --transcript FILE: the command line, every prompt
+// shown with the answer given, and every action taken, in order, so a session
+// can be reviewed afterwards or attached to a bug report. Paths are redacted
+// as they are written: the home directory becomes ~, and path components
+// matching a --transcript-redact glob become "***". Only paths are: the
+// arguments that aren't options, what trache quotes in its messages, and
+// words holding a path separator. The rest of the text is left alone, so a
+// glob can't blank out the wording around them. The file is only readable
+// by its owner.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+use crate::config;
+
+/// What redacted path components are written as
+const REDACTED: &str = "***";
+
+struct Transcript {
+    file: File,
+    redactor: Redactor,
+}
+
+static TRANSCRIPT: Mutex<Option<Transcript>> = Mutex::new(None);
+
+struct Redactor {
+    home: Option<String>,
+    components: GlobSet,
+}
+
+impl Redactor {
+    fn new(home: Option<&Path>, globs: &[String]) -> Result<Self, String> {
+        let mut builder = GlobSetBuilder::new();
+        for glob in globs {
+            let glob = Glob::new(glob).map_err(|e| format!("--transcript-redact: {e}"))?;
+            builder.add(glob);
+        }
+        Ok(Redactor {
+            home: home
+                .map(|h| h.to_string_lossy().trim_end_matches('/').to_string())
+                .filter(|h| !h.is_empty()),
+            components: builder
+                .build()
+                .map_err(|e| format!("--transcript-redact: {e}"))?,
+        })
+    }
+
+    /// `path` with the home directory shortened to ~ and the components
+    /// (runs between '/' and '\\') that match a glob replaced.
+    fn redact_path(&self, path: &str) -> String {
+        let mut path = path.to_string();
+        if let Some(home) = &self.home {
+            let mut out = String::with_capacity(path.len());
+            let mut rest = path.as_str();
+            while let Some(at) = rest.find(home.as_str()) {
+                let after = &rest[at + home.len()..];
+                out.push_str(&rest[..at]);
+                if after.is_empty() || after.starts_with(['/', '\\']) {
+                    out.push('~');
+                } else {
+                    out.push_str(home);
+                }
+                rest = after;
+            }
+            out.push_str(rest);
+            path = out;
+        }
+        if self.components.is_empty() {
+            return path;
+        }
+        path.split_inclusive(['/', '\\'])
+            .map(|piece| {
+                let end = piece.trim_end_matches(['/', '\\']);
+                if !end.is_empty() && self.components.is_match(end) {
+                    format!("{REDACTED}{}", &piece[end.len()..])
+                } else {
+                    piece.to_string()
+                }
+            })
+            .collect()
+    }
+
+    /// `text` with the paths in it redacted: what's between single quotes,
+    /// as trache quotes paths, and words holding a path separator.
+    fn redact(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        for (i, part) in text.split('\'').enumerate() {
+            if i > 0 {
+                out.push('\'');
+            }
+            if i % 2 == 1 {
+                out.push_str(&self.redact_path(part));
+                continue;
+            }
+            for word in part.split_inclusive(' ') {
+                let bare = word.trim_end_matches(' ');
+                if bare.contains(['/', '\\']) {
+                    out.push_str(&self.redact_path(bare));
+                    out.push_str(&word[bare.len()..]);
+                } else {
+                    out.push_str(word);
+                }
+            }
+        }
+        out
+    }
+
+    /// A command-line argument: a path unless it's an option.
+    fn redact_arg(&self, arg: &str) -> String {
+        if arg.starts_with('-') {
+            self.redact(arg)
+        } else {
+            self.redact_path(arg)
+        }
+    }
+}
+
+/// Start a transcript in `path`, replacing any earlier one, and record the
+/// command line.
+pub fn init(path: &Path, redact: &[String], args: &[String]) -> Result<(), String> {
+    let redactor = Redactor::new(config::home_dir().as_deref(), redact)?;
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options
+        .open(path)
+        .map_err(|e| format!("cannot create transcript '{}': {e}", path.display()))?;
+    let command: Vec<String> = args.iter().map(|a| redactor.redact_arg(a)).collect();
+    *TRANSCRIPT.lock().unwrap() = Some(Transcript { file, redactor });
+    write_as_is(&format!(
+        "# trache {} transcript, {}",
+        env!("CARGO_PKG_VERSION"),
+        chrono::Local::now().to_rfc3339()
+    ));
+    write_as_is(&format!("$ {}", command.join(" ")));
+    Ok(())
+}
+
+/// Text shown to the user ahead of a question, such as a menu of choices.
+pub fn shown(text: &str) {
+    for line in text.lines().filter(|l| !l.is_empty()) {
+        write(line);
+    }
+}
+
+/// A question and the answer typed to it; None when input ended instead.
+pub fn asked(prompt: &str, answer: Option<&str>) {
+    write(strip_colors(prompt).trim_end());
+    match answer {
+        Some(answer) => write(&format!("> {}", answer.trim_end_matches(['\r', '\n']))),
+        None => write("> (end of input)"),
+    }
+}
+
+/// An action on `path` and how it went.
+pub fn action(op: &str, path: &Path, error: Option<String>) {
+    let redacted = with_redactor(|r| {
+        let path = r.redact_path(&path.to_string_lossy());
+        (path, error.map(|e| r.redact(&e)))
+    });
+    match redacted {
+        Some((path, None)) => write_as_is(&format!("{op} '{path}': ok")),
+        Some((path, Some(e))) => write_as_is(&format!("{op} '{path}': error: {e}")),
+        None => {}
+    }
+}
+
+/// What `f` makes of the transcript's redactor, if there's a transcript.
+fn with_redactor<T>(f: impl FnOnce(&Redactor) -> T) -> Option<T> {
+    TRANSCRIPT.lock().unwrap().as_ref().map(|t| f(&t.redactor))
+}
+
+/// `text` without SGR escape sequences, which colored prompts carry.
+fn strip_colors(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find("\x1b[") {
+        out.push_str(&rest[..at]);
+        rest = &rest[at + 2..];
+        match rest.find('m') {
+            Some(end) => rest = &rest[end + 1..],
+            None => rest = "",
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn write(line: &str) {
+    emit(line, true);
+}
+
+fn write_as_is(line: &str) {
+    emit(line, false);
+}
+
+fn emit(line: &str, redact: bool) {
+    let mut guard = TRANSCRIPT.lock().unwrap();
+    let Some(transcript) = guard.as_mut() else {
+        return;
+    };
+    let line = if redact {
+        transcript.redactor.redact(line)
+    } else {
+        line.to_string()
+    };
+    if let Err(e) = writeln!(transcript.file, "{line}") {
+        eprintln!("trache: cannot write transcript: {e}");
+        // Don't repeat the warning for every remaining line
+        *guard = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor(globs: &[&str]) -> Redactor {
+        let globs: Vec<String> = globs.iter().map(|g| g.to_string()).collect();
+        Redactor::new(Some(Path::new("/home/alice")), &globs).unwrap()
+    }
+
+    #[test]
+    fn test_home_becomes_tilde() {
+        let r = redactor(&[]);
+        assert_eq!(
+            r.redact("trash '/home/alice/notes.txt': ok"),
+            "trash '~/notes.txt': ok"
+        );
+        assert_eq!(r.redact("/home/alice2/x"), "/home/alice2/x");
+        assert_eq!(r.redact("cd /home/alice"), "cd ~");
+    }
+
+    #[test]
+    fn test_matching_components_are_replaced() {
+        let r = redactor(&["client-*", "*.key"]);
+        assert_eq!(
+            r.redact("trache: remove regular file '/srv/client-acme/db.key'? "),
+            "trache: remove regular file '/srv/***/***'? "
+        );
+        assert_eq!(r.redact("$ trache notes.txt"), "$ trache notes.txt");
+        assert_eq!(r.redact_arg("db.key"), "***");
+        assert_eq!(
+            r.redact_arg("--trash-dir=/srv/client-a"),
+            "--trash-dir=/srv/***"
+        );
+    }
+
+    #[test]
+    fn test_only_paths_are_redacted() {
+        let r = redactor(&["re*", "*"]);
+        assert_eq!(
+            r.redact("trache: remove regular file 'notes.txt'? "),
+            "trache: remove regular file '***'? "
+        );
+        assert_eq!(r.redact("> yes"), "> yes");
+        assert_eq!(
+            r.redact("error: in use by /usr/bin/vim"),
+            "error: in use by /***/***/***"
+        );
+    }
+
+    #[test]
+    fn test_colors_are_stripped() {
+        assert_eq!(
+            strip_colors("\x1b[1;31mremove 'a' [1 entry, 0 B]?\x1b[0m "),
+            "remove 'a' [1 entry, 0 B]? "
+        );
+    }
+}
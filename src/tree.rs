@@ -0,0 +1,126 @@
+// --trash-list --tree: trashed items laid out by their original paths, the
+// way `tree` would have shown them before they were trashed. Directories
+// that only lead to items are drawn as well, a chain of them on one line,
+// and the tree is rooted at the deepest directory holding every item.
+#![cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Default)]
+struct Node {
+    children: BTreeMap<OsString, Node>,
+    /// Items trashed from this very path, already rendered
+    items: Vec<String>,
+}
+
+/// The lines drawing `items`, each an original path and what to show for
+/// the item trashed from it.
+pub fn render(items: &[(PathBuf, String)]) -> Vec<String> {
+    let Some(root) = common_dir(items.iter().map(|(path, _)| path.as_path())) else {
+        return Vec::new();
+    };
+    let mut top = Node::default();
+    for (path, label) in items {
+        let rest = path.strip_prefix(&root).unwrap_or(path);
+        let mut node = &mut top;
+        for part in rest.components() {
+            node = node
+                .children
+                .entry(part.as_os_str().to_os_string())
+                .or_default();
+        }
+        node.items.push(label.clone());
+    }
+    let mut lines = vec![root.display().to_string()];
+    draw(&top, "", &mut lines);
+    lines
+}
+
+/// The deepest directory every path lies under.
+fn common_dir<'a>(mut paths: impl Iterator<Item = &'a Path>) -> Option<PathBuf> {
+    let first = paths.next()?;
+    let mut common: Vec<Component> = first.parent().unwrap_or(first).components().collect();
+    for path in paths {
+        let parent = path.parent().unwrap_or(path);
+        let shared = common
+            .iter()
+            .zip(parent.components())
+            .take_while(|(a, b)| *a == b)
+            .count();
+        common.truncate(shared);
+    }
+    Some(common.iter().collect())
+}
+
+fn draw(node: &Node, indent: &str, lines: &mut Vec<String>) {
+    let count = node.children.len();
+    for (i, (name, child)) in node.children.iter().enumerate() {
+        let last = i + 1 == count;
+        let (branch, more) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        // Run a chain of directories with nothing else in them into one line
+        let mut label = name.to_string_lossy().into_owned();
+        let mut child = child;
+        while child.items.is_empty() && child.children.len() == 1 {
+            let (next_name, next) = child.children.iter().next().unwrap();
+            label = format!("{label}/{}", next_name.to_string_lossy());
+            child = next;
+        }
+        if child.items.is_empty() {
+            lines.push(format!("{indent}{branch}{label}/"));
+        }
+        for item in &child.items {
+            lines.push(format!("{indent}{branch}{label}  {item}"));
+        }
+        draw(child, &format!("{indent}{more}"), lines);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(items: &[(&str, &str)]) -> String {
+        let items: Vec<(PathBuf, String)> = items
+            .iter()
+            .map(|(path, label)| (PathBuf::from(path), label.to_string()))
+            .collect();
+        render(&items).join("\n")
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            tree(&[
+                ("/home/me/app/src/main.rs", "#1"),
+                ("/home/me/app/build", "#2"),
+                ("/home/me/app/src/deep/er/x.rs", "#3"),
+                ("/home/me/app/README", "#4"),
+                ("/home/me/app/README", "#5"),
+            ]),
+            "/home/me/app\n\
+             ├── README  #4\n\
+             ├── README  #5\n\
+             ├── build  #2\n\
+             └── src/\n\
+             \x20   ├── deep/er/x.rs  #3\n\
+             \x20   └── main.rs  #1"
+        );
+    }
+
+    #[test]
+    fn test_item_inside_a_trashed_dir() {
+        assert_eq!(
+            tree(&[("/p/dir", "#1"), ("/p/dir/file", "#2")]),
+            "/p\n\
+             └── dir  #1\n\
+             \x20   └── file  #2"
+        );
+        assert_eq!(tree(&[]), "");
+    }
+}
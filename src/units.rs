@@ -0,0 +1,449 @@
+// Helpers for human-friendly durations and sizes.
+// Parts are only used by trash management, which macOS doesn't support yet.
+#![cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, Months, NaiveDate, NaiveDateTime, NaiveTime};
+
+/// Parse a duration such as "30s", "15m", "12h", "7d" or "2w"; units may
+/// also be spelled out ("10 minutes", "1 hour"). A bare number is taken as
+/// seconds.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let s = input.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let n: u64 = num
+        .parse()
+        .map_err(|_| format!("invalid duration: '{input}'"))?;
+    let secs = match unit.trim() {
+        "" | "s" | "sec" | "secs" | "second" | "seconds" => 1,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60,
+        "h" | "hour" | "hours" => 60 * 60,
+        "d" | "day" | "days" => 24 * 60 * 60,
+        "w" | "week" | "weeks" => 7 * 24 * 60 * 60,
+        other => return Err(format!("invalid duration unit '{other}' in '{input}'")),
+    };
+    n.checked_mul(secs)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("duration too large: '{input}'"))
+}
+
+/// Parse the start of a time window: a duration back from `now` ("10m",
+/// "10 minutes ago") or a local date and time ("2024-05-01 14:30",
+/// "2024-05-01", RFC 3339). Returns seconds since the Unix epoch.
+pub fn parse_since(input: &str, now: DateTime<Local>) -> Result<i64, String> {
+    let s = input.trim();
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Ok(t.timestamp());
+    }
+    for format in [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+    ] {
+        if let Ok(t) = NaiveDateTime::parse_from_str(s, format) {
+            return local_timestamp(t, input);
+        }
+    }
+    if let Ok(d) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return local_timestamp(d.and_time(NaiveTime::MIN), input);
+    }
+
+    let ago = s.strip_suffix("ago").unwrap_or(s);
+    let duration = parse_duration(ago).map_err(|_| {
+        format!("invalid time: '{input}' (try \"10m\", \"2 hours ago\" or \"2024-05-01 14:30\")")
+    })?;
+    i64::try_from(duration.as_secs())
+        .ok()
+        .and_then(|secs| now.timestamp().checked_sub(secs))
+        .ok_or_else(|| format!("duration too large: '{input}'"))
+}
+
+fn local_timestamp(t: NaiveDateTime, input: &str) -> Result<i64, String> {
+    t.and_local_timezone(Local)
+        .earliest()
+        .map(|t| t.timestamp())
+        .ok_or_else(|| format!("no such local time: '{input}'"))
+}
+
+/// Parse a size such as "4k", "100M", "10G" or "1TiB" (powers of 1024).
+/// A bare number is taken as bytes.
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let s = input.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let n: f64 = num
+        .parse()
+        .map_err(|_| format!("invalid size: '{input}'"))?;
+    let unit = unit.trim().to_ascii_lowercase();
+    let unit = unit
+        .strip_suffix("ib")
+        .or_else(|| unit.strip_suffix('b'))
+        .unwrap_or(&unit);
+    let exp = match unit {
+        "" => 0,
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        "t" => 4,
+        "p" => 5,
+        _ => return Err(format!("invalid size unit in '{input}'")),
+    };
+    Ok((n * 1024f64.powi(exp)).round() as u64)
+}
+
+/// A comparison against an item's size, from a `size:` pattern prefix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SizeFilter {
+    op: SizeOp,
+    bytes: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SizeOp {
+    Less,
+    AtMost,
+    Equal,
+    AtLeast,
+    Greater,
+}
+
+impl SizeFilter {
+    /// Parse ">100M", "<=4k", "=0" and the like; a bare size means "=".
+    pub fn parse(input: &str) -> Result<SizeFilter, String> {
+        let (op, size) = [
+            ("<=", SizeOp::AtMost),
+            (">=", SizeOp::AtLeast),
+            ("<", SizeOp::Less),
+            (">", SizeOp::Greater),
+            ("=", SizeOp::Equal),
+        ]
+        .into_iter()
+        .find_map(|(prefix, op)| input.strip_prefix(prefix).map(|rest| (op, rest)))
+        .unwrap_or((SizeOp::Equal, input));
+        let bytes = parse_size(size).map_err(|e| format!("size:{input}: {e}"))?;
+        Ok(SizeFilter { op, bytes })
+    }
+
+    pub fn accepts(&self, size: u64) -> bool {
+        match self.op {
+            SizeOp::Less => size < self.bytes,
+            SizeOp::AtMost => size <= self.bytes,
+            SizeOp::Equal => size == self.bytes,
+            SizeOp::AtLeast => size >= self.bytes,
+            SizeOp::Greater => size > self.bytes,
+        }
+    }
+}
+
+/// A window of deletion times, from a `deleted:` pattern prefix. Both ends
+/// are seconds since the epoch; `from` is inclusive and `until` exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DateFilter {
+    from: Option<i64>,
+    until: Option<i64>,
+}
+
+impl DateFilter {
+    /// Parse a local date or period ("2024", "2024-05", "2024-05-01"),
+    /// optionally compared ("<2024", ">=2024-01-01"), or a range of them
+    /// ("2024-01..2024-03", either end open). A bare period matches times
+    /// within it; comparisons are against the whole period, so ">2024-05"
+    /// means June or later.
+    pub fn parse(input: &str) -> Result<DateFilter, String> {
+        let period = |s: &str| parse_period(s).map_err(|e| format!("deleted:{input}: {e}"));
+        if let Some((from, until)) = input.split_once("..") {
+            let from = if from.is_empty() {
+                None
+            } else {
+                Some(period(from)?.0)
+            };
+            let until = if until.is_empty() {
+                None
+            } else {
+                Some(period(until)?.1)
+            };
+            return Ok(DateFilter { from, until });
+        }
+        let (op, date) = ["<=", ">=", "<", ">", "="]
+            .into_iter()
+            .find_map(|op| input.strip_prefix(op).map(|rest| (op, rest)))
+            .unwrap_or(("=", input));
+        let (start, end) = period(date)?;
+        let (from, until) = match op {
+            "<" => (None, Some(start)),
+            "<=" => (None, Some(end)),
+            ">" => (Some(end), None),
+            ">=" => (Some(start), None),
+            _ => (Some(start), Some(end)),
+        };
+        Ok(DateFilter { from, until })
+    }
+
+    pub fn accepts(&self, time: i64) -> bool {
+        self.from.is_none_or(|from| time >= from) && self.until.is_none_or(|until| time < until)
+    }
+}
+
+/// Start and end (exclusive) of a local year, month or day.
+fn parse_period(input: &str) -> Result<(i64, i64), String> {
+    let invalid = || "invalid date (try 2024, 2024-05 or 2024-05-01)".to_string();
+    let parts: Vec<u32> = input
+        .split('-')
+        .map(|p| p.parse().map_err(|_| invalid()))
+        .collect::<Result<_, _>>()?;
+    let (start, end) = match parts[..] {
+        [y] => {
+            let start = NaiveDate::from_ymd_opt(y as i32, 1, 1).ok_or_else(invalid)?;
+            (start, start.checked_add_months(Months::new(12)))
+        }
+        [y, m] => {
+            let start = NaiveDate::from_ymd_opt(y as i32, m, 1).ok_or_else(invalid)?;
+            (start, start.checked_add_months(Months::new(1)))
+        }
+        [y, m, d] => {
+            let start = NaiveDate::from_ymd_opt(y as i32, m, d).ok_or_else(invalid)?;
+            (start, start.succ_opt())
+        }
+        _ => return Err(invalid()),
+    };
+    let end = end.ok_or_else(invalid)?;
+    Ok((
+        local_timestamp(start.and_time(NaiveTime::MIN), input)?,
+        local_timestamp(end.and_time(NaiveTime::MIN), input)?,
+    ))
+}
+
+/// Format a byte count for humans, e.g. "512 B" or "1.3 GiB".
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Sum of file sizes below `path`, without following symlinks.
+pub fn disk_usage(path: &Path) -> u64 {
+    let Ok(meta) = path.symlink_metadata() else {
+        return 0;
+    };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries.flatten().map(|e| disk_usage(&e.path())).sum()
+}
+
+/// Number of entries at and below `path`, without following symlinks.
+/// Stops counting once the total passes `limit`.
+pub fn count_entries(path: &Path, limit: u64) -> u64 {
+    let Ok(meta) = path.symlink_metadata() else {
+        return 0;
+    };
+    let mut count = 1;
+    if meta.is_dir()
+        && let Ok(entries) = fs::read_dir(path)
+    {
+        for entry in entries.flatten() {
+            if count > limit {
+                break;
+            }
+            count += count_entries(&entry.path(), limit - count);
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disk_usage_tree() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        fs::write(tmp.path().join("a"), "12345").unwrap();
+        fs::create_dir(tmp.path().join("sub")).unwrap();
+        fs::write(tmp.path().join("sub").join("b"), "123").unwrap();
+        assert_eq!(disk_usage(tmp.path()), 8);
+    }
+
+    #[test]
+    fn test_count_entries() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        fs::write(tmp.path().join("a"), "").unwrap();
+        fs::create_dir(tmp.path().join("sub")).unwrap();
+        fs::write(tmp.path().join("sub").join("b"), "").unwrap();
+        fs::write(tmp.path().join("sub").join("c"), "").unwrap();
+        assert_eq!(count_entries(tmp.path(), u64::MAX), 5);
+        assert_eq!(count_entries(&tmp.path().join("a"), u64::MAX), 1);
+        assert_eq!(count_entries(&tmp.path().join("missing"), u64::MAX), 0);
+        // Stops early, but still reports more than the limit
+        assert!(count_entries(tmp.path(), 2) > 2);
+    }
+
+    #[test]
+    fn test_disk_usage_missing() {
+        assert_eq!(disk_usage(Path::new("/nonexistent/trache/path")), 0);
+    }
+
+    #[test]
+    fn test_parse_duration_units() {
+        assert_eq!(parse_duration("45").unwrap(), Duration::from_secs(45));
+        assert_eq!(parse_duration("45s").unwrap(), Duration::from_secs(45));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(900));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(604800));
+        assert_eq!(parse_duration("1w").unwrap(), Duration::from_secs(604800));
+    }
+
+    #[test]
+    fn test_parse_duration_words() {
+        assert_eq!(
+            parse_duration("10 minutes").unwrap(),
+            Duration::from_secs(600)
+        );
+        assert_eq!(parse_duration("1 hour").unwrap(), Duration::from_secs(3600));
+        assert_eq!(
+            parse_duration("2days").unwrap(),
+            Duration::from_secs(172800)
+        );
+    }
+
+    #[test]
+    fn test_parse_since_durations() {
+        let now = Local::now();
+        let t = now.timestamp();
+        assert_eq!(parse_since("10m", now).unwrap(), t - 600);
+        assert_eq!(parse_since("10 minutes ago", now).unwrap(), t - 600);
+        assert_eq!(parse_since(" 2 hours ago ", now).unwrap(), t - 7200);
+    }
+
+    #[test]
+    fn test_parse_since_timestamps() {
+        let now = Local::now();
+        let expected = NaiveDate::from_ymd_opt(2024, 5, 1)
+            .unwrap()
+            .and_hms_opt(14, 30, 0)
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap()
+            .timestamp();
+        assert_eq!(parse_since("2024-05-01 14:30", now).unwrap(), expected);
+        assert_eq!(parse_since("2024-05-01T14:30:00", now).unwrap(), expected);
+        assert_eq!(
+            parse_since("2024-05-01T14:30:00Z", now).unwrap(),
+            1_714_573_800
+        );
+        assert_eq!(
+            parse_since("2024-05-01", now).unwrap(),
+            expected - (14 * 60 + 30) * 60
+        );
+    }
+
+    #[test]
+    fn test_parse_since_invalid() {
+        assert!(parse_since("yesterday-ish", Local::now()).is_err());
+        assert!(parse_since("2024-13-01", Local::now()).is_err());
+    }
+
+    #[test]
+    fn test_parse_duration_invalid() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("3x").is_err());
+        assert!(parse_duration("-3d").is_err());
+    }
+
+    #[test]
+    fn test_parse_size_units() {
+        assert_eq!(parse_size("100").unwrap(), 100);
+        assert_eq!(parse_size("4k").unwrap(), 4096);
+        assert_eq!(parse_size("4K").unwrap(), 4096);
+        assert_eq!(parse_size("100M").unwrap(), 100 * 1024 * 1024);
+        assert_eq!(parse_size("10G").unwrap(), 10 * 1024 * 1024 * 1024);
+        assert_eq!(parse_size("1GiB").unwrap(), 1024 * 1024 * 1024);
+        assert_eq!(parse_size("1.5k").unwrap(), 1536);
+    }
+
+    #[test]
+    fn test_parse_size_invalid() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("G").is_err());
+        assert!(parse_size("10X").is_err());
+    }
+
+    #[test]
+    fn test_size_filter() {
+        let over = SizeFilter::parse(">100M").unwrap();
+        assert!(over.accepts(100 * 1024 * 1024 + 1));
+        assert!(!over.accepts(100 * 1024 * 1024));
+        let under = SizeFilter::parse("<=4k").unwrap();
+        assert!(under.accepts(4096));
+        assert!(!under.accepts(4097));
+        assert!(SizeFilter::parse("0").unwrap().accepts(0));
+        assert!(SizeFilter::parse(">").is_err());
+        assert!(SizeFilter::parse("~4k").is_err());
+    }
+
+    #[test]
+    fn test_date_filter() {
+        let at = |y, m, d| {
+            local_timestamp(
+                NaiveDate::from_ymd_opt(y, m, d)
+                    .unwrap()
+                    .and_hms_opt(12, 0, 0)
+                    .unwrap(),
+                "",
+            )
+            .unwrap()
+        };
+        let may = DateFilter::parse("2024-05").unwrap();
+        assert!(may.accepts(at(2024, 5, 31)));
+        assert!(!may.accepts(at(2024, 6, 1)));
+        assert!(!may.accepts(at(2024, 4, 30)));
+        let after = DateFilter::parse(">2024-05").unwrap();
+        assert!(after.accepts(at(2024, 6, 1)));
+        assert!(!after.accepts(at(2024, 5, 31)));
+        assert!(
+            DateFilter::parse(">=2024-01-01")
+                .unwrap()
+                .accepts(at(2024, 1, 1))
+        );
+        assert!(
+            DateFilter::parse("<=2023")
+                .unwrap()
+                .accepts(at(2023, 12, 31))
+        );
+        assert!(!DateFilter::parse("<2023").unwrap().accepts(at(2023, 1, 1)));
+        let q1 = DateFilter::parse("2024-01..2024-03").unwrap();
+        assert!(q1.accepts(at(2024, 3, 31)));
+        assert!(!q1.accepts(at(2024, 4, 1)));
+        assert!(DateFilter::parse("..2024").unwrap().accepts(at(1999, 1, 1)));
+        assert!(DateFilter::parse("2024-13").is_err());
+        assert!(DateFilter::parse("yesterday").is_err());
+        assert!(DateFilter::parse("2024-05-01-02").is_err());
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(2048), "2.0 KiB");
+        assert_eq!(format_size(1395864371), "1.3 GiB");
+    }
+}
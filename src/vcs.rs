@@ -0,0 +1,203 @@
+// Repositories with uncommitted work inside a directory about to be trashed
+// recursively. Each git, Mercurial or Subversion checkout found is asked for
+// its status with its own tool; one that can't be asked (the tool isn't
+// installed, say) is taken to be clean rather than holding up the removal.
+// The checkout may be anyone's, so its tool runs without the checkout's own
+// configuration having a say: no git hooks or fsmonitor, no hgrc files or
+// Mercurial extensions.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Git,
+    Mercurial,
+    Subversion,
+}
+
+impl Kind {
+    const ALL: [Kind; 3] = [Kind::Git, Kind::Mercurial, Kind::Subversion];
+
+    /// The metadata entry marking a checkout's top directory
+    fn marker(self) -> &'static str {
+        match self {
+            Kind::Git => ".git",
+            Kind::Mercurial => ".hg",
+            Kind::Subversion => ".svn",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Git => "git",
+            Kind::Mercurial => "Mercurial",
+            Kind::Subversion => "Subversion",
+        }
+    }
+
+    /// Whether the checkout at `dir` has changes its tool reports, untracked
+    /// files included; None when the tool can't tell.
+    fn dirty(self, dir: &Path) -> Option<bool> {
+        let mut cmd = match self {
+            Kind::Git => {
+                let mut cmd = Command::new("git");
+                cmd.args([
+                    "-c",
+                    "core.fsmonitor=",
+                    "-c",
+                    "core.hooksPath=/dev/null",
+                    "--no-optional-locks",
+                ])
+                .arg("-C")
+                .arg(dir)
+                .args(["status", "--porcelain"]);
+                cmd
+            }
+            Kind::Mercurial => {
+                let mut cmd = Command::new("hg");
+                cmd.env("HGRCPATH", "")
+                    .env("HGRCSKIPREPO", "1")
+                    .env("HGPLAIN", "1")
+                    .arg("--cwd")
+                    .arg(dir)
+                    .arg("status");
+                cmd
+            }
+            Kind::Subversion => {
+                let mut cmd = Command::new("svn");
+                cmd.arg("status").arg(dir);
+                cmd
+            }
+        };
+        let output = cmd
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        output.status.success().then_some(!output.stdout.is_empty())
+    }
+}
+
+/// A checkout with uncommitted changes.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Repo {
+    pub path: PathBuf,
+    pub kind: Kind,
+}
+
+/// The checkouts with uncommitted changes at or under `dir`. A checkout's
+/// insides aren't searched for more.
+pub fn dirty_repos(dir: &Path) -> Vec<Repo> {
+    let mut found = Vec::new();
+    walk(dir, &mut found);
+    found
+}
+
+fn walk(dir: &Path, found: &mut Vec<Repo>) {
+    if let Some(kind) = Kind::ALL
+        .into_iter()
+        .find(|kind| dir.join(kind.marker()).symlink_metadata().is_ok())
+    {
+        if kind.dirty(dir) == Some(true) {
+            found.push(Repo {
+                path: dir.to_path_buf(),
+                kind,
+            });
+        }
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut dirs: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|e| e.path())
+        .collect();
+    dirs.sort();
+    for sub in dirs {
+        walk(&sub, found);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(dir: &Path, args: &[&str]) -> bool {
+        Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    }
+
+    #[test]
+    fn test_dirty_git_repo() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let repo = tmp.path().join("projects/app");
+        fs::create_dir_all(&repo).unwrap();
+        if !git(tmp.path(), &["--version"]) {
+            eprintln!("skipping test_dirty_git_repo: git is not installed");
+            return;
+        }
+        assert!(git(&repo, &["init", "-q"]));
+        assert!(dirty_repos(tmp.path()).is_empty());
+
+        fs::write(repo.join("main.rs"), "fn main() {}").unwrap();
+        assert_eq!(
+            dirty_repos(tmp.path()),
+            [Repo {
+                path: repo.clone(),
+                kind: Kind::Git
+            }]
+        );
+
+        assert!(git(&repo, &["add", "."]));
+        assert!(git(
+            &repo,
+            &[
+                "-c",
+                "user.name=t",
+                "-c",
+                "user.email=t@t",
+                "commit",
+                "-qm",
+                "init"
+            ]
+        ));
+        assert!(dirty_repos(tmp.path()).is_empty());
+
+        // The checkout's own hooks and fsmonitor never run
+        let ran = tmp.path().join("ran");
+        let hook = format!("#!/bin/sh\ntouch '{}'\n", ran.display());
+        fs::write(repo.join(".git/hooks/fsmonitor"), hook).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let perms = fs::Permissions::from_mode(0o755);
+            fs::set_permissions(repo.join(".git/hooks/fsmonitor"), perms).unwrap();
+        }
+        assert!(git(
+            &repo,
+            &["config", "core.fsmonitor", ".git/hooks/fsmonitor"]
+        ));
+        fs::write(repo.join("main.rs"), "fn main() { }").unwrap();
+        assert_eq!(dirty_repos(tmp.path()).len(), 1);
+        assert!(!ran.exists());
+    }
+
+    #[test]
+    fn test_unknown_status_is_clean() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        // Not a checkout Subversion (if installed at all) can read
+        fs::create_dir_all(tmp.path().join("wc/.svn")).unwrap();
+        fs::write(tmp.path().join("wc/file"), "x").unwrap();
+        assert!(dirty_repos(tmp.path()).is_empty());
+    }
+}
@@ -0,0 +1,166 @@
+// Restores that span several volumes: one batch per volume, run side by
+// side so a slow external disk doesn't hold up the rest, with a combined
+// summary of what each got done. Volumes are told apart by the device their
+// trash directory is on (on Windows, by the recycle bin itself), so a volume
+// holding both the home trash and a .Trash-$uid still makes one batch.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use trash::TrashItem;
+
+use crate::items;
+
+/// How one volume's batch went
+pub struct Outcome {
+    /// The trash directories its items came from
+    pub dirs: Vec<PathBuf>,
+    pub count: usize,
+    /// How many of its items left the trash, even if the batch failed
+    pub restored: usize,
+    pub result: Result<(), trash::Error>,
+}
+
+/// `items` in one batch per volume, in volume order.
+pub fn group(items: Vec<TrashItem>) -> Vec<Vec<TrashItem>> {
+    group_by(items, volume)
+}
+
+fn group_by<K: Ord>(items: Vec<TrashItem>, key: impl Fn(&TrashItem) -> K) -> Vec<Vec<TrashItem>> {
+    let mut batches: BTreeMap<K, Vec<TrashItem>> = BTreeMap::new();
+    for item in items {
+        batches.entry(key(&item)).or_default().push(item);
+    }
+    batches.into_values().collect()
+}
+
+/// The device the item's trash directory is on, if it can still be read.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+fn volume(item: &TrashItem) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    items::trash_dir(item)
+        .metadata()
+        .ok()
+        .map(|meta| meta.dev())
+}
+
+/// Each drive has a recycle bin of its own.
+#[cfg(target_os = "windows")]
+fn volume(item: &TrashItem) -> PathBuf {
+    items::trash_dir(item)
+}
+
+/// Run `restore` on each of `batches` in a thread of its own, returning the
+/// outcomes in the same order. `restore` says how many of a batch's items
+/// left the trash as well as how the call went.
+pub fn restore_side_by_side<F>(batches: Vec<Vec<TrashItem>>, restore: F) -> Vec<Outcome>
+where
+    F: Fn(Vec<TrashItem>) -> (usize, Result<(), trash::Error>) + Sync,
+{
+    let restore = &restore;
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = batches
+            .into_iter()
+            .map(|batch| {
+                let mut dirs: Vec<PathBuf> = batch.iter().map(items::trash_dir).collect();
+                dirs.sort();
+                dirs.dedup();
+                let count = batch.len();
+                (dirs, count, scope.spawn(move || restore(batch)))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|(dirs, count, handle)| {
+                let (restored, result) = handle.join().unwrap_or_else(|_| {
+                    let panicked = trash::Error::Unknown {
+                        description: "restore thread panicked".into(),
+                    };
+                    (0, Err(panicked))
+                });
+                Outcome {
+                    dirs,
+                    count,
+                    restored,
+                    result,
+                }
+            })
+            .collect()
+    })
+}
+
+/// The line totalling `outcomes`, e.g. "Restored 5 of 7 item(s) from 2
+/// volume(s)".
+pub fn summary(outcomes: &[Outcome]) -> String {
+    let restored: usize = outcomes.iter().map(|o| o.restored).sum();
+    let count: usize = outcomes.iter().map(|o| o.count).sum();
+    format!(
+        "Restored {restored} of {count} item(s) from {} volume(s)",
+        outcomes.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+
+    fn item(trash: &str, name: &str) -> TrashItem {
+        TrashItem {
+            id: OsString::from(format!("{trash}/info/{name}.trashinfo")),
+            name: name.into(),
+            original_parent: PathBuf::from("/home/u"),
+            time_deleted: 0,
+        }
+    }
+
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    fn test_trash_dirs_on_one_volume_make_one_batch() {
+        let items = vec![
+            item("/usb/.Trash-1000", "a"),
+            item("/home/u/.local/share/Trash", "b"),
+            item("/home/.Trash-1000", "c"),
+        ];
+        // Keyed as `volume` would be, by a stand-in device number
+        let batches = group_by(items, |item| items::trash_dir(item).starts_with("/usb"));
+        let names: Vec<Vec<_>> = batches
+            .iter()
+            .map(|batch| batch.iter().map(|i| i.name.clone()).collect())
+            .collect();
+        assert_eq!(names, [vec!["b", "c"], vec!["a"]]);
+    }
+
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    fn test_partial_failure_keeps_its_counts() {
+        let batches = vec![
+            vec![item("/home/t", "a"), item("/home/t", "b")],
+            vec![
+                item("/usb/t", "c"),
+                item("/usb/t", "d"),
+                item("/usb/t", "e"),
+            ],
+        ];
+        let outcomes = restore_side_by_side(batches, |batch| {
+            if batch[0].name == "c" {
+                let e = trash::Error::Unknown {
+                    description: "disk went away".into(),
+                };
+                (1, Err(e))
+            } else {
+                (batch.len(), Ok(()))
+            }
+        });
+
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(outcomes[0].dirs, [PathBuf::from("/home/t")]);
+        assert!(outcomes[0].result.is_ok());
+        assert_eq!((outcomes[1].restored, outcomes[1].count), (1, 3));
+        assert!(outcomes[1].result.is_err());
+        assert_eq!(
+            summary(&outcomes),
+            "Restored 3 of 5 item(s) from 2 volume(s)"
+        );
+    }
+}
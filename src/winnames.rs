@@ -0,0 +1,136 @@
+// Windows file name rules, for restoring items that were trashed on another
+// OS or imported: reserved device names, forbidden characters, trailing dots
+// and spaces, and length limits.
+#![cfg_attr(not(windows), allow(dead_code))]
+
+use std::path::Path;
+
+/// Longest path the Win32 API accepts without long path support.
+pub const MAX_PATH: usize = 260;
+const MAX_COMPONENT: usize = 255;
+
+const RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+fn is_forbidden(c: char) -> bool {
+    matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*') || c.is_control()
+}
+
+fn is_reserved(name: &str) -> bool {
+    // "CON.txt" and "con .log" are as reserved as "CON"
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    RESERVED.iter().any(|r| r.eq_ignore_ascii_case(stem))
+}
+
+/// Why `name` can't be used as a file name on Windows, if it can't.
+pub fn name_problem(name: &str) -> Option<String> {
+    if let Some(c) = name.chars().find(|&c| is_forbidden(c)) {
+        return Some(format!("contains the character {c:?}"));
+    }
+    if is_reserved(name) {
+        return Some("is a reserved device name".into());
+    }
+    if name.ends_with('.') || name.ends_with(' ') {
+        return Some("ends with a dot or space".into());
+    }
+    if name.chars().count() > MAX_COMPONENT {
+        return Some(format!("is longer than {MAX_COMPONENT} characters"));
+    }
+    None
+}
+
+/// Why `path` can't be restored to on Windows, if it can't.
+pub fn path_problem(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_string_lossy();
+    if let Some(problem) = name_problem(&name) {
+        return Some(format!("'{name}' {problem}"));
+    }
+    let len = path.as_os_str().len();
+    if len >= MAX_PATH {
+        return Some(format!(
+            "the path is {len} characters long; Windows allows {} unless long paths are enabled",
+            MAX_PATH - 1
+        ));
+    }
+    None
+}
+
+/// A close variant of `name` that Windows accepts.
+pub fn sanitize(name: &str) -> String {
+    let mut clean: String = name
+        .chars()
+        .map(|c| if is_forbidden(c) { '_' } else { c })
+        .collect();
+    let trimmed = clean.trim_end_matches(['.', ' ']).len();
+    clean.truncate(trimmed);
+    if clean.is_empty() {
+        clean.push('_');
+    }
+    if is_reserved(&clean) {
+        clean.insert(0, '_');
+    }
+    if clean.chars().count() > MAX_COMPONENT {
+        // Keep the extension where possible
+        let ext = Path::new(&clean)
+            .extension()
+            .map(|e| format!(".{}", e.to_string_lossy()))
+            .filter(|e| e.chars().count() < 16)
+            .unwrap_or_default();
+        let keep = MAX_COMPONENT - ext.chars().count();
+        clean = clean.chars().take(keep).collect::<String>() + &ext;
+    }
+    clean
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_names() {
+        for name in ["notes.txt", "CONFIG.sys", "console", "a.b.c", ".hidden"] {
+            assert_eq!(name_problem(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        for name in [
+            "a:b",
+            "what?",
+            "x|y",
+            "tab\tname",
+            "CON",
+            "con.txt",
+            "LPT1.log",
+            "dot.",
+        ] {
+            assert!(name_problem(name).is_some(), "{name}");
+        }
+        assert!(name_problem(&"a".repeat(256)).is_some());
+    }
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize("a:b?.txt"), "a_b_.txt");
+        assert_eq!(sanitize("CON.txt"), "_CON.txt");
+        assert_eq!(sanitize("trailing. . "), "trailing");
+        assert_eq!(sanitize("..."), "_");
+        let long = format!("{}.txt", "a".repeat(300));
+        let clean = sanitize(&long);
+        assert_eq!(clean.chars().count(), 255);
+        assert!(clean.ends_with(".txt"));
+        for name in ["a:b?.txt", "CON.txt", "trailing. . ", "...", long.as_str()] {
+            assert_eq!(name_problem(&sanitize(name)), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_path_problem_length() {
+        let path = format!("C:/{}/file.txt", "d".repeat(260));
+        assert!(path_problem(Path::new(&path)).is_some());
+        assert_eq!(path_problem(Path::new("C:/Users/me/file.txt")), None);
+    }
+}
@@ -1,11 +1,38 @@
 use assert_cmd::Command;
-use assert_cmd::cargo::cargo_bin_cmd;
 use predicates::prelude::*;
 use std::fs;
 use tempfile::TempDir;
 
+thread_local! {
+    /// The test's own home, holding its trash, config and state. Tests run
+    /// on threads of their own, so a test's commands share one home and
+    /// tests don't see each other's items.
+    static HOME: TempDir = TempDir::new().unwrap();
+}
+
+/// `program`, run with a home of the test's own, so no trache it starts
+/// reaches the user's trash or config.
+fn at_home(program: impl AsRef<std::ffi::OsStr>) -> std::process::Command {
+    let mut cmd = std::process::Command::new(program);
+    HOME.with(|home| {
+        cmd.env("HOME", home.path())
+            .env("XDG_DATA_HOME", home.path().join("data"))
+            .env_remove("XDG_CONFIG_HOME")
+            .env_remove("XDG_STATE_HOME");
+    });
+    cmd
+}
+
+/// trache at the test's own home; see [`at_home`].
 fn trache() -> Command {
-    cargo_bin_cmd!("trache")
+    Command::from_std(at_home(assert_cmd::cargo::cargo_bin!("trache")))
+}
+
+/// trache with the user's own home and trash, for the tests that are about
+/// where the system puts things.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn system_trache() -> Command {
+    Command::new(assert_cmd::cargo::cargo_bin!("trache"))
 }
 
 #[test]
@@ -224,7 +251,9 @@ fn test_prompt_once_with_many_files() {
     cmd.write_stdin("y\n")
         .assert()
         .success()
-        .stderr(predicate::str::contains("remove 5 argument(s)?"));
+        .stderr(predicate::str::contains(
+            "remove 5 argument(s) [5 entries, 35 B]?",
+        ));
 
     for f in &files {
         assert!(!f.exists());
@@ -255,6 +284,139 @@ fn test_prompt_once_declined() {
     }
 }
 
+#[test]
+fn test_summary() {
+    let tmp = TempDir::new().unwrap();
+    let a = tmp.path().join("a.txt");
+    let b = tmp.path().join("b.txt");
+    fs::write(&a, "x".repeat(1000)).unwrap();
+    fs::write(&b, "x".repeat(1048)).unwrap();
+
+    trache()
+        .arg("-v")
+        .args([&a, &b, &tmp.path().join("missing")])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "trache: trashed 2 item(s) (2.0 KiB), skipped 0, failed 1\n",
+        ));
+
+    fs::write(&a, "").unwrap();
+    fs::write(&b, "").unwrap();
+    trache()
+        .args(["--summary", "-i"])
+        .args([&a, &b])
+        .write_stdin("y\nn\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::ends_with(
+            "trache: trashed 1 item(s) (0 B), skipped 1, failed 0\n",
+        ));
+    assert!(!a.exists() && b.exists());
+
+    // -v with one file prints no totals
+    trache().arg("-v").arg(&b).assert().success().stderr("");
+}
+
+#[test]
+fn test_max_size_no_prompt() {
+    let tmp = TempDir::new().unwrap();
+    let small = tmp.path().join("small.txt");
+    let big = tmp.path().join("big.txt");
+    fs::write(&small, "x").unwrap();
+    fs::write(&big, "x".repeat(5000)).unwrap();
+
+    trache()
+        .args(["--max-size-no-prompt", "1K"])
+        .args([&small, &big])
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("remove '"))
+        .stderr(predicate::str::contains("big.txt', which is"))
+        .stderr(predicate::str::contains("small.txt").not());
+    assert!(!small.exists() && big.exists());
+
+    let config = tmp.path().join("config.toml");
+    fs::write(&config, "max_size_no_prompt = \"1K\"\n").unwrap();
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .args(["--max-size-no-prompt", "0"])
+        .arg(&big)
+        .assert()
+        .success()
+        .stderr("");
+    assert!(!big.exists());
+
+    fs::write(&big, "x".repeat(5000)).unwrap();
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg("-f")
+        .arg(&big)
+        .assert()
+        .success()
+        .stderr("");
+    assert!(!big.exists());
+
+    trache()
+        .args(["--max-size-no-prompt", "lots"])
+        .arg(&small)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--max-size-no-prompt: "));
+}
+
+#[test]
+fn test_prompt_once_thresholds() {
+    let tmp = TempDir::new().unwrap();
+    let small = tmp.path().join("small.txt");
+    let big = tmp.path().join("big.txt");
+    fs::write(&small, "x").unwrap();
+    fs::write(&big, "x".repeat(2000)).unwrap();
+    let config = tmp.path().join("config.toml");
+
+    fs::write(&config, "bulk_prompt_count = 1\n").unwrap();
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg("-I")
+        .args([&small, &big])
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("remove 2 argument(s)"));
+    assert!(small.exists() && big.exists());
+
+    fs::write(&config, "bulk_prompt_size = \"1K\"\n").unwrap();
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg("-I")
+        .arg(&big)
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("remove 1 argument(s)"));
+    assert!(big.exists());
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg("-I")
+        .arg(&small)
+        .assert()
+        .success()
+        .stderr("");
+    assert!(!small.exists());
+
+    fs::write(&config, "bulk_prompt_size = \"lots\"\n").unwrap();
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg("-I")
+        .arg(&big)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("bulk_prompt_size:"));
+
+    trache().arg(&big).assert().success();
+}
+
 #[test]
 fn test_force_overrides_interactive() {
     let tmp = TempDir::new().unwrap();
@@ -376,6 +538,36 @@ fn test_preserve_root_explicit() {
         ));
 }
 
+#[test]
+#[cfg(windows)]
+fn test_preserve_root_blocks_windows_roots() {
+    trache()
+        .args(["-r", "C:\\"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("(a drive root)"));
+
+    // A stand-in profile, so nothing real is at stake
+    let tmp = TempDir::new().unwrap();
+    let profile = tmp.path().join("profile");
+    fs::create_dir_all(profile.join("Documents")).unwrap();
+    trache()
+        .env("USERPROFILE", &profile)
+        .arg("-r")
+        .arg(&profile)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("(your user profile)"));
+    assert!(profile.exists());
+    trache()
+        .env("USERPROFILE", &profile)
+        .arg("-r")
+        .arg(profile.join("Documents"))
+        .assert()
+        .success();
+    assert!(!profile.join("Documents").exists());
+}
+
 #[test]
 fn test_no_preserve_root_flag_accepted() {
     // --no-preserve-root should be accepted (but we test with a safe file)
@@ -729,6 +921,34 @@ fn test_trash_undo_collision_skip() {
         .success();
 }
 
+#[test]
+#[cfg_attr(target_os = "macos", ignore)]
+fn test_trash_undo_collision_quit() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("systest_col_quit.txt");
+    fs::write(&file, "original").unwrap();
+
+    trache().arg(&file).assert().success();
+    fs::write(&file, "blocker").unwrap();
+
+    // Cancelled, without an error on top of the answer
+    trache()
+        .arg("-i")
+        .arg("--trash-undo")
+        .arg("full:systest_col_quit.txt")
+        .write_stdin("q\n")
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("Error:").not());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "blocker");
+
+    trache()
+        .arg("--trash-purge")
+        .arg("full:systest_col_quit.txt")
+        .assert()
+        .success();
+}
+
 // Interactive undo: twin cases
 
 #[test]
@@ -806,3 +1026,3959 @@ fn test_symlink_removes_link_not_target() {
     assert!(!link.exists()); // Link should be gone
     assert!(target.exists()); // Target should still exist
 }
+
+#[test]
+#[cfg_attr(target_os = "macos", ignore)]
+fn test_trash_undo_to_temp() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("systest_to_temp.txt");
+    fs::write(&file, "sandboxed").unwrap();
+
+    trache().arg(&file).assert().success();
+
+    let output = trache()
+        .arg("--trash-undo")
+        .arg("full:systest_to_temp.txt")
+        .arg("--to-temp")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Restored item(s) to"))
+        .get_output()
+        .stdout
+        .clone();
+
+    // Original location is left alone
+    assert!(!file.exists());
+
+    let stdout = String::from_utf8(output).unwrap();
+    let sandbox = stdout
+        .lines()
+        .last()
+        .and_then(|l| l.strip_prefix("Restored item(s) to "))
+        .unwrap();
+    let restored = std::path::Path::new(sandbox).join("systest_to_temp.txt");
+    assert_eq!(fs::read_to_string(&restored).unwrap(), "sandboxed");
+    fs::remove_dir_all(sandbox).unwrap();
+}
+
+#[test]
+fn test_to_temp_requires_undo() {
+    trache()
+        .arg("--to-temp")
+        .arg("--trash-list")
+        .assert()
+        .failure();
+}
+
+#[test]
+#[cfg_attr(target_os = "macos", ignore)]
+fn test_trash_clean_dry_run() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("systest_clean.txt");
+    fs::write(&file, "hello").unwrap();
+    let config = tmp.path().join("config.toml");
+    fs::write(&config, "[retention]\nmax_total_size = \"0\"\n").unwrap();
+
+    trache().arg(&file).assert().success();
+
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg("--trash-clean")
+        .arg("--trash-dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "would purge (over max_total_size)",
+        ))
+        .stdout(predicate::str::contains("systest_clean.txt"));
+
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg("--trash-clean")
+        .arg("--trash-dry-run")
+        .arg("--porcelain")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"dry_run\":true"))
+        .stdout(predicate::str::contains("systest_clean.txt"));
+
+    // cleanup
+    trache()
+        .arg("--trash-purge")
+        .arg("full:systest_clean.txt")
+        .assert()
+        .success();
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_clean_keep_per_path_and_rules() {
+    let tmp = TempDir::new().unwrap();
+    let config = tmp.path().join("config.toml");
+    fs::write(
+        &config,
+        "[retention]\n\
+         keep_per_path = 1\n\
+         [[retention.rule]]\n\
+         pattern = \"*.md\"\n\
+         keep_per_path = 0\n",
+    )
+    .unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path())
+            .env("TRACHE_CONFIG", &config)
+            .arg("--sandbox");
+        cmd
+    };
+    sandbox().arg("--trash-list").assert().success();
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/playground", unsafe {
+            libc::getuid()
+        }));
+
+    sandbox().arg("notes.txt").assert().success();
+    fs::write(playground.join("notes.txt"), "again").unwrap();
+    sandbox().args(["notes.txt", "todo.md"]).assert().success();
+    sandbox().arg("drafts/chapter1.txt").assert().success();
+
+    let output = sandbox()
+        .args(["--trash-clean", "--trash-dry-run"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout
+            .matches("would purge (beyond keep_per_path): ")
+            .count(),
+        2,
+        "{stdout}"
+    );
+    assert_eq!(stdout.matches("notes.txt").count(), 1, "{stdout}");
+    assert!(stdout.contains("todo.md"), "{stdout}");
+    assert!(!stdout.contains("chapter1.txt"), "{stdout}");
+}
+
+#[test]
+#[cfg_attr(target_os = "macos", ignore)]
+fn test_trash_clean_requires_rules() {
+    let tmp = TempDir::new().unwrap();
+    let config = tmp.path().join("config.toml");
+    fs::write(&config, "").unwrap();
+
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg("--trash-clean")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no retention rules"));
+}
+
+#[test]
+#[cfg_attr(target_os = "macos", ignore)]
+fn test_audit_log_records_operations() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("systest_audit.txt");
+    fs::write(&file, "hello").unwrap();
+    let log = tmp.path().join("audit.log");
+    let config = tmp.path().join("config.toml");
+    fs::write(
+        &config,
+        format!(
+            "[audit]\nenabled = true\npath = {:?}\n",
+            log.to_string_lossy()
+        ),
+    )
+    .unwrap();
+
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg(&file)
+        .assert()
+        .success();
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg("--trash-purge")
+        .arg("full:systest_audit.txt")
+        .assert()
+        .success();
+
+    let text = fs::read_to_string(&log).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains("\"op\":\"trash\""));
+    assert!(lines[0].contains("systest_audit.txt"));
+    assert!(lines[0].contains("\"outcome\":\"ok\""));
+    assert!(lines[1].contains("\"op\":\"purge\""));
+}
+
+#[test]
+#[cfg_attr(target_os = "macos", ignore)]
+fn test_trash_rename_then_undo() {
+    let tmp = TempDir::new().unwrap();
+    let old_dir = tmp.path().join("old_proj");
+    let new_dir = tmp.path().join("new_proj");
+    fs::create_dir(&old_dir).unwrap();
+    let file = old_dir.join("systest_rename.txt");
+    fs::write(&file, "moved").unwrap();
+
+    trache().arg(&file).assert().success();
+    fs::rename(&old_dir, &new_dir).unwrap();
+
+    trache()
+        .arg("--trash-rename")
+        .arg(&old_dir)
+        .arg(&new_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Renamed:"));
+
+    trache()
+        .arg("--trash-undo")
+        .arg("full:systest_rename.txt")
+        .assert()
+        .success();
+
+    let restored = new_dir.join("systest_rename.txt");
+    assert_eq!(fs::read_to_string(&restored).unwrap(), "moved");
+    assert!(!old_dir.exists());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_ignore_mounts_hides_items() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("systest_ignore_mounts.txt");
+    fs::write(&file, "hello").unwrap();
+    let config = tmp.path().join("config.toml");
+    fs::write(&config, "ignore_mounts = [\"/\"]\n").unwrap();
+
+    trache().arg(&file).assert().success();
+
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("systest_ignore_mounts.txt").not());
+
+    // cleanup
+    trache()
+        .arg("--trash-purge")
+        .arg("full:systest_ignore_mounts.txt")
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_log_format_json_events() {
+    let tmp = TempDir::new().unwrap();
+    let kept = tmp.path().join("kept.txt");
+    let gone = tmp.path().join("gone.txt");
+    fs::write(&kept, "a").unwrap();
+    fs::write(&gone, "b").unwrap();
+
+    trache()
+        .arg("--log-format=json")
+        .arg("-i")
+        .arg(&kept)
+        .arg(&gone)
+        .write_stdin("n\ny\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""event":"skipped""#))
+        .stdout(predicate::str::contains(r#""reason":"declined""#))
+        .stdout(predicate::str::contains(r#""event":"trashed""#))
+        .stdout(predicate::str::contains(r#""answer":true"#));
+
+    assert!(kept.exists());
+    assert!(!gone.exists());
+}
+
+#[test]
+fn test_log_format_json_error_event() {
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path().join("subdir");
+    fs::create_dir(&dir).unwrap();
+
+    trache()
+        .arg("--log-format=json")
+        .arg(&dir)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(r#""event":"errored""#))
+        .stdout(predicate::str::contains("Is a directory"));
+}
+
+#[test]
+#[cfg_attr(target_os = "macos", ignore)]
+fn test_trash_stats_shows_policy() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("systest_stats.txt");
+    fs::write(&file, "hello").unwrap();
+    let config = tmp.path().join("config.toml");
+    fs::write(&config, "[retention]\nmax_age = \"30d\"\n").unwrap();
+
+    trache().arg(&file).assert().success();
+
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg("--trash-stats")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("retention: max_age=30d"))
+        .stdout(predicate::str::contains("Total:"));
+
+    // cleanup
+    trache()
+        .arg("--trash-purge")
+        .arg("full:systest_stats.txt")
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_shred_deletes_without_trashing() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("systest_shred.txt");
+    fs::write(&file, "secret").unwrap();
+
+    trache()
+        .arg("--shred=1")
+        .arg("-v")
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("shredded"));
+
+    assert!(!file.exists());
+}
+
+#[test]
+fn test_shred_prompts_and_requires_recursive() {
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path().join("subdir");
+    fs::create_dir(&dir).unwrap();
+    fs::write(dir.join("a.txt"), "a").unwrap();
+
+    trache().arg("--shred").arg(&dir).assert().failure();
+    assert!(dir.exists());
+
+    trache()
+        .arg("--shred")
+        .arg("-ri")
+        .arg(&dir)
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("shred directory"));
+    assert!(dir.exists());
+
+    trache()
+        .arg("--shred")
+        .arg("-r")
+        .arg(&dir)
+        .assert()
+        .success();
+    assert!(!dir.exists());
+}
+
+#[test]
+#[cfg_attr(target_os = "macos", ignore)]
+fn test_rm_fallback_still_trashes_when_possible() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("systest_rm_fallback.txt");
+    fs::write(&file, "hello").unwrap();
+
+    trache()
+        .arg("--rm-fallback=always")
+        .arg(&file)
+        .assert()
+        .success();
+    assert!(!file.exists());
+
+    trache()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("systest_rm_fallback.txt"));
+
+    trache()
+        .arg("--trash-purge")
+        .arg("full:systest_rm_fallback.txt")
+        .assert()
+        .success();
+}
+
+#[test]
+#[cfg(unix)]
+fn test_report_command_receives_events() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("systest_report.txt");
+    fs::write(&file, "hello").unwrap();
+    let out = tmp.path().join("report.jsonl");
+    let config = tmp.path().join("config.toml");
+    fs::write(
+        &config,
+        format!(
+            "[report]\ncommand = [\"sh\", \"-c\", \"cat >> '{}'\"]\n",
+            out.display()
+        ),
+    )
+    .unwrap();
+
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg("--shred=1")
+        .arg(&file)
+        .assert()
+        .success();
+
+    let report = fs::read_to_string(&out).unwrap();
+    assert!(report.contains(r#""op":"shred""#));
+    assert!(report.contains("systest_report.txt"));
+    assert!(report.contains(r#""size":5"#));
+}
+
+#[test]
+fn test_readonly_refuses_trash() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("test.txt");
+    fs::write(&file, "hello").unwrap();
+
+    trache()
+        .env("TRACHE_READONLY", "1")
+        .arg(&file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "refusing to trash files: read-only mode",
+        ));
+    assert!(file.exists());
+
+    trache()
+        .env("TRACHE_READONLY", "1")
+        .arg("--trash-dry-run")
+        .arg(&file)
+        .assert()
+        .success();
+    assert!(file.exists());
+}
+
+#[test]
+#[cfg_attr(target_os = "macos", ignore)]
+fn test_readonly_config_allows_listing() {
+    let tmp = TempDir::new().unwrap();
+    let config = tmp.path().join("config.toml");
+    fs::write(&config, "readonly = true\n").unwrap();
+
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg("--trash-list")
+        .assert()
+        .success();
+
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg("--trash-purge")
+        .arg("systest_readonly_*")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("readonly = true in config"));
+}
+
+/// Uses /dev/shm as a stand-in for removable media. When it isn't a
+/// separate mount from $HOME it says so on stderr and passes without
+/// checking anything.
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_on_other_mount_uses_topdir_trash() {
+    use std::os::unix::fs::MetadataExt;
+
+    let skip = |why: &str| eprintln!("skipping test_trash_on_other_mount_uses_topdir_trash: {why}");
+    let shm = std::path::Path::new("/dev/shm");
+    let home = std::env::var_os("HOME").map(std::path::PathBuf::from);
+    let (Ok(shm_meta), Some(Ok(home_meta))) = (shm.metadata(), home.map(|h| h.metadata())) else {
+        return skip("needs /dev/shm and $HOME");
+    };
+    if shm_meta.dev() == home_meta.dev() {
+        return skip("/dev/shm is on the same file system as $HOME");
+    }
+
+    let tmp = TempDir::new_in(shm).unwrap();
+    let file = tmp.path().join("systest_topdir.txt");
+    fs::write(&file, "hello").unwrap();
+
+    system_trache().arg(&file).assert().success();
+
+    let uid = unsafe { libc::getuid() };
+    let info = shm
+        .join(format!(".Trash-{uid}"))
+        .join("info")
+        .join("systest_topdir.txt.trashinfo");
+    let text = fs::read_to_string(&info).unwrap();
+    let relative = file.strip_prefix(shm).unwrap();
+    assert!(text.contains(&format!("Path={}\n", relative.display())));
+
+    system_trache()
+        .arg("--trash-undo")
+        .arg(format!("path:{}", file.display()))
+        .assert()
+        .success();
+    assert!(file.exists());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_tutorial_runs_in_sandbox() {
+    let tmp = TempDir::new().unwrap();
+
+    trache()
+        .env("TMPDIR", tmp.path())
+        .arg("--tutorial")
+        .write_stdin("\n\n\n\n\n\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Restored item(s)."))
+        .stdout(predicate::str::contains("Permanently deleted item(s)."));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_sandbox_refuses_outside_paths() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("test.txt");
+    fs::write(&file, "hello").unwrap();
+
+    trache()
+        .env("TMPDIR", tmp.path())
+        .arg("--sandbox")
+        .arg(&file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is outside the sandbox"));
+    assert!(file.exists());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_undo_keeps_execute_bit_and_mtime() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, SystemTime};
+
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("systest_attrs.sh");
+    fs::write(&file, "#!/bin/sh\n").unwrap();
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o750)).unwrap();
+    let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+    fs::File::open(&file).unwrap().set_modified(mtime).unwrap();
+
+    trache().arg(&file).assert().success();
+    trache()
+        .arg("--trash-undo")
+        .arg("full:systest_attrs.sh")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("could not preserve").not());
+
+    let meta = file.metadata().unwrap();
+    assert_eq!(meta.permissions().mode() & 0o777, 0o750);
+    assert_eq!(meta.modified().unwrap(), mtime);
+}
+
+#[test]
+#[cfg_attr(target_os = "macos", ignore)]
+fn test_trash_dirs_and_dir_pattern() {
+    let tmp = TempDir::new().unwrap();
+    let a = tmp.path().join("systest_dirs_a.txt");
+    let b = tmp.path().join("systest_dirs_b.txt");
+    fs::write(&a, "a").unwrap();
+    fs::write(&b, "b").unwrap();
+    let dir = tmp.path().canonicalize().unwrap();
+
+    trache().arg(&a).arg(&b).assert().success();
+
+    trache()
+        .arg("--trash-dirs")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("2\t{}\n", dir.display())));
+
+    trache()
+        .arg("--trash-undo")
+        .arg(format!("string:dir:full:{}", dir.display()))
+        .assert()
+        .success();
+    assert!(a.exists());
+    assert!(b.exists());
+}
+
+#[test]
+#[cfg_attr(target_os = "macos", ignore)]
+fn test_duplicates_keep_newest() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("systest_dupe.bin");
+    let other = tmp.path().join("systest_dupe_other.bin");
+    // Unique contents, so copies left by other runs don't join the group
+    let contents = format!("artifact built in {}", tmp.path().display());
+    let scope = format!("string:path:{}", tmp.path().display());
+
+    for _ in 0..3 {
+        fs::write(&file, &contents).unwrap();
+        trache().arg(&file).assert().success();
+    }
+    fs::write(&other, "different").unwrap();
+    trache().arg(&other).assert().success();
+
+    trache()
+        .arg("--trash-list")
+        .arg("--duplicates")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("3 identical copies"));
+
+    trache()
+        .arg("--trash-purge")
+        .arg(&scope)
+        .arg("--duplicates-keep-newest")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Purging").count(2));
+
+    trache()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(file.display().to_string()).count(1))
+        .stdout(predicate::str::contains(other.display().to_string()));
+
+    trache().arg("--trash-purge").arg(&scope).assert().success();
+}
+
+#[test]
+#[cfg_attr(target_os = "macos", ignore)]
+fn test_trash_undo_batch_conflict_policy() {
+    let tmp = TempDir::new().unwrap();
+    let names = [
+        "systest_batch_a.txt",
+        "systest_batch_b.txt",
+        "systest_batch_c.txt",
+    ];
+    for name in names {
+        let file = tmp.path().join(name);
+        fs::write(&file, "original").unwrap();
+        trache().arg(&file).assert().success();
+        fs::write(&file, "blocker").unwrap();
+    }
+
+    trache()
+        .arg("-i")
+        .arg("--trash-undo")
+        .arg(format!("string:path:{}", tmp.path().display()))
+        .write_stdin("k\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("3 conflicts detected"))
+        .stdout(predicate::str::contains("Restored as").count(3));
+
+    for name in names {
+        assert_eq!(
+            fs::read_to_string(tmp.path().join(name)).unwrap(),
+            "blocker"
+        );
+        let stem = name.trim_end_matches(".txt");
+        let kept = tmp.path().join(format!("{stem}-untrash_1.txt"));
+        assert_eq!(fs::read_to_string(kept).unwrap(), "original");
+    }
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_undo_restores_symlinks_as_links() {
+    use std::os::unix::fs::symlink;
+
+    let tmp = TempDir::new().unwrap();
+    let target_file = tmp.path().join("target.txt");
+    let target_dir = tmp.path().join("target_dir");
+    fs::write(&target_file, "hello").unwrap();
+    fs::create_dir(&target_dir).unwrap();
+    fs::write(target_dir.join("inner.txt"), "inner").unwrap();
+
+    let links = [
+        ("systest_link_file", tmp.path().join("target.txt")),
+        ("systest_link_rel", std::path::PathBuf::from("target.txt")),
+        ("systest_link_dir", tmp.path().join("target_dir")),
+        ("systest_link_dangling", tmp.path().join("missing")),
+    ];
+    for (name, target) in &links {
+        let link = tmp.path().join(name);
+        symlink(target, &link).unwrap();
+        trache().arg(&link).assert().success();
+        assert!(link.symlink_metadata().is_err());
+    }
+    // Trashing the links must leave their targets alone
+    assert!(target_file.exists());
+    assert!(target_dir.join("inner.txt").exists());
+
+    trache()
+        .arg("--trash-undo")
+        .arg(format!("string:dir:full:{}", tmp.path().display()))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("warning").not());
+
+    for (name, target) in &links {
+        let link = tmp.path().join(name);
+        assert!(link.symlink_metadata().unwrap().is_symlink(), "{name}");
+        assert_eq!(&fs::read_link(&link).unwrap(), target, "{name}");
+    }
+}
+
+#[test]
+#[cfg_attr(target_os = "macos", ignore)]
+fn test_trash_cat_and_head() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("systest_cat.txt");
+    fs::write(&file, "line 1\nline 2\nline 3\n").unwrap();
+    let pattern = format!("string:path:full:{}", file.display());
+
+    trache().arg(&file).assert().success();
+
+    trache()
+        .arg("--trash-cat")
+        .arg(&pattern)
+        .assert()
+        .success()
+        .stdout("line 1\nline 2\nline 3\n");
+
+    trache()
+        .arg("--trash-cat")
+        .arg(&pattern)
+        .arg("--trash-head")
+        .arg("2")
+        .assert()
+        .success()
+        .stdout("line 1\nline 2\n");
+
+    // Still in the trash
+    trache()
+        .arg("--trash-purge")
+        .arg(&pattern)
+        .assert()
+        .success();
+}
+
+#[test]
+#[cfg_attr(target_os = "macos", ignore)]
+fn test_restore_as() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("systest_restore_as.txt");
+    fs::write(&file, "old copy").unwrap();
+    trache().arg(&file).assert().success();
+    fs::write(&file, "new copy").unwrap();
+
+    trache()
+        .arg("--trash-undo")
+        .arg(format!("string:path:{}", file.display()))
+        .arg("--restore-as")
+        .arg("systest_restore_as.old.txt")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("as:"));
+
+    // The replacement is untouched and the old copy sits next to it
+    assert_eq!(fs::read_to_string(&file).unwrap(), "new copy");
+    let restored = tmp.path().join("systest_restore_as.old.txt");
+    assert_eq!(fs::read_to_string(&restored).unwrap(), "old copy");
+}
+
+#[test]
+#[cfg_attr(target_os = "macos", ignore)]
+fn test_restore_as_needs_single_match() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("systest_restore_as_multi.txt");
+    for content in ["one", "two"] {
+        fs::write(&file, content).unwrap();
+        trache().arg(&file).assert().success();
+    }
+    let pattern = format!("string:path:{}", file.display());
+
+    trache()
+        .arg("--trash-undo")
+        .arg(&pattern)
+        .arg("--restore-as")
+        .arg("other.txt")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("exactly one matching item"));
+    assert!(!tmp.path().join("other.txt").exists());
+
+    trache()
+        .arg("--trash-undo")
+        .arg(&pattern)
+        .arg("--restore-as")
+        .arg("sub/other.txt")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("plain file name"));
+
+    trache()
+        .arg("--trash-purge")
+        .arg(&pattern)
+        .assert()
+        .success();
+}
+
+#[test]
+#[cfg_attr(target_os = "macos", ignore)]
+fn test_trash_undo_inner() {
+    let tmp = TempDir::new().unwrap();
+    let project = tmp.path().join("systest_inner_project");
+    fs::create_dir_all(project.join("src/nested")).unwrap();
+    fs::write(project.join("Cargo.toml"), "[package]").unwrap();
+    fs::write(project.join("src/main.rs"), "fn main() {}").unwrap();
+    fs::write(project.join("src/nested/lib.rs"), "// lib").unwrap();
+    fs::write(project.join("src/notes.txt"), "notes").unwrap();
+    trache().arg("-r").arg(&project).assert().success();
+    let pattern = format!("string:path:full:{}", project.display());
+
+    trache()
+        .arg("--trash-undo")
+        .arg(&pattern)
+        .arg("--inner")
+        .arg("*.rs")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Extracted:"));
+
+    assert!(project.join("src/main.rs").exists());
+    assert!(project.join("src/nested/lib.rs").exists());
+    assert!(!project.join("src/notes.txt").exists());
+    assert!(!project.join("Cargo.toml").exists());
+
+    // The rest of the directory is still in the trash
+    trache()
+        .arg("--trash-undo")
+        .arg(&pattern)
+        .arg("--inner")
+        .arg("src/notes.txt")
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(project.join("src/notes.txt")).unwrap(),
+        "notes"
+    );
+
+    trache()
+        .arg("--trash-purge")
+        .arg(&pattern)
+        .assert()
+        .success();
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_purge_by_index() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox().arg("notes.txt").arg("todo.md").assert().success();
+    sandbox()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"(?m)^1 .* notes\.txt ").unwrap())
+        .stdout(predicate::str::is_match(r"(?m)^2 .* todo\.md ").unwrap());
+
+    sandbox()
+        .arg("--trash-purge-n")
+        .arg("2")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("todo.md"));
+
+    sandbox()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes.txt"))
+        .stdout(predicate::str::contains("todo.md").not());
+
+    sandbox()
+        .arg("--trash-purge-n")
+        .arg("2-3")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("out of range"));
+}
+
+/// Points a /dev/shm item at a path on $HOME's file system, so restoring it
+/// in place fails with EXDEV. When /dev/shm isn't a separate mount it says
+/// so on stderr and passes without checking anything.
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_undo_rescue_on_cross_device() {
+    use std::os::unix::fs::MetadataExt;
+
+    let skip = |why: &str| eprintln!("skipping test_trash_undo_rescue_on_cross_device: {why}");
+    let shm = std::path::Path::new("/dev/shm");
+    let home = std::env::var_os("HOME").map(std::path::PathBuf::from);
+    let (Ok(shm_meta), Some(Ok(home_meta))) = (shm.metadata(), home.map(|h| h.metadata())) else {
+        return skip("needs /dev/shm and $HOME");
+    };
+    if shm_meta.dev() == home_meta.dev() {
+        return skip("/dev/shm is on the same file system as $HOME");
+    }
+
+    let tmp = TempDir::new_in(shm).unwrap();
+    let elsewhere = TempDir::new_in(std::env::var_os("HOME").unwrap()).unwrap();
+    if elsewhere.path().metadata().unwrap().dev() == shm_meta.dev() {
+        return skip("a directory under $HOME is on /dev/shm's file system");
+    }
+    let file = tmp.path().join("systest_rescue.txt");
+    fs::write(&file, "rescue me").unwrap();
+    system_trache().arg(&file).assert().success();
+
+    let moved = elsewhere.path().join("systest_rescue.txt");
+    system_trache()
+        .arg("--trash-rename")
+        .arg(&file)
+        .arg(&moved)
+        .assert()
+        .success();
+    let pattern = format!("string:path:full:{}", moved.display());
+
+    system_trache()
+        .arg("--trash-undo")
+        .arg(&pattern)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--rescue"));
+    assert!(!moved.exists());
+
+    let rescue = elsewhere.path().join("rescue");
+    system_trache()
+        .arg("--trash-undo")
+        .arg(&pattern)
+        .arg(format!("--rescue={}", rescue.display()))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("rescued it to"));
+    let rescued = rescue.join(moved.strip_prefix("/").unwrap());
+    assert_eq!(fs::read_to_string(rescued).unwrap(), "rescue me");
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_purge_interactive_selection() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox()
+        .arg("drafts/chapter1.txt")
+        .arg("drafts/chapter2.txt")
+        .assert()
+        .success();
+
+    // Matches are numbered by path: chapter1 is 1, chapter2 is 2
+    sandbox()
+        .arg("-i")
+        .arg("--trash-purge")
+        .arg("chapter")
+        .write_stdin("2\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("1: "))
+        .stdout(predicate::str::contains("chapter2.txt"))
+        .stdout(predicate::str::contains("chapter1.txt").not());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_undo_since() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox().arg("notes.txt").arg("todo.md").assert().success();
+
+    sandbox()
+        .arg("--trash-undo-since")
+        .arg("2000-01-01")
+        .arg("--trash-dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes.txt"))
+        .stdout(predicate::str::contains("todo.md"));
+
+    // Nothing was trashed in the future
+    let tomorrow = chrono::Local::now() + chrono::Duration::days(1);
+    sandbox()
+        .arg("--trash-undo-since")
+        .arg(tomorrow.format("%Y-%m-%d %H:%M").to_string())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No items trashed since"));
+
+    sandbox()
+        .arg("--trash-undo-since")
+        .arg("10 minutes ago")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Restored item(s)."));
+
+    sandbox()
+        .arg("--trash-undo-since")
+        .arg("whenever")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid time"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_list_group_by_dir() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox()
+        .args(["notes.txt", "drafts/chapter1.txt", "drafts/chapter2.txt"])
+        .assert()
+        .success();
+    let output = sandbox()
+        .args(["--trash-list", "--group-by", "dir"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let headers: Vec<&str> = stdout.lines().filter(|l| l.starts_with('/')).collect();
+    assert_eq!(headers.len(), 2, "{stdout}");
+    assert!(
+        headers[0].ends_with("/playground (1 item(s), 30 B)"),
+        "{stdout}"
+    );
+    assert!(
+        headers[1].ends_with("/playground/drafts (2 item(s), 50 B)"),
+        "{stdout}"
+    );
+    assert!(stdout.contains(" chapter1.txt\n"), "{stdout}");
+
+    sandbox()
+        .args(["--group-by", "dir"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--trash-list"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_list_tree() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox()
+        .args(["drafts/chapter1.txt", "drafts/chapter2.txt", "notes.txt"])
+        .assert()
+        .success();
+    let output = sandbox().args(["--trash-list", "--tree"]).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 5, "{stdout}");
+    assert!(lines[0].ends_with("/playground"), "{stdout}");
+    assert_eq!(lines[1], "├── drafts/");
+    assert!(
+        lines[2].starts_with("│   ├── chapter1.txt  #1 "),
+        "{stdout}"
+    );
+    assert!(
+        lines[3].starts_with("│   └── chapter2.txt  #2 "),
+        "{stdout}"
+    );
+    assert!(lines[4].starts_with("└── notes.txt  #3 "), "{stdout}");
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_purge_dir() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox()
+        .args(["-r", "drafts", "notes.txt"])
+        .assert()
+        .success();
+    sandbox()
+        .args(["--trash-purge-dir", "drafts", "--trash-dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("would purge: "))
+        .stdout(predicate::str::contains("notes.txt").not());
+    sandbox()
+        .args(["--trash-purge-dir", "drafts"])
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "permanently delete these 1 item(s) trashed from 'drafts'? ",
+        ))
+        .stdout(predicate::str::contains("Nothing purged."));
+    sandbox()
+        .args(["--trash-purge-dir", "drafts"])
+        .write_stdin("y\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Permanently deleted 1 item(s)."));
+    sandbox()
+        .args(["-f", "--trash-purge-dir", "drafts"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Nothing in the trash came from 'drafts'.",
+        ));
+    sandbox()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes.txt"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_restore_puts_directories_back_first() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox().arg("--trash-list").assert().success();
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/playground", unsafe {
+            libc::getuid()
+        }));
+
+    // Two copies of a file, then the directory it was in
+    sandbox().arg("drafts/chapter1.txt").assert().success();
+    fs::write(playground.join("drafts/chapter1.txt"), "again").unwrap();
+    sandbox().arg("drafts/chapter1.txt").assert().success();
+    sandbox().args(["-r", "drafts"]).assert().success();
+
+    sandbox()
+        .args(["-i", "--trash-undo", "*"])
+        .write_stdin("a\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Restored: "))
+        .stdout(predicate::str::contains("chapter1-untrash_2.txt"));
+    assert!(playground.join("drafts/chapter2.txt").exists());
+    assert!(playground.join("drafts/chapter1-untrash_1.txt").exists());
+    assert!(playground.join("drafts/chapter1-untrash_2.txt").exists());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_purge_keep_newest() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox().arg("--trash-list").assert().success();
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/playground", unsafe {
+            libc::getuid()
+        }));
+
+    for i in 0..3 {
+        fs::write(playground.join("notes.txt"), format!("version {i}")).unwrap();
+        sandbox().arg("notes.txt").assert().success();
+    }
+    sandbox().arg("todo.md").assert().success();
+
+    sandbox()
+        .args(["--trash-purge", "*", "--keep", "3"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "No path matching '*' was trashed more than 3 time(s).",
+        ));
+    sandbox()
+        .args(["--trash-purge", "*", "--keep", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Purging (1/2, "))
+        .stdout(predicate::str::contains("Purging (2/2, "))
+        .stdout(predicate::str::contains("todo.md").not());
+    let output = sandbox().arg("--trash-list").output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.matches("notes.txt\n").count(), 1, "{stdout}");
+    assert!(stdout.contains("todo.md"), "{stdout}");
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_undo_label() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox()
+        .args(["--label", "tidy", "notes.txt", "todo.md"])
+        .assert()
+        .success();
+    sandbox().arg("drafts/chapter1.txt").assert().success();
+    sandbox()
+        .args(["--label", "tidy", "--trash-list"])
+        .assert()
+        .failure();
+
+    sandbox()
+        .args(["--trash-undo-label", "tidy"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes.txt"))
+        .stdout(predicate::str::contains("todo.md"))
+        .stdout(predicate::str::contains("chapter1.txt").not());
+
+    sandbox()
+        .args(["--trash-undo-label", "tidy"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "No items trashed with label 'tidy' found in trash.",
+        ));
+    sandbox()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("chapter1.txt"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_dry_run_output_json_plan() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox().arg("--trash-list").assert().success();
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/playground", unsafe {
+            libc::getuid()
+        }));
+    let steps = |args: &[&str]| -> Vec<serde_json::Value> {
+        let output = sandbox().args(args).output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    };
+
+    let plan = steps(&["--trash-dry-run", "--output", "json", "todo.md"]);
+    assert_eq!(plan.len(), 1);
+    assert_eq!(plan[0]["action"], "trash");
+    assert!(plan[0]["source"].as_str().unwrap().ends_with("/todo.md"));
+    assert!(plan[0].get("destination").is_none());
+    assert!(playground.join("todo.md").exists());
+
+    // Two copies of notes.txt in the trash, and a new one in their place
+    sandbox().arg("notes.txt").assert().success();
+    fs::write(playground.join("notes.txt"), "again").unwrap();
+    sandbox().arg("notes.txt").assert().success();
+    fs::write(playground.join("notes.txt"), "third").unwrap();
+
+    let plan = steps(&["--trash-undo", "*", "--trash-dry-run", "--output", "json"]);
+    assert_eq!(plan.len(), 2);
+    for step in &plan {
+        assert_eq!(step["action"], "restore");
+        assert!(
+            step["destination"]
+                .as_str()
+                .unwrap()
+                .ends_with("/playground/notes.txt")
+        );
+        assert_eq!(step["conflicts"], serde_json::json!(["collision", "twin"]));
+    }
+    assert_eq!(
+        fs::read_to_string(playground.join("notes.txt")).unwrap(),
+        "third"
+    );
+
+    let plan = steps(&["--trash-purge", "*", "--trash-dry-run", "--output", "json"]);
+    assert_eq!(plan.len(), 2);
+    assert!(plan.iter().all(|step| step["action"] == "purge"));
+
+    sandbox()
+        .args(["--trash-undo", "*", "--output", "json"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--output"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_diff() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox().arg("--trash-list").assert().success();
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/playground", unsafe {
+            libc::getuid()
+        }));
+
+    sandbox().arg("notes.txt").assert().success();
+    sandbox()
+        .args(["--trash-diff", "notes.txt"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Nothing exists at"));
+
+    fs::copy(
+        playground.join("../data/Trash/files/notes.txt"),
+        playground.join("notes.txt"),
+    )
+    .unwrap();
+    sandbox()
+        .args(["--trash-diff", "notes.txt"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("identical"));
+
+    fs::write(playground.join("notes.txt"), "Water the plants.\n").unwrap();
+    sandbox()
+        .args(["--trash-diff", "notes.txt"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(trashed "))
+        .stdout(predicate::str::contains("@@ -1 +1 @@\n"))
+        .stdout(predicate::str::contains("\n+Water the plants.\n"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_checksum_and_verify() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox().arg("--trash-list").assert().success();
+    let trash = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/data/Trash/files", unsafe {
+            libc::getuid()
+        }));
+
+    sandbox()
+        .args(["--checksum", "notes.txt", "todo.md"])
+        .assert()
+        .success();
+    sandbox().args(["-r", "drafts"]).assert().success();
+    sandbox().args(["--verify", "notes.txt"]).assert().failure();
+
+    let mut todo = fs::OpenOptions::new()
+        .append(true)
+        .open(trash.join("todo.md"))
+        .unwrap();
+    std::io::Write::write_all(&mut todo, b"tampered\n").unwrap();
+
+    sandbox()
+        .args(["--trash-undo", "*", "--verify"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "todo.md' changed while in the trash",
+        ))
+        .stderr(predicate::str::contains("notes.txt' changed").not())
+        .stderr(predicate::str::contains("1 item(s) match their checksums"))
+        .stderr(predicate::str::contains(
+            "1 item(s) have no recorded checksum",
+        ));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_export() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    let archive = tmp.path().join("out.tar");
+
+    sandbox().arg("notes.txt").assert().success();
+    sandbox().args(["-r", "drafts"]).assert().success();
+    sandbox()
+        .args(["--trash-export", "*", "--to"])
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Exported 2 item(s)"));
+
+    let listing = std::process::Command::new("tar")
+        .arg("tf")
+        .arg(&archive)
+        .output()
+        .unwrap();
+    let listing = String::from_utf8(listing.stdout).unwrap();
+    assert!(listing.starts_with("trache-export.json\n"));
+    assert!(listing.contains("items/1/drafts/chapter1.txt\n"));
+    assert!(listing.contains("items/2/notes.txt\n"));
+
+    // Still in the trash, and the archive isn't replaced without -f
+    sandbox()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes.txt"));
+    sandbox()
+        .args(["--trash-export", "*", "--to"])
+        .arg(&archive)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already exists"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_import() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox().arg("--trash-list").assert().success();
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/playground", unsafe {
+            libc::getuid()
+        }));
+    let archive = tmp.path().join("out.tgz");
+
+    sandbox()
+        .args(["-r", "notes.txt", "drafts"])
+        .assert()
+        .success();
+    let listed = sandbox().arg("--trash-list").output().unwrap().stdout;
+    sandbox()
+        .args(["--trash-export", "*", "--to"])
+        .arg(&archive)
+        .assert()
+        .success();
+    sandbox().args(["--trash-empty", "-f"]).assert().success();
+
+    // Back in the trash just as they were
+    sandbox()
+        .arg("--trash-import")
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Imported: "));
+    sandbox()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(listed);
+
+    // Or straight back in place, skipping what's already there
+    sandbox().args(["--trash-empty", "-f"]).assert().success();
+    fs::write(playground.join("notes.txt"), "new").unwrap();
+    sandbox()
+        .arg("--trash-import")
+        .arg(&archive)
+        .arg("--in-place")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Restored: "))
+        .stderr(predicate::str::contains("notes.txt': already exists"));
+    assert!(playground.join("drafts/chapter2.txt").is_file());
+    assert_eq!(
+        fs::read_to_string(playground.join("notes.txt")).unwrap(),
+        "new"
+    );
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_dir() {
+    let tmp = TempDir::new().unwrap();
+    let trash = tmp.path().join("trash");
+    let file = tmp.path().join("a b.txt");
+    fs::write(&file, "hello").unwrap();
+    let in_dir = || {
+        let mut cmd = trache();
+        cmd.arg("--trash-dir").arg(&trash);
+        cmd
+    };
+
+    in_dir().arg(&file).assert().success();
+    assert!(!file.exists());
+    assert!(trash.join("files/a b.txt").is_file());
+    assert!(trash.join("info/a b.txt.trashinfo").is_file());
+    in_dir()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("a b.txt"));
+
+    in_dir()
+        .args(["--trash-undo", "a b.txt"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&file).unwrap(), "hello");
+
+    in_dir().arg(&file).assert().success();
+    in_dir()
+        .args(["--trash-purge", "a b.txt"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_dir(trash.join("files")).unwrap().count(), 0);
+    assert_eq!(fs::read_dir(trash.join("info")).unwrap().count(), 0);
+}
+
+#[test]
+fn test_mock_backend() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("kept.txt");
+    fs::write(&file, "x").unwrap();
+
+    // Only books are kept, so nothing real is trashed
+    trache()
+        .env("TRACHE_BACKEND", "mock")
+        .arg("-v")
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("trashed"));
+    assert!(file.exists());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_daemon_needs_a_session_bus() {
+    trache()
+        .env("DBUS_SESSION_BUS_ADDRESS", "unix:path=/nonexistent/bus")
+        .arg("--daemon")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot serve on the session bus"));
+    trache()
+        .args(["--daemon", "--trash-list"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_serve_stdio() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox().arg("--trash-list").assert().success();
+    let notes = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/playground/notes.txt", unsafe {
+            libc::getuid()
+        }));
+    let notes = notes.to_str().unwrap();
+
+    let requests = format!(
+        "{{\"jsonrpc\": \"2.0\", \"id\": 1, \"method\": \"trash\", \"params\": {{\"paths\": [\"{notes}\"]}}}}\n\
+         {{\"jsonrpc\": \"2.0\", \"id\": 2, \"method\": \"list\"}}\n"
+    );
+    let output = sandbox()
+        .args(["--serve-stdio", "-v"])
+        .write_stdin(requests)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    // -v's "trashed" message goes to stderr, out of the way of responses
+    assert!(String::from_utf8_lossy(&output.stderr).contains("trashed"));
+    let replies: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(replies.len(), 2);
+    assert_eq!(replies[0]["result"]["failed"], serde_json::json!({}));
+    let item = &replies[1]["result"]["items"][0];
+    assert_eq!(item["path"], notes);
+    assert!(!std::path::Path::new(notes).exists());
+
+    fs::write(notes, "new").unwrap();
+    let request = format!(
+        "{{\"jsonrpc\": \"2.0\", \"id\": 3, \"method\": \"restore\", \"params\": {{\"ids\": [{}]}}}}\n",
+        item["id"]
+    );
+    let output = sandbox()
+        .arg("--serve-stdio")
+        .write_stdin(request)
+        .output()
+        .unwrap();
+    let reply: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let restored = notes.replace("notes.txt", "notes-untrash_1.txt");
+    assert_eq!(
+        reply["result"]["restored"][item["id"].as_str().unwrap()],
+        restored
+    );
+    assert_eq!(fs::read_to_string(notes).unwrap(), "new");
+
+    // Paths outside the sandbox, and read-only mode, fail call by call
+    let outside = tmp.path().join("outside.txt");
+    fs::write(&outside, "").unwrap();
+    let todo = notes.replace("notes.txt", "todo.md");
+    let request = format!(
+        "{{\"jsonrpc\": \"2.0\", \"id\": 4, \"method\": \"trash\", \"params\": {{\"paths\": [\"{}\"]}}}}\n",
+        outside.display()
+    );
+    let output = sandbox()
+        .arg("--serve-stdio")
+        .write_stdin(request)
+        .output()
+        .unwrap();
+    let reply: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let failed = reply["result"]["failed"][outside.to_str().unwrap()]
+        .as_str()
+        .unwrap();
+    assert!(failed.contains("is outside the sandbox"));
+    assert!(outside.exists());
+
+    let request = format!(
+        "{{\"jsonrpc\": \"2.0\", \"id\": 5, \"method\": \"trash\", \"params\": {{\"paths\": [\"{todo}\"]}}}}\n"
+    );
+    let output = sandbox()
+        .arg("--serve-stdio")
+        .env("TRACHE_READONLY", "1")
+        .write_stdin(request)
+        .output()
+        .unwrap();
+    let reply: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        reply["result"]["failed"][&todo],
+        "read-only mode (TRACHE_READONLY is set)"
+    );
+    assert!(std::path::Path::new(&todo).exists());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_check_and_repair() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox().arg("notes.txt").assert().success();
+    sandbox()
+        .arg("--trash-check")
+        .assert()
+        .success()
+        .stdout("No problems found.\n");
+
+    let trash = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/data/Trash", unsafe {
+            libc::getuid()
+        }));
+    fs::write(trash.join("files/stray"), "").unwrap();
+    fs::write(trash.join("info/gone.trashinfo"), "[Trash Info]\n").unwrap();
+    fs::write(
+        trash.join("info/notes.txt.trashinfo"),
+        "[Trash Info]\nPath=/somewhere/notes.txt\nnonsense\n",
+    )
+    .unwrap();
+
+    sandbox()
+        .arg("--trash-check")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "stray': contents with no .trashinfo record",
+        ))
+        .stdout(predicate::str::contains(
+            "gone.trashinfo': record with no contents",
+        ))
+        .stdout(predicate::str::contains(
+            "malformed record: stray line 'nonsense'",
+        ))
+        .stderr(predicate::str::contains("3 problem(s) found"));
+    sandbox()
+        .args(["--trash-check", "--repair", "--trash-dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("would remove the record"));
+    assert!(trash.join("info/gone.trashinfo").exists());
+
+    sandbox()
+        .args(["--trash-check", "--repair"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("rewrite the record: done"));
+    sandbox()
+        .arg("--trash-check")
+        .assert()
+        .success()
+        .stdout("No problems found.\n");
+    sandbox()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("stray"))
+        .stdout(predicate::str::contains("/somewhere/notes.txt"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_parallel_mv_journal_ids_stay_unique() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox().arg("--trash-list").assert().success();
+    let root = tmp
+        .path()
+        .join(format!("trache-sandbox-{}", unsafe { libc::getuid() }));
+    for i in 0..8 {
+        fs::write(root.join(format!("playground/f{i}")), "").unwrap();
+    }
+
+    std::thread::scope(|s| {
+        for i in 0..8 {
+            let mut cmd = sandbox();
+            cmd.args(["--mv", &format!("f{i}"), &format!("g{i}")]);
+            s.spawn(move || cmd.assert().success());
+        }
+    });
+    let journal = fs::read_to_string(root.join("state/trache/moves.jsonl")).unwrap();
+    let mut ids: Vec<u64> = journal
+        .lines()
+        .map(|line| {
+            serde_json::from_str::<serde_json::Value>(line).unwrap()["id"]
+                .as_u64()
+                .unwrap()
+        })
+        .collect();
+    ids.sort();
+    assert_eq!(ids, (1..=8).collect::<Vec<_>>());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_listing_keeps_an_index() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox().arg("notes.txt").assert().success();
+    sandbox()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes.txt"));
+    let root = tmp
+        .path()
+        .join(format!("trache-sandbox-{}", unsafe { libc::getuid() }));
+    let index = fs::read_to_string(root.join("state/trache/index.json")).unwrap();
+    assert!(index.contains("notes.txt.trashinfo"));
+
+    // What changes behind its back still shows
+    fs::write(
+        root.join("data/Trash/info/notes.txt.trashinfo"),
+        format!(
+            "[Trash Info]\nPath={}/renamed.txt\nDeletionDate=2024-01-15T10:30:00\n",
+            root.join("playground").display()
+        ),
+    )
+    .unwrap();
+    sandbox()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("renamed.txt"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_purge_more_than_one_chunk() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox().arg("--trash-list").assert().success();
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/playground", unsafe {
+            libc::getuid()
+        }));
+    let names: Vec<String> = (0..1200).map(|i| format!("f{i:04}")).collect();
+    for name in &names {
+        fs::write(playground.join(name), "").unwrap();
+    }
+    sandbox().args(&names).assert().success();
+    let listed = sandbox().arg("--trash-list").output().unwrap().stdout;
+    assert_eq!(String::from_utf8(listed).unwrap().lines().count(), 1200);
+
+    sandbox()
+        .args(["--trash-purge", "f*"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Permanently deleted item(s)."));
+    sandbox()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout("Trash is empty.\n");
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_empty_carries_on_past_a_bad_item() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox().args(["notes.txt", "todo.md"]).assert().success();
+    // A record whose contents are gone can't be purged by the trash crate
+    let root = tmp
+        .path()
+        .join(format!("trache-sandbox-{}", unsafe { libc::getuid() }));
+    fs::write(
+        root.join("data/Trash/info/ghost.trashinfo"),
+        "[Trash Info]\nPath=/nowhere/ghost\nDeletionDate=2024-01-15T10:30:00\n",
+    )
+    .unwrap();
+
+    sandbox()
+        .args(["--trash-empty", "-f"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot purge '/nowhere/ghost'"))
+        .stderr(predicate::str::contains(
+            "1 of 3 item(s) could not be purged; 2 were",
+        ));
+    let listed = sandbox().arg("--trash-list").output().unwrap().stdout;
+    let listed = String::from_utf8(listed).unwrap();
+    assert_eq!(listed.lines().count(), 1);
+    assert!(listed.contains("/nowhere/ghost"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_completes_patterns_from_the_trash() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp
+        .path()
+        .join(format!("trache-sandbox-{}", unsafe { libc::getuid() }));
+    trache()
+        .env("TMPDIR", tmp.path())
+        .args(["--sandbox", "notes.txt", "todo.md"])
+        .assert()
+        .success();
+    let complete = |line: &[&str]| {
+        let output = trache()
+            .env("XDG_DATA_HOME", root.join("data"))
+            .env("TRACHE_COMPLETE", "fish")
+            .arg("--")
+            .args(line)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let notes = root.join("playground/notes.txt");
+    assert_eq!(
+        complete(&["trache", "--trash-undo", "no"]),
+        format!("notes.txt\t{}\n", notes.display())
+    );
+    assert_eq!(complete(&["trache", "purge", "icase:path:/nowhere"]), "");
+    assert!(
+        complete(&["trache", "restore", "path:"])
+            .lines()
+            .any(|line| line == format!("path:{}", notes.display()))
+    );
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_filter() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox().arg("--trash-list").assert().success();
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/playground", unsafe {
+            libc::getuid()
+        }));
+    fs::write(playground.join("big.log"), vec![0u8; 4096]).unwrap();
+    sandbox()
+        .args(["-r", "notes.txt", "todo.md", "drafts", "big.log"])
+        .current_dir(&playground)
+        .assert()
+        .success();
+
+    sandbox()
+        .args(["--trash-list", "--filter", "type == dir || size >= 4k"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("drafts"))
+        .stdout(predicate::str::contains("big.log"))
+        .stdout(predicate::str::contains("notes.txt").not());
+    sandbox()
+        .args(["--trash-list=*.txt", "--filter", "age < 1d"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes.txt"))
+        .stdout(predicate::str::contains("todo.md").not());
+
+    sandbox()
+        .args(["--trash-purge", "--filter", r#"name ~ "*.log" && age < 1h"#])
+        .assert()
+        .success();
+    sandbox()
+        .args(["--trash-undo", "--filter", "name != drafts && type == file"])
+        .assert()
+        .success();
+    assert!(playground.join("notes.txt").exists());
+    assert!(playground.join("todo.md").exists());
+    assert!(!playground.join("big.log").exists());
+    assert!(!playground.join("drafts").exists());
+
+    sandbox()
+        .args(["--trash-list", "--filter", "colour == red"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--filter: unknown field 'colour'"));
+    sandbox()
+        .args(["--trash-stats", "--filter", "size > 1M"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--filter only works with"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_batch() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox().arg("--trash-list").assert().success();
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/playground", unsafe {
+            libc::getuid()
+        }));
+    fs::write(playground.join("with space.txt"), "").unwrap();
+    let commands = format!(
+        "# tidy up\n\
+         trash {p}/notes.txt\n\
+         trash {p}/todo.md\n\
+         trash {p}/with space.txt\n\
+         \n\
+         undo todo.md\n\
+         purge notes*\n\
+         frobnicate\n",
+        p = playground.display()
+    );
+    sandbox()
+        .arg("--batch")
+        .write_stdin(commands)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Restored item(s)."))
+        .stdout(predicate::str::contains("Permanently deleted item(s)."))
+        .stderr(predicate::str::contains(
+            "line 8: unknown command 'frobnicate'",
+        ))
+        .stderr(predicate::str::contains("1 of 6 batch command(s) failed"));
+    assert!(playground.join("todo.md").exists());
+    assert!(!playground.join("notes.txt").exists());
+    sandbox()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("with space.txt"))
+        .stdout(predicate::str::contains("notes.txt").not());
+
+    sandbox()
+        .arg("--batch")
+        .write_stdin("undo with*\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Ran 1 batch command(s)."));
+
+    // Its paths stay inside the sandbox, and read-only mode holds
+    let outside = tmp.path().join("outside.txt");
+    fs::write(&outside, "").unwrap();
+    sandbox()
+        .arg("--batch")
+        .write_stdin(format!("trash {}\n", outside.display()))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is outside the sandbox"));
+    assert!(outside.exists());
+    sandbox()
+        .arg("--batch")
+        .env("TRACHE_READONLY", "1")
+        .write_stdin(format!("trash {}/todo.md\n", playground.display()))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("read-only mode"));
+    assert!(playground.join("todo.md").exists());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_id_pattern_picks_one_of_twins() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    // Two items with the same name and original path
+    sandbox().arg("notes.txt").assert().success();
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/playground", unsafe {
+            libc::getuid()
+        }));
+    fs::write(playground.join("notes.txt"), "second").unwrap();
+    sandbox().arg("notes.txt").assert().success();
+
+    let listing = sandbox().arg("--trash-list").output().unwrap();
+    let listing = String::from_utf8(listing.stdout).unwrap();
+    let ids: Vec<&str> = listing
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .collect();
+    assert_eq!(ids.len(), 2);
+    assert_ne!(ids[0], ids[1]);
+
+    let dry_run = sandbox()
+        .arg("--trash-undo")
+        .arg(format!("id:{}", ids[1]))
+        .arg("--trash-dry-run")
+        .output()
+        .unwrap();
+    let dry_run = String::from_utf8(dry_run.stdout).unwrap();
+    assert_eq!(dry_run.matches("would restore").count(), 1);
+
+    sandbox()
+        .arg("--trash-undo")
+        .arg(format!("id:{}", ids[1]))
+        .assert()
+        .success();
+    assert!(playground.join("notes.txt").exists());
+
+    // The other one is still there under the same ID
+    sandbox()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(ids[0]))
+        .stdout(predicate::str::contains(ids[1]).not());
+}
+
+#[test]
+#[cfg_attr(target_os = "macos", ignore)]
+fn test_max_delete_refuses_before_touching_anything() {
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path().join("systest_max_delete");
+    fs::create_dir(&dir).unwrap();
+    for i in 0..5 {
+        fs::write(dir.join(format!("f{i}")), "x").unwrap();
+    }
+    let single = tmp.path().join("systest_max_delete_single.txt");
+    fs::write(&single, "x").unwrap();
+
+    // 1 file + the directory and its 5 files
+    trache()
+        .arg("-r")
+        .arg("--max-delete")
+        .arg("6")
+        .arg(&single)
+        .arg(&dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--max-delete"));
+    assert!(single.exists());
+    assert!(dir.join("f4").exists());
+
+    let config = tmp.path().join("config.toml");
+    fs::write(&config, "max_delete = 1\n").unwrap();
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg("-r")
+        .arg(&dir)
+        .assert()
+        .failure();
+
+    // 0 lifts the configured limit
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg("-r")
+        .arg("--max-delete")
+        .arg("0")
+        .arg(&single)
+        .arg(&dir)
+        .assert()
+        .success();
+    assert!(!dir.exists());
+
+    trache()
+        .arg("--trash-purge")
+        .arg(format!("string:path:{}", tmp.path().display()))
+        .assert()
+        .success();
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_info() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox()
+        .arg("-r")
+        .arg("notes.txt")
+        .arg("drafts")
+        .assert()
+        .success();
+
+    sandbox()
+        .arg("--trash-info")
+        .arg("full:notes.txt")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Original:  "))
+        .stdout(predicate::str::contains("Size:      30 B (30 bytes)"))
+        .stdout(predicate::str::contains("Type:      file"))
+        .stdout(predicate::str::contains("Mode:      rw"))
+        .stdout(predicate::str::contains("  [Trash Info]"))
+        .stdout(predicate::str::contains("DeletionDate="));
+
+    sandbox()
+        .arg("--trash-info")
+        .arg("drafts")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Type:      directory, 2 entries"));
+
+    sandbox()
+        .arg("--trash-info")
+        .arg("nothing-like-this")
+        .assert()
+        .failure();
+}
+
+#[test]
+#[cfg_attr(target_os = "macos", ignore)]
+fn test_sync_with_manifest() {
+    let tmp = TempDir::new().unwrap();
+    let site = tmp.path().join("systest_sync_site");
+    fs::create_dir_all(site.join("assets")).unwrap();
+    fs::create_dir_all(site.join("stale")).unwrap();
+    fs::write(site.join("index.html"), "").unwrap();
+    fs::write(site.join("assets/app.js"), "").unwrap();
+    fs::write(site.join("assets/old.js"), "").unwrap();
+    fs::write(site.join("stale/page.html"), "").unwrap();
+    let manifest = tmp.path().join("manifest");
+    fs::write(&manifest, "index.html\0assets/app.js\0").unwrap();
+
+    trache()
+        .arg("--sync-with")
+        .arg(&manifest)
+        .arg(&site)
+        .assert()
+        .success();
+
+    assert!(site.join("index.html").exists());
+    assert!(site.join("assets/app.js").exists());
+    assert!(!site.join("assets/old.js").exists());
+    assert!(!site.join("stale").exists());
+
+    trache()
+        .arg("--sync-with")
+        .arg(&manifest)
+        .arg(&site)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Nothing to trash"));
+
+    trache()
+        .arg("--trash-purge")
+        .arg(format!("string:path:{}", site.display()))
+        .assert()
+        .success();
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_undo_dated_dir() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/playground", unsafe {
+            libc::getuid()
+        }));
+
+    sandbox().arg("notes.txt").assert().success();
+    fs::write(playground.join("notes.txt"), "replacement").unwrap();
+
+    sandbox()
+        .arg("--trash-undo")
+        .arg("full:notes.txt")
+        .arg("--dated-dir")
+        .assert()
+        .success();
+
+    let dated = playground.join(format!(
+        "restored-{}",
+        chrono::Local::now().format("%Y%m%d")
+    ));
+    assert_eq!(
+        fs::read_to_string(playground.join("notes.txt")).unwrap(),
+        "replacement"
+    );
+    assert!(dated.join("notes.txt").exists());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_color_list() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox().arg("-r").arg("drafts").assert().success();
+
+    sandbox()
+        .env("LS_COLORS", "di=01;35")
+        .arg("--color=always")
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\x1b[01;35mdrafts\x1b[0m"));
+
+    // Not a terminal, so auto means no color
+    sandbox()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\x1b[").not());
+
+    sandbox()
+        .arg("--color=always")
+        .arg("-v")
+        .arg("todo.md")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("trashed '"))
+        .stdout(predicate::str::contains("\x1b[").not());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_list_format() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox().arg("notes.txt").assert().success();
+
+    sandbox()
+        .arg("--trash-list")
+        .arg("--format")
+        .arg("{n}|{name}|{size_bytes}|{{x}}")
+        .assert()
+        .success()
+        .stdout("1|notes.txt|30|{x}\n");
+
+    // The help's example, as a shell passes it
+    sandbox()
+        .args(["--trash-list", "--format", r"{name}\t{size}"])
+        .assert()
+        .success()
+        .stdout("notes.txt\t30 B\n");
+
+    sandbox()
+        .arg("--trash-list")
+        .arg("--format")
+        .arg("{name} {sise}")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown placeholder {sise}"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_mv_and_undo_op() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}", unsafe { libc::getuid() }))
+        .join("playground");
+
+    sandbox().arg("--trash-list").assert().success();
+    sandbox()
+        .args(["--mv", "notes.txt", "drafts"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("--trash-undo-op=1"));
+    assert!(!playground.join("notes.txt").exists());
+    assert!(playground.join("drafts/notes.txt").exists());
+
+    // Never replaces an existing file
+    sandbox()
+        .args(["--mv", "todo.md", "drafts/chapter1.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already exists"));
+
+    sandbox()
+        .args(["--mv", "todo.md", "todo-later.md"])
+        .assert()
+        .success();
+
+    // Undo a specific move, then the latest remaining one
+    sandbox().arg("--trash-undo-op=1").assert().success();
+    assert!(playground.join("notes.txt").exists());
+    assert!(!playground.join("drafts/notes.txt").exists());
+
+    sandbox().arg("--trash-undo-op").assert().success();
+    assert!(playground.join("todo.md").exists());
+    assert!(!playground.join("todo-later.md").exists());
+
+    sandbox()
+        .arg("--trash-undo-op")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no recorded moves"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_list_output_csv_tsv() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox().arg("--trash-list").assert().success();
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}", unsafe { libc::getuid() }))
+        .join("playground");
+    fs::write(playground.join("a,\"b\"\tc.txt"), "x").unwrap();
+    sandbox().arg("a,\"b\"\tc.txt").assert().success();
+
+    let output = sandbox()
+        .args(["--trash-list", "--output", "csv"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    assert_eq!(lines.next(), Some("n,id,deleted,name,path"));
+    let row = lines.next().unwrap();
+    assert!(row.starts_with("1,"), "{row}");
+    assert!(row.contains(",\"a,\"\"b\"\"\tc.txt\","), "{row}");
+
+    sandbox()
+        .args(["--trash-list", "--output", "tsv"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\ta,\"b\"\\tc.txt\t"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_time_style() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox().arg("notes.txt").assert().success();
+
+    sandbox()
+        .args(["--trash-list", "--time-style", "relative"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(" just now notes.txt "));
+
+    sandbox()
+        .args(["--trash-list", "--time-style=+[%Y]"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            " [{}] notes.txt ",
+            chrono::Local::now().format("%Y")
+        )));
+
+    sandbox()
+        .args(["--trash-list", "--time-style", "locale"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid time style 'locale'"));
+}
+
+#[test]
+fn test_schema() {
+    let output = trache().arg("--schema").output().unwrap();
+    assert!(output.status.success());
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["version"], 1);
+    for name in ["list", "events", "clean", "audit"] {
+        assert!(doc["outputs"][name]["schema"].is_object(), "missing {name}");
+    }
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_list_output_json() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox().arg("notes.txt").assert().success();
+    let output = sandbox()
+        .args(["--trash-list", "--output", "json"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let item: serde_json::Value = serde_json::from_str(stdout.trim()).unwrap();
+    assert_eq!(item["n"], 1);
+    assert_eq!(item["name"], "notes.txt");
+    assert!(
+        item["path"]
+            .as_str()
+            .unwrap()
+            .ends_with("/playground/notes.txt")
+    );
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_subcommands() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}", unsafe { libc::getuid() }))
+        .join("playground");
+
+    sandbox()
+        .args(["rm", "-r", "notes.txt", "drafts"])
+        .assert()
+        .success();
+    assert!(!playground.join("drafts").exists());
+
+    sandbox()
+        .args(["list", "--format", "{name}"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes.txt"))
+        .stdout(predicate::str::contains("drafts"));
+
+    sandbox().args(["restore", "notes.txt"]).assert().success();
+    assert!(playground.join("notes.txt").exists());
+
+    sandbox()
+        .args(["purge", "drafts", "--trash-dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("drafts"));
+
+    sandbox()
+        .args(["empty", "--trash-dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("would empty trash"));
+
+    sandbox().args(["--trash-list", "empty"]).assert().failure();
+}
+
+#[test]
+fn test_subcommand_refuses_file_with_same_name() {
+    let tmp = TempDir::new().unwrap();
+    fs::write(tmp.path().join("empty"), "x").unwrap();
+
+    trache()
+        .current_dir(tmp.path())
+        .arg("empty")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("both a command and a file"));
+    assert!(tmp.path().join("empty").exists());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_subcommand_refused_with_rm_flags() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox().args(["notes.txt"]).assert().success();
+
+    for args in [
+        &["-f", "empty"][..],
+        &["empty", "-f"],
+        &["-rf", "purge", "*"],
+        &["-i", "restore", "notes.txt"],
+    ] {
+        sandbox()
+            .args(args)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("isn't taken as a command"));
+    }
+    sandbox()
+        .args(["list", "--format", "{name}"])
+        .assert()
+        .success()
+        .stdout("notes.txt\n");
+    sandbox().args(["-f", "list"]).assert().success();
+}
+
+#[test]
+fn test_mode_with_files_is_an_error() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("somefile");
+    fs::write(&file, "x").unwrap();
+
+    trache()
+        .arg("--trash-undo=x")
+        .arg(&file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--trash-undo acts on the trash and doesn't take FILES",
+        ))
+        .stderr(predicate::str::contains("--trash-undo 'string:path:"));
+
+    trache()
+        .arg("--trash-list")
+        .arg(&file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("leave out --trash-list"));
+    assert!(file.exists());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_invoked_as_rm() {
+    let tmp = TempDir::new().unwrap();
+    let rm = tmp.path().join("rm");
+    std::os::unix::fs::symlink(assert_cmd::cargo::cargo_bin!("trache"), &rm).unwrap();
+    let rm = || {
+        let mut cmd = Command::from_std(at_home(&rm));
+        cmd.current_dir(tmp.path());
+        cmd
+    };
+
+    // Subcommand names and --trash-* flags are just files or bad options
+    fs::write(tmp.path().join("list"), "x").unwrap();
+    rm().args(["-v", "list"])
+        .assert()
+        .success()
+        .stdout("removed 'list'\n");
+    assert!(!tmp.path().join("list").exists());
+
+    rm().arg("--trash-empty")
+        .assert()
+        .code(1)
+        .stderr("rm: unrecognized option '--trash-empty'\nTry 'rm --help' for more information.\n");
+
+    rm().assert()
+        .code(1)
+        .stderr("rm: missing operand\nTry 'rm --help' for more information.\n");
+    rm().arg("-f").assert().success().stderr("");
+
+    rm().arg("missing")
+        .assert()
+        .code(1)
+        .stderr("rm: cannot remove 'missing': No such file or directory\n");
+
+    fs::create_dir(tmp.path().join("dir")).unwrap();
+    rm().arg("dir")
+        .assert()
+        .code(1)
+        .stderr("rm: cannot remove 'dir': Is a directory\n");
+    rm().args(["-rv", "dir"])
+        .assert()
+        .success()
+        .stdout("removed directory 'dir'\n");
+
+    fs::write(tmp.path().join("empty.txt"), "").unwrap();
+    rm().args(["-i", "empty.txt"])
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stderr("rm: remove regular empty file 'empty.txt'? ");
+    assert!(tmp.path().join("empty.txt").exists());
+
+    rm().arg("--version")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("rm (trache) "));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_compat_gnu() {
+    let tmp = TempDir::new().unwrap();
+    let gnu = || {
+        let mut cmd = trache();
+        cmd.current_dir(tmp.path()).args(["--compat", "gnu"]);
+        cmd
+    };
+
+    gnu()
+        .arg("missing")
+        .assert()
+        .code(1)
+        .stderr("rm: cannot remove 'missing': No such file or directory\n");
+
+    fs::create_dir(tmp.path().join("dir")).unwrap();
+    fs::write(tmp.path().join("dir/a.txt"), "x").unwrap();
+    gnu()
+        .args(["-ri", "dir"])
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stderr("rm: remove directory 'dir' recursively? ");
+    assert!(tmp.path().join("dir/a.txt").exists());
+    gnu()
+        .args(["-ri", "dir"])
+        .write_stdin("y\n")
+        .assert()
+        .success()
+        .stderr("rm: remove directory 'dir' recursively? ");
+    assert!(!tmp.path().join("dir").exists());
+
+    fs::create_dir(tmp.path().join("dir")).unwrap();
+    gnu()
+        .args(["-ri", "dir"])
+        .write_stdin("y\n")
+        .assert()
+        .success()
+        .stderr("rm: remove directory 'dir'? ");
+    assert!(!tmp.path().join("dir").exists());
+
+    // trache's own options are still there
+    fs::write(tmp.path().join("b.txt"), "x").unwrap();
+    gnu()
+        .args(["--trash-dry-run", "b.txt"])
+        .assert()
+        .success()
+        .stdout("would trash 'b.txt'\n");
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_icase_prefix() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}", unsafe { libc::getuid() }))
+        .join("playground");
+
+    for pattern in [
+        "icase:full:NOTES.TXT",
+        "icase:regex:^Notes\\.TXT$",
+        "string:icase:OTES.t",
+    ] {
+        sandbox().arg("notes.txt").assert().success();
+        sandbox()
+            .args(["--trash-undo", "full:NOTES.TXT"])
+            .assert()
+            .success();
+        assert!(!playground.join("notes.txt").exists(), "{pattern}");
+
+        sandbox().args(["--trash-undo", pattern]).assert().success();
+        assert!(playground.join("notes.txt").exists(), "{pattern}");
+    }
+
+    // case: undoes an earlier icase:
+    sandbox().arg("notes.txt").assert().success();
+    sandbox()
+        .args(["--trash-undo", "icase:case:full:NOTES.TXT"])
+        .assert()
+        .success();
+    assert!(!playground.join("notes.txt").exists());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_purge_and_empty_scoped_by_paths() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox()
+        .args(["-r", "notes.txt", "todo.md", "drafts/chapter1.txt"])
+        .assert()
+        .success();
+
+    sandbox()
+        .args(["--trash-purge", "*", "drafts", "--trash-dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("chapter1.txt"))
+        .stdout(predicate::str::contains("notes.txt").not());
+
+    // The scope doesn't have to exist any more
+    sandbox().args(["-r", "drafts"]).assert().success();
+    sandbox()
+        .args(["purge", "chapter*", "drafts"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("chapter1.txt"));
+
+    sandbox()
+        .args(["--trash-empty", "drafts"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Permanently deleted 1 item(s)."));
+    sandbox()
+        .args(["empty", "drafts"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Nothing in the trash came from there.",
+        ));
+
+    sandbox()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes.txt"))
+        .stdout(predicate::str::contains("todo.md"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_hidden_and_no_hidden() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}", unsafe { libc::getuid() }))
+        .join("playground");
+
+    sandbox().arg("--trash-list").assert().success();
+    fs::write(playground.join(".env"), "SECRET=1").unwrap();
+    sandbox().args([".env", "notes.txt"]).assert().success();
+
+    // Like the shell: '*' skips dotfiles, '.env' names one
+    sandbox()
+        .args(["--no-hidden", "--trash-undo", "*"])
+        .assert()
+        .success();
+    assert!(playground.join("notes.txt").exists());
+    assert!(!playground.join(".env").exists());
+    sandbox()
+        .args(["--no-hidden", "--trash-undo", "full:.e*"])
+        .assert()
+        .success();
+    assert!(playground.join(".env").exists());
+
+    // Negating a pattern doesn't bring dotfiles in
+    sandbox().args([".env", "notes.txt"]).assert().success();
+    sandbox()
+        .args(["--no-hidden", "--trash-undo", "not:*.md"])
+        .assert()
+        .success();
+    assert!(playground.join("notes.txt").exists());
+    assert!(!playground.join(".env").exists());
+    sandbox().args(["restore", "full:.env"]).assert().success();
+
+    // By default '*' restores everything
+    sandbox().args([".env", "notes.txt"]).assert().success();
+    sandbox().args(["restore", "*"]).assert().success();
+    assert!(playground.join("notes.txt").exists());
+    assert!(playground.join(".env").exists());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_not_prefix() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox().args(["notes.txt", "todo.md"]).assert().success();
+
+    // Filtered listings keep the numbers of the full listing
+    sandbox()
+        .arg("--trash-list=not:notes")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("2 "))
+        .stdout(predicate::str::contains("notes.txt").not());
+    sandbox()
+        .args(["list", "not:not:notes", "--output", "csv"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\n1,"))
+        .stdout(predicate::str::contains("todo.md").not());
+
+    sandbox()
+        .args(["--trash-purge", "not:*.md"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes.txt"));
+    sandbox()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("todo.md"))
+        .stdout(predicate::str::contains("notes.txt").not());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_not_negates_only_what_follows() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox().args(["notes.txt", "todo.md"]).assert().success();
+    sandbox().args(["-r", "drafts"]).assert().success();
+
+    // Files not named *.md: the directory stays out
+    sandbox()
+        .arg("--trash-list=type:file:not:*.md")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes.txt"))
+        .stdout(predicate::str::contains("todo.md").not())
+        .stdout(predicate::str::contains("drafts").not());
+    // Anything but files named *.md: the directory comes in
+    sandbox()
+        .arg("--trash-list=not:type:file:*.md")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes.txt"))
+        .stdout(predicate::str::contains("drafts"))
+        .stdout(predicate::str::contains("todo.md").not());
+}
+
+#[test]
+fn test_prompt_shows_risk_inputs() {
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path().join("dir");
+    fs::create_dir(&dir).unwrap();
+    fs::write(dir.join("a"), "12345").unwrap();
+    fs::write(dir.join("b"), "123").unwrap();
+
+    trache()
+        .args(["-ri", "--color=always"])
+        .arg(&dir)
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        // Yellow: a directory, three entries
+        .stderr(predicate::str::contains("\x1b[33mtrache: remove directory"))
+        .stderr(predicate::str::contains(
+            "recursively [3 entries, 8 B]?\x1b[0m ",
+        ));
+    assert!(dir.exists());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_size_prefix() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}", unsafe { libc::getuid() }))
+        .join("playground");
+
+    // 30, 39 and 18 bytes
+    sandbox()
+        .args(["notes.txt", "todo.md", "drafts/chapter2.txt"])
+        .assert()
+        .success();
+
+    sandbox()
+        .arg("--trash-list=size:<31")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes.txt"))
+        .stdout(predicate::str::contains("chapter2.txt"))
+        .stdout(predicate::str::contains("todo.md").not());
+
+    sandbox()
+        .args(["--trash-undo", "size:>=30:*.txt"])
+        .assert()
+        .success();
+    assert!(playground.join("notes.txt").exists());
+    assert!(!playground.join("drafts/chapter2.txt").exists());
+
+    sandbox()
+        .args(["--trash-purge", "not:size:>20"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("chapter2.txt"))
+        .stdout(predicate::str::contains("todo.md").not());
+
+    sandbox()
+        .args(["--trash-list=size:big"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("size:big: invalid size"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_transcript() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp = TempDir::new().unwrap();
+    let log = tmp.path().join("session.txt");
+
+    trache()
+        .env("TMPDIR", tmp.path())
+        .args(["--sandbox", "-i", "notes.txt", "todo.md", "--transcript"])
+        .arg(&log)
+        .args(["--transcript-redact", "todo*"])
+        .write_stdin("y\nn\n")
+        .assert()
+        .success();
+
+    let transcript = fs::read_to_string(&log).unwrap();
+    let lines: Vec<&str> = transcript.lines().collect();
+    assert!(lines[0].starts_with("# trache "), "{transcript}");
+    assert!(lines[1].starts_with("$ ") && lines[1].contains(" *** "));
+    assert_eq!(
+        lines[2],
+        "trache: remove regular file 'notes.txt' [1 entry, 30 B]?"
+    );
+    assert_eq!(lines[3], "> y");
+    assert!(lines[4].starts_with("trash '/") && lines[4].ends_with("/playground/notes.txt': ok"));
+    assert_eq!(
+        lines[5],
+        "trache: remove regular file '***' [1 entry, 39 B]?"
+    );
+    assert_eq!(lines[6], "> n");
+    assert_eq!(lines.len(), 7);
+    assert!(!transcript.contains("todo.md"));
+    let mode = fs::metadata(&log).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_type_prefix() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}", unsafe { libc::getuid() }))
+        .join("playground");
+
+    sandbox().arg("--trash-list").assert().success();
+    fs::create_dir(playground.join("target")).unwrap();
+    fs::write(playground.join("drafts/target"), "not a build dir").unwrap();
+    std::os::unix::fs::symlink("notes.txt", playground.join("link")).unwrap();
+    sandbox()
+        .args(["-r", "target", "drafts/target", "link"])
+        .assert()
+        .success();
+
+    sandbox()
+        .arg("--trash-list=type:symlink:*")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("link"))
+        .stdout(predicate::str::contains("target").not());
+
+    sandbox()
+        .args(["--trash-purge", "type:dir:full:target"])
+        .assert()
+        .success();
+    sandbox()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("drafts/target"))
+        .stdout(predicate::str::contains("playground/target").not());
+
+    sandbox()
+        .args(["--trash-list=type:socket:*"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "type:socket: expected file, dir or symlink",
+        ));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_deleted_prefix() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox().args(["notes.txt", "todo.md"]).assert().success();
+
+    sandbox()
+        .arg("--trash-list=deleted:..1999:*.txt")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No matching items in trash."));
+    sandbox()
+        .arg("--trash-list=deleted:>=2000-01-01:*.txt")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes.txt"))
+        .stdout(predicate::str::contains("todo.md").not());
+
+    sandbox()
+        .args(["--trash-purge", "deleted:>2000"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("todo.md"));
+
+    sandbox()
+        .args(["--trash-list=deleted:2024-13"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("deleted:2024-13: invalid date"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_pinned_items_survive_purge_and_empty() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox()
+        .args(["notes.txt", "todo.md", "drafts/chapter1.txt"])
+        .assert()
+        .success();
+    sandbox()
+        .args(["--trash-pin", "full:notes.txt"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Pinned: "));
+    sandbox()
+        .args(["--trash-info", "full:notes.txt"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Pinned:    yes"));
+
+    sandbox()
+        .args(["--trash-purge", "*.txt"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("chapter1.txt"))
+        .stdout(predicate::str::contains("notes.txt").not())
+        .stderr(predicate::str::contains("leaving 1 pinned item(s)"));
+    sandbox().arg("--trash-empty").assert().success();
+    sandbox()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes.txt"))
+        .stdout(predicate::str::contains("todo.md").not());
+
+    sandbox()
+        .args(["--trash-unpin", "notes"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Unpinned: "));
+    sandbox().arg("--trash-empty").assert().success();
+    sandbox()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes.txt").not());
+}
+
+#[test]
+fn test_question_mark_explains_prompt() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("a.txt");
+    fs::write(&file, "x").unwrap();
+
+    trache()
+        .arg("-i")
+        .arg(&file)
+        .write_stdin("?\ny\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("(y) Yes: go ahead"))
+        .stderr(predicate::str::contains("--trash-undo"));
+    assert!(!file.exists());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_patterns_from() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}", unsafe { libc::getuid() }))
+        .join("playground");
+    let list = tmp.path().join("patterns.txt");
+    fs::write(
+        &list,
+        "# cleaned up by mistake\nfull:notes.txt\n\n  path:*/drafts/chapter1.txt  \n",
+    )
+    .unwrap();
+
+    sandbox()
+        .args([
+            "notes.txt",
+            "todo.md",
+            "drafts/chapter1.txt",
+            "drafts/chapter2.txt",
+        ])
+        .assert()
+        .success();
+
+    sandbox()
+        .arg("--trash-undo")
+        .arg("--patterns-from")
+        .arg(&list)
+        .assert()
+        .success();
+    assert!(playground.join("notes.txt").exists());
+    assert!(playground.join("drafts/chapter1.txt").exists());
+    assert!(!playground.join("todo.md").exists());
+    assert!(!playground.join("drafts/chapter2.txt").exists());
+
+    // PATTERN adds to the file's patterns
+    sandbox()
+        .args(["--trash-purge", "full:todo.md", "--patterns-from"])
+        .arg(&list)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("todo.md"))
+        .stdout(predicate::str::contains("chapter2.txt").not());
+
+    fs::write(&list, "regex:(\n").unwrap();
+    sandbox()
+        .args(["--trash-purge", "--patterns-from"])
+        .arg(&list)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("patterns.txt:1: invalid regex"));
+
+    sandbox()
+        .arg("--trash-undo")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--trash-undo needs a PATTERN"));
+}
+
+#[test]
+fn test_yes_answers_from_config() {
+    let tmp = TempDir::new().unwrap();
+    let config = tmp.path().join("config.toml");
+    fs::write(&config, "yes_answers = [\"ja\"]\n").unwrap();
+    let file = tmp.path().join("a.txt");
+    fs::write(&file, "x").unwrap();
+
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg("-i")
+        .arg(&file)
+        .write_stdin("nein\n")
+        .assert()
+        .success();
+    assert!(file.exists());
+
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg("-i")
+        .arg(&file)
+        .write_stdin("Ja\n")
+        .assert()
+        .success();
+    assert!(!file.exists());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_exit_codes() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox().arg("missing.txt").assert().code(1);
+    sandbox()
+        .args(["notes.txt", "missing.txt"])
+        .assert()
+        .code(3);
+    sandbox()
+        .args(["--status-map", "partial=1", "todo.md", "missing.txt"])
+        .assert()
+        .code(1);
+    // Failures with one cause can be told apart
+    sandbox()
+        .args(["--status-map", "not_found=44", "missing.txt"])
+        .assert()
+        .code(44);
+    sandbox()
+        .args(["--status-map", "is_directory=45", "drafts"])
+        .assert()
+        .code(45);
+    sandbox()
+        .args(["--status-map", "is_directory=45", "drafts", "missing.txt"])
+        .assert()
+        .code(1);
+
+    sandbox()
+        .args(["-i", "drafts/chapter1.txt"])
+        .write_stdin("n\n")
+        .assert()
+        .code(0);
+    sandbox()
+        .args(["-i", "--status-map", "declined=6", "drafts/chapter1.txt"])
+        .write_stdin("n\n")
+        .assert()
+        .code(6);
+
+    let config = tmp.path().join("config.toml");
+    fs::write(&config, "[exit_codes]\nfailure = 9\n").unwrap();
+    sandbox()
+        .env("TRACHE_CONFIG", &config)
+        .arg("missing.txt")
+        .assert()
+        .code(9);
+    sandbox()
+        .args(["--status-map", "oops=3", "drafts/chapter1.txt"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("unknown status 'oops'"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_find() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox()
+        .args([
+            "--find",
+            "--name",
+            "*.txt",
+            "--size",
+            "+20",
+            "--trash-dry-run",
+            ".",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes.txt"))
+        .stdout(predicate::str::contains("chapter1.txt"))
+        .stdout(predicate::str::contains("chapter2.txt").not());
+
+    sandbox()
+        .args(["--find", "--name", "chapter*", "--size", "-20", "."])
+        .assert()
+        .success();
+    sandbox()
+        .arg("--trash-list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("chapter2.txt"))
+        .stdout(predicate::str::contains("chapter1.txt").not());
+
+    sandbox()
+        .args(["--find", "--mtime", "+30", "."])
+        .assert()
+        .success()
+        .stdout("Nothing to trash: no entry matches.\n");
+    sandbox()
+        .args(["--find", "."])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("give at least one of"));
+    sandbox()
+        .args(["--name", "*.txt", "."])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--find"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_prune_empty_dirs() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+
+    sandbox().arg("--trash-list").assert().success();
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/playground", unsafe {
+            libc::getuid()
+        }));
+    fs::create_dir_all(playground.join("old/cache/tmp")).unwrap();
+    fs::create_dir_all(playground.join("drafts/empty")).unwrap();
+
+    sandbox()
+        .args(["--prune-empty-dirs", "--trash-dry-run", "."])
+        .assert()
+        .success()
+        .stdout(
+            "would trash './drafts/empty'\n\
+             would trash './old/cache/tmp'\n\
+             would trash './old/cache'\n\
+             would trash './old'\n",
+        );
+
+    // Keeping the innermost directory keeps its parents
+    sandbox()
+        .args(["--prune-empty-dirs", "-i", "-v", "."])
+        .write_stdin("y\nn\ny\ny\n")
+        .assert()
+        .success()
+        .stdout("trashed './drafts/empty'\n");
+    assert!(!playground.join("drafts/empty").exists());
+    assert!(playground.join("drafts/chapter1.txt").exists());
+    assert!(playground.join("old/cache/tmp").exists());
+
+    sandbox()
+        .args(["--prune-empty-dirs", "-v", "."])
+        .assert()
+        .success()
+        .stdout("trashed './old/cache/tmp'\ntrashed './old/cache'\ntrashed './old'\n");
+    sandbox()
+        .args(["--prune-empty-dirs", "."])
+        .assert()
+        .success()
+        .stdout("Nothing to trash: no empty directories.\n");
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_trash_analyze() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox()
+        .arg("--trash-analyze")
+        .assert()
+        .success()
+        .stdout("No wasted space found.\n");
+    let root = tmp
+        .path()
+        .join(format!("trache-sandbox-{}", unsafe { libc::getuid() }));
+
+    // A trash holding nothing but a stray file, reclaimed by trache itself
+    let home = tmp.path().join("home");
+    let trash = root.join("data/Trash");
+    fs::create_dir_all(trash.join("files")).unwrap();
+    fs::create_dir_all(trash.join("info")).unwrap();
+    fs::write(trash.join("files/lost"), "abcd").unwrap();
+    let purge = format!("path:string:full:{}/trache-rescue/lost", home.display());
+    sandbox()
+        .env("HOME", &home)
+        .arg("--trash-analyze")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "/data/Trash/files/lost\n  trache --trash-check --repair\n",
+        ))
+        .stdout(predicate::str::contains(format!(
+            "  trache --trash-purge {purge}\n"
+        )));
+    sandbox()
+        .env("HOME", &home)
+        .args(["--trash-check", "--repair"])
+        .assert()
+        .success();
+    sandbox()
+        .env("HOME", &home)
+        .args(["--trash-purge", &purge])
+        .assert()
+        .success();
+    sandbox()
+        .arg("--trash-analyze")
+        .assert()
+        .success()
+        .stdout("No wasted space found.\n");
+
+    // Two copies of notes.txt, an old todo.md and a stray file
+    sandbox().arg("notes.txt").assert().success();
+    let playground = root.join("playground");
+    fs::write(
+        playground.join("notes.txt"),
+        fs::read(root.join("data/Trash/files/notes.txt")).unwrap(),
+    )
+    .unwrap();
+    sandbox().arg("notes.txt").assert().success();
+    sandbox().arg("todo.md").assert().success();
+    let info = root.join("data/Trash/info/todo.md.trashinfo");
+    let text = fs::read_to_string(&info).unwrap();
+    let old = regex::Regex::new("DeletionDate=.*").unwrap();
+    fs::write(
+        &info,
+        old.replace(&text, "DeletionDate=2001-02-03T04:05:06")
+            .as_ref(),
+    )
+    .unwrap();
+    fs::write(root.join("data/Trash/files/stray"), "xyz").unwrap();
+
+    sandbox()
+        .arg("--trash-analyze")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Duplicates: 1 older copies of 1 item(s), 30 B\n  \
+             trache --trash-purge --duplicates-keep-newest '*'\n",
+        ))
+        .stdout(predicate::str::is_match(r"Cold items: 1 trashed before \d{4}-\d\d-\d\d, 39 B\n  trache --trash-purge 'deleted:<\d{4}-\d\d-\d\d:\*'\n").unwrap())
+        .stdout(predicate::str::contains("1 path(s) with no .trashinfo record, 3 B"))
+        .stdout(predicate::str::contains("/data/Trash/files/stray\n"))
+        .stdout(predicate::str::ends_with("Reclaimable: 72 B\n"));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_hooks() {
+    let tmp = TempDir::new().unwrap();
+    let file = tmp.path().join("systest_hooks.txt");
+    fs::write(&file, "hello").unwrap();
+    let log = tmp.path().join("hooks.log");
+    let config = tmp.path().join("config.toml");
+    let logging = |hook: &str| {
+        format!(
+            "{hook} = [\"sh\", \"-c\", \"echo $TRACHE_HOOK >> '{0}'; cat >> '{0}'\"]\n",
+            log.display()
+        )
+    };
+
+    fs::write(&config, "[hooks]\npre_trash = [\"false\"]\n").unwrap();
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg(&file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "pre_trash hook: false exited with exit status: 1; nothing was trashed",
+        ));
+    assert!(file.exists());
+    // Shredding asks the same hook first
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg("--shred=1")
+        .arg(&file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("pre_trash hook"));
+    assert!(file.exists());
+
+    fs::write(
+        &config,
+        format!(
+            "[hooks]\n{}{}pre_purge = [\"false\"]\n",
+            logging("pre_trash"),
+            logging("post_trash")
+        ),
+    )
+    .unwrap();
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .arg(&file)
+        .assert()
+        .success();
+    assert!(!file.exists());
+    let shown = file.display();
+    assert_eq!(
+        fs::read_to_string(&log).unwrap(),
+        format!("pre_trash\n{shown}\npost_trash\n{shown}\n")
+    );
+
+    trache()
+        .env("TRACHE_CONFIG", &config)
+        .args(["--trash-purge", "full:systest_hooks.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("nothing was purged"));
+
+    // cleanup
+    trache()
+        .args(["--trash-purge", "full:systest_hooks.txt"])
+        .assert()
+        .success();
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_notify() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp = TempDir::new().unwrap();
+    let bin = tmp.path().join("bin");
+    fs::create_dir(&bin).unwrap();
+    let log = tmp.path().join("notify.log");
+    let fake = bin.join("notify-send");
+    fs::write(
+        &fake,
+        format!("#!/bin/sh\nprintf '%s\\n' \"$@\" >> '{}'\n", log.display()),
+    )
+    .unwrap();
+    fs::set_permissions(&fake, fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap());
+    let config = tmp.path().join("config.toml");
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path())
+            .env("PATH", &path)
+            .env("TRACHE_CONFIG", &config)
+            .arg("--sandbox");
+        cmd
+    };
+
+    fs::write(&config, "[notify]\nmin_items = 2\n").unwrap();
+    sandbox().args(["--notify", "notes.txt"]).assert().success();
+    assert!(!log.exists());
+
+    sandbox()
+        .args(["--notify", "todo.md", "drafts", "-r"])
+        .assert()
+        .success();
+    let shown = fs::read_to_string(&log).unwrap();
+    assert!(shown.starts_with("--app-name=trache\ntrache\nTrashed 2 item(s) in "));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_interactive_smart() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox().arg("--trash-list").assert().success();
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/playground", unsafe {
+            libc::getuid()
+        }));
+
+    sandbox()
+        .args(["--interactive=smart", "-r", "notes.txt", "drafts"])
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::starts_with(
+            "trache: remove directory 'drafts' recursively [",
+        ));
+    assert!(!playground.join("notes.txt").exists());
+    assert!(playground.join("drafts").exists());
+
+    let sub = tmp.path().join("sub");
+    fs::create_dir(&sub).unwrap();
+    fs::write(tmp.path().join("systest_smart.txt"), "x").unwrap();
+    trache()
+        .current_dir(&sub)
+        .args(["--interactive=smart", "../systest_smart.txt"])
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "remove regular file '../systest_smart.txt' (outside the current directory)",
+        ));
+    assert!(tmp.path().join("systest_smart.txt").exists());
+
+    // With the current directory gone, where the file is can't be told
+    let file = tmp.path().join("systest_smart.txt");
+    let output = at_home("sh")
+        .arg("-c")
+        .arg("cd \"$1\" && rmdir \"$1\" && exec \"$2\" --interactive=smart \"$3\" </dev/null")
+        .arg("sh")
+        .arg(&sub)
+        .arg(assert_cmd::cargo::cargo_bin!("trache"))
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("(can't tell where it is)"),
+        "{output:?}"
+    );
+    assert!(file.exists());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_uncommitted_checkout_warning() {
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox().arg("--trash-list").assert().success();
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/playground", unsafe {
+            libc::getuid()
+        }));
+    let repo = playground.join("work/app");
+    fs::create_dir_all(&repo).unwrap();
+    let initialized = std::process::Command::new("git")
+        .args(["init", "-q"])
+        .current_dir(&repo)
+        .status()
+        .is_ok_and(|s| s.success());
+    if !initialized {
+        return; // no git here
+    }
+    fs::write(repo.join("main.rs"), "fn main() {}").unwrap();
+
+    sandbox()
+        .args(["-r", "work"])
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "warning: 'work/app' is a git checkout with uncommitted changes",
+        ))
+        .stderr(predicate::str::contains("remove directory 'work' anyway?"));
+    assert!(repo.exists());
+
+    sandbox()
+        .args(["-rf", "work"])
+        .assert()
+        .success()
+        .stderr("");
+    assert!(!repo.exists());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_open_files() {
+    let tmp = TempDir::new().unwrap();
+    let config = tmp.path().join("config.toml");
+    let sandbox = || {
+        let mut cmd = trache();
+        cmd.env("TMPDIR", tmp.path())
+            .env("TRACHE_CONFIG", &config)
+            .arg("--sandbox");
+        cmd
+    };
+    sandbox().arg("--trash-list").assert().success();
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/playground", unsafe {
+            libc::getuid()
+        }));
+    let mut holder = std::process::Command::new("sh")
+        .arg("-c")
+        .arg("exec 3<notes.txt 4<todo.md; echo ready; read line")
+        .current_dir(&playground)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut ready = String::new();
+    std::io::BufRead::read_line(
+        &mut std::io::BufReader::new(holder.stdout.as_mut().unwrap()),
+        &mut ready,
+    )
+    .unwrap();
+    let held = format!("is open in sh (pid {})", holder.id());
+
+    fs::write(&config, "open_files = \"prompt\"\n").unwrap();
+    sandbox()
+        .arg("notes.txt")
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(format!(
+            "'notes.txt' {held}; remove it anyway?"
+        )));
+    assert!(playground.join("notes.txt").exists());
+
+    fs::write(&config, "open_files = \"warn\"\n").unwrap();
+    sandbox()
+        .arg("todo.md")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(format!(
+            "warning: 'todo.md' {held}"
+        )));
+    assert!(!playground.join("todo.md").exists());
+
+    drop(holder.stdin.take());
+    holder.wait().unwrap();
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn test_interrupt_stops_after_current_item() {
+    use std::io::{Read, Write};
+    use std::process::Stdio;
+
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = at_home(assert_cmd::cargo::cargo_bin!("trache"));
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox().arg("--trash-list").output().unwrap();
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/playground", unsafe {
+            libc::getuid()
+        }));
+
+    let mut child = sandbox()
+        .args(["-i", "notes.txt", "todo.md"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = child.stderr.take().unwrap();
+    let mut seen = Vec::new();
+    let mut byte = [0];
+    while !seen.ends_with(b"? ") && stderr.read(&mut byte).unwrap() == 1 {
+        seen.push(byte[0]);
+    }
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGINT);
+    }
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(b"y\n").unwrap();
+    drop(stdin);
+    let mut rest = String::new();
+    stderr.read_to_string(&mut rest).unwrap();
+    let status = child.wait().unwrap();
+
+    assert_eq!(status.code(), Some(5), "{rest}");
+    assert!(rest.contains("interrupted; stopping after the current item"));
+    assert!(rest.contains(
+        "Error: interrupted after trashing 1 item(s); trache --resume trashes the rest, \
+         trache --resume=rollback restores what was trashed"
+    ));
+    assert!(!playground.join("notes.txt").exists());
+    assert!(playground.join("todo.md").exists());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_resume_killed_batch() {
+    use std::io::{Read, Write};
+    use std::process::Stdio;
+
+    let tmp = TempDir::new().unwrap();
+    let sandbox = || {
+        let mut cmd = at_home(assert_cmd::cargo::cargo_bin!("trache"));
+        cmd.env("TMPDIR", tmp.path()).arg("--sandbox");
+        cmd
+    };
+    sandbox().arg("--trash-list").output().unwrap();
+    let playground = tmp
+        .path()
+        .join(format!("trache-sandbox-{}/playground", unsafe {
+            libc::getuid()
+        }));
+    // Trash notes.txt, then die at the prompt for todo.md
+    let killed_batch = || {
+        let mut child = sandbox()
+            .args(["-i", "notes.txt", "todo.md"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stderr = child.stderr.take().unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        let mut byte = [0];
+        for answer in [&b"y\n"[..], b""] {
+            let mut seen = Vec::new();
+            while !seen.ends_with(b"? ") && stderr.read(&mut byte).unwrap() == 1 {
+                seen.push(byte[0]);
+            }
+            stdin.write_all(answer).unwrap();
+            stdin.flush().unwrap();
+        }
+        child.kill().unwrap();
+        child.wait().unwrap();
+    };
+
+    killed_batch();
+    assert!(!playground.join("notes.txt").exists());
+    assert!(playground.join("todo.md").exists());
+    let output = sandbox().args(["--resume=rollback"]).output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(playground.join("notes.txt").exists());
+    assert!(playground.join("todo.md").exists());
+    let output = sandbox().arg("--resume").output().unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Nothing to resume.\n"
+    );
+
+    killed_batch();
+    let output = sandbox().arg("--resume").output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stdout)
+            .contains("Trashing the 1 item(s) left by the batch of ")
+    );
+    assert!(!playground.join("notes.txt").exists());
+    assert!(!playground.join("todo.md").exists());
+}
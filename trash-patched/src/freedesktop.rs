@@ -14,7 +14,7 @@ use std::{
     io::{BufRead, BufReader, Write},
     os::unix::{
         ffi::{OsStrExt, OsStringExt},
-        fs::PermissionsExt,
+        fs::{DirBuilderExt, MetadataExt, PermissionsExt},
     },
     path::{Component, Path, PathBuf},
 };
@@ -81,28 +81,116 @@ impl TrashContext {
             debug!("Deleting {:?}", path);
             let topdir = get_first_topdir_containing_path(&path, &sorted_mount_points);
             debug!("The topdir of this file is {:?}", topdir);
-            if topdir == home_topdir {
-                debug!("The topdir was identical to the home topdir, so moving to the home trash.");
+            if uses_home_trash(topdir, &home_topdir) {
+                debug!("The topdir belongs to the home trash, so moving to the home trash.");
                 // Note that the following function creates the trash folder
                 // and its required subfolders in case they don't exist.
-                move_to_trash(path, &home_trash, topdir)?;
-            } else if topdir.to_str() == Some("/var/home") && home_topdir.to_str() == Some("/") {
-                debug!("The topdir is '/var/home' but the home_topdir is '/', moving to the home trash anyway.");
-                move_to_trash(path, &home_trash, topdir)?;
+                move_to_trash(path, &home_trash, None)?;
             } else {
+                // Removable media and other mounts get their own trash, with
+                // paths recorded relative to the topdir so they still resolve
+                // when the volume is mounted somewhere else.
                 execute_on_mounted_trash_folders::<Error, _>(
                     uid,
                     topdir,
                     true,
                     true,
-                    |trash_path| move_to_trash(&path, trash_path, topdir),
-                )?;
+                    |trash_path| move_to_trash(&path, trash_path, Some(topdir)),
+                )
+                .map_err(|error| unusable_topdir_trash(error, &path, topdir))?;
             }
         }
         Ok(())
     }
 }
 
+/// Files under `topdir` go to the home trash rather than a trash on their own
+/// volume. '/var/home' is how some image-based distros mount the home directories
+/// of a '/' home topdir.
+fn uses_home_trash(topdir: &Path, home_topdir: &Path) -> bool {
+    topdir == home_topdir
+        || (topdir.to_str() == Some("/var/home") && home_topdir.to_str() == Some("/"))
+}
+
+/// What trashing a file onto its own volume's trash would run into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopdirTrashReport {
+    /// Mount point of the volume
+    pub topdir: PathBuf,
+    /// Why `$topdir/.Trash/$uid` will be passed over, if `$topdir/.Trash` exists
+    pub shared_problem: Option<String>,
+    /// `$topdir/.Trash-$uid`, the fallback
+    pub private_trash: PathBuf,
+    /// Why the fallback can't be used or created
+    pub private_problem: Option<String>,
+}
+
+pub fn topdir_trash_report(path: &Path) -> Result<Option<TopdirTrashReport>, Error> {
+    let sorted_mount_points = get_sorted_mount_points()?;
+    let home_topdir = home_topdir(&sorted_mount_points)?;
+    let topdir = get_first_topdir_containing_path(path, &sorted_mount_points);
+    if uses_home_trash(topdir, &home_topdir) {
+        return Ok(None);
+    }
+    let uid = unsafe { libc::getuid() };
+
+    let shared = topdir.join(".Trash");
+    let shared_problem = match folder_validity(&shared) {
+        Err(_) => None,
+        Ok(TrashValidity::InvalidSymlink) => Some(format!(
+            "'{}' is a symbolic link, which the trash specification forbids",
+            shared.display()
+        )),
+        Ok(TrashValidity::InvalidNotSticky) => Some(format!(
+            "'{}' doesn't have the sticky bit set (fix with chmod +t)",
+            shared.display()
+        )),
+        Ok(TrashValidity::Valid) => {
+            trash_folder_problem(&shared.join(uid.to_string()), &shared, uid)
+        }
+    };
+    let private_trash = topdir.join(format!(".Trash-{uid}"));
+    let private_problem = trash_folder_problem(&private_trash, topdir, uid);
+    Ok(Some(TopdirTrashReport {
+        topdir: topdir.to_owned(),
+        shared_problem,
+        private_trash,
+        private_problem,
+    }))
+}
+
+/// Why the per-user trash folder `path` (created inside `parent` when
+/// missing) can't be used.
+fn trash_folder_problem(path: &Path, parent: &Path, uid: u32) -> Option<String> {
+    match path.symlink_metadata() {
+        Ok(_) if !is_own_dir(path, uid) => Some(format!(
+            "'{}' is not a directory owned by you",
+            path.display()
+        )),
+        Ok(_) => None,
+        Err(_) if !is_writable(parent) => Some(format!(
+            "cannot create '{}': '{}' is not writable",
+            path.display(),
+            parent.display()
+        )),
+        Err(_) => None,
+    }
+}
+
+/// A real directory (not a symlink to one) owned by `uid`, as the
+/// specification requires of per-user trash folders on shared volumes.
+fn is_own_dir(path: &Path, uid: u32) -> bool {
+    path.symlink_metadata()
+        .is_ok_and(|meta| meta.is_dir() && meta.uid() == uid)
+}
+
+fn is_writable(dir: &Path) -> bool {
+    let Ok(dir) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    unsafe { libc::access(dir.as_ptr(), libc::W_OK | libc::X_OK) == 0 }
+}
+
 pub fn list() -> Result<Vec<TrashItem>, Error> {
     let EvaluatedTrashFolders {
         trash_folders,
@@ -196,11 +284,16 @@ pub fn list() -> Result<Vec<TrashItem>, Error> {
                 } else {
                     break 'info_lines;
                 };
-                let mut split = line.split('=');
-
-                // Just unwraping here because the system is assumed to follow the specification.
-                let key = split.next().unwrap().trim();
-                let value = split.next().unwrap().trim();
+                // Other tools and crashes leave malformed records behind; skip
+                // such lines rather than trusting the specification was followed
+                let Some((key, value)) = line.split_once('=') else {
+                    warn!(
+                        "Skipping a malformed line in the trash info file '{:?}': {:?}",
+                        info_path, line
+                    );
+                    continue 'info_lines;
+                };
+                let (key, value) = (key.trim(), value.trim());
 
                 if key == "Path" {
                     let value_path = {
@@ -212,10 +305,7 @@ pub fn list() -> Result<Vec<TrashItem>, Error> {
                         }
                     };
                     name = value_path.file_name().map(|name| name.to_owned());
-                    let parent = value_path
-                        .parent()
-                        .expect("Absolute path to trashed item should have a parent");
-                    original_parent = Some(parent.into());
+                    original_parent = value_path.parent().map(|parent| parent.into());
                 } else if key == "DeletionDate" {
                     #[cfg(feature = "chrono")]
                     {
@@ -452,7 +542,10 @@ where
         std::fs::create_dir_all(&item.original_parent)
             .map_err(|e| fs_error(&item.original_parent, e))?;
         let mut collision = false;
-        if file.is_dir() {
+        // Don't follow links: a link to a directory is restored as a link,
+        // which needs a file placeholder rather than a directory
+        let is_dir = file.symlink_metadata().map(|m| m.is_dir()).unwrap_or(false);
+        if is_dir {
             // NOTE create_dir_all succeeds when the path already exist but create_dir
             // fails with `std::io::ErrorKind::AlreadyExists`.
             if let Err(e) = std::fs::create_dir(&original_path) {
@@ -483,7 +576,15 @@ where
                 remaining_items: remaining,
             });
         }
-        std::fs::rename(&file, &original_path).map_err(|e| fs_error(&file, e))?;
+        if let Err(e) = std::fs::rename(&file, &original_path) {
+            // Don't leave the placeholder behind to block a later attempt
+            let _ = if is_dir {
+                std::fs::remove_dir(&original_path)
+            } else {
+                std::fs::remove_file(&original_path)
+            };
+            return Err(fs_error(&file, e));
+        }
         std::fs::remove_file(info_file).map_err(|e| fs_error(info_file, e))?;
     }
     Ok(())
@@ -515,11 +616,23 @@ where
         let validity = folder_validity(&trash_path).map_err(E::from)?;
         if validity == TrashValidity::Valid {
             let users_trash_path = trash_path.join(uid.to_string());
-            if users_trash_path.exists() && users_trash_path.is_dir() {
+            // The spec requires creating $topdir/.Trash/$uid when it's missing;
+            // if that isn't allowed, fall back to $topdir/.Trash-$uid below
+            if create_folder && !users_trash_path.exists() {
+                if let Err(e) = fs::DirBuilder::new().mode(0o700).create(&users_trash_path) {
+                    debug!("Could not create {:?}: {}", users_trash_path, e);
+                }
+            }
+            if is_own_dir(&users_trash_path, uid) {
                 op(users_trash_path)?;
                 if first_only {
                     return Ok(());
                 }
+            } else if users_trash_path.symlink_metadata().is_ok() {
+                warn!(
+                    "Skipping {:?}: not a directory owned by uid {}",
+                    users_trash_path, uid
+                );
             }
         } else {
             warn!(
@@ -531,9 +644,22 @@ where
     // See if there's a ".Trash-$UID" directory at the mounted location
     let trash_path = topdir.join(format!(".Trash-{uid}"));
     let should_execute;
-    if !trash_path.exists() || !trash_path.is_dir() {
+    if trash_path.symlink_metadata().is_ok() && !is_own_dir(&trash_path, uid) {
+        // Someone else's directory, or a symlink planted to redirect our files
         if create_folder {
-            std::fs::create_dir(&trash_path).map_err(|e| E::from((trash_path.to_owned(), e)))?;
+            let error = std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "not a directory owned by you",
+            );
+            return Err(E::from((trash_path, error)));
+        }
+        should_execute = false;
+    } else if !trash_path.exists() || !trash_path.is_dir() {
+        if create_folder {
+            fs::DirBuilder::new()
+                .mode(0o700)
+                .create(&trash_path)
+                .map_err(|e| E::from((trash_path.to_owned(), e)))?;
             should_execute = true;
         } else {
             should_execute = false;
@@ -547,10 +673,34 @@ where
     Ok(())
 }
 
+/// Classify failures to set up or write a mount's trash as
+/// [`Error::UnsupportedTrashVolume`], so callers can tell "this volume has no
+/// usable trash" apart from problems with the file itself.
+fn unusable_topdir_trash(error: Error, src: &Path, topdir: &Path) -> Error {
+    match error {
+        Error::FileSystem { path, source } if is_in_topdir_trash(&path, topdir) => {
+            Error::UnsupportedTrashVolume {
+                path: src.to_owned(),
+                reason: format!("cannot use trash at '{}': {source}", path.display()),
+            }
+        }
+        error => error,
+    }
+}
+
+fn is_in_topdir_trash(path: &Path, topdir: &Path) -> bool {
+    path.strip_prefix(topdir)
+        .ok()
+        .and_then(|relative| relative.components().next())
+        .is_some_and(|first| first.as_os_str().as_bytes().starts_with(b".Trash"))
+}
+
+/// `topdir` is set for trash folders on mounted volumes, in which case the
+/// recorded path is relative to it, as the specification allows.
 fn move_to_trash(
     src: impl AsRef<Path>,
     trash_folder: impl AsRef<Path>,
-    _topdir: impl AsRef<Path>,
+    topdir: Option<&Path>,
 ) -> Result<(), Error> {
     let src = src.as_ref();
     let trash_folder = trash_folder.as_ref();
@@ -610,8 +760,12 @@ fn move_to_trash(
                 // Write the info file before actually moving anything
                 writeln!(file, "[Trash Info]")
                     .and_then(|_| {
-                        let absolute_uri = encode_uri_path(src);
-                        writeln!(file, "Path={absolute_uri}").and_then(|_| {
+                        let recorded = topdir
+                            .and_then(|topdir| src.strip_prefix(topdir).ok())
+                            .filter(|relative| !relative.as_os_str().is_empty())
+                            .unwrap_or(src);
+                        let uri = encode_uri_path(recorded);
+                        writeln!(file, "Path={uri}").and_then(|_| {
                             #[cfg(feature = "chrono")]
                             {
                                 let now = chrono::Local::now();
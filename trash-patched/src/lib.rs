@@ -224,6 +224,9 @@ impl fmt::Display for Error {
                 "cannot move '{}' to Trash because the containing volume does not provide a usable Trash ({reason})",
                 path.display()
             ),
+            #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+            Self::FileSystem { path, source } => write!(f, "'{}': {source}", path.display()),
+            Self::Unknown { description } => f.write_str(description),
             _ => write!(f, "Error during a `trash` operation: {self:?}"),
         }
     }
@@ -434,6 +437,20 @@ pub mod os_limited {
         platform::trash_folders()
     }
 
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    pub use platform::TopdirTrashReport;
+
+    /// **freedesktop only**
+    ///
+    /// Checks the trash folders that trashing `path` would use when it lives on
+    /// a volume other than the home trash's, without creating anything.
+    /// Returns `None` for paths that go to the home trash. `path` should be
+    /// absolute with its parent directories canonicalized, as trashing sees it.
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios")))]
+    pub fn topdir_trash_report(path: &std::path::Path) -> Result<Option<TopdirTrashReport>, Error> {
+        platform::topdir_trash_report(path)
+    }
+
     /// Returns the [`TrashItemMetadata`] for a [`TrashItem`]
     ///
     /// # Example